@@ -0,0 +1,143 @@
+//! Measures what gzip compression of Firestore requests costs in CPU time and
+//! saves in bytes, for documents of different shapes. Run it with:
+//!
+//! ```text
+//! cargo bench --bench compression
+//! ```
+//!
+//! The documents are encoded to protobuf like in a write request and
+//! compressed at the level tonic uses. The compression ratio of each document
+//! is printed before its timings, and criterion reports the throughput. Put
+//! the throughput next to the bandwidth to your Firestore region to see
+//! whether compression pays off: at 100 MB/s of compression throughput, a
+//! 1 MB document costs 10 ms of CPU time, which is only worth it if sending
+//! the saved bytes takes longer than that. For network time across regions,
+//! run the `compression` example.
+
+use std::{collections::HashMap, io::Write};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use firestore_grpc::v1::{value::ValueType, ArrayValue, Document, MapValue, Value};
+use flate2::{write::GzEncoder, Compression};
+use prost::Message;
+
+/// The compression level tonic uses for gzip.
+const LEVEL: u32 = 6;
+
+fn to_value(json: &serde_json::Value) -> Value {
+    let value_type = match json {
+        serde_json::Value::Null => ValueType::NullValue(0),
+        serde_json::Value::Bool(b) => ValueType::BooleanValue(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => ValueType::IntegerValue(i),
+            None => ValueType::DoubleValue(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => ValueType::StringValue(s.clone()),
+        serde_json::Value::Array(values) => ValueType::ArrayValue(ArrayValue {
+            values: values.iter().map(to_value).collect(),
+        }),
+        serde_json::Value::Object(fields) => ValueType::MapValue(MapValue {
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.clone(), to_value(v)))
+                .collect(),
+        }),
+    };
+
+    Value {
+        value_type: Some(value_type),
+    }
+}
+
+fn encode(json: serde_json::Value) -> Vec<u8> {
+    let fields: HashMap<String, Value> = match to_value(&json).value_type {
+        Some(ValueType::MapValue(map)) => map.fields,
+        _ => unreachable!("documents are maps"),
+    };
+
+    Document {
+        name: "projects/p/databases/(default)/documents/benchmark/doc".to_string(),
+        fields,
+        ..Default::default()
+    }
+    .encode_to_vec()
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(LEVEL));
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Documents from compressing well to barely compressing at all.
+fn documents() -> Vec<(&'static str, Vec<u8>)> {
+    let text = "The quick brown fox jumps over the lazy dog. ";
+
+    // A simple linear congruential generator, so the numbers look random
+    // without a dependency on rand
+    let mut state: u64 = 42;
+    let mut next = move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        state >> 33
+    };
+
+    let records: Vec<_> = (0..2_000)
+        .map(|i| {
+            serde_json::json!({
+                "id": format!("user-{i}"),
+                "email": format!("user-{i}@example.com"),
+                "score": next() % 10_000,
+                "active": i % 3 == 0,
+            })
+        })
+        .collect();
+    let numbers: Vec<_> = (0..20_000).map(|_| next() as f64 / 7.0).collect();
+
+    vec![
+        (
+            "repetitive text",
+            encode(serde_json::json!({ "lines": vec![text; 10_000] })),
+        ),
+        ("records", encode(serde_json::json!({ "records": records }))),
+        (
+            "random numbers",
+            encode(serde_json::json!({ "embedding": numbers })),
+        ),
+    ]
+}
+
+fn compression(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gzip");
+
+    for (name, encoded) in documents() {
+        let compressed = gzip(&encoded);
+        println!(
+            "{}: {} bytes, {} gzipped ({:.0}%)",
+            name,
+            encoded.len(),
+            compressed.len(),
+            compressed.len() as f64 / encoded.len() as f64 * 100.0
+        );
+
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_with_input(BenchmarkId::new("compress", name), &encoded, |b, bytes| {
+            b.iter(|| gzip(bytes))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("decompress", name),
+            &compressed,
+            |b, bytes| {
+                b.iter(|| {
+                    let mut decoder = flate2::write::GzDecoder::new(Vec::new());
+                    decoder.write_all(bytes).unwrap();
+                    decoder.finish().unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, compression);
+criterion_main!(benches);
@@ -0,0 +1,74 @@
+//! Compares writing and reading large documents with and without gzip
+//! compression. Run it with:
+//!
+//! ```text
+//! cargo run --release --example compression --features compression
+//! ```
+//!
+//! Compression pays off when the time saved on the wire outweighs the time
+//! spent compressing, so the result depends on how compressible the documents
+//! are and how far away the Firestore region is. Adjust the documents below
+//! to resemble your own.
+
+use std::time::{Duration, Instant};
+
+use fireplace::{
+    firestore::{
+        client::{FirestoreClient, FirestoreClientOptions},
+        collection,
+    },
+    ServiceAccount,
+};
+
+const DOCUMENTS: usize = 50;
+
+#[tokio::main]
+async fn main() {
+    let service_account = ServiceAccount::from_file("./test-service-account.json").unwrap();
+
+    // A document of roughly 500 KB of repetitive text, which compresses well
+    let line = "The quick brown fox jumps over the lazy dog. ";
+    let doc = serde_json::json!({
+        "lines": vec![line; 10_000],
+    });
+
+    for gzip in [false, true] {
+        let options = FirestoreClientOptions::default().gzip(gzip);
+        let mut client = FirestoreClient::initialise(service_account.clone(), options)
+            .await
+            .unwrap();
+
+        let (written, read) = run(&mut client, &doc).await;
+
+        println!(
+            "gzip: {:<5}  write: {:>8.1?}/doc  read: {:>8.1?}/doc",
+            gzip,
+            written / DOCUMENTS as u32,
+            read / DOCUMENTS as u32,
+        );
+    }
+}
+
+async fn run(client: &mut FirestoreClient, doc: &serde_json::Value) -> (Duration, Duration) {
+    let docs = collection("compression-benchmark");
+
+    let start = Instant::now();
+    for i in 0..DOCUMENTS {
+        client
+            .set_document(&docs.doc(i.to_string()), doc)
+            .await
+            .unwrap();
+    }
+    let written = start.elapsed();
+
+    let start = Instant::now();
+    for i in 0..DOCUMENTS {
+        client
+            .get_document::<serde_json::Value>(&docs.doc(i.to_string()))
+            .await
+            .unwrap();
+    }
+    let read = start.elapsed();
+
+    (written, read)
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use anyhow::Context;
 use jsonwebtoken::{get_current_timestamp, Algorithm, EncodingKey};
 use serde::{Deserialize, Serialize};
@@ -9,9 +11,19 @@ const GOOGLE_TOKEN_AUDIENCE: &str = "https://accounts.google.com/o/oauth2/token"
 const GOOGLE_AUTH_TOKEN_HOST: &str = "accounts.google.com";
 const GOOGLE_AUTH_TOKEN_PATH: &str = "/o/oauth2/token";
 
+/// The scopes needed by the Firebase APIs used in this crate.
+const DEFAULT_SCOPES: &[&str] = &[
+    "https://www.googleapis.com/auth/cloud-platform",
+    "https://www.googleapis.com/auth/firebase.database",
+    "https://www.googleapis.com/auth/firebase.messaging",
+    "https://www.googleapis.com/auth/identitytoolkit",
+    "https://www.googleapis.com/auth/userinfo.email",
+];
+
 pub struct ApiAuthTokenManager {
     service_account: ServiceAccount,
-    current_access_token: RwLock<Option<AccessToken>>,
+    /// Cached access tokens by their space-separated scopes.
+    access_tokens: RwLock<HashMap<String, AccessToken>>,
     http_client: reqwest::Client,
 }
 
@@ -19,34 +31,51 @@ impl ApiAuthTokenManager {
     pub fn new(service_account: ServiceAccount) -> Self {
         Self {
             service_account,
-            current_access_token: RwLock::new(None),
+            access_tokens: RwLock::new(HashMap::new()),
             http_client: reqwest::Client::new(),
         }
     }
 
     pub async fn get_access_token(&self) -> anyhow::Result<String> {
-        match self.get_non_expired_token().await {
-            Some(token) => Ok(token),
-            None => {
-                let mut token_guard = self.current_access_token.write().await;
-                let access_token = self.fetch_access_token().await?;
-                let token = access_token.access_token.clone();
-                *token_guard = Some(access_token);
-                Ok(token)
-            }
+        self.get_access_token_with_scopes(DEFAULT_SCOPES).await
+    }
+
+    /// Gets an OAuth 2.0 access token for the service account with the given
+    /// scopes. Tokens are cached per set of scopes until they expire.
+    pub async fn get_access_token_with_scopes(&self, scopes: &[&str]) -> anyhow::Result<String> {
+        anyhow::ensure!(!scopes.is_empty(), "At least one scope must be given");
+
+        let scope = scopes.join(" ");
+
+        if let Some(token) = self.get_non_expired_token(&scope).await {
+            return Ok(token);
         }
+
+        let mut token_guard = self.access_tokens.write().await;
+
+        // Many tasks may have seen the expired token at the same time and
+        // queued up for the write lock. Only the first one needs to refresh
+        // the token - the rest can use the token it fetched.
+        if let Some(token) = token_guard.get(&scope).filter(|t| !t.has_expired()) {
+            return Ok(token.access_token.clone());
+        }
+
+        let access_token = self.fetch_access_token(&scope).await?;
+        let token = access_token.access_token.clone();
+        token_guard.insert(scope, access_token);
+        Ok(token)
     }
 
-    async fn get_non_expired_token(&self) -> Option<String> {
-        match self.current_access_token.read().await.as_ref() {
+    async fn get_non_expired_token(&self, scope: &str) -> Option<String> {
+        match self.access_tokens.read().await.get(scope) {
             Some(token) if !token.has_expired() => Some(token.access_token.clone()),
             _ => None,
         }
     }
 
     #[tracing::instrument(name = "Fetch Auth access token", skip(self))]
-    async fn fetch_access_token(&self) -> Result<AccessToken, anyhow::Error> {
-        let jwt = self.create_auth_jwt()?;
+    async fn fetch_access_token(&self, scope: &str) -> Result<AccessToken, anyhow::Error> {
+        let jwt = self.create_auth_jwt(scope)?;
 
         let post_data = format!(
             "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer&assertion={}",
@@ -92,21 +121,12 @@ impl ApiAuthTokenManager {
         Ok(access_token)
     }
 
-    fn create_auth_jwt(&self) -> Result<String, anyhow::Error> {
-        let scope = [
-            "https://www.googleapis.com/auth/cloud-platform",
-            "https://www.googleapis.com/auth/firebase.database",
-            "https://www.googleapis.com/auth/firebase.messaging",
-            "https://www.googleapis.com/auth/identitytoolkit",
-            "https://www.googleapis.com/auth/userinfo.email",
-        ]
-        .join(" ");
-
+    fn create_auth_jwt(&self, scope: &str) -> Result<String, anyhow::Error> {
         let issued_at_time = get_current_timestamp();
         let expires_at = issued_at_time + (60 * 60);
 
         let claims = Claims {
-            scope: &scope,
+            scope,
             aud: GOOGLE_TOKEN_AUDIENCE,
             iss: &self.service_account.client_email,
             iat: issued_at_time,
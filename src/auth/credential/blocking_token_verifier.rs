@@ -0,0 +1,181 @@
+use anyhow::Context;
+use jsonwebtoken::Validation;
+
+use crate::{
+    auth::models::{BlockingEvent, BlockingRequest},
+    error::FirebaseError,
+};
+
+use super::{
+    id_token_verifier::{decoding_key, issuer_for},
+    public_keys::{PublicKeyCacheOptions, PublicKeys},
+};
+
+/// Blocking functions deployed to Cloud Run get an audience ending in this.
+const CLOUD_RUN_AUDIENCE_SUFFIX: &str = ".run.app";
+
+/// Verifies the requests that Identity Platform sends to
+/// [blocking functions](https://cloud.google.com/identity-platform/docs/blocking-functions),
+/// such as `beforeCreate` and `beforeSignIn`, so they can be implemented
+/// with fireplace types. Like [`IdTokenVerifier`](super::IdTokenVerifier),
+/// it doesn't need a service account.
+///
+/// The request carries a JWT signed by Google, which must be issued for the
+/// verifier's project. Its audience is the URL of the function, which should
+/// be configured with [`audience`](Self::audience). Without it, any function
+/// URL on Cloud Run is accepted.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+/// # let body = "{}";
+/// use fireplace::auth::{
+///     models::{BlockingError, BlockingEventType, BlockingRequest, BlockingResponse},
+///     BlockingTokenVerifier,
+/// };
+///
+/// let verifier = BlockingTokenVerifier::new("my-project")?
+///     .audience("https://before-create-abc123-ew.a.run.app");
+///
+/// let request: BlockingRequest = serde_json::from_str(body).unwrap();
+/// let event = verifier.verify_request(&request).await?;
+///
+/// let is_employee = event
+///     .user_record
+///     .email
+///     .as_deref()
+///     .map_or(false, |email| email.ends_with("@example.com"));
+///
+/// let response_body = match event.event_type() {
+///     BlockingEventType::BeforeCreate if !is_employee => {
+///         serde_json::to_string(&BlockingError::permission_denied("Unauthorized email"))
+///     }
+///     _ => serde_json::to_string(
+///         &BlockingResponse::new().custom_claims(serde_json::json!({ "role": "employee" }))?,
+///     ),
+/// };
+/// # Ok(())
+/// # }
+/// ```
+pub struct BlockingTokenVerifier {
+    public_keys: PublicKeys,
+    project_id: String,
+    audience: Option<String>,
+}
+
+impl BlockingTokenVerifier {
+    /// Create a verifier for requests from the given project. Only a Cloud
+    /// Run audience is checked until [`audience`](Self::audience) is set.
+    pub fn new(project_id: impl Into<String>) -> Result<Self, FirebaseError> {
+        let http_client = reqwest::Client::builder()
+            .https_only(true)
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            public_keys: PublicKeys::new(http_client),
+            project_id: project_id.into(),
+            audience: None,
+        })
+    }
+
+    /// Only accept tokens for the function at the given URL.
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Configure the cache of the public keys used for verifying tokens. See
+    /// [`PublicKeyCacheOptions`].
+    pub fn with_public_key_cache(self, options: PublicKeyCacheOptions) -> Self {
+        Self {
+            public_keys: self.public_keys.with_options(options),
+            ..self
+        }
+    }
+
+    /// Fetches the public keys used for verifying tokens, unless they are
+    /// already cached.
+    pub async fn prefetch_keys(&self) -> Result<(), FirebaseError> {
+        self.public_keys.prefetch().await?;
+        Ok(())
+    }
+
+    /// Verifies the JWT in the body of a blocking function request and
+    /// decodes the event it describes.
+    pub async fn verify_request(
+        &self,
+        request: &BlockingRequest,
+    ) -> Result<BlockingEvent, FirebaseError> {
+        self.verify_token(&request.data.jwt).await
+    }
+
+    /// Verifies a blocking function JWT and decodes the event it describes.
+    /// Fails if the token is in a bad format, expired, not issued for the
+    /// verifier's project and function, or if the signature is invalid.
+    #[tracing::instrument(name = "Verify blocking function token", skip(self, token))]
+    pub async fn verify_token(&self, token: &str) -> Result<BlockingEvent, FirebaseError> {
+        self.decode(token)
+            .await
+            .map_err(FirebaseError::ValidateTokenError)
+    }
+
+    async fn decode(&self, token: &str) -> Result<BlockingEvent, anyhow::Error> {
+        let decoding_key = decoding_key(&self.public_keys, token).await?;
+
+        let mut validation = Validation::new(jsonwebtoken::Algorithm::RS256);
+        validation.set_issuer(&[issuer_for(&self.project_id)]);
+        // The audience is checked below, since it may only be known by suffix
+        validation.validate_aud = false;
+
+        let decoded = jsonwebtoken::decode::<serde_json::Value>(token, &decoding_key, &validation)?;
+
+        let audience = decoded
+            .claims
+            .get("aud")
+            .and_then(|aud| aud.as_str())
+            .context("Blocking function token is missing an audience")?;
+        anyhow::ensure!(
+            is_valid_audience(audience, self.audience.as_deref()),
+            "Blocking function token has an unexpected audience '{}'",
+            audience
+        );
+
+        let event = serde_json::from_value(decoded.claims)
+            .context("Failed to deserialize blocking function token claims")?;
+
+        Ok(event)
+    }
+}
+
+fn is_valid_audience(audience: &str, expected: Option<&str>) -> bool {
+    match expected {
+        Some(expected) => audience == expected,
+        None => audience
+            .trim_end_matches('/')
+            .split('/')
+            .nth(2)
+            .map_or(false, |host| host.ends_with(CLOUD_RUN_AUDIENCE_SUFFIX)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audience_must_match_function() {
+        let url = "https://before-create-abc123-ew.a.run.app";
+
+        assert!(is_valid_audience(url, Some(url)));
+        assert!(!is_valid_audience(url, Some("https://other.a.run.app")));
+        assert!(is_valid_audience(url, None));
+        assert!(!is_valid_audience(
+            "https://evil.example.com/.run.app",
+            None
+        ));
+        assert!(!is_valid_audience("my-project", None));
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Context;
+use jsonwebtoken::{DecodingKey, Validation};
+use serde::de::DeserializeOwned;
+
+use crate::error::FirebaseError;
+
+use super::public_keys::{PublicKeyCacheOptions, PublicKeys};
+
+/// Verifies Firebase ID tokens issued for any of a set of projects. Unlike
+/// [`FirebaseAuthClient`](crate::auth::FirebaseAuthClient), it doesn't need a
+/// service account, which makes it suitable for e.g. a gateway that fronts
+/// several Firebase projects.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+/// # use ulid::Ulid;
+/// # use fireplace::auth::models::NewUser;
+/// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+/// # let user_id = auth_client
+/// #     .create_user(NewUser {
+/// #         display_name: None,
+/// #         email: format!("{}@example.com", Ulid::new()),
+/// #         password: Ulid::new().to_string(),
+/// #     })
+/// #     .await?;
+/// # let custom_token = auth_client.create_custom_token(&user_id).await?;
+/// # let id_token = auth_client.sign_in_with_custom_token(&custom_token).await?;
+/// # let project_id = std::env::var("FIREBASE_PROJECT_ID").unwrap();
+/// use fireplace::auth::IdTokenVerifier;
+///
+/// let verifier = IdTokenVerifier::new(["some-other-project", &project_id])?;
+///
+/// let claims = verifier
+///     .decode_id_token::<serde_json::Value>(&id_token)
+///     .await?;
+/// assert_eq!(claims["aud"], project_id);
+///
+/// // Tokens for projects outside of the set are rejected
+/// let verifier = IdTokenVerifier::new(["some-other-project"])?;
+/// assert!(verifier
+///     .decode_id_token::<serde_json::Value>(&id_token)
+///     .await
+///     .is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub struct IdTokenVerifier {
+    public_keys: PublicKeys,
+    project_ids: Vec<String>,
+}
+
+impl IdTokenVerifier {
+    /// Create a verifier that accepts ID tokens for any of the given
+    /// projects. Fails if no project IDs are given.
+    pub fn new(
+        project_ids: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Self, FirebaseError> {
+        let project_ids: Vec<String> = project_ids.into_iter().map(Into::into).collect();
+
+        if project_ids.is_empty() {
+            return Err(anyhow::anyhow!("At least one project ID must be given").into());
+        }
+
+        let http_client = reqwest::Client::builder()
+            .https_only(true)
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            public_keys: PublicKeys::new(http_client),
+            project_ids,
+        })
+    }
+
+    /// Configure the cache of the public keys used for verifying tokens. See
+    /// [`PublicKeyCacheOptions`].
+    pub fn with_public_key_cache(self, options: PublicKeyCacheOptions) -> Self {
+        Self {
+            public_keys: self.public_keys.with_options(options),
+            project_ids: self.project_ids,
+        }
+    }
+
+    /// Fetches the public keys used for verifying tokens, unless they are
+    /// already cached. Call this at startup to avoid the first verifications
+    /// waiting for the keys to be fetched.
+    pub async fn prefetch_keys(&self) -> Result<(), FirebaseError> {
+        self.public_keys.prefetch().await?;
+        Ok(())
+    }
+
+    /// Fetches the public keys used for verifying tokens, even if they are
+    /// already cached.
+    pub async fn refresh_keys(&self) -> Result<(), FirebaseError> {
+        self.public_keys.refresh().await?;
+        Ok(())
+    }
+
+    /// Verifies an ID token and decodes its claims. Fails if the token is in
+    /// a bad format, expired, not issued for one of the verifier's projects,
+    /// or if the signature is invalid.
+    #[tracing::instrument(name = "Verify ID token", skip(self, token))]
+    pub async fn decode_id_token<C: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<C, FirebaseError> {
+        decode_id_token(&self.public_keys, token, &self.project_ids)
+            .await
+            .map_err(FirebaseError::ValidateTokenError)
+    }
+
+    /// Like [`decode_id_token`](Self::decode_id_token), but also fails with
+    /// [`TenantMismatch`](FirebaseError::TenantMismatch) unless the user
+    /// signed in to the given tenant.
+    #[tracing::instrument(name = "Verify tenant ID token", skip(self, token))]
+    pub async fn decode_id_token_for_tenant<C: DeserializeOwned>(
+        &self,
+        token: &str,
+        tenant_id: &str,
+    ) -> Result<C, FirebaseError> {
+        let claims = self.decode_id_token(token).await?;
+        claims_for_tenant(claims, tenant_id)
+    }
+}
+
+/// Verifies an ID token based on the docs at <https://firebase.google.com/docs/auth/admin/verify-id-tokens#verify_id_tokens_using_a_third-party_jwt_library>
+///
+/// The token must have been issued for one of the given projects.
+pub(super) async fn decode_id_token<C: DeserializeOwned>(
+    public_keys: &PublicKeys,
+    token: &str,
+    project_ids: &[String],
+) -> Result<C, anyhow::Error> {
+    let decoding_key = decoding_key(public_keys, token).await?;
+
+    let issuers: Vec<String> = project_ids.iter().map(|id| issuer_for(id)).collect();
+
+    let mut validation = Validation::new(jsonwebtoken::Algorithm::RS256);
+    validation.set_audience(project_ids);
+    validation.set_issuer(&issuers);
+
+    let decoded = jsonwebtoken::decode::<serde_json::Value>(token, &decoding_key, &validation)?;
+
+    // The audience and issuer are validated independently of each other, so
+    // we need to check that they belong to the same project
+    let audience = decoded
+        .claims
+        .get("aud")
+        .and_then(|aud| aud.as_str())
+        .context("ID token is missing an audience")?;
+    anyhow::ensure!(
+        decoded.claims.get("iss").and_then(|iss| iss.as_str()) == Some(&issuer_for(audience)),
+        "ID token issuer does not match its audience"
+    );
+    tenant_claim(&decoded.claims)?;
+
+    let claims =
+        serde_json::from_value(decoded.claims).context("Failed to deserialize ID token claims")?;
+
+    Ok(claims)
+}
+
+/// The tenant the user of an ID token signed in to, if any. Fails if the
+/// tenant claim is present but not a tenant ID.
+fn tenant_claim(claims: &serde_json::Value) -> Result<Option<&str>, anyhow::Error> {
+    match claims
+        .get("firebase")
+        .and_then(|firebase| firebase.get("tenant"))
+    {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(tenant)) if !tenant.is_empty() => Ok(Some(tenant)),
+        Some(_) => anyhow::bail!("ID token has an invalid tenant claim"),
+    }
+}
+
+/// Checks that the verified claims of an ID token belong to the given tenant,
+/// and deserializes them.
+pub(crate) fn claims_for_tenant<C: DeserializeOwned>(
+    claims: serde_json::Value,
+    tenant_id: &str,
+) -> Result<C, FirebaseError> {
+    let actual = tenant_claim(&claims).map_err(FirebaseError::ValidateTokenError)?;
+
+    if actual != Some(tenant_id) {
+        return Err(FirebaseError::TenantMismatch {
+            expected: tenant_id.to_string(),
+            actual: actual.map(str::to_string),
+        });
+    }
+
+    serde_json::from_value(claims)
+        .context("Failed to deserialize ID token claims")
+        .map_err(FirebaseError::ValidateTokenError)
+}
+
+/// Looks up the Google public key that the token was signed with, based on
+/// the key ID in its header.
+pub(super) async fn decoding_key(
+    public_keys: &PublicKeys,
+    token: &str,
+) -> Result<DecodingKey, anyhow::Error> {
+    let header = jsonwebtoken::decode_header(token)?;
+
+    if header.alg != jsonwebtoken::Algorithm::RS256 {
+        anyhow::bail!("Invalid ID token JWT algorithm");
+    }
+
+    let public_key_id = header
+        .kid
+        .context("ID token is missing public key ID in header")?;
+
+    let public_key = public_keys
+        .get(&public_key_id)
+        .await?
+        .context("Unrecognized public key in header of ID token")?;
+
+    let decoding_key = DecodingKey::from_rsa_pem(public_key.as_ref())
+        .context("Invalid public key format in ID token")?;
+
+    Ok(decoding_key)
+}
+
+pub(super) fn issuer_for(project_id: &str) -> String {
+    format!("https://securetoken.google.com/{}", project_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims_with_tenant(tenant: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "sub": "some-user-id",
+            "firebase": { "sign_in_provider": "password", "tenant": tenant },
+        })
+    }
+
+    #[test]
+    fn accepts_claims_of_expected_tenant() {
+        let claims = claims_with_tenant(serde_json::json!("tenant-a"));
+
+        let decoded: serde_json::Value = claims_for_tenant(claims.clone(), "tenant-a").unwrap();
+
+        assert_eq!(decoded, claims);
+    }
+
+    #[test]
+    fn rejects_claims_of_other_or_no_tenant() {
+        let err = claims_for_tenant::<serde_json::Value>(
+            claims_with_tenant(serde_json::json!("tenant-b")),
+            "tenant-a",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            FirebaseError::TenantMismatch { expected, actual }
+                if expected == "tenant-a" && actual.as_deref() == Some("tenant-b")
+        ));
+
+        let err = claims_for_tenant::<serde_json::Value>(
+            claims_with_tenant(serde_json::Value::Null),
+            "tenant-a",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            FirebaseError::TenantMismatch { actual: None, .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_tenant_claims() {
+        assert!(tenant_claim(&claims_with_tenant(serde_json::json!(""))).is_err());
+        assert!(tenant_claim(&claims_with_tenant(serde_json::json!(42))).is_err());
+        assert_eq!(
+            tenant_claim(&serde_json::json!({ "firebase": {} })).unwrap(),
+            None
+        );
+    }
+}
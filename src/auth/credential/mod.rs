@@ -1,6 +1,12 @@
 mod api_auth_token;
+mod blocking_token_verifier;
+mod id_token_verifier;
 mod public_keys;
 mod token_handler;
 
 pub use api_auth_token::ApiAuthTokenManager;
+pub use blocking_token_verifier::BlockingTokenVerifier;
+pub(crate) use id_token_verifier::claims_for_tenant;
+pub use id_token_verifier::IdTokenVerifier;
+pub use public_keys::PublicKeyCacheOptions;
 pub use token_handler::UserTokenManager;
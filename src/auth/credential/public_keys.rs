@@ -6,9 +6,70 @@ use std::{
 use anyhow::Context;
 use tokio::sync::RwLock;
 
+/// How long to wait before trying to fetch the public keys again after a
+/// failed fetch, while still serving the stale keys.
+const STALE_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Options for the cache of Google's public keys, which are used to verify ID
+/// tokens.
+///
+/// The keys are cached for as long as Google's `Cache-Control` header says,
+/// clamped to the minimum and maximum TTL. By default the TTL is not clamped,
+/// and stale keys are never used.
+#[derive(Debug, Clone)]
+pub struct PublicKeyCacheOptions {
+    min_ttl: Duration,
+    max_ttl: Duration,
+    max_staleness: Duration,
+}
+
+impl Default for PublicKeyCacheOptions {
+    fn default() -> Self {
+        Self {
+            min_ttl: Duration::ZERO,
+            max_ttl: Duration::MAX,
+            max_staleness: Duration::ZERO,
+        }
+    }
+}
+
+impl PublicKeyCacheOptions {
+    /// Create options with the default cache behaviour.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cache the keys for at least this long, even if Google says otherwise.
+    pub fn min_ttl(mut self, min_ttl: Duration) -> Self {
+        self.min_ttl = min_ttl;
+        self
+    }
+
+    /// Cache the keys for at most this long, even if Google says otherwise.
+    pub fn max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = max_ttl;
+        self
+    }
+
+    /// If fetching new keys fails, keep using the expired keys for up to this
+    /// long after they expired. Fetching is retried in the meantime.
+    pub fn max_staleness(mut self, max_staleness: Duration) -> Self {
+        self.max_staleness = max_staleness;
+        self
+    }
+
+    fn ttl(&self, max_age: Option<Duration>) -> Duration {
+        max_age
+            .unwrap_or(Duration::from_secs(5 * 60))
+            .min(self.max_ttl)
+            .max(self.min_ttl)
+    }
+}
+
 pub(super) struct PublicKeys {
     public_key_map: RwLock<Option<PublicKeyMap>>,
     http_client: reqwest::Client,
+    options: PublicKeyCacheOptions,
 }
 
 impl PublicKeys {
@@ -16,13 +77,21 @@ impl PublicKeys {
         Self {
             public_key_map: RwLock::new(None),
             http_client,
+            options: PublicKeyCacheOptions::default(),
         }
     }
 
-    pub async fn get(&self, key_id: &str) -> Result<Option<String>, anyhow::Error> {
-        if self.should_update().await {
-            self.update().await?;
+    /// Replaces the cache options, discarding any cached keys.
+    pub fn with_options(self, options: PublicKeyCacheOptions) -> Self {
+        Self {
+            public_key_map: RwLock::new(None),
+            http_client: self.http_client,
+            options,
         }
+    }
+
+    pub async fn get(&self, key_id: &str) -> Result<Option<String>, anyhow::Error> {
+        self.prefetch().await?;
 
         let public_key_map = self.public_key_map.read().await;
 
@@ -36,30 +105,73 @@ impl PublicKeys {
         Ok(key)
     }
 
-    async fn update(&self) -> Result<(), anyhow::Error> {
-        let mut public_key_map = self.public_key_map.write().await;
+    /// Fetches the keys if they aren't cached or have expired.
+    pub async fn prefetch(&self) -> Result<(), anyhow::Error> {
+        if self.should_update().await {
+            self.update(false).await?;
+        }
 
-        let pkm = PublicKeyMap::fetch(&self.http_client).await.map_err(|e| {
-            tracing::error!("Failed to fetch public keys: {}", e);
-            e
-        })?;
+        Ok(())
+    }
 
-        *public_key_map = Some(pkm);
+    /// Fetches the keys regardless of whether the cached keys have expired.
+    pub async fn refresh(&self) -> Result<(), anyhow::Error> {
+        self.update(true).await
+    }
 
-        Ok(())
+    async fn update(&self, force: bool) -> Result<(), anyhow::Error> {
+        let mut public_key_map = self.public_key_map.write().await;
+
+        // Another task may have updated the keys while we were waiting for the
+        // lock, in which case we don't need to fetch them again
+        if !force && !needs_update(public_key_map.as_ref()) {
+            return Ok(());
+        }
+
+        match PublicKeyMap::fetch(&self.http_client, &self.options).await {
+            Ok(pkm) => {
+                *public_key_map = Some(pkm);
+                Ok(())
+            }
+            Err(e) => match public_key_map.as_mut() {
+                Some(pkm) if is_within_staleness(pkm, &self.options) => {
+                    tracing::warn!("Failed to fetch public keys, using stale keys: {}", e);
+                    pkm.update_by = Instant::now() + STALE_RETRY_INTERVAL;
+                    Ok(())
+                }
+                _ => {
+                    tracing::error!("Failed to fetch public keys: {}", e);
+                    Err(e)
+                }
+            },
+        }
     }
 
     async fn should_update(&self) -> bool {
-        match self.public_key_map.read().await.as_ref() {
-            None => true,
-            Some(pkm) if Instant::now() >= pkm.update_by => true,
-            _ => false,
-        }
+        needs_update(self.public_key_map.read().await.as_ref())
+    }
+}
+
+fn needs_update(public_key_map: Option<&PublicKeyMap>) -> bool {
+    match public_key_map {
+        None => true,
+        Some(pkm) => Instant::now() >= pkm.update_by,
     }
 }
 
+fn is_within_staleness(public_key_map: &PublicKeyMap, options: &PublicKeyCacheOptions) -> bool {
+    public_key_map
+        .expires_at
+        .checked_add(options.max_staleness)
+        .map_or(true, |stale_by| Instant::now() < stale_by)
+}
+
 struct PublicKeyMap {
+    /// When to try fetching the keys again.
     update_by: Instant,
+    /// When the keys expire according to the cache options. They can still
+    /// be used for a while after this if fetching new keys fails.
+    expires_at: Instant,
     keys: HashMap<String, String>,
 }
 
@@ -67,7 +179,10 @@ impl PublicKeyMap {
     const PUBLIC_KEYS_URL: &'static str =
         "https://www.googleapis.com/robot/v1/metadata/x509/securetoken@system.gserviceaccount.com";
 
-    async fn fetch(client: &reqwest::Client) -> Result<Self, anyhow::Error> {
+    async fn fetch(
+        client: &reqwest::Client,
+        options: &PublicKeyCacheOptions,
+    ) -> Result<Self, anyhow::Error> {
         tracing::debug!("Refreshing x509 public key certificates from Google");
 
         let res = client.get(Self::PUBLIC_KEYS_URL).send().await?;
@@ -93,7 +208,7 @@ impl PublicKeyMap {
                     .map_err(|_| anyhow::anyhow!("Invalid max-age in Cache-Control header: {}", s))
             })
             .transpose()?
-            .unwrap_or(5 * 60);
+            .map(Duration::from_secs);
 
         let certificates = res.json::<HashMap<String, String>>().await?;
         let mut public_keys = HashMap::with_capacity(certificates.len());
@@ -105,9 +220,45 @@ impl PublicKeyMap {
             public_keys.insert(key_id, public_key);
         }
 
+        let expires_at = Instant::now()
+            .checked_add(options.ttl(max_age))
+            .unwrap_or_else(far_future);
+
         Ok(Self {
-            update_by: Instant::now() + Duration::from_secs(max_age),
+            update_by: expires_at,
+            expires_at,
             keys: public_keys,
         })
     }
 }
+
+/// An instant so far in the future that it is never reached in practice.
+fn far_future() -> Instant {
+    Instant::now() + Duration::from_secs(100 * 365 * 24 * 60 * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ttl_is_clamped_to_options() {
+        let options = PublicKeyCacheOptions::new()
+            .min_ttl(Duration::from_secs(60))
+            .max_ttl(Duration::from_secs(600));
+
+        assert_eq!(
+            options.ttl(Some(Duration::from_secs(10))),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            options.ttl(Some(Duration::from_secs(3600))),
+            Duration::from_secs(600)
+        );
+        assert_eq!(options.ttl(None), Duration::from_secs(300));
+        assert_eq!(
+            PublicKeyCacheOptions::new().ttl(Some(Duration::from_secs(3600))),
+            Duration::from_secs(3600)
+        );
+    }
+}
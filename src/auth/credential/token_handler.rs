@@ -1,10 +1,18 @@
+use std::time::Duration;
+
 use anyhow::Context;
-use jsonwebtoken::{get_current_timestamp, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{get_current_timestamp, Algorithm};
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::public_keys::PublicKeys;
+use super::{
+    id_token_verifier::decode_id_token,
+    public_keys::{PublicKeyCacheOptions, PublicKeys},
+};
 
-use crate::ServiceAccount;
+use crate::{
+    auth::models::{CustomTokenOptions, MAX_CUSTOM_TOKEN_TTL},
+    ServiceAccount,
+};
 
 const FIREBASE_AUDIENCE: &str =
     "https://identitytoolkit.googleapis.com/google.identity.identitytoolkit.v1.IdentityToolkit";
@@ -22,7 +30,28 @@ impl UserTokenManager {
         }
     }
 
-    /// Verifies an ID token based on the docs at <https://firebase.google.com/docs/auth/admin/verify-id-tokens#verify_id_tokens_using_a_third-party_jwt_library>
+    /// Replaces the options of the public key cache, discarding any cached
+    /// keys.
+    pub fn with_public_key_cache(self, options: PublicKeyCacheOptions) -> Self {
+        Self {
+            public_keys: self.public_keys.with_options(options),
+            service_account: self.service_account,
+        }
+    }
+
+    /// Fetches the public keys used for verifying ID tokens, unless they are
+    /// already cached.
+    pub async fn prefetch_keys(&self) -> Result<(), anyhow::Error> {
+        self.public_keys.prefetch().await
+    }
+
+    /// Fetches the public keys used for verifying ID tokens, even if they are
+    /// already cached.
+    pub async fn refresh_keys(&self) -> Result<(), anyhow::Error> {
+        self.public_keys.refresh().await
+    }
+
+    /// Verifies an ID token issued for the service account's project.
     ///
     /// Fails if the token is in a bad format, expired, not issued for this
     /// project, or if the signature is invalid.
@@ -30,44 +59,23 @@ impl UserTokenManager {
         &self,
         token: &str,
     ) -> Result<C, anyhow::Error> {
-        let header = jsonwebtoken::decode_header(token)?;
-
-        if header.alg != jsonwebtoken::Algorithm::RS256 {
-            anyhow::bail!("Invalid ID token JWT algorithm");
-        }
-
-        let public_key_id = header
-            .kid
-            .context("ID token is missing public key ID in header")?;
-
-        let public_key = self
-            .public_keys
-            .get(&public_key_id)
-            .await?
-            .context("Unrecognized public key in header of ID token")?;
-
-        let mut validation = Validation::new(jsonwebtoken::Algorithm::RS256);
-        validation.set_audience(&[&self.service_account.project_id]);
-        validation.set_issuer(&[&format!(
-            "https://securetoken.google.com/{}",
-            &self.service_account.project_id
-        )]);
-
-        let decoded = jsonwebtoken::decode(
+        decode_id_token(
+            &self.public_keys,
             token,
-            &DecodingKey::from_rsa_pem(public_key.as_ref())
-                .context("Invalid public key format in ID token")?,
-            &validation,
-        )?;
-
-        Ok(decoded.claims)
+            std::slice::from_ref(&self.service_account.project_id),
+        )
+        .await
     }
 
     /// Creates and signs a custom token for a user ID, which the user can use
     /// to authenticate against Firebase services.
     ///
     /// See the official [Firebase Auth docs for creating custom tokens](https://firebase.google.com/docs/auth/admin/create-custom-tokens#create_custom_tokens_using_a_third-party_jwt_library>).
-    pub async fn create_custom_token(&self, uid: &str) -> Result<String, anyhow::Error> {
+    pub async fn create_custom_token(
+        &self,
+        uid: &str,
+        options: &CustomTokenOptions,
+    ) -> Result<String, anyhow::Error> {
         #[derive(Serialize)]
         struct CustomTokenClaims<'a> {
             aud: &'a str,
@@ -80,8 +88,7 @@ impl UserTokenManager {
 
         let header = jsonwebtoken::Header::new(Algorithm::RS256);
 
-        let issued_at_time = get_current_timestamp();
-        let expires_at = issued_at_time + (60 * 60);
+        let (issued_at_time, expires_at) = custom_token_lifetime(get_current_timestamp(), options)?;
 
         let claims = CustomTokenClaims {
             iss: &self.service_account.client_email,
@@ -102,3 +109,62 @@ impl UserTokenManager {
         Ok(jwt)
     }
 }
+
+/// Returns the issued-at and expiry timestamps for a custom token created at
+/// the given time.
+fn custom_token_lifetime(
+    now: u64,
+    options: &CustomTokenOptions,
+) -> Result<(u64, u64), anyhow::Error> {
+    // The expiry has a resolution of seconds, so anything shorter would
+    // produce a token that is already expired
+    anyhow::ensure!(
+        options.ttl >= Duration::from_secs(1),
+        "Custom token lifetime must be at least one second"
+    );
+    anyhow::ensure!(
+        options.ttl + options.issued_at_skew <= MAX_CUSTOM_TOKEN_TTL,
+        "Custom token lifetime plus issued-at skew may not exceed {} seconds",
+        MAX_CUSTOM_TOKEN_TTL.as_secs()
+    );
+
+    let issued_at_time = now.saturating_sub(options.issued_at_skew.as_secs());
+    let expires_at = now + options.ttl.as_secs();
+
+    Ok((issued_at_time, expires_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_custom_token_lifetime_is_one_hour() {
+        let (iat, exp) = custom_token_lifetime(1000, &CustomTokenOptions::new()).unwrap();
+        assert_eq!((iat, exp), (1000, 1000 + 60 * 60));
+    }
+
+    #[test]
+    fn custom_token_lifetime_applies_ttl_and_skew() {
+        let options = CustomTokenOptions::new()
+            .ttl(Duration::from_secs(5 * 60))
+            .issued_at_skew(Duration::from_secs(30));
+
+        let (iat, exp) = custom_token_lifetime(1000, &options).unwrap();
+        assert_eq!((iat, exp), (970, 1300));
+    }
+
+    #[test]
+    fn custom_token_lifetime_rejects_out_of_bounds_ttl() {
+        let too_long = CustomTokenOptions::new()
+            .ttl(MAX_CUSTOM_TOKEN_TTL)
+            .issued_at_skew(Duration::from_secs(1));
+        assert!(custom_token_lifetime(1000, &too_long).is_err());
+
+        let empty = CustomTokenOptions::new().ttl(Duration::ZERO);
+        assert!(custom_token_lifetime(1000, &empty).is_err());
+
+        let sub_second = CustomTokenOptions::new().ttl(Duration::from_millis(500));
+        assert!(custom_token_lifetime(1000, &sub_second).is_err());
+    }
+}
@@ -24,9 +24,22 @@ pub struct SpecificAuthApiErrorInfo {
     pub reason: String,
 }
 
+impl AuthApiErrorResponse {
+    /// The error code, such as `EMAIL_EXISTS`. Some messages have details
+    /// after the code, e.g. `INVALID_CONTINUE_URI : Continue URL is invalid`.
+    pub(crate) fn code(&self) -> &str {
+        self.error
+            .message
+            .split(" : ")
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+}
+
 impl From<AuthApiErrorResponse> for FirebaseError {
     fn from(err: AuthApiErrorResponse) -> Self {
-        match err.error.message.as_ref() {
+        match err.code() {
             "EMAIL_EXISTS" => FirebaseError::EmailAlreadyExists,
             "USER_NOT_FOUND" => FirebaseError::UserNotFound,
             _ => anyhow!("{:?}", err).into(),
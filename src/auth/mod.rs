@@ -1,31 +1,53 @@
+use std::future;
+use std::pin::Pin;
+use std::time::SystemTime;
+
 use anyhow::Context;
-use reqwest::Response;
+use futures::{Stream, StreamExt, TryStreamExt};
+use reqwest::{Method, Response};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use crate::{
     auth::{
         error::AuthApiErrorResponse,
-        models::{UpdateUserBody, UpdateUserValues},
+        models::{SendOobCodeBody, UpdateUserBody, UpdateUserValues},
     },
     error::FirebaseError,
     ServiceAccount,
 };
 
 use self::{
-    credential::{ApiAuthTokenManager, UserTokenManager},
-    models::{GetAccountInfoResponse, NewUser, User},
+    credential::{claims_for_tenant, ApiAuthTokenManager, UserTokenManager},
+    models::{
+        ActionCodeSettings, BulkClaimsResult, CustomTokenOptions, DownloadAccountResponse,
+        GetAccountInfoResponse, ListUsersOptions, NewUser, PageDeduplicator, PasswordHashConfig,
+        ProjectConfigResponse, UnknownUserFields, User, UsersPage, BULK_CLAIMS_CONCURRENCY,
+        MAX_LIST_USERS_RESULTS,
+    },
+    quota::QuotaTracker,
 };
 
-mod credential;
+pub(crate) mod credential;
 mod error;
 pub mod models;
+mod quota;
 pub mod test_helpers;
 
+pub use credential::{BlockingTokenVerifier, IdTokenVerifier, PublicKeyCacheOptions};
+pub use quota::{ApiUsage, QuotaTracking};
+
+type AuthStream<'a, T> = Pin<Box<dyn Stream<Item = Result<T, FirebaseError>> + Send + 'a>>;
+
 pub struct FirebaseAuthClient {
     client: reqwest::Client,
     api_url: String,
+    admin_api_url: String,
+    project_id: String,
     user_token_manager: UserTokenManager,
     api_auth_token_manager: ApiAuthTokenManager,
+    quota_tracker: Option<QuotaTracker>,
+    unknown_user_fields: UnknownUserFields,
 }
 
 impl FirebaseAuthClient {
@@ -35,6 +57,7 @@ impl FirebaseAuthClient {
             .build()
             .context("Failed to create HTTP client")?;
 
+        let project_id = service_account.project_id.clone();
         let credential_manager = ApiAuthTokenManager::new(service_account.clone());
         let token_handler = UserTokenManager::new(service_account, client.clone());
 
@@ -42,19 +65,248 @@ impl FirebaseAuthClient {
             user_token_manager: token_handler,
             client,
             api_url: "https://identitytoolkit.googleapis.com/v1".to_string(),
+            admin_api_url: "https://identitytoolkit.googleapis.com/admin/v2".to_string(),
+            project_id,
             api_auth_token_manager: credential_manager,
+            quota_tracker: None,
+            unknown_user_fields: UnknownUserFields::default(),
         })
     }
 
+    /// Configure the cache of the public keys used for verifying ID tokens.
+    /// See [`PublicKeyCacheOptions`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use std::time::Duration;
+    ///
+    /// use fireplace::auth::PublicKeyCacheOptions;
+    ///
+    /// let auth_client = auth_client.with_public_key_cache(
+    ///     PublicKeyCacheOptions::new()
+    ///         .min_ttl(Duration::from_secs(60))
+    ///         .max_staleness(Duration::from_secs(60 * 60)),
+    /// );
+    ///
+    /// // Warm the cache so the first verification doesn't have to wait
+    /// auth_client.prefetch_keys().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_public_key_cache(self, options: PublicKeyCacheOptions) -> Self {
+        Self {
+            user_token_manager: self.user_token_manager.with_public_key_cache(options),
+            ..self
+        }
+    }
+
+    /// Track the requests this client sends to the Identity Toolkit API, so
+    /// bulk jobs can keep their request rate below the project's quotas. The
+    /// usage can be read with [`api_usage`](Self::api_usage), and a callback
+    /// can be called when a threshold is reached. See [`QuotaTracking`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use std::time::Duration;
+    ///
+    /// use fireplace::auth::QuotaTracking;
+    ///
+    /// let auth_client = auth_client.with_quota_tracking(
+    ///     QuotaTracking::new().window(Duration::from_secs(10)),
+    /// );
+    ///
+    /// for user_id in ["a", "b", "c"] {
+    ///     // Back off before the project's quota is exceeded
+    ///     while auth_client.api_usage().qps() > 100.0 {
+    ///         tokio::time::sleep(Duration::from_millis(100)).await;
+    ///     }
+    ///     auth_client.get_user(user_id).await?;
+    /// }
+    ///
+    /// assert_eq!(auth_client.api_usage().total_requests, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_quota_tracking(self, tracking: QuotaTracking) -> Self {
+        Self {
+            quota_tracker: Some(QuotaTracker::new(tracking)),
+            ..self
+        }
+    }
+
+    /// Configure what happens when a user returned by
+    /// [`get_user`](Self::get_user) or [`list_users`](Self::list_users) has
+    /// attributes that fireplace doesn't know about, so you notice when
+    /// Google adds fields that should be modeled. By default, they are kept
+    /// in [`User::other`] without notice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::UnknownUserFields;
+    ///
+    /// let auth_client = auth_client.with_unknown_user_fields(UnknownUserFields::Warn);
+    ///
+    /// // Logs a warning if the user has attributes fireplace doesn't know
+    /// let user = auth_client.get_user("some-user-id").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_unknown_user_fields(self, policy: UnknownUserFields) -> Self {
+        Self {
+            unknown_user_fields: policy,
+            ..self
+        }
+    }
+
+    /// Returns the requests sent to the Identity Toolkit API so far. Always
+    /// empty unless [`with_quota_tracking`](Self::with_quota_tracking) was
+    /// used.
+    pub fn api_usage(&self) -> ApiUsage {
+        self.quota_tracker
+            .as_ref()
+            .map(QuotaTracker::usage)
+            .unwrap_or_default()
+    }
+
+    /// Fetches the public keys used for verifying ID tokens, unless they are
+    /// already cached. Call this at startup to avoid the first verifications
+    /// waiting for the keys to be fetched.
+    pub async fn prefetch_keys(&self) -> Result<(), FirebaseError> {
+        self.user_token_manager.prefetch_keys().await?;
+        Ok(())
+    }
+
+    /// Fetches the public keys used for verifying ID tokens, even if they are
+    /// already cached, e.g. after Google has rotated its keys.
+    pub async fn refresh_keys(&self) -> Result<(), FirebaseError> {
+        self.user_token_manager.refresh_keys().await?;
+        Ok(())
+    }
+
+    /// Gets an OAuth 2.0 access token for the service account with the given
+    /// scopes, which can be used to call other Google APIs. Tokens are cached
+    /// per set of scopes and refreshed once they expire.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// let access_token = auth_client
+    ///     .access_token(&["https://www.googleapis.com/auth/bigquery"])
+    ///     .await?;
+    ///
+    /// let request = reqwest::Client::new()
+    ///     .get("https://bigquery.googleapis.com/bigquery/v2/projects/my-project/datasets")
+    ///     .bearer_auth(access_token);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Get access token", skip(self))]
+    pub async fn access_token(&self, scopes: &[&str]) -> Result<String, FirebaseError> {
+        let access_token = self
+            .api_auth_token_manager
+            .get_access_token_with_scopes(scopes)
+            .await?;
+
+        Ok(access_token)
+    }
+
     fn url(&self, path: impl AsRef<str>) -> String {
         format!("{}{}", self.api_url, path.as_ref())
     }
 
+    /// Like [`url`](Self::url), but for endpoints that are scoped to the
+    /// project.
+    fn project_url(&self, path: impl AsRef<str>) -> String {
+        format!(
+            "{}/projects/{}{}",
+            self.api_url,
+            self.project_id,
+            path.as_ref()
+        )
+    }
+
+    /// Applies the configured [`UnknownUserFields`] policy to users returned
+    /// by the API.
+    fn check_unknown_fields(&self, users: &[User]) -> Result<(), FirebaseError> {
+        if self.unknown_user_fields == UnknownUserFields::Ignore {
+            return Ok(());
+        }
+
+        for user in users {
+            let unknown = user.unknown_fields();
+            if unknown.is_empty() {
+                continue;
+            }
+
+            match self.unknown_user_fields {
+                UnknownUserFields::Ignore => {}
+                UnknownUserFields::Warn => tracing::warn!(
+                    "User '{}' has unknown attributes: {}",
+                    user.uid,
+                    unknown.join(", ")
+                ),
+                UnknownUserFields::Deny => {
+                    return Err(anyhow::anyhow!(
+                        "User '{}' has unknown attributes: {}",
+                        user.uid,
+                        unknown.join(", ")
+                    )
+                    .into())
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`project_url`](Self::project_url), but for the admin API, which
+    /// manages the configuration of the project.
+    fn admin_project_url(&self, path: impl AsRef<str>) -> String {
+        format!(
+            "{}/projects/{}{}",
+            self.admin_api_url,
+            self.project_id,
+            path.as_ref()
+        )
+    }
+
     /// Creates a new `POST` request builder with the `Authorization` header set
     /// to an authorized admin access token.
     async fn auth_post(
         &self,
         url: impl AsRef<str>,
+    ) -> Result<reqwest::RequestBuilder, FirebaseError> {
+        self.auth_request(Method::POST, url).await
+    }
+
+    /// Creates a new `GET` request builder with the `Authorization` header set
+    /// to an authorized admin access token.
+    async fn auth_get(
+        &self,
+        url: impl AsRef<str>,
+    ) -> Result<reqwest::RequestBuilder, FirebaseError> {
+        self.auth_request(Method::GET, url).await
+    }
+
+    async fn auth_request(
+        &self,
+        method: Method,
+        url: impl AsRef<str>,
     ) -> Result<reqwest::RequestBuilder, FirebaseError> {
         let access_token = self
             .api_auth_token_manager
@@ -65,9 +317,13 @@ impl FirebaseAuthClient {
                 e
             })?;
 
+        if let Some(tracker) = &self.quota_tracker {
+            tracker.record_request();
+        }
+
         let builder = self
             .client
-            .post(url.as_ref())
+            .request(method, url.as_ref())
             .header("Authorization", format!("Bearer {}", access_token));
 
         Ok(builder)
@@ -209,22 +465,113 @@ impl FirebaseAuthClient {
         Ok(id_token_claims)
     }
 
+    /// Like [`decode_id_token`](Self::decode_id_token), but also checks that
+    /// the user signed in to the given tenant of a multi-tenant project.
+    /// Fails with [`TenantMismatch`](FirebaseError::TenantMismatch) if the
+    /// token belongs to another tenant or to no tenant at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # use ulid::Ulid;
+    /// # use fireplace::auth::models::NewUser;
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// # let user_id = auth_client
+    /// #     .create_user(NewUser {
+    /// #         display_name: None,
+    /// #         email: format!("{}@example.com", Ulid::new()),
+    /// #         password: Ulid::new().to_string(),
+    /// #     })
+    /// #     .await?;
+    /// # let custom_token = auth_client.create_custom_token(&user_id).await?;
+    /// # let id_token = auth_client.sign_in_with_custom_token(&custom_token).await?;
+    /// use fireplace::{auth::models::IdTokenClaims, error::FirebaseError};
+    ///
+    /// // The user signed in to the project itself rather than to a tenant
+    /// let result = auth_client
+    ///     .decode_id_token_for_tenant::<IdTokenClaims>(&id_token, "tenant-a")
+    ///     .await;
+    ///
+    /// assert!(matches!(
+    ///     result,
+    ///     Err(FirebaseError::TenantMismatch { actual: None, .. })
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Decode tenant ID token", skip(self, token))]
+    pub async fn decode_id_token_for_tenant<C: DeserializeOwned>(
+        &self,
+        token: &str,
+        tenant_id: &str,
+    ) -> Result<C, FirebaseError> {
+        let claims = self.decode_id_token(token).await?;
+        claims_for_tenant(claims, tenant_id)
+    }
+
     /// Create a custom token for a user, which can then be used to sign into
     /// Firebase.
     ///
     /// # Examples
     ///
     /// See the first example for [`decode_id_token`](Self::decode_id_token).
-    #[tracing::instrument(name = "Create custom token", skip(self, user_id))]
     pub async fn create_custom_token(
         &self,
         user_id: impl AsRef<str>,
+    ) -> Result<String, FirebaseError> {
+        self.create_custom_token_with_options(user_id, CustomTokenOptions::default())
+            .await
+    }
+
+    /// Like [`create_custom_token`](Self::create_custom_token), but allows
+    /// configuring the lifetime of the token. Fails if the lifetime is outside
+    /// of the bounds allowed by Firebase.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use std::time::Duration;
+    ///
+    /// use fireplace::auth::models::CustomTokenOptions;
+    ///
+    /// // A short-lived token that tolerates a bit of clock skew
+    /// let options = CustomTokenOptions::new()
+    ///     .ttl(Duration::from_secs(5 * 60))
+    ///     .issued_at_skew(Duration::from_secs(30));
+    ///
+    /// let custom_token = auth_client
+    ///     .create_custom_token_with_options("some-user-id", options)
+    ///     .await?;
+    ///
+    /// // Firebase does not allow custom tokens to live longer than an hour
+    /// let too_long = CustomTokenOptions::new().ttl(Duration::from_secs(2 * 60 * 60));
+    /// let result = auth_client
+    ///     .create_custom_token_with_options("some-user-id", too_long)
+    ///     .await;
+    ///
+    /// assert!(result.is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Create custom token", skip(self, user_id))]
+    pub async fn create_custom_token_with_options(
+        &self,
+        user_id: impl AsRef<str>,
+        options: CustomTokenOptions,
     ) -> Result<String, FirebaseError> {
         let user_id = user_id.as_ref();
 
         tracing::debug!("Creating custom token for user '{}'", user_id);
 
-        let id_token_claims = self.user_token_manager.create_custom_token(user_id).await?;
+        let id_token_claims = self
+            .user_token_manager
+            .create_custom_token(user_id, &options)
+            .await?;
 
         Ok(id_token_claims)
     }
@@ -302,10 +649,335 @@ impl FirebaseAuthClient {
         let res_body: GetAccountInfoResponse =
             res.json().await.context("Failed to read response JSON")?;
         let user = res_body.users.and_then(|mut users| users.pop());
+        if let Some(user) = &user {
+            self.check_unknown_fields(std::slice::from_ref(user))?;
+        }
 
         Ok(user)
     }
 
+    /// Retrieves a single page of users. Use the returned
+    /// [`next_page_token`](UsersPage::next_page_token) to fetch the following
+    /// page, or use [`get_all_users`](Self::get_all_users) to iterate over all
+    /// users without handling pagination yourself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::ListUsersOptions;
+    ///
+    /// let first_page = auth_client
+    ///     .list_users(ListUsersOptions::new().max_results(2))
+    ///     .await?;
+    ///
+    /// assert!(first_page.users.len() <= 2);
+    ///
+    /// // The page token can be stored and used later to continue from where
+    /// // we left off
+    /// if let Some(token) = first_page.next_page_token {
+    ///     let second_page = auth_client
+    ///         .list_users(ListUsersOptions::new().max_results(2).page_token(token))
+    ///         .await?;
+    ///
+    ///     assert!(second_page.users.len() <= 2);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "List users", skip(self))]
+    pub async fn list_users(&self, options: ListUsersOptions) -> Result<UsersPage, FirebaseError> {
+        let max_results = options.max_results.unwrap_or(MAX_LIST_USERS_RESULTS);
+
+        let mut query = vec![("maxResults", max_results.to_string())];
+        if let Some(page_token) = options.page_token {
+            query.push(("nextPageToken", page_token));
+        }
+
+        let res = self
+            .auth_get(self.project_url("/accounts:batchGet"))
+            .await?
+            .query(&query)
+            .send()
+            .await
+            .context("Failed to send list users request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to list users", res).await);
+        }
+
+        let res_body: DownloadAccountResponse =
+            res.json().await.context("Failed to read response JSON")?;
+        let users = res_body.users.unwrap_or_default();
+        self.check_unknown_fields(&users)?;
+
+        Ok(UsersPage {
+            users,
+            next_page_token: res_body.next_page_token.filter(|t| !t.is_empty()),
+        })
+    }
+
+    /// Returns a stream of all users in the project, fetching pages of users
+    /// as the stream is consumed. The page size and the page to start from
+    /// can be controlled with the given options.
+    ///
+    /// Firebase lists users ordered by their ID, and each page token points
+    /// just past the last user of its page, so the listing is not a snapshot:
+    ///
+    /// - Users created mid-scan are included if their ID sorts after the
+    ///   current position, and are otherwise missed.
+    /// - Users deleted mid-scan are missing if they hadn't been reached yet.
+    /// - Users updated mid-scan appear once, with the data they had when
+    ///   their page was fetched.
+    ///
+    /// If users created or deleted mid-scan shift a page boundary so that a
+    /// page repeats users from the previous page, the repeated users are
+    /// skipped. Only the previous page is remembered, so a user could still
+    /// be yielded twice if a boundary shifts by more than a page; dedupe by
+    /// `uid` if that matters. If Firebase returns the same page token again,
+    /// the stream fails instead of looping forever.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::ListUsersOptions;
+    /// use futures::TryStreamExt;
+    ///
+    /// let mut users = auth_client.get_all_users(ListUsersOptions::new().max_results(100));
+    ///
+    /// while let Some(user) = users.try_next().await? {
+    ///     println!("Found user with ID '{}'", user.uid);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_all_users(&self, options: ListUsersOptions) -> AuthStream<'_, User> {
+        let state = (Some(options), PageDeduplicator::default());
+
+        let pages =
+            futures::stream::try_unfold(state, move |(options, mut deduplicator)| async move {
+                let options = match options {
+                    Some(options) => options,
+                    None => return Ok(None),
+                };
+
+                let page = self.list_users(options.clone()).await?;
+
+                if page.next_page_token.is_some() && page.next_page_token == options.page_token {
+                    return Err(anyhow::anyhow!(
+                        "Listing users returned the same page token twice"
+                    )
+                    .into());
+                }
+
+                let users = deduplicator.dedupe(page.users);
+                let users = futures::stream::iter(users.into_iter().map(Ok::<User, FirebaseError>));
+                let next_options = page.next_page_token.map(|token| options.page_token(token));
+
+                Ok::<_, FirebaseError>(Some((users, (next_options, deduplicator))))
+            });
+
+        Box::pin(pages.try_flatten())
+    }
+
+    /// Returns a stream of users whose display name starts with the given
+    /// prefix, ignoring case. At most `max_matches` users are returned.
+    ///
+    /// Firebase Auth has no server-side search, so this pages through all
+    /// users and filters them as they come in. Only one page of users is held
+    /// in memory at a time, and no more pages are fetched once enough matches
+    /// have been found or the stream is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use futures::TryStreamExt;
+    ///
+    /// let matches: Vec<_> = auth_client.search_users("jo", 10).try_collect().await?;
+    ///
+    /// assert!(matches.len() <= 10);
+    /// for user in matches {
+    ///     let display_name = user.display_name.unwrap().to_lowercase();
+    ///     assert!(display_name.starts_with("jo"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_users(
+        &self,
+        prefix: impl AsRef<str>,
+        max_matches: usize,
+    ) -> AuthStream<'_, User> {
+        let prefix = prefix.as_ref().to_lowercase();
+
+        let matches = self
+            .get_all_users(ListUsersOptions::new())
+            .try_filter(move |user| {
+                let is_match = user
+                    .display_name
+                    .as_ref()
+                    .map_or(false, |name| name.to_lowercase().starts_with(&prefix));
+                future::ready(is_match)
+            })
+            .take(max_matches);
+
+        Box::pin(matches)
+    }
+
+    /// Returns a stream of users who have not been active since the given
+    /// time, e.g. to find dormant accounts to clean up. A user's last activity
+    /// is the latest of when they were created, last signed in, and last
+    /// refreshed their ID token. See [`User::login_history`].
+    ///
+    /// Like [`search_users`](Self::search_users), this pages through all
+    /// users and filters them as they come in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use std::time::{Duration, SystemTime};
+    /// use futures::TryStreamExt;
+    ///
+    /// let a_year_ago = SystemTime::now() - Duration::from_secs(365 * 24 * 60 * 60);
+    /// let mut dormant = auth_client.users_inactive_since(a_year_ago);
+    ///
+    /// while let Some(user) = dormant.try_next().await? {
+    ///     let last_active_at = user.login_history().last_active_at().unwrap();
+    ///     assert!(last_active_at < a_year_ago);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn users_inactive_since(&self, since: SystemTime) -> AuthStream<'_, User> {
+        let inactive = self
+            .get_all_users(ListUsersOptions::new())
+            .try_filter(move |user| future::ready(user.login_history().is_inactive_since(since)));
+
+        Box::pin(inactive)
+    }
+
+    /// Writes all users to the given writer as newline-delimited JSON, one
+    /// user per line. Returns the number of exported users.
+    ///
+    /// Users are fetched page by page as they are written, so a slow writer
+    /// will not cause the whole user base to be buffered in memory. Each line
+    /// can be deserialized into a [`User`] again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::{ListUsersOptions, User};
+    ///
+    /// let mut output = Vec::new();
+    /// let exported = auth_client
+    ///     .export_users(ListUsersOptions::new(), &mut output)
+    ///     .await?;
+    ///
+    /// let lines = String::from_utf8(output).unwrap();
+    /// assert_eq!(lines.lines().count(), exported);
+    ///
+    /// for line in lines.lines() {
+    ///     let user: User = serde_json::from_str(line).unwrap();
+    ///     println!("Exported user with ID '{}'", user.uid);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Export users", skip(self, writer))]
+    pub async fn export_users<W: AsyncWrite + Unpin>(
+        &self,
+        options: ListUsersOptions,
+        writer: &mut W,
+    ) -> Result<usize, FirebaseError> {
+        let mut users = self.get_all_users(options);
+        let mut exported = 0;
+
+        while let Some(user) = users.try_next().await? {
+            let mut line = serde_json::to_vec(&user).context("Failed to serialize user")?;
+            line.push(b'\n');
+
+            writer
+                .write_all(&line)
+                .await
+                .context("Failed to write exported user")?;
+
+            exported += 1;
+        }
+
+        writer
+            .flush()
+            .await
+            .context("Failed to flush exported users")?;
+
+        tracing::info!("Exported {} users", exported);
+
+        Ok(exported)
+    }
+
+    /// Retrieves the configuration of the hash function used for the
+    /// passwords of the project, which is needed to verify the exported
+    /// [`password_hash`](User::password_hash) of users in another auth
+    /// system. Returns `None` if the project has no hash configuration.
+    ///
+    /// The service account needs permission to read the project's auth
+    /// configuration, e.g. through the Firebase Authentication Admin role.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::ListUsersOptions;
+    /// use futures::TryStreamExt;
+    ///
+    /// let hash_config = auth_client.get_password_hash_config().await?;
+    /// println!("Passwords are hashed with {:?}", hash_config.map(|c| c.algorithm));
+    ///
+    /// let mut users = auth_client.get_all_users(ListUsersOptions::new());
+    /// while let Some(user) = users.try_next().await? {
+    ///     if let (Some(hash), Some(salt)) = (user.password_hash, user.salt) {
+    ///         println!("User '{}' has password hash {} with salt {}", user.uid, hash, salt);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Get password hash config", skip(self))]
+    pub async fn get_password_hash_config(
+        &self,
+    ) -> Result<Option<PasswordHashConfig>, FirebaseError> {
+        let res = self
+            .auth_get(self.admin_project_url("/config"))
+            .await?
+            .send()
+            .await
+            .context("Failed to send get project config request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to get project config", res).await);
+        }
+
+        let res_body: ProjectConfigResponse =
+            res.json().await.context("Failed to read response JSON")?;
+
+        Ok(res_body.sign_in.and_then(|sign_in| sign_in.hash_config))
+    }
+
     /// Creates a new user in Firebase Auth using the email/password provider.
     ///
     /// # Examples
@@ -455,6 +1127,104 @@ impl FirebaseAuthClient {
         Ok(res_body)
     }
 
+    /// Changes the email of a user and returns a link that the user can open
+    /// to verify the new email. The email is marked as unverified until the
+    /// link has been opened.
+    ///
+    /// The link is only returned, not sent, so you can deliver it however you
+    /// like, e.g. in your own email template.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::{ActionCodeSettings, NewUser};
+    /// use ulid::Ulid;
+    ///
+    /// let user_id = auth_client
+    ///     .create_user(NewUser {
+    ///         display_name: None,
+    ///         email: format!("{}@example.com", Ulid::new()),
+    ///         password: Ulid::new().to_string(),
+    ///     })
+    ///     .await?;
+    ///
+    /// let new_email = format!("{}@example.com", Ulid::new()).to_lowercase();
+    /// let link = auth_client
+    ///     .update_email_with_verification(
+    ///         &user_id,
+    ///         &new_email,
+    ///         ActionCodeSettings::new("https://example.com/email-verified"),
+    ///     )
+    ///     .await?;
+    ///
+    /// println!("Send this link to {new_email}: {link}");
+    ///
+    /// let user = auth_client.get_user(&user_id).await?.unwrap();
+    /// assert_eq!(user.email, Some(new_email));
+    /// assert_eq!(user.email_verified, Some(false));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Update email with verification", skip_all, fields(user_id = %user_id.as_ref()))]
+    pub async fn update_email_with_verification(
+        &self,
+        user_id: impl AsRef<str>,
+        new_email: impl AsRef<str>,
+        action_code_settings: ActionCodeSettings,
+    ) -> Result<String, FirebaseError> {
+        let new_email = new_email.as_ref();
+
+        self.update_user(
+            user_id,
+            UpdateUserValues::new()
+                .email(new_email)
+                .email_verified(false),
+        )
+        .await?;
+
+        let body_values =
+            SendOobCodeBody::verify_email(new_email, Some(action_code_settings.clone()));
+        let body = serde_json::to_string(&body_values)
+            .context("Failed to serialize email verification request")?;
+
+        let res = self
+            .auth_post(self.project_url("/accounts:sendOobCode"))
+            .await?
+            .body(body)
+            .send()
+            .await
+            .context("Failed to send email verification link request")?;
+
+        if !res.status().is_success() {
+            let err_res = res
+                .json::<AuthApiErrorResponse>()
+                .await
+                .context("Failed to read error response JSON")?;
+            let err = match action_code_settings.error_for_code(err_res.code()) {
+                Some(settings_err) => FirebaseError::InvalidActionCodeSettings(settings_err),
+                None => err_res.into(),
+            };
+
+            tracing::error!("Failed to generate email verification link: {err}");
+
+            return Err(err);
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SendOobCodeResponse {
+            oob_link: String,
+        }
+
+        let res_body: SendOobCodeResponse =
+            res.json().await.context("Failed to read response JSON")?;
+
+        Ok(res_body.oob_link)
+    }
+
     /// Signs into Firebase with a custom generated token, which you can get
     /// from [`create_custom_token`](Self::create_custom_token). Returns an ID
     /// token for Firebase.
@@ -583,6 +1353,176 @@ impl FirebaseAuthClient {
 
         Ok(())
     }
+
+    /// Sets the custom claims of many users, e.g. when rolling out a new role
+    /// scheme. Works like [`set_custom_user_claims`](Self::set_custom_user_claims)
+    /// for each user, sending up to [`BULK_CLAIMS_CONCURRENCY`] updates at a
+    /// time.
+    ///
+    /// A failure for one user doesn't stop the others from being updated.
+    /// Instead, the failures are collected in the returned result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::NewUser;
+    /// use ulid::Ulid;
+    ///
+    /// let mut user_ids = vec![];
+    /// for _ in 0..3 {
+    ///     let user_id = auth_client
+    ///         .create_user(NewUser {
+    ///             display_name: None,
+    ///             email: format!("{}@example.com", Ulid::new()),
+    ///             password: Ulid::new().to_string(),
+    ///         })
+    ///         .await?;
+    ///     user_ids.push(user_id);
+    /// }
+    ///
+    /// let claims = user_ids
+    ///     .iter()
+    ///     .map(|user_id| (user_id.clone(), serde_json::json!({ "role": "seller" })))
+    ///     .collect();
+    ///
+    /// let result = auth_client.set_custom_user_claims_bulk(claims).await;
+    ///
+    /// assert!(result.is_success());
+    /// assert_eq!(result.success_count, 3);
+    ///
+    /// let user = auth_client.get_user(&user_ids[0]).await?.unwrap();
+    /// assert_eq!(user.custom_claims["role"], "seller");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Set custom user claims in bulk", skip_all, fields(count = claims.len()))]
+    pub async fn set_custom_user_claims_bulk<C: Serialize>(
+        &self,
+        claims: Vec<(String, C)>,
+    ) -> BulkClaimsResult {
+        let result = futures::stream::iter(claims)
+            .map(|(user_id, new_claims)| async move {
+                let result = self.set_custom_user_claims(&user_id, new_claims).await;
+                (user_id, result)
+            })
+            .buffer_unordered(BULK_CLAIMS_CONCURRENCY)
+            .fold(BulkClaimsResult::default(), |mut acc, (user_id, result)| {
+                acc.record(user_id, result);
+                future::ready(acc)
+            })
+            .await;
+
+        tracing::info!(
+            "Set custom claims for {} users, {} failed",
+            result.success_count,
+            result.failures.len()
+        );
+
+        result
+    }
+
+    /// Disables a user and records why in the user's custom claims, under a
+    /// `disabled` claim containing the given reason and the time of disabling
+    /// in seconds since the Unix epoch. Existing claims are merged with it.
+    ///
+    /// Disabling the user and writing the claims happens in a single update,
+    /// so the user is never disabled without a recorded reason or vice versa.
+    ///
+    /// Merging the claims is **not atomic**: the existing claims are read
+    /// first and written back along with the reason, and the Auth API has no
+    /// way to make the update conditional on the claims being unchanged.
+    /// Claims set by another writer in between are lost, so don't call this
+    /// concurrently with other updates of the user's claims.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+    /// use fireplace::auth::models::NewUser;
+    /// use ulid::Ulid;
+    ///
+    /// let user_id = auth_client
+    ///     .create_user(NewUser {
+    ///         display_name: None,
+    ///         email: format!("{}@example.com", Ulid::new()),
+    ///         password: Ulid::new().to_string(),
+    ///     })
+    ///     .await?;
+    ///
+    /// auth_client
+    ///     .set_custom_user_claims(&user_id, serde_json::json!({ "role": "seller" }))
+    ///     .await?;
+    ///
+    /// let user = auth_client
+    ///     .disable_user_merging_claims(&user_id, "spam")
+    ///     .await?;
+    ///
+    /// assert_eq!(user.disabled, Some(true));
+    /// assert_eq!(user.custom_claims["role"], "seller");
+    /// assert_eq!(user.custom_claims["disabled"]["reason"], "spam");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Disable user merging claims", skip_all, fields(user_id = %user_id.as_ref()))]
+    pub async fn disable_user_merging_claims(
+        &self,
+        user_id: impl AsRef<str>,
+        reason: impl Into<String>,
+    ) -> Result<User, FirebaseError> {
+        let user_id = user_id.as_ref();
+
+        // Not atomic, see the docs
+        let user = self
+            .get_user(user_id)
+            .await?
+            .ok_or(FirebaseError::UserNotFound)?;
+
+        let disabled_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .context("System time is before the Unix epoch")?
+            .as_secs();
+
+        let mut custom_claims = match user.custom_claims {
+            serde_json::Value::Object(claims) => claims,
+            _ => serde_json::Map::new(),
+        };
+        custom_claims.insert(
+            "disabled".to_string(),
+            serde_json::json!({
+                "reason": reason.into(),
+                "at": disabled_at,
+            }),
+        );
+
+        let body = serde_json::json!({
+            "localId": user_id,
+            "disableUser": true,
+            "customAttributes": serde_json::Value::Object(custom_claims).to_string(),
+        });
+
+        let res = self
+            .auth_post(self.url("/accounts:update"))
+            .await?
+            .body(body.to_string())
+            .send()
+            .await
+            .context("Failed to send disable user request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to disable user", res).await);
+        }
+
+        let res_body: User = res.json().await.context("Failed to read response JSON")?;
+
+        tracing::info!("Disabled user with id '{}'", &res_body.uid);
+
+        Ok(res_body)
+    }
 }
 
 async fn response_error(msg: &'static str, res: Response) -> FirebaseError {
@@ -0,0 +1,207 @@
+use serde::Serialize;
+
+/// Settings for email action links, such as email verification links. They
+/// decide where the user is sent after completing the action, and whether the
+/// link should be opened in a mobile app.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionCodeSettings {
+    #[serde(rename = "continueUrl")]
+    url: String,
+    #[serde(rename = "canHandleCodeInApp")]
+    handle_code_in_app: bool,
+    #[serde(rename = "iOSBundleId", skip_serializing_if = "Option::is_none")]
+    ios_bundle_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    android_package_name: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    android_install_app: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    android_minimum_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dynamic_link_domain: Option<String>,
+}
+
+impl ActionCodeSettings {
+    /// Create settings that send the user to the given URL after completing
+    /// the action.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            handle_code_in_app: false,
+            ios_bundle_id: None,
+            android_package_name: None,
+            android_install_app: false,
+            android_minimum_version: None,
+            dynamic_link_domain: None,
+        }
+    }
+
+    /// Whether the link should be opened in a mobile app rather than in the
+    /// browser.
+    pub fn handle_code_in_app(mut self, handle_code_in_app: bool) -> Self {
+        self.handle_code_in_app = handle_code_in_app;
+        self
+    }
+
+    /// The bundle ID of the iOS app to open the link in.
+    pub fn ios_bundle_id(mut self, bundle_id: impl Into<String>) -> Self {
+        self.ios_bundle_id = Some(bundle_id.into());
+        self
+    }
+
+    /// The package name of the Android app to open the link in. Optionally
+    /// installs the app if it isn't already, as long as it is at least the
+    /// given version.
+    pub fn android_package_name(
+        mut self,
+        package_name: impl Into<String>,
+        install_app: bool,
+        minimum_version: Option<impl Into<String>>,
+    ) -> Self {
+        self.android_package_name = Some(package_name.into());
+        self.android_install_app = install_app;
+        self.android_minimum_version = minimum_version.map(Into::into);
+        self
+    }
+
+    /// The dynamic link domain to use for the link.
+    pub fn dynamic_link_domain(mut self, domain: impl Into<String>) -> Self {
+        self.dynamic_link_domain = Some(domain.into());
+        self
+    }
+}
+
+/// Why the Firebase Auth API rejected the [`ActionCodeSettings`] of an email
+/// action link, along with the offending setting where there is one.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ActionCodeSettingsError {
+    #[error("The continue URL '{url}' is invalid")]
+    InvalidContinueUrl { url: String },
+
+    #[error("A continue URL is required")]
+    MissingContinueUrl,
+
+    #[error(
+        "The domain '{domain}' of the continue URL must be added to the authorized domains of the project"
+    )]
+    UnauthorizedDomain { domain: String },
+
+    #[error("An iOS bundle ID is required when the link is handled in an app")]
+    MissingIosBundleId,
+
+    #[error("An Android package name is required when the Android app should be installed")]
+    MissingAndroidPackageName,
+
+    #[error("The dynamic link domain is not configured for the project")]
+    InvalidDynamicLinkDomain { domain: Option<String> },
+}
+
+impl ActionCodeSettings {
+    /// Maps an error code of the Firebase Auth API to the setting that caused
+    /// it, if the error is about the settings at all.
+    pub(crate) fn error_for_code(&self, code: &str) -> Option<ActionCodeSettingsError> {
+        let err = match code {
+            "INVALID_CONTINUE_URI" => ActionCodeSettingsError::InvalidContinueUrl {
+                url: self.url.clone(),
+            },
+            "MISSING_CONTINUE_URI" => ActionCodeSettingsError::MissingContinueUrl,
+            "UNAUTHORIZED_DOMAIN" => ActionCodeSettingsError::UnauthorizedDomain {
+                domain: url_host(&self.url).to_string(),
+            },
+            "MISSING_IOS_BUNDLE_ID" => ActionCodeSettingsError::MissingIosBundleId,
+            "MISSING_ANDROID_PACKAGE_NAME" => ActionCodeSettingsError::MissingAndroidPackageName,
+            "INVALID_DYNAMIC_LINK_DOMAIN" => ActionCodeSettingsError::InvalidDynamicLinkDomain {
+                domain: self.dynamic_link_domain.clone(),
+            },
+            _ => return None,
+        };
+
+        Some(err)
+    }
+}
+
+/// The host of a URL, e.g. `example.com` for `https://user@example.com:8080/a`.
+fn url_host(url: &str) -> &str {
+    let after_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = after_scheme
+        .split(|c| matches!(c, '/' | '?' | '#'))
+        .next()
+        .unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+
+    host.split(':').next().unwrap_or_default()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SendOobCodeBody<'a> {
+    request_type: &'static str,
+    email: &'a str,
+    return_oob_link: bool,
+    #[serde(flatten)]
+    settings: Option<ActionCodeSettings>,
+}
+
+impl<'a> SendOobCodeBody<'a> {
+    pub(crate) fn verify_email(email: &'a str, settings: Option<ActionCodeSettings>) -> Self {
+        Self {
+            request_type: "VERIFY_EMAIL",
+            email,
+            return_oob_link: true,
+            settings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_verification_request_with_settings() {
+        let settings = ActionCodeSettings::new("https://example.com/verified")
+            .handle_code_in_app(true)
+            .ios_bundle_id("com.example.ios");
+
+        let body = serde_json::to_value(SendOobCodeBody::verify_email(
+            "mario@example.com",
+            Some(settings),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "requestType": "VERIFY_EMAIL",
+                "email": "mario@example.com",
+                "returnOobLink": true,
+                "continueUrl": "https://example.com/verified",
+                "canHandleCodeInApp": true,
+                "iOSBundleId": "com.example.ios",
+            })
+        );
+    }
+
+    #[test]
+    fn maps_error_codes_to_offending_settings() {
+        let settings = ActionCodeSettings::new("https://tenant.example.com:8080/welcome?step=2")
+            .dynamic_link_domain("example.page.link");
+
+        assert_eq!(
+            settings.error_for_code("UNAUTHORIZED_DOMAIN"),
+            Some(ActionCodeSettingsError::UnauthorizedDomain {
+                domain: "tenant.example.com".to_string()
+            })
+        );
+        assert_eq!(
+            settings.error_for_code("INVALID_DYNAMIC_LINK_DOMAIN"),
+            Some(ActionCodeSettingsError::InvalidDynamicLinkDomain {
+                domain: Some("example.page.link".to_string())
+            })
+        );
+        assert_eq!(settings.error_for_code("EMAIL_NOT_FOUND"), None);
+    }
+}
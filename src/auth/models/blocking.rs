@@ -0,0 +1,352 @@
+use anyhow::Context;
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+use crate::error::FirebaseError;
+
+const EVENT_TYPE_PREFIX: &str = "providers/cloud.auth/eventTypes/user.";
+
+/// The body of a request that Identity Platform sends to a blocking function.
+/// Verify it with
+/// [`BlockingTokenVerifier`](crate::auth::BlockingTokenVerifier).
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockingRequest {
+    pub data: BlockingRequestData,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockingRequestData {
+    /// The signed token describing the event.
+    pub jwt: String,
+}
+
+/// The kind of event a blocking function is called for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockingEventType {
+    BeforeCreate,
+    BeforeSignIn,
+    /// An event type that fireplace doesn't know about yet.
+    Other(String),
+}
+
+/// An event a blocking function is called for, decoded from the verified
+/// token of a [`BlockingRequest`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockingEvent {
+    pub event_id: String,
+    /// The raw event type, e.g.
+    /// `providers/cloud.auth/eventTypes/user.beforeCreate:password`. See
+    /// [`event_type`](Self::event_type) for a parsed version.
+    #[serde(rename = "event_type")]
+    pub raw_event_type: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub locale: Option<String>,
+    /// How the user signed in, e.g. `password` or `google.com`.
+    pub sign_in_method: Option<String>,
+    pub tenant_id: Option<String>,
+    pub user_record: BlockingUserRecord,
+    /// The user info returned by the identity provider, as a JSON string.
+    pub raw_user_info: Option<String>,
+    pub oauth_id_token: Option<String>,
+    pub oauth_access_token: Option<String>,
+    pub oauth_refresh_token: Option<String>,
+    pub oauth_expires_in: Option<u64>,
+    /// All other claims of the token.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl BlockingEvent {
+    pub fn event_type(&self) -> BlockingEventType {
+        let event_type = self
+            .raw_event_type
+            .strip_prefix(EVENT_TYPE_PREFIX)
+            .and_then(|s| s.split(':').next());
+
+        match event_type {
+            Some("beforeCreate") => BlockingEventType::BeforeCreate,
+            Some("beforeSignIn") => BlockingEventType::BeforeSignIn,
+            _ => BlockingEventType::Other(self.raw_event_type.clone()),
+        }
+    }
+}
+
+/// The user that is being created or signed in.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockingUserRecord {
+    pub uid: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub display_name: Option<String>,
+    pub photo_url: Option<String>,
+    pub phone_number: Option<String>,
+    pub disabled: Option<bool>,
+    pub metadata: Option<BlockingUserMetadata>,
+    #[serde(default)]
+    pub provider_data: Vec<BlockingUserInfo>,
+    #[serde(default)]
+    pub custom_claims: serde_json::Value,
+    pub tenant_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockingUserMetadata {
+    /// When the user was created, in milliseconds since the Unix epoch.
+    pub creation_time: Option<i64>,
+    /// When the user last signed in, in milliseconds since the Unix epoch.
+    pub last_sign_in_time: Option<i64>,
+}
+
+/// A user's identity with a single provider.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockingUserInfo {
+    pub uid: Option<String>,
+    pub provider_id: String,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub photo_url: Option<String>,
+    pub phone_number: Option<String>,
+}
+
+/// The response of a blocking function that lets the user through, optionally
+/// updating some of their attributes. Serialize it as the response body.
+#[derive(Debug, Clone, Default)]
+pub struct BlockingResponse {
+    display_name: Option<String>,
+    photo_url: Option<String>,
+    disabled: Option<bool>,
+    email_verified: Option<bool>,
+    custom_claims: Option<serde_json::Value>,
+    session_claims: Option<serde_json::Value>,
+}
+
+impl BlockingResponse {
+    /// Create a response that lets the user through without changes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    pub fn photo_url(mut self, photo_url: impl Into<String>) -> Self {
+        self.photo_url = Some(photo_url.into());
+        self
+    }
+
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.disabled = Some(disabled);
+        self
+    }
+
+    pub fn email_verified(mut self, email_verified: bool) -> Self {
+        self.email_verified = Some(email_verified);
+        self
+    }
+
+    /// Set the user's custom claims, which are stored on the user and end up
+    /// in all of their ID tokens. Only allowed in `beforeCreate` and
+    /// `beforeSignIn`.
+    pub fn custom_claims<C: Serialize>(mut self, claims: C) -> Result<Self, FirebaseError> {
+        self.custom_claims =
+            Some(serde_json::to_value(claims).context("Failed to serialize custom claims")?);
+        Ok(self)
+    }
+
+    /// Set claims that only end up in the ID token of this sign-in. Only
+    /// allowed in `beforeSignIn`.
+    pub fn session_claims<C: Serialize>(mut self, claims: C) -> Result<Self, FirebaseError> {
+        self.session_claims =
+            Some(serde_json::to_value(claims).context("Failed to serialize session claims")?);
+        Ok(self)
+    }
+}
+
+impl Serialize for BlockingResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct UserRecord<'a> {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            display_name: Option<&'a String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            photo_url: Option<&'a String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            disabled: Option<&'a bool>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            email_verified: Option<&'a bool>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            custom_claims: Option<&'a serde_json::Value>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            session_claims: Option<&'a serde_json::Value>,
+            update_mask: String,
+        }
+
+        let update_mask = [
+            ("displayName", self.display_name.is_some()),
+            ("photoUrl", self.photo_url.is_some()),
+            ("disabled", self.disabled.is_some()),
+            ("emailVerified", self.email_verified.is_some()),
+            ("customClaims", self.custom_claims.is_some()),
+            ("sessionClaims", self.session_claims.is_some()),
+        ]
+        .iter()
+        .filter(|(_, is_set)| *is_set)
+        .map(|(field, _)| *field)
+        .collect::<Vec<_>>()
+        .join(",");
+
+        if update_mask.is_empty() {
+            return serializer.serialize_struct("BlockingResponse", 0)?.end();
+        }
+
+        let mut response = serializer.serialize_struct("BlockingResponse", 1)?;
+        response.serialize_field(
+            "userRecord",
+            &UserRecord {
+                display_name: self.display_name.as_ref(),
+                photo_url: self.photo_url.as_ref(),
+                disabled: self.disabled.as_ref(),
+                email_verified: self.email_verified.as_ref(),
+                custom_claims: self.custom_claims.as_ref(),
+                session_claims: self.session_claims.as_ref(),
+                update_mask,
+            },
+        )?;
+        response.end()
+    }
+}
+
+/// The response of a blocking function that blocks the user from being
+/// created or signing in. Serialize it as the response body, and respond with
+/// the status code from [`http_status`](Self::http_status).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockingError {
+    status: &'static str,
+    http_status: u16,
+    message: String,
+}
+
+impl BlockingError {
+    /// The user isn't allowed to sign up or sign in, e.g. because their email
+    /// domain isn't allowed.
+    pub fn permission_denied(message: impl Into<String>) -> Self {
+        Self {
+            status: "PERMISSION_DENIED",
+            http_status: 403,
+            message: message.into(),
+        }
+    }
+
+    /// The request was malformed, e.g. because of missing user attributes.
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self {
+            status: "INVALID_ARGUMENT",
+            http_status: 400,
+            message: message.into(),
+        }
+    }
+
+    /// Something went wrong in the function itself.
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self {
+            status: "INTERNAL",
+            http_status: 500,
+            message: message.into(),
+        }
+    }
+
+    pub fn http_status(&self) -> u16 {
+        self.http_status
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Serialize for BlockingError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let error = serde_json::json!({
+            "status": self.status,
+            "message": self.message,
+        });
+
+        let mut response = serializer.serialize_struct("BlockingError", 1)?;
+        response.serialize_field("error", &error)?;
+        response.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserializes_event() {
+        let event: BlockingEvent = serde_json::from_value(serde_json::json!({
+            "iss": "https://securetoken.google.com/my-project",
+            "aud": "https://before-create-abc123-ew.a.run.app",
+            "iat": 1664995067,
+            "exp": 1664995667,
+            "event_id": "some-event-id",
+            "event_type": "providers/cloud.auth/eventTypes/user.beforeCreate:password",
+            "ip_address": "127.0.0.1",
+            "sign_in_method": "password",
+            "user_record": {
+                "uid": "some-user-id",
+                "email": "mario@example.com",
+                "email_verified": false,
+                "metadata": { "creation_time": 1664995067000i64 },
+                "provider_data": [{ "provider_id": "password", "email": "mario@example.com" }]
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(event.event_type(), BlockingEventType::BeforeCreate);
+        assert_eq!(event.user_record.uid, "some-user-id");
+        assert_eq!(event.user_record.provider_data[0].provider_id, "password");
+        assert_eq!(
+            event.other["aud"],
+            "https://before-create-abc123-ew.a.run.app"
+        );
+    }
+
+    #[test]
+    fn response_lists_updated_fields_in_mask() {
+        let response = BlockingResponse::new()
+            .display_name("Mario")
+            .custom_claims(serde_json::json!({ "role": "plumber" }))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "userRecord": {
+                    "displayName": "Mario",
+                    "customClaims": { "role": "plumber" },
+                    "updateMask": "displayName,customClaims"
+                }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(BlockingResponse::new()).unwrap(),
+            serde_json::json!({})
+        );
+    }
+
+    #[test]
+    fn error_serializes_status_and_message() {
+        let error = BlockingError::permission_denied("Unauthorized email");
+
+        assert_eq!(error.http_status(), 403);
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "error": { "status": "PERMISSION_DENIED", "message": "Unauthorized email" }
+            })
+        );
+    }
+}
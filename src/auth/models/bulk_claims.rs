@@ -0,0 +1,55 @@
+use crate::error::FirebaseError;
+
+/// How many custom claims updates
+/// [`set_custom_user_claims_bulk`](crate::auth::FirebaseAuthClient::set_custom_user_claims_bulk)
+/// sends at the same time.
+pub const BULK_CLAIMS_CONCURRENCY: usize = 10;
+
+/// The outcome of
+/// [`set_custom_user_claims_bulk`](crate::auth::FirebaseAuthClient::set_custom_user_claims_bulk).
+#[derive(Debug, Default)]
+pub struct BulkClaimsResult {
+    /// How many users had their claims set.
+    pub success_count: usize,
+    /// The users whose claims could not be set, with the reason why.
+    pub failures: Vec<BulkClaimsFailure>,
+}
+
+impl BulkClaimsResult {
+    /// Whether the claims were set for every user.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub(crate) fn record(&mut self, user_id: String, result: Result<(), FirebaseError>) {
+        match result {
+            Ok(()) => self.success_count += 1,
+            Err(error) => self.failures.push(BulkClaimsFailure { user_id, error }),
+        }
+    }
+}
+
+/// A user whose custom claims could not be set during a bulk update.
+#[derive(Debug)]
+pub struct BulkClaimsFailure {
+    pub user_id: String,
+    pub error: FirebaseError,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_successes_and_failures() {
+        let mut result = BulkClaimsResult::default();
+        result.record("mario".to_string(), Ok(()));
+        result.record("luigi".to_string(), Err(FirebaseError::UserNotFound));
+        result.record("peach".to_string(), Ok(()));
+
+        assert_eq!(result.success_count, 2);
+        assert!(!result.is_success());
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].user_id, "luigi");
+    }
+}
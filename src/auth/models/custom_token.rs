@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+/// The longest lifetime Firebase accepts for a custom token.
+pub const MAX_CUSTOM_TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Options for creating custom tokens with
+/// [`create_custom_token_with_options`](crate::auth::FirebaseAuthClient::create_custom_token_with_options).
+#[derive(Debug, Clone)]
+pub struct CustomTokenOptions {
+    pub(crate) ttl: Duration,
+    pub(crate) issued_at_skew: Duration,
+}
+
+impl Default for CustomTokenOptions {
+    fn default() -> Self {
+        Self {
+            ttl: MAX_CUSTOM_TOKEN_TTL,
+            issued_at_skew: Duration::ZERO,
+        }
+    }
+}
+
+impl CustomTokenOptions {
+    /// Create options for a token that is valid for one hour, which is the
+    /// maximum allowed by Firebase.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how long the token should be valid for, counting from when it is
+    /// created. Must be at least one second, and together with the issued-at skew it may
+    /// not exceed [`MAX_CUSTOM_TOKEN_TTL`].
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Backdate the token's issued-at time by the given duration. This helps
+    /// if the clock of the machine verifying the token lags behind.
+    pub fn issued_at_skew(mut self, issued_at_skew: Duration) -> Self {
+        self.issued_at_skew = issued_at_skew;
+        self
+    }
+}
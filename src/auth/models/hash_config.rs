@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// The configuration of the hash function that Firebase Auth uses for the
+/// passwords of a project. Together with a user's
+/// [`password_hash`](super::User::password_hash) and
+/// [`salt`](super::User::salt), it is what another auth system needs to verify
+/// the user's password after the user has been exported.
+///
+/// Firebase uses a modified version of scrypt, where the signer key and salt
+/// separator are project-wide secrets. All binary values are base64-encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordHashConfig {
+    /// The hash algorithm, e.g. `SCRYPT` for Firebase's modified scrypt.
+    pub algorithm: String,
+    /// The base64-encoded key used to sign the password hashes.
+    pub signer_key: Option<String>,
+    /// The base64-encoded separator inserted between the salt and the
+    /// password.
+    pub salt_separator: Option<String>,
+    /// The number of rounds of the hash function.
+    pub rounds: Option<u32>,
+    /// The memory cost of the hash function.
+    pub memory_cost: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProjectConfigResponse {
+    pub sign_in: Option<SignInConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SignInConfig {
+    pub hash_config: Option<PasswordHashConfig>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserializes_hash_config_from_project_config() {
+        let config: ProjectConfigResponse = serde_json::from_value(serde_json::json!({
+            "name": "projects/my-project/config",
+            "signIn": {
+                "email": { "enabled": true },
+                "hashConfig": {
+                    "algorithm": "SCRYPT",
+                    "signerKey": "c2lnbmVyLWtleQ==",
+                    "saltSeparator": "Bw==",
+                    "rounds": 8,
+                    "memoryCost": 14
+                }
+            }
+        }))
+        .unwrap();
+
+        let hash_config = config.sign_in.unwrap().hash_config.unwrap();
+        assert_eq!(hash_config.algorithm, "SCRYPT");
+        assert_eq!(hash_config.signer_key.as_deref(), Some("c2lnbmVyLWtleQ=="));
+        assert_eq!(hash_config.rounds, Some(8));
+        assert_eq!(hash_config.memory_cost, Some(14));
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The standard claims of a Firebase ID token, for use with
+/// [`decode_id_token`](crate::auth::FirebaseAuthClient::decode_id_token).
+///
+/// Custom claims set with
+/// [`set_custom_user_claims`](crate::auth::FirebaseAuthClient::set_custom_user_claims)
+/// end up in [`custom_claims`](Self::custom_claims).
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+/// # use ulid::Ulid;
+/// # use fireplace::auth::models::NewUser;
+/// # let auth_client = fireplace::auth::test_helpers::initialise()?;
+/// # let user_id = auth_client
+/// #     .create_user(NewUser {
+/// #         display_name: None,
+/// #         email: format!("{}@example.com", Ulid::new()),
+/// #         password: Ulid::new().to_string(),
+/// #     })
+/// #     .await?;
+/// # let custom_token = auth_client.create_custom_token(&user_id).await?;
+/// # let id_token = auth_client.sign_in_with_custom_token(&custom_token).await?;
+/// use fireplace::auth::models::IdTokenClaims;
+///
+/// let claims = auth_client
+///     .decode_id_token::<IdTokenClaims>(&id_token)
+///     .await?;
+///
+/// assert_eq!(claims.uid, user_id);
+/// assert!(claims.firebase.is_custom_token());
+/// assert!(!claims.firebase.is_anonymous());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    /// The ID of the user the token was issued to.
+    #[serde(rename = "sub")]
+    pub uid: String,
+    /// The project ID the token was issued for.
+    pub aud: String,
+    pub iss: String,
+    /// When the token was issued, in seconds since the Unix epoch.
+    pub iat: u64,
+    /// When the token expires, in seconds since the Unix epoch.
+    pub exp: u64,
+    /// When the user authenticated, in seconds since the Unix epoch.
+    pub auth_time: u64,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub phone_number: Option<String>,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+    pub firebase: FirebaseClaims,
+    /// All other claims, including custom claims.
+    #[serde(flatten)]
+    pub custom_claims: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The `firebase` claim of an ID token, which describes how the user signed
+/// in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FirebaseClaims {
+    /// The identities linked to the user, by provider ID, e.g.
+    /// `{"email": ["mario@example.com"], "google.com": ["1234567890"]}`.
+    #[serde(default)]
+    pub identities: HashMap<String, Vec<String>>,
+    /// The provider the user signed in with, e.g. `password`, `google.com`,
+    /// `phone`, `anonymous` or `custom`.
+    pub sign_in_provider: String,
+    /// The second factor used to sign in, if any.
+    pub sign_in_second_factor: Option<String>,
+    /// The tenant the user belongs to, if any.
+    pub tenant: Option<String>,
+}
+
+impl IdTokenClaims {
+    /// The tenant the user signed in to, or `None` if they signed in to the
+    /// project itself.
+    pub fn tenant_id(&self) -> Option<&str> {
+        self.firebase.tenant.as_deref()
+    }
+}
+
+impl FirebaseClaims {
+    /// Whether the user signed in with email and password.
+    pub fn is_email_provider(&self) -> bool {
+        self.sign_in_provider == "password"
+    }
+
+    /// Whether the user signed in anonymously.
+    pub fn is_anonymous(&self) -> bool {
+        self.sign_in_provider == "anonymous"
+    }
+
+    /// Whether the user signed in with a custom token.
+    pub fn is_custom_token(&self) -> bool {
+        self.sign_in_provider == "custom"
+    }
+
+    /// Whether the user has an identity with the given provider, e.g.
+    /// `google.com`, regardless of which provider they signed in with.
+    pub fn has_identity(&self, provider_id: &str) -> bool {
+        self.identities
+            .get(provider_id)
+            .map_or(false, |ids| !ids.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserializes_standard_and_custom_claims() {
+        let json = serde_json::json!({
+            "iss": "https://securetoken.google.com/my-project",
+            "aud": "my-project",
+            "auth_time": 1664995067,
+            "user_id": "some-user-id",
+            "sub": "some-user-id",
+            "iat": 1664995067,
+            "exp": 1664998667,
+            "email": "mario@example.com",
+            "email_verified": false,
+            "role": "plumber",
+            "firebase": {
+                "identities": {
+                    "email": ["mario@example.com"],
+                    "google.com": ["1234567890"]
+                },
+                "sign_in_provider": "password"
+            }
+        });
+
+        let claims: IdTokenClaims = serde_json::from_value(json).unwrap();
+
+        assert_eq!(claims.uid, "some-user-id");
+        assert_eq!(claims.email.as_deref(), Some("mario@example.com"));
+        assert_eq!(claims.custom_claims["role"], "plumber");
+        assert!(claims.firebase.is_email_provider());
+        assert!(!claims.firebase.is_anonymous());
+        assert!(claims.firebase.has_identity("google.com"));
+        assert!(!claims.firebase.has_identity("apple.com"));
+    }
+
+    #[test]
+    fn anonymous_users_have_no_identities() {
+        let firebase: FirebaseClaims = serde_json::from_value(serde_json::json!({
+            "identities": {},
+            "sign_in_provider": "anonymous"
+        }))
+        .unwrap();
+
+        assert!(firebase.is_anonymous());
+        assert!(firebase.identities.is_empty());
+    }
+}
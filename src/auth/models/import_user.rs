@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+use super::{deserialize_custom_attributes, serialize_custom_attributes, User};
+
+/// A user to import into a project. Serializes to the shape of a user in the
+/// Identity Toolkit `accounts:batchCreate` request.
+///
+/// Convert exported [`User`]s into records with [`From`], and back again, to
+/// move users between projects without mapping each attribute by hand.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::auth::models::{ImportUserRecord, User};
+///
+/// let exported: User = serde_json::from_value(serde_json::json!({
+///     "localId": "some-user-id",
+///     "email": "mario@example.com",
+///     "customAttributes": "{\"role\":\"plumber\"}",
+/// }))
+/// .unwrap();
+///
+/// let record = ImportUserRecord::from(exported);
+///
+/// assert_eq!(record.uid, "some-user-id");
+/// assert_eq!(record.custom_claims["role"], "plumber");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportUserRecord {
+    #[serde(rename = "localId")]
+    pub uid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub photo_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
+    /// The base64-encoded password hash, as exported from the source project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    /// The base64-encoded salt of the password hash.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    #[serde(
+        default,
+        rename = "customAttributes",
+        deserialize_with = "deserialize_custom_attributes",
+        serialize_with = "serialize_custom_attributes",
+        skip_serializing_if = "serde_json::Value::is_null"
+    )]
+    pub custom_claims: serde_json::Value,
+    /// The linked identity providers, in the shape the Firebase Auth API uses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_user_info: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    /// When the user was created, in milliseconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// When the user last signed in, in milliseconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_login_at: Option<String>,
+}
+
+impl From<User> for ImportUserRecord {
+    fn from(user: User) -> Self {
+        let provider_user_info = user.other.get("providerUserInfo").cloned();
+
+        Self {
+            uid: user.uid,
+            email: user.email,
+            email_verified: user.email_verified,
+            phone_number: user.phone_number,
+            display_name: user.display_name,
+            photo_url: user.photo_url,
+            disabled: user.disabled,
+            password_hash: user.password_hash,
+            salt: user.salt,
+            custom_claims: user.custom_claims,
+            provider_user_info,
+            tenant_id: user.tenant_id,
+            created_at: user.created_at,
+            last_login_at: user.last_login_at,
+        }
+    }
+}
+
+impl From<ImportUserRecord> for User {
+    fn from(record: ImportUserRecord) -> Self {
+        let mut other = serde_json::Map::new();
+        if let Some(provider_user_info) = record.provider_user_info {
+            other.insert("providerUserInfo".to_string(), provider_user_info);
+        }
+
+        Self {
+            uid: record.uid,
+            password_hash: record.password_hash,
+            password_updated_at: None,
+            email: record.email,
+            email_verified: record.email_verified,
+            phone_number: record.phone_number,
+            display_name: record.display_name,
+            photo_url: record.photo_url,
+            disabled: record.disabled,
+            salt: record.salt,
+            custom_claims: record.custom_claims,
+            valid_since: None,
+            tenant_id: record.tenant_id,
+            created_at: record.created_at,
+            last_login_at: record.last_login_at,
+            last_refresh_at: None,
+            other: serde_json::Value::Object(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_to_record_round_trip() {
+        let json = serde_json::json!({
+            "localId": "some-user-id",
+            "email": "mario@example.com",
+            "emailVerified": true,
+            "passwordHash": "aGFzaA==",
+            "salt": "c2FsdA==",
+            "customAttributes": "{\"roles\":[\"plumber\"]}",
+            "createdAt": "1663061252000",
+            "lastLoginAt": "1663061253000",
+            "providerUserInfo": [{ "providerId": "password" }],
+        });
+
+        let user: User = serde_json::from_value(json.clone()).unwrap();
+        let record = ImportUserRecord::from(user);
+        let serialized = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(serialized, json);
+
+        let user = User::from(record);
+        assert_eq!(user.uid, "some-user-id");
+        assert_eq!(user.custom_claims["roles"][0], "plumber");
+        assert_eq!(user.other["providerUserInfo"], json["providerUserInfo"]);
+    }
+
+    #[test]
+    fn omits_missing_attributes() {
+        let record = ImportUserRecord::from(User::from(ImportUserRecord {
+            uid: "some-user-id".to_string(),
+            email: None,
+            email_verified: None,
+            phone_number: None,
+            display_name: None,
+            photo_url: None,
+            disabled: None,
+            password_hash: None,
+            salt: None,
+            custom_claims: serde_json::Value::Null,
+            provider_user_info: None,
+            tenant_id: None,
+            created_at: None,
+            last_login_at: None,
+        }));
+
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            serde_json::json!({ "localId": "some-user-id" })
+        );
+    }
+}
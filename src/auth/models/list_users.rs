@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use super::User;
+
+/// The most users Firebase will return in a single page.
+pub const MAX_LIST_USERS_RESULTS: u32 = 1000;
+
+/// Options for listing users with
+/// [`list_users`](crate::auth::FirebaseAuthClient::list_users) and
+/// [`get_all_users`](crate::auth::FirebaseAuthClient::get_all_users).
+#[derive(Debug, Clone, Default)]
+pub struct ListUsersOptions {
+    pub(crate) max_results: Option<u32>,
+    pub(crate) page_token: Option<String>,
+}
+
+impl ListUsersOptions {
+    /// Create options that start from the first page and use the maximum page
+    /// size.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many users to fetch per page. Firebase allows at most
+    /// [`MAX_LIST_USERS_RESULTS`] users per page, which is also the default.
+    pub fn max_results(mut self, max_results: u32) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Start listing from the page identified by the given token, e.g. to
+    /// resume an interrupted export.
+    pub fn page_token(mut self, page_token: impl Into<String>) -> Self {
+        self.page_token = Some(page_token.into());
+        self
+    }
+}
+
+/// A single page of users.
+#[derive(Debug)]
+pub struct UsersPage {
+    pub users: Vec<User>,
+    /// The token for the next page, or `None` if this is the last page.
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadAccountResponse {
+    pub users: Option<Vec<User>>,
+    pub next_page_token: Option<String>,
+}
+
+/// Drops users that were already yielded from the previous page when paging
+/// through all users. Users created or deleted mid-scan can shift the page
+/// boundaries, so the first users of a page may repeat the last users of the
+/// previous one.
+#[derive(Debug, Default)]
+pub(crate) struct PageDeduplicator {
+    previous_uids: HashSet<String>,
+}
+
+impl PageDeduplicator {
+    pub(crate) fn dedupe(&mut self, mut users: Vec<User>) -> Vec<User> {
+        let uids: HashSet<String> = users.iter().map(|user| user.uid.clone()).collect();
+        users.retain(|user| !self.previous_uids.contains(&user.uid));
+        self.previous_uids = uids;
+        users
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(uid: &str) -> User {
+        serde_json::from_value(serde_json::json!({ "localId": uid })).unwrap()
+    }
+
+    fn uids(users: &[User]) -> Vec<&str> {
+        users.iter().map(|user| user.uid.as_str()).collect()
+    }
+
+    #[test]
+    fn drops_users_repeated_from_previous_page() {
+        let mut deduplicator = PageDeduplicator::default();
+
+        let first = deduplicator.dedupe(vec![user("a"), user("b"), user("c")]);
+        let second = deduplicator.dedupe(vec![user("c"), user("d"), user("e")]);
+        let third = deduplicator.dedupe(vec![user("f"), user("a")]);
+
+        assert_eq!(uids(&first), vec!["a", "b", "c"]);
+        assert_eq!(uids(&second), vec!["d", "e"]);
+        // Only the previous page is remembered, to keep memory bounded
+        assert_eq!(uids(&third), vec!["f", "a"]);
+    }
+}
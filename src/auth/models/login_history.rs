@@ -0,0 +1,150 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::User;
+
+/// When a user was created and when they were last active, parsed from the
+/// timestamps of a [`User`]. See [`User::login_history`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoginHistory {
+    pub created_at: Option<SystemTime>,
+    /// When the user last signed in.
+    pub last_login_at: Option<SystemTime>,
+    /// When the user's ID token was last refreshed, which happens
+    /// continuously while they use an app without signing in again.
+    pub last_refresh_at: Option<SystemTime>,
+}
+
+impl LoginHistory {
+    /// The latest time the user is known to have been active. Falls back to
+    /// when the user was created if they never signed in.
+    pub fn last_active_at(&self) -> Option<SystemTime> {
+        [self.created_at, self.last_login_at, self.last_refresh_at]
+            .into_iter()
+            .flatten()
+            .max()
+    }
+
+    /// Whether the user has not been active since the given time. Users
+    /// without any known activity are not considered inactive.
+    pub fn is_inactive_since(&self, since: SystemTime) -> bool {
+        self.last_active_at().map_or(false, |at| at < since)
+    }
+}
+
+impl User {
+    /// Parses the user's activity timestamps. Timestamps that are missing or
+    /// in an unexpected format are `None`.
+    pub fn login_history(&self) -> LoginHistory {
+        LoginHistory {
+            created_at: self.created_at.as_deref().and_then(parse_millis),
+            last_login_at: self.last_login_at.as_deref().and_then(parse_millis),
+            last_refresh_at: self.last_refresh_at.as_deref().and_then(parse_rfc3339),
+        }
+    }
+}
+
+/// `createdAt` and `lastLoginAt` are milliseconds since the Unix epoch, as
+/// strings.
+fn parse_millis(millis: &str) -> Option<SystemTime> {
+    let millis = millis.parse::<u64>().ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_millis(millis))
+}
+
+/// `lastRefreshAt` is an RFC 3339 timestamp in UTC, such as
+/// `2022-09-13T09:27:32.123Z`.
+fn parse_rfc3339(timestamp: &str) -> Option<SystemTime> {
+    let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(str::parse::<u64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        // Pad or cut the fraction to nanosecond precision
+        let digits: String = fraction
+            .chars()
+            .chain("000000000".chars())
+            .take(9)
+            .collect();
+        digits.parse::<u32>().ok()?
+    };
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let secs = days * 86_400 + hour * 3_600 + minute * 60 + second;
+
+    UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
+
+/// The number of days since 1970-01-01 of a date in the proleptic Gregorian
+/// calendar, following Howard Hinnant's `days_from_civil` algorithm.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64, millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn parses_user_timestamps() {
+        let user: User = serde_json::from_value(serde_json::json!({
+            "localId": "some-user-id",
+            "createdAt": "1663061252000",
+            "lastLoginAt": "1663061253000",
+            "lastRefreshAt": "2022-09-13T09:27:34.123Z",
+        }))
+        .unwrap();
+
+        let history = user.login_history();
+
+        assert_eq!(history.created_at, Some(at(1663061252, 0)));
+        assert_eq!(history.last_login_at, Some(at(1663061253, 0)));
+        assert_eq!(history.last_refresh_at, Some(at(1663061254, 123)));
+        assert_eq!(history.last_active_at(), history.last_refresh_at);
+    }
+
+    #[test]
+    fn rejects_malformed_timestamps() {
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse_rfc3339("2000-03-01T00:00:00Z"),
+            Some(at(951868800, 0))
+        );
+        assert_eq!(parse_rfc3339("2022-09-13 09:27:34Z"), None);
+        assert_eq!(parse_rfc3339("2022-13-13T09:27:34Z"), None);
+        assert_eq!(parse_millis("yesterday"), None);
+    }
+
+    #[test]
+    fn users_without_activity_are_not_inactive() {
+        let history = LoginHistory::default();
+        assert!(!history.is_inactive_since(SystemTime::now()));
+
+        let history = LoginHistory {
+            created_at: Some(at(1663061252, 0)),
+            ..Default::default()
+        };
+        assert!(history.is_inactive_since(at(1663061253, 0)));
+        assert!(!history.is_inactive_since(at(1663061252, 0)));
+    }
+}
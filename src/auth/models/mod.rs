@@ -1,9 +1,27 @@
 use std::str::FromStr;
 
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+mod action_code;
+mod blocking;
+mod bulk_claims;
+mod custom_token;
+mod hash_config;
+mod id_token_claims;
+mod import_user;
+mod list_users;
+mod login_history;
 mod update_user;
 
+pub use action_code::*;
+pub use blocking::*;
+pub use bulk_claims::*;
+pub use custom_token::*;
+pub use hash_config::*;
+pub use id_token_claims::*;
+pub use import_user::*;
+pub use list_users::*;
+pub use login_history::*;
 pub use update_user::*;
 
 #[derive(Debug, Deserialize)]
@@ -11,11 +29,20 @@ pub(crate) struct GetAccountInfoResponse {
     pub users: Option<Vec<User>>,
 }
 
-#[derive(Debug, Deserialize)]
+/// A Firebase Auth user.
+///
+/// The user serializes to the same JSON shape as the Firebase Auth API
+/// returns, so serialized users can be deserialized again.
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
     #[serde(rename = "localId")]
     pub uid: String,
+    /// The base64-encoded hash of the user's password. Only returned when
+    /// listing users, and only if the service account may read password
+    /// hashes. See
+    /// [`get_password_hash_config`](crate::auth::FirebaseAuthClient::get_password_hash_config)
+    /// for how it was hashed.
     pub password_hash: Option<String>,
     pub password_updated_at: Option<u64>,
     pub email: Option<String>,
@@ -24,11 +51,14 @@ pub struct User {
     pub display_name: Option<String>,
     pub photo_url: Option<String>,
     pub disabled: Option<bool>,
+    /// The base64-encoded salt of the [`password_hash`](Self::password_hash).
     pub salt: Option<String>,
     #[serde(
         default,
         rename = "customAttributes",
-        deserialize_with = "deserialize_custom_attributes"
+        deserialize_with = "deserialize_custom_attributes",
+        serialize_with = "serialize_custom_attributes",
+        skip_serializing_if = "serde_json::Value::is_null"
     )]
     pub custom_claims: serde_json::Value,
     pub valid_since: Option<String>,
@@ -38,10 +68,62 @@ pub struct User {
     pub created_at: Option<String>,
     pub last_login_at: Option<String>,
     pub last_refresh_at: Option<String>,
+    /// All other attributes returned by the API. See
+    /// [`unknown_fields`](Self::unknown_fields) for noticing attributes that
+    /// Google has added.
     #[serde(flatten)]
     pub other: serde_json::Value,
 }
 
+/// Attributes that the Firebase Auth API documents for users, but that
+/// [`User`] doesn't model, so they end up in [`User::other`].
+const UNMODELED_USER_FIELDS: &[&str] = &[
+    "customAuth",
+    "dateOfBirth",
+    "emailLinkSignin",
+    "initialEmail",
+    "language",
+    "mfaInfo",
+    "passkeyInfo",
+    "providerUserInfo",
+    "rawPassword",
+    "screenName",
+    "timeZone",
+    "version",
+];
+
+impl User {
+    /// The names of the attributes in [`other`](Self::other) that the
+    /// Firebase Auth API doesn't document, e.g. because Google has added
+    /// them since this version of fireplace was released.
+    pub fn unknown_fields(&self) -> Vec<&str> {
+        let mut unknown: Vec<&str> = self
+            .other
+            .as_object()
+            .into_iter()
+            .flat_map(|other| other.keys())
+            .map(String::as_str)
+            .filter(|key| !UNMODELED_USER_FIELDS.contains(key))
+            .collect();
+        unknown.sort_unstable();
+        unknown
+    }
+}
+
+/// What a [`FirebaseAuthClient`](crate::auth::FirebaseAuthClient) does when
+/// a user returned by the API has [unknown fields](User::unknown_fields). See
+/// [`with_unknown_user_fields`](crate::auth::FirebaseAuthClient::with_unknown_user_fields).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownUserFields {
+    /// Keep the unknown fields in [`User::other`] without further notice.
+    #[default]
+    Ignore,
+    /// Log a warning with the names of the unknown fields.
+    Warn,
+    /// Fail the request, e.g. to catch new fields in tests.
+    Deny,
+}
+
 fn deserialize_custom_attributes<'de, T, D>(deserializer: D) -> Result<T, D::Error>
 where
     T: FromStr + Default,
@@ -56,6 +138,17 @@ where
     Ok(t)
 }
 
+/// The Firebase Auth API represents custom attributes as a JSON-encoded string.
+fn serialize_custom_attributes<S>(
+    custom_claims: &serde_json::Value,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&custom_claims.to_string())
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NewUser {
@@ -63,3 +156,43 @@ pub struct NewUser {
     pub email: String,
     pub password: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_json_round_trip() {
+        let json = serde_json::json!({
+            "localId": "some-user-id",
+            "email": "mario@example.com",
+            "emailVerified": true,
+            "customAttributes": "{\"roles\":[\"plumber\"]}",
+            "createdAt": "1663061252000",
+            "providerUserInfo": [{ "providerId": "password" }],
+        });
+
+        let user: User = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(user.custom_claims["roles"][0], "plumber");
+
+        let serialized = serde_json::to_value(&user).unwrap();
+        let rehydrated: User = serde_json::from_value(serialized.clone()).unwrap();
+
+        assert_eq!(serialized["localId"], json["localId"]);
+        assert_eq!(serialized["providerUserInfo"], json["providerUserInfo"]);
+        assert_eq!(rehydrated.custom_claims, user.custom_claims);
+    }
+
+    #[test]
+    fn only_undocumented_fields_are_unknown() {
+        let user: User = serde_json::from_value(serde_json::json!({
+            "localId": "some-user-id",
+            "providerUserInfo": [{ "providerId": "password" }],
+            "mfaInfo": [],
+            "shinyNewField": true,
+        }))
+        .unwrap();
+
+        assert_eq!(user.unknown_fields(), vec!["shinyNewField"]);
+    }
+}
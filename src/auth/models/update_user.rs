@@ -5,6 +5,7 @@ use serde::Serialize;
 pub struct UpdateUserValues {
     display_name: Option<Option<String>>,
     email: Option<String>,
+    email_verified: Option<bool>,
     password: Option<String>,
 }
 
@@ -26,6 +27,12 @@ impl UpdateUserValues {
         self
     }
 
+    /// Mark the user's email as verified or unverified.
+    pub fn email_verified(mut self, email_verified: bool) -> Self {
+        self.email_verified = Some(email_verified);
+        self
+    }
+
     /// Update the user's password.
     pub fn password(mut self, password: impl Into<String>) -> Self {
         self.password = Some(password.into());
@@ -42,6 +49,8 @@ pub(crate) struct UpdateUserBody<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     email: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     password: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     delete_attribute: Vec<&'static str>,
@@ -61,6 +70,7 @@ impl<'a> UpdateUserBody<'a> {
             local_id: user_id,
             display_name: values.display_name.flatten(),
             email: values.email,
+            email_verified: values.email_verified,
             password: values.password,
             delete_attribute,
         }
@@ -0,0 +1,224 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+type ThresholdCallback = Arc<dyn Fn(ApiUsage) + Send + Sync>;
+
+/// Configures how a [`FirebaseAuthClient`](super::FirebaseAuthClient) tracks
+/// its requests to the Identity Toolkit API, so bulk jobs can throttle
+/// themselves below the project's quotas instead of running into `429`
+/// responses. See
+/// [`with_quota_tracking`](super::FirebaseAuthClient::with_quota_tracking).
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use fireplace::auth::QuotaTracking;
+///
+/// let tracking = QuotaTracking::new()
+///     .window(Duration::from_secs(10))
+///     .on_threshold(400.0, |usage| {
+///         eprintln!("Auth API usage is at {:.0} QPS", usage.qps());
+///     });
+/// ```
+#[derive(Clone)]
+pub struct QuotaTracking {
+    window: Duration,
+    threshold: Option<(f64, ThresholdCallback)>,
+}
+
+impl Default for QuotaTracking {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            threshold: None,
+        }
+    }
+}
+
+impl fmt::Debug for QuotaTracking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaTracking")
+            .field("window", &self.window)
+            .field(
+                "threshold_qps",
+                &self.threshold.as_ref().map(|(qps, _)| qps),
+            )
+            .finish()
+    }
+}
+
+impl QuotaTracking {
+    /// Track requests over a rolling window of one minute, without a
+    /// threshold.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the length of the rolling window that the request rate is
+    /// computed over.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Call the given function when the request rate reaches the given number
+    /// of requests per second. It is called once each time the rate crosses
+    /// the threshold, not for every request above it, and from the task that
+    /// sends the request, so it should return quickly.
+    pub fn on_threshold(
+        mut self,
+        qps: f64,
+        callback: impl Fn(ApiUsage) + Send + Sync + 'static,
+    ) -> Self {
+        self.threshold = Some((qps, Arc::new(callback)));
+        self
+    }
+}
+
+/// A snapshot of the requests a
+/// [`FirebaseAuthClient`](super::FirebaseAuthClient) has sent to the Identity
+/// Toolkit API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApiUsage {
+    /// The number of requests sent since tracking started.
+    pub total_requests: u64,
+    /// The number of requests sent within the rolling window.
+    pub requests_in_window: u64,
+    /// The length of the rolling window.
+    pub window: Duration,
+}
+
+impl ApiUsage {
+    /// The average number of requests per second within the rolling window.
+    pub fn qps(&self) -> f64 {
+        if self.window.is_zero() {
+            return 0.0;
+        }
+        self.requests_in_window as f64 / self.window.as_secs_f64()
+    }
+}
+
+/// Counts requests according to a [`QuotaTracking`].
+#[derive(Debug)]
+pub(crate) struct QuotaTracker {
+    options: QuotaTracking,
+    state: Mutex<TrackerState>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    requests: VecDeque<Instant>,
+    total_requests: u64,
+    above_threshold: bool,
+}
+
+impl QuotaTracker {
+    pub fn new(options: QuotaTracking) -> Self {
+        Self {
+            options,
+            state: Mutex::new(TrackerState::default()),
+        }
+    }
+
+    pub fn record_request(&self) {
+        self.record_request_at(Instant::now());
+    }
+
+    pub fn usage(&self) -> ApiUsage {
+        let mut state = self.state.lock().unwrap();
+        self.usage_at(&mut state, Instant::now())
+    }
+
+    fn record_request_at(&self, now: Instant) {
+        let crossed = {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push_back(now);
+            state.total_requests += 1;
+
+            let usage = self.usage_at(&mut state, now);
+            self.options.threshold.as_ref().and_then(|(qps, callback)| {
+                let is_above = usage.qps() >= *qps;
+                let crossed = is_above && !state.above_threshold;
+                state.above_threshold = is_above;
+                crossed.then(|| (usage, callback.clone()))
+            })
+        };
+
+        // The lock is released, so the callback may inspect the usage itself
+        if let Some((usage, callback)) = crossed {
+            callback(usage);
+        }
+    }
+
+    fn usage_at(&self, state: &mut TrackerState, now: Instant) -> ApiUsage {
+        while let Some(&oldest) = state.requests.front() {
+            if now.duration_since(oldest) < self.options.window {
+                break;
+            }
+            state.requests.pop_front();
+        }
+
+        ApiUsage {
+            total_requests: state.total_requests,
+            requests_in_window: state.requests.len() as u64,
+            window: self.options.window,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn counts_requests_in_rolling_window() {
+        let tracker = QuotaTracker::new(QuotaTracking::new().window(Duration::from_secs(10)));
+        let start = Instant::now();
+
+        for i in 0..20 {
+            tracker.record_request_at(start + Duration::from_secs(i));
+        }
+
+        let mut state = tracker.state.lock().unwrap();
+        let usage = tracker.usage_at(&mut state, start + Duration::from_secs(19));
+        assert_eq!(usage.total_requests, 20);
+        assert_eq!(usage.requests_in_window, 10);
+        assert_eq!(usage.qps(), 1.0);
+    }
+
+    #[test]
+    fn calls_back_once_per_threshold_crossing() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let calls_in_callback = calls.clone();
+        let tracker = QuotaTracker::new(
+            QuotaTracking::new()
+                .window(Duration::from_secs(1))
+                .on_threshold(3.0, move |_| {
+                    calls_in_callback.fetch_add(1, Ordering::Relaxed);
+                }),
+        );
+        let start = Instant::now();
+
+        // A burst above the threshold only calls back once
+        for _ in 0..5 {
+            tracker.record_request_at(start);
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Once the rate has dropped, crossing the threshold again calls back
+        let later = start + Duration::from_secs(5);
+        tracker.record_request_at(later);
+        tracker.record_request_at(later);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        tracker.record_request_at(later);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,156 @@
+//! Cancelling in-flight Firestore and Firebase Auth work, e.g. when a service
+//! shuts down.
+//!
+//! Long-running operations like queries, listeners and listing users can take
+//! a while to finish on their own, and a request to an unresponsive server
+//! only fails once the network times out. Wrap them with [`cancellable`] or
+//! [`CancelStreamExt::cancel_on`] to stop them as soon as a
+//! [`CancellationToken`] is cancelled instead. A
+//! [`BufferedWriter`](crate::firestore::buffered::BufferedWriter) accepts a
+//! token through its
+//! [options](crate::firestore::buffered::BufferedWriterOptions::cancellation_token).
+//!
+//! Cancelled operations fail with [`FirebaseError::Cancelled`]. Dropping the
+//! request stops it, but writes that already reached the server may still be
+//! applied.
+
+use std::future::Future;
+
+use futures::{stream, Stream, StreamExt};
+
+use crate::error::FirebaseError;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Run the future until it completes or the token is cancelled, whichever
+/// comes first. Fails with [`FirebaseError::Cancelled`] if the token was
+/// cancelled, even if it was cancelled before the future was started.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::{
+///     cancel::{cancellable, CancellationToken},
+///     error::FirebaseError,
+///     firestore::collection,
+/// };
+///
+/// let shutdown = CancellationToken::new();
+/// shutdown.cancel();
+///
+/// let doc_ref = collection("greetings").doc("cancelled");
+/// let result = cancellable(&shutdown, client.get_document::<serde_json::Value>(&doc_ref)).await;
+/// assert!(matches!(result, Err(FirebaseError::Cancelled)));
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cancellable<T, F>(token: &CancellationToken, future: F) -> Result<T, FirebaseError>
+where
+    F: Future<Output = Result<T, FirebaseError>>,
+{
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(FirebaseError::Cancelled),
+        result = future => result,
+    }
+}
+
+/// Cancelling streams of results, like the streams of queries and listeners.
+pub trait CancelStreamExt<'a, T>: Stream<Item = Result<T, FirebaseError>> + Send + 'a {
+    /// Stop the stream as soon as the token is cancelled. The stream then
+    /// yields a single [`FirebaseError::Cancelled`] error and ends, so
+    /// consumers that stop at the first error don't mistake the cancellation
+    /// for the end of the results.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::{
+    ///     cancel::{CancelStreamExt, CancellationToken},
+    ///     error::FirebaseError,
+    ///     firestore::{
+    ///         collection,
+    ///         query::{filter, EqualTo},
+    ///     },
+    /// };
+    /// use futures::TryStreamExt;
+    ///
+    /// let shutdown = CancellationToken::new();
+    /// let greetings = client
+    ///     .run_query::<serde_json::Value>(
+    ///         collection("greetings").with_filter(filter("language", EqualTo("en"))),
+    ///     )
+    ///     .await?
+    ///     .cancel_on(shutdown.clone());
+    ///
+    /// shutdown.cancel();
+    /// let result = greetings.try_collect::<Vec<_>>().await;
+    /// assert!(matches!(result, Err(FirebaseError::Cancelled)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn cancel_on(
+        self,
+        token: CancellationToken,
+    ) -> std::pin::Pin<Box<dyn Stream<Item = Result<T, FirebaseError>> + Send + 'a>>
+    where
+        Self: Sized,
+        T: Send + 'a,
+    {
+        let state = (Some(Box::pin(self)), token);
+
+        stream::unfold(state, |(inner, token)| async move {
+            let mut inner = inner?;
+
+            tokio::select! {
+                biased;
+                _ = token.cancelled() => Some((Err(FirebaseError::Cancelled), (None, token))),
+                item = inner.next() => item.map(|item| (item, (Some(inner), token))),
+            }
+        })
+        .boxed()
+    }
+}
+
+impl<'a, T, S> CancelStreamExt<'a, T> for S where
+    S: Stream<Item = Result<T, FirebaseError>> + Send + 'a
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cancelled_stream_yields_one_error_and_ends() {
+        let token = CancellationToken::new();
+        let mut values = stream::iter(vec![Ok::<_, FirebaseError>(1), Ok(2)])
+            .chain(stream::pending())
+            .cancel_on(token.clone());
+
+        assert_eq!(values.try_next().await.unwrap(), Some(1));
+        assert_eq!(values.try_next().await.unwrap(), Some(2));
+
+        token.cancel();
+        assert!(matches!(
+            values.next().await,
+            Some(Err(FirebaseError::Cancelled))
+        ));
+        assert!(values.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn completed_future_is_returned_if_not_cancelled() {
+        let token = CancellationToken::new();
+        let result = cancellable(&token, async { Ok::<_, FirebaseError>(42) }).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+}
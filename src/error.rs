@@ -8,12 +8,39 @@ pub enum FirebaseError {
     #[error("{0}")]
     DocumentNotfound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Email already exists")]
     EmailAlreadyExists,
 
     #[error("User not found")]
     UserNotFound,
 
+    #[error("Invalid action code settings: {0}")]
+    InvalidActionCodeSettings(crate::auth::models::ActionCodeSettingsError),
+
+    #[error("Invalid document: {0}")]
+    InvalidDocument(crate::firestore::validate::Violations),
+
+    #[error("Operation {name} failed: {error}")]
+    OperationFailed {
+        name: String,
+        error: crate::firestore::admin::OperationError,
+    },
+
+    #[error("Operation {name} did not finish in time")]
+    OperationTimedOut { name: String },
+
+    #[error("ID token does not belong to tenant '{expected}'")]
+    TenantMismatch {
+        expected: String,
+        actual: Option<String>,
+    },
+
+    #[error("The operation was cancelled")]
+    Cancelled,
+
     #[error("Failed to validate token: {0}")]
     ValidateTokenError(anyhow::Error),
 
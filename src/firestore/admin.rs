@@ -0,0 +1,1308 @@
+//! Managing Firestore databases through the Firestore Admin API, such as
+//! exporting documents to Cloud Storage, importing them again and managing
+//! composite indexes and TTL policies, or creating and deleting whole
+//! databases. See [`FirestoreAdminClient`].
+
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::Context;
+use firestore_grpc::tonic;
+use reqwest::{Method, Response};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{auth::credential::ApiAuthTokenManager, error::FirebaseError, ServiceAccount};
+
+/// A client for the Firestore Admin API, which manages databases rather than
+/// the documents in them. Unlike
+/// [`FirestoreClient`](super::client::FirestoreClient), it always acts as the
+/// service account, which needs e.g. the `Cloud Datastore Import Export
+/// Admin` role for exports.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+/// use std::time::Duration;
+///
+/// use fireplace::{
+///     firestore::admin::{ExportOptions, FirestoreAdminClient},
+///     ServiceAccount,
+/// };
+///
+/// let service_account = ServiceAccount::from_file("./test-service-account.json")?;
+/// let admin = FirestoreAdminClient::new(service_account)?;
+///
+/// let operation = admin
+///     .export_documents(
+///         ExportOptions::new("gs://my-backups/2022-10-01").collection_ids(["users", "orders"]),
+///     )
+///     .await?;
+///
+/// // Exports can take a long time, so the operation is polled until it's done
+/// let operation = admin
+///     .wait_for_operation(
+///         operation,
+///         Duration::from_secs(10),
+///         Duration::from_secs(60 * 60),
+///     )
+///     .await?;
+///
+/// println!("Exported to {:?}", operation.output_uri_prefix());
+/// # Ok(())
+/// # }
+/// ```
+pub struct FirestoreAdminClient {
+    client: reqwest::Client,
+    api_url: String,
+    project_id: String,
+    database_id: String,
+    api_auth_token_manager: ApiAuthTokenManager,
+}
+
+impl FirestoreAdminClient {
+    pub fn new(service_account: ServiceAccount) -> Result<Self, FirebaseError> {
+        let client = reqwest::Client::builder()
+            .https_only(true)
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            client,
+            api_url: "https://firestore.googleapis.com/v1".to_string(),
+            project_id: service_account.project_id.clone(),
+            database_id: "(default)".to_string(),
+            api_auth_token_manager: ApiAuthTokenManager::new(service_account),
+        })
+    }
+
+    /// Manage the database with the given ID rather than the default
+    /// database.
+    pub fn with_database(self, database_id: impl Into<String>) -> Self {
+        Self {
+            database_id: database_id.into(),
+            ..self
+        }
+    }
+
+    /// Starts exporting documents to Cloud Storage. Returns the long-running
+    /// operation of the export, which can be polled with
+    /// [`get_operation`](Self::get_operation) or
+    /// [`wait_for_operation`](Self::wait_for_operation).
+    #[tracing::instrument(name = "Export Firestore documents", skip(self))]
+    pub async fn export_documents(
+        &self,
+        options: ExportOptions,
+    ) -> Result<Operation, FirebaseError> {
+        self.start_operation("exportDocuments", &options, "Failed to export documents")
+            .await
+    }
+
+    /// Starts importing documents from an export in Cloud Storage, e.g. to
+    /// restore a backup or to seed another environment. Imported documents
+    /// overwrite existing documents with the same name, while other documents
+    /// are left untouched.
+    ///
+    /// Returns the long-running operation of the import. If the import fails,
+    /// e.g. because the export doesn't exist,
+    /// [`wait_for_operation`](Self::wait_for_operation) returns an
+    /// [`OperationFailed`](FirebaseError::OperationFailed) error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// use std::time::Duration;
+    ///
+    /// use firestore_grpc::tonic::Code;
+    /// use fireplace::{
+    ///     error::FirebaseError,
+    ///     firestore::admin::{FirestoreAdminClient, ImportOptions},
+    ///     ServiceAccount,
+    /// };
+    ///
+    /// let service_account = ServiceAccount::from_file("./test-service-account.json")?;
+    /// let admin = FirestoreAdminClient::new(service_account)?;
+    ///
+    /// let operation = admin
+    ///     .import_documents(
+    ///         ImportOptions::new("gs://my-backups/2022-10-01").collection_ids(["users"]),
+    ///     )
+    ///     .await?;
+    ///
+    /// let result = admin
+    ///     .wait_for_operation(
+    ///         operation,
+    ///         Duration::from_secs(10),
+    ///         Duration::from_secs(60 * 60),
+    ///     )
+    ///     .await;
+    ///
+    /// match result {
+    ///     Ok(_) => println!("Restored the backup"),
+    ///     Err(FirebaseError::OperationFailed { error, .. }) if error.code() == Code::NotFound => {
+    ///         println!("The backup doesn't exist")
+    ///     }
+    ///     Err(err) => return Err(err),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Import Firestore documents", skip(self))]
+    pub async fn import_documents(
+        &self,
+        options: ImportOptions,
+    ) -> Result<Operation, FirebaseError> {
+        self.start_operation("importDocuments", &options, "Failed to import documents")
+            .await
+    }
+
+    /// Gets the current state of a long-running operation by its name.
+    pub async fn get_operation(&self, name: &str) -> Result<Operation, FirebaseError> {
+        let url = format!("{}/{}", self.api_url, name);
+
+        let res = self
+            .admin_request(Method::GET, url)
+            .await?
+            .send()
+            .await
+            .context("Failed to send get operation request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to get operation", res).await);
+        }
+
+        let operation = res
+            .json()
+            .await
+            .context("Failed to read operation response JSON")?;
+
+        Ok(operation)
+    }
+
+    /// Polls the operation at the given interval until it is done. Returns the
+    /// finished operation, or an
+    /// [`OperationFailed`](FirebaseError::OperationFailed) error if the
+    /// operation failed.
+    ///
+    /// Gives up with an
+    /// [`OperationTimedOut`](FirebaseError::OperationTimedOut) error if the
+    /// operation isn't done within `timeout`. The operation keeps running,
+    /// and can be waited for again by its name with
+    /// [`get_operation`](Self::get_operation).
+    pub async fn wait_for_operation(
+        &self,
+        mut operation: Operation,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<Operation, FirebaseError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        while !operation.done {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Err(FirebaseError::OperationTimedOut {
+                    name: operation.name,
+                });
+            }
+
+            tokio::time::sleep(poll_interval.min(remaining)).await;
+            operation = self.get_operation(&operation.name).await?;
+        }
+
+        match operation.error {
+            Some(error) => Err(FirebaseError::OperationFailed {
+                name: operation.name,
+                error,
+            }),
+            None => Ok(operation),
+        }
+    }
+
+    /// Lists the composite indexes of the given collection group, or of all
+    /// collection groups if `None`. Single-field indexes, which Firestore
+    /// manages automatically, are not included.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// use std::time::Duration;
+    ///
+    /// use fireplace::{
+    ///     firestore::admin::{FirestoreAdminClient, Index, IndexField, QueryScope},
+    ///     ServiceAccount,
+    /// };
+    ///
+    /// let service_account = ServiceAccount::from_file("./test-service-account.json")?;
+    /// let admin = FirestoreAdminClient::new(service_account)?;
+    ///
+    /// // Make sure the indexes the app needs exist as part of deploying it
+    /// let wanted = Index::new(QueryScope::Collection)
+    ///     .field(IndexField::Ascending("type".to_string()))
+    ///     .field(IndexField::Descending("rating".to_string()));
+    ///
+    /// let existing = admin.list_indexes(Some("landmarks")).await?;
+    /// if !existing.iter().any(|index| index.has_same_fields(&wanted)) {
+    ///     let operation = admin.create_index("landmarks", &wanted).await?;
+    ///     admin
+    ///         .wait_for_operation(
+    ///             operation,
+    ///             Duration::from_secs(30),
+    ///             Duration::from_secs(10 * 60),
+    ///         )
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_indexes(
+        &self,
+        collection_group: Option<&str>,
+    ) -> Result<Vec<Index>, FirebaseError> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ListIndexesResponse {
+            #[serde(default)]
+            indexes: Vec<Index>,
+            next_page_token: Option<String>,
+        }
+
+        let url = self.indexes_url(collection_group.unwrap_or("-"));
+        let mut indexes = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            let mut request = self.admin_request(Method::GET, &url).await?;
+            if let Some(token) = &page_token {
+                request = request.query(&[("pageToken", token)]);
+            }
+
+            let res = request
+                .send()
+                .await
+                .context("Failed to send list indexes request")?;
+
+            if !res.status().is_success() {
+                return Err(response_error("Failed to list indexes", res).await);
+            }
+
+            let page: ListIndexesResponse = res
+                .json()
+                .await
+                .context("Failed to read list indexes response JSON")?;
+
+            indexes.extend(page.indexes);
+            page_token = page.next_page_token.filter(|token| !token.is_empty());
+            if page_token.is_none() {
+                return Ok(indexes);
+            }
+        }
+    }
+
+    /// Starts creating a composite index on the given collection group.
+    /// Returns the long-running operation that builds the index, which can
+    /// take several minutes.
+    pub async fn create_index(
+        &self,
+        collection_group: &str,
+        index: &Index,
+    ) -> Result<Operation, FirebaseError> {
+        let res = self
+            .admin_request(Method::POST, self.indexes_url(collection_group))
+            .await?
+            .json(index)
+            .send()
+            .await
+            .context("Failed to send create index request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to create index", res).await);
+        }
+
+        let operation = res
+            .json()
+            .await
+            .context("Failed to read create index response JSON")?;
+
+        Ok(operation)
+    }
+
+    /// Deletes the composite index with the given
+    /// [`name`](Index::name).
+    pub async fn delete_index(&self, name: &str) -> Result<(), FirebaseError> {
+        let res = self
+            .admin_request(Method::DELETE, format!("{}/{}", self.api_url, name))
+            .await?
+            .send()
+            .await
+            .context("Failed to send delete index request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to delete index", res).await);
+        }
+
+        Ok(())
+    }
+
+    /// Lists the TTL policies of the given collection group, or of all
+    /// collection groups if `None`.
+    pub async fn list_ttl_policies(
+        &self,
+        collection_group: Option<&str>,
+    ) -> Result<Vec<TtlPolicy>, FirebaseError> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ListFieldsResponse {
+            #[serde(default)]
+            fields: Vec<TtlPolicy>,
+            next_page_token: Option<String>,
+        }
+
+        let url = format!(
+            "{}/projects/{}/databases/{}/collectionGroups/{}/fields",
+            self.api_url,
+            self.project_id,
+            self.database_id,
+            collection_group.unwrap_or("-")
+        );
+        let mut policies = Vec::new();
+        let mut page_token = None;
+
+        loop {
+            // Only fields with a TTL configuration can be listed
+            let mut request = self
+                .admin_request(Method::GET, &url)
+                .await?
+                .query(&[("filter", "ttlConfig:*")]);
+            if let Some(token) = &page_token {
+                request = request.query(&[("pageToken", token)]);
+            }
+
+            let res = request
+                .send()
+                .await
+                .context("Failed to send list TTL policies request")?;
+
+            if !res.status().is_success() {
+                return Err(response_error("Failed to list TTL policies", res).await);
+            }
+
+            let page: ListFieldsResponse = res
+                .json()
+                .await
+                .context("Failed to read list TTL policies response JSON")?;
+
+            policies.extend(page.fields);
+            page_token = page.next_page_token.filter(|token| !token.is_empty());
+            if page_token.is_none() {
+                return Ok(policies);
+            }
+        }
+    }
+
+    /// Starts enabling TTL on a timestamp field of the given collection
+    /// group, so Firestore deletes documents some time after the time in
+    /// the field has passed. Documents without the field, or where it isn't
+    /// a timestamp, are never deleted.
+    ///
+    /// Returns the long-running operation that applies the policy, which can
+    /// take several minutes. A collection group can only have a single TTL
+    /// field.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// use std::time::Duration;
+    ///
+    /// use fireplace::{firestore::admin::FirestoreAdminClient, ServiceAccount};
+    ///
+    /// let service_account = ServiceAccount::from_file("./test-service-account.json")?;
+    /// let admin = FirestoreAdminClient::new(service_account)?;
+    ///
+    /// // Delete sessions once their `expireAt` time has passed
+    /// let operation = admin.create_ttl_policy("sessions", "expireAt").await?;
+    /// admin
+    ///     .wait_for_operation(
+    ///         operation,
+    ///         Duration::from_secs(30),
+    ///         Duration::from_secs(10 * 60),
+    ///     )
+    ///     .await?;
+    ///
+    /// let policies = admin.list_ttl_policies(Some("sessions")).await?;
+    /// assert_eq!(policies[0].field_path, "expireAt");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_ttl_policy(
+        &self,
+        collection_group: &str,
+        field_path: &str,
+    ) -> Result<Operation, FirebaseError> {
+        let body = serde_json::json!({ "ttlConfig": {} });
+        self.update_ttl_config(
+            collection_group,
+            field_path,
+            body,
+            "Failed to create TTL policy",
+        )
+        .await
+    }
+
+    /// Starts disabling TTL on the field of the given collection group.
+    /// Returns the long-running operation that removes the policy.
+    pub async fn delete_ttl_policy(
+        &self,
+        collection_group: &str,
+        field_path: &str,
+    ) -> Result<Operation, FirebaseError> {
+        // Updating the TTL configuration without one removes it
+        let body = serde_json::json!({});
+        self.update_ttl_config(
+            collection_group,
+            field_path,
+            body,
+            "Failed to delete TTL policy",
+        )
+        .await
+    }
+
+    async fn update_ttl_config(
+        &self,
+        collection_group: &str,
+        field_path: &str,
+        body: serde_json::Value,
+        error_msg: &'static str,
+    ) -> Result<Operation, FirebaseError> {
+        let url = format!(
+            "{}/projects/{}/databases/{}/collectionGroups/{}/fields/{}",
+            self.api_url, self.project_id, self.database_id, collection_group, field_path
+        );
+
+        let res = self
+            .admin_request(Method::PATCH, url)
+            .await?
+            .query(&[("updateMask", "ttlConfig")])
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to send update field request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error(error_msg, res).await);
+        }
+
+        let operation = res
+            .json()
+            .await
+            .context("Failed to read update field response JSON")?;
+
+        Ok(operation)
+    }
+
+    /// Lists the databases of the project.
+    pub async fn list_databases(&self) -> Result<Vec<Database>, FirebaseError> {
+        #[derive(Deserialize)]
+        struct ListDatabasesResponse {
+            #[serde(default)]
+            databases: Vec<Database>,
+        }
+
+        let res = self
+            .admin_request(Method::GET, self.databases_url())
+            .await?
+            .send()
+            .await
+            .context("Failed to send list databases request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to list databases", res).await);
+        }
+
+        let response: ListDatabasesResponse = res
+            .json()
+            .await
+            .context("Failed to read list databases response JSON")?;
+
+        Ok(response.databases)
+    }
+
+    /// Gets the database with the given ID, or `None` if it doesn't exist.
+    pub async fn get_database(&self, database_id: &str) -> Result<Option<Database>, FirebaseError> {
+        let res = self
+            .admin_request(
+                Method::GET,
+                format!("{}/{}", self.databases_url(), database_id),
+            )
+            .await?
+            .send()
+            .await
+            .context("Failed to send get database request")?;
+
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to get database", res).await);
+        }
+
+        let database = res
+            .json()
+            .await
+            .context("Failed to read get database response JSON")?;
+
+        Ok(Some(database))
+    }
+
+    /// Starts creating a database with the given ID in the project. Returns
+    /// the long-running operation that creates it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// use std::time::Duration;
+    ///
+    /// use fireplace::{
+    ///     firestore::admin::{Database, DatabaseType, FirestoreAdminClient},
+    ///     ServiceAccount,
+    /// };
+    ///
+    /// let service_account = ServiceAccount::from_file("./test-service-account.json")?;
+    /// let admin = FirestoreAdminClient::new(service_account)?;
+    ///
+    /// // A database for a single test run, deleted again afterwards
+    /// let database = Database::new("eur3", DatabaseType::FirestoreNative);
+    /// let operation = admin.create_database("test-run-42", &database).await?;
+    /// admin
+    ///     .wait_for_operation(
+    ///         operation,
+    ///         Duration::from_secs(5),
+    ///         Duration::from_secs(10 * 60),
+    ///     )
+    ///     .await?;
+    ///
+    /// // ... run the tests against the database
+    ///
+    /// admin.delete_database("test-run-42").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(name = "Create Firestore database", skip(self))]
+    pub async fn create_database(
+        &self,
+        database_id: &str,
+        database: &Database,
+    ) -> Result<Operation, FirebaseError> {
+        let res = self
+            .admin_request(Method::POST, self.databases_url())
+            .await?
+            .query(&[("databaseId", database_id)])
+            .json(database)
+            .send()
+            .await
+            .context("Failed to send create database request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to create database", res).await);
+        }
+
+        let operation = res
+            .json()
+            .await
+            .context("Failed to read create database response JSON")?;
+
+        Ok(operation)
+    }
+
+    /// Starts deleting the database with the given ID, including all of its
+    /// documents. Returns the long-running operation that deletes it.
+    #[tracing::instrument(name = "Delete Firestore database", skip(self))]
+    pub async fn delete_database(&self, database_id: &str) -> Result<Operation, FirebaseError> {
+        let res = self
+            .admin_request(
+                Method::DELETE,
+                format!("{}/{}", self.databases_url(), database_id),
+            )
+            .await?
+            .send()
+            .await
+            .context("Failed to send delete database request")?;
+
+        if !res.status().is_success() {
+            return Err(response_error("Failed to delete database", res).await);
+        }
+
+        let operation = res
+            .json()
+            .await
+            .context("Failed to read delete database response JSON")?;
+
+        Ok(operation)
+    }
+
+    fn databases_url(&self) -> String {
+        format!("{}/projects/{}/databases", self.api_url, self.project_id)
+    }
+
+    fn indexes_url(&self, collection_group: &str) -> String {
+        format!(
+            "{}/projects/{}/databases/{}/collectionGroups/{}/indexes",
+            self.api_url, self.project_id, self.database_id, collection_group
+        )
+    }
+
+    /// Calls a custom method of the database that starts a long-running
+    /// operation, e.g. `exportDocuments`.
+    async fn start_operation<B: Serialize>(
+        &self,
+        method: &str,
+        body: &B,
+        error_msg: &'static str,
+    ) -> Result<Operation, FirebaseError> {
+        let url = format!(
+            "{}/projects/{}/databases/{}:{}",
+            self.api_url, self.project_id, self.database_id, method
+        );
+
+        let res = self
+            .admin_request(Method::POST, url)
+            .await?
+            .json(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to send {} request", method))?;
+
+        if !res.status().is_success() {
+            return Err(response_error(error_msg, res).await);
+        }
+
+        let operation = res
+            .json()
+            .await
+            .with_context(|| format!("Failed to read {} response JSON", method))?;
+
+        Ok(operation)
+    }
+
+    async fn admin_request(
+        &self,
+        method: Method,
+        url: impl AsRef<str>,
+    ) -> Result<reqwest::RequestBuilder, FirebaseError> {
+        let access_token = self.api_auth_token_manager.get_access_token().await?;
+
+        let builder = self
+            .client
+            .request(method, url.as_ref())
+            .header("Authorization", format!("Bearer {}", access_token));
+
+        Ok(builder)
+    }
+}
+
+/// What to export with
+/// [`export_documents`](FirestoreAdminClient::export_documents).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    output_uri_prefix: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    collection_ids: Vec<String>,
+}
+
+impl ExportOptions {
+    /// Export to the given Cloud Storage location, e.g. `gs://my-bucket` or
+    /// `gs://my-bucket/some/prefix`.
+    pub fn new(output_uri_prefix: impl Into<String>) -> Self {
+        Self {
+            output_uri_prefix: output_uri_prefix.into(),
+            collection_ids: vec![],
+        }
+    }
+
+    /// Only export the collections with the given IDs, including
+    /// subcollections with those IDs. All collections are exported by
+    /// default.
+    pub fn collection_ids(mut self, ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.collection_ids = ids.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// What to import with
+/// [`import_documents`](FirestoreAdminClient::import_documents).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportOptions {
+    input_uri_prefix: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    collection_ids: Vec<String>,
+}
+
+impl ImportOptions {
+    /// Import the export at the given Cloud Storage location, i.e. the
+    /// [`output_uri_prefix`](Operation::output_uri_prefix) of the export.
+    pub fn new(input_uri_prefix: impl Into<String>) -> Self {
+        Self {
+            input_uri_prefix: input_uri_prefix.into(),
+            collection_ids: vec![],
+        }
+    }
+
+    /// Only import the collections with the given IDs, which must have been
+    /// part of the export. All exported collections are imported by default.
+    pub fn collection_ids(mut self, ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.collection_ids = ids.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// A composite index, which Firestore needs for queries that filter or order
+/// by several fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Index {
+    /// The resource name of the index, assigned by Firestore when it is
+    /// created. Empty for new indexes.
+    #[serde(default, skip_serializing)]
+    pub name: String,
+    pub query_scope: QueryScope,
+    /// The indexed fields, in order.
+    pub fields: Vec<IndexField>,
+    /// Whether the index is `CREATING`, `READY` or `NEEDS_REPAIR`. Not set
+    /// for new indexes.
+    #[serde(default, skip_serializing)]
+    pub state: Option<String>,
+}
+
+impl Index {
+    /// A new index without any fields. Add fields with
+    /// [`field`](Self::field).
+    pub fn new(query_scope: QueryScope) -> Self {
+        Self {
+            name: String::new(),
+            query_scope,
+            fields: vec![],
+            state: None,
+        }
+    }
+
+    /// Add the next indexed field.
+    pub fn field(mut self, field: IndexField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    /// The collection group the index belongs to, if it has a name.
+    pub fn collection_group(&self) -> Option<&str> {
+        let (_, rest) = self.name.split_once("/collectionGroups/")?;
+        rest.split('/').next()
+    }
+
+    /// Whether the index has the same scope and fields as the other index.
+    /// Firestore adds an implicit `__name__` field at the end of indexes,
+    /// which is ignored.
+    pub fn has_same_fields(&self, other: &Index) -> bool {
+        fn explicit_fields(index: &Index) -> &[IndexField] {
+            match index.fields.split_last() {
+                Some((last, rest)) if last.field_path() == "__name__" => rest,
+                _ => &index.fields,
+            }
+        }
+
+        self.query_scope == other.query_scope && explicit_fields(self) == explicit_fields(other)
+    }
+}
+
+/// Which queries an [`Index`] serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum QueryScope {
+    /// Queries of a single collection.
+    Collection,
+    /// Collection group queries, across all collections with the same ID.
+    CollectionGroup,
+}
+
+/// A field of an [`Index`], by its path, and how it is indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "RawIndexField", try_from = "RawIndexField")]
+pub enum IndexField {
+    Ascending(String),
+    Descending(String),
+    /// For `array-contains` filters.
+    ArrayContains(String),
+    /// For vector search on embeddings with the given number of dimensions,
+    /// using a flat index.
+    Vector {
+        field_path: String,
+        dimension: u32,
+    },
+}
+
+impl IndexField {
+    pub fn field_path(&self) -> &str {
+        match self {
+            Self::Ascending(path) | Self::Descending(path) | Self::ArrayContains(path) => path,
+            Self::Vector { field_path, .. } => field_path,
+        }
+    }
+}
+
+/// The JSON representation of an [`IndexField`].
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawIndexField {
+    field_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    array_config: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vector_config: Option<RawVectorConfig>,
+}
+
+/// The JSON representation of the configuration of an
+/// [`IndexField::Vector`]. Flat indexes are the only kind there is, but the
+/// kind must still be given.
+#[derive(Serialize, Deserialize)]
+struct RawVectorConfig {
+    dimension: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flat: Option<serde_json::Value>,
+}
+
+impl From<IndexField> for RawIndexField {
+    fn from(field: IndexField) -> Self {
+        let (field_path, order, array_config, vector_config) = match field {
+            IndexField::Ascending(path) => (path, Some("ASCENDING"), None, None),
+            IndexField::Descending(path) => (path, Some("DESCENDING"), None, None),
+            IndexField::ArrayContains(path) => (path, None, Some("CONTAINS"), None),
+            IndexField::Vector {
+                field_path,
+                dimension,
+            } => {
+                let config = RawVectorConfig {
+                    dimension,
+                    flat: Some(serde_json::json!({})),
+                };
+                (field_path, None, None, Some(config))
+            }
+        };
+
+        Self {
+            field_path,
+            order: order.map(str::to_string),
+            array_config: array_config.map(str::to_string),
+            vector_config,
+        }
+    }
+}
+
+impl TryFrom<RawIndexField> for IndexField {
+    type Error = String;
+
+    fn try_from(raw: RawIndexField) -> Result<Self, Self::Error> {
+        let order = raw.order.as_deref();
+        let array_config = raw.array_config.as_deref();
+
+        match (order, array_config, raw.vector_config) {
+            (Some("ASCENDING"), None, None) => Ok(Self::Ascending(raw.field_path)),
+            (Some("DESCENDING"), None, None) => Ok(Self::Descending(raw.field_path)),
+            (None, Some("CONTAINS"), None) => Ok(Self::ArrayContains(raw.field_path)),
+            (None, None, Some(config)) => Ok(Self::Vector {
+                field_path: raw.field_path,
+                dimension: config.dimension,
+            }),
+            _ => Err(format!(
+                "Unsupported index configuration of field '{}'",
+                raw.field_path
+            )),
+        }
+    }
+}
+
+/// A Firestore database of the project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Database {
+    /// The resource name of the database, assigned by Firestore when it is
+    /// created. Empty for new databases.
+    #[serde(default, skip_serializing)]
+    pub name: String,
+    /// Where the database is stored, e.g. `eur3` or `us-east1`.
+    pub location_id: String,
+    #[serde(rename = "type")]
+    pub database_type: DatabaseType,
+    /// When the database was created, as an RFC 3339 timestamp. Not set for
+    /// new databases.
+    #[serde(default, skip_serializing)]
+    pub create_time: Option<String>,
+}
+
+impl Database {
+    pub fn new(location_id: impl Into<String>, database_type: DatabaseType) -> Self {
+        Self {
+            name: String::new(),
+            location_id: location_id.into(),
+            database_type,
+            create_time: None,
+        }
+    }
+
+    /// The ID of the database, e.g. `(default)`, if it has a name.
+    pub fn database_id(&self) -> Option<&str> {
+        let (_, id) = self.name.rsplit_once("/databases/")?;
+        Some(id)
+    }
+}
+
+/// The mode of a [`Database`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DatabaseType {
+    /// Firestore in Native mode, which this crate works with.
+    FirestoreNative,
+    /// Firestore in Datastore mode.
+    DatastoreMode,
+}
+
+/// A field of a collection group whose documents are deleted once the
+/// timestamp in the field has passed. See
+/// [`create_ttl_policy`](FirestoreAdminClient::create_ttl_policy).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawField")]
+pub struct TtlPolicy {
+    pub collection_group: String,
+    pub field_path: String,
+    /// Whether the policy is `CREATING`, `ACTIVE` or `NEEDS_REPAIR`.
+    pub state: Option<String>,
+}
+
+/// The JSON representation of a field with a TTL configuration.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawField {
+    name: String,
+    ttl_config: Option<RawTtlConfig>,
+}
+
+#[derive(Deserialize)]
+struct RawTtlConfig {
+    state: Option<String>,
+}
+
+impl TryFrom<RawField> for TtlPolicy {
+    type Error = String;
+
+    fn try_from(raw: RawField) -> Result<Self, Self::Error> {
+        let (_, rest) = raw
+            .name
+            .split_once("/collectionGroups/")
+            .ok_or_else(|| format!("Invalid field name '{}'", raw.name))?;
+        let (collection_group, field_path) = rest
+            .split_once("/fields/")
+            .ok_or_else(|| format!("Invalid field name '{}'", raw.name))?;
+
+        Ok(Self {
+            collection_group: collection_group.to_string(),
+            field_path: field_path.to_string(),
+            state: raw.ttl_config.and_then(|config| config.state),
+        })
+    }
+}
+
+/// A long-running operation of the Firestore Admin API, such as an export,
+/// an import or building an index.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Operation {
+    /// The name of the operation, for use with
+    /// [`get_operation`](FirestoreAdminClient::get_operation).
+    pub name: String,
+    /// Whether the operation has finished, either successfully or not.
+    #[serde(default)]
+    pub done: bool,
+    pub metadata: Option<OperationMetadata>,
+    /// Why the operation failed, if it did.
+    pub error: Option<OperationError>,
+    /// The result of the operation once it has finished successfully.
+    pub response: Option<serde_json::Value>,
+}
+
+impl Operation {
+    /// Where the documents were exported to, once an export has finished.
+    pub fn output_uri_prefix(&self) -> Option<&str> {
+        self.response.as_ref()?.get("outputUriPrefix")?.as_str()
+    }
+}
+
+/// The progress of an [`Operation`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationMetadata {
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    /// The state of the operation, e.g. `PROCESSING` or `SUCCESSFUL`.
+    pub operation_state: Option<String>,
+    pub progress_documents: Option<Progress>,
+    pub progress_bytes: Option<Progress>,
+    #[serde(default)]
+    pub collection_ids: Vec<String>,
+    /// Where an export writes to.
+    pub output_uri_prefix: Option<String>,
+    /// Where an import reads from.
+    pub input_uri_prefix: Option<String>,
+    /// The name of the index being built.
+    pub index: Option<String>,
+    /// The state of an index or field operation, e.g. `PROCESSING`.
+    pub state: Option<String>,
+    /// The name of the field whose TTL policy is being updated.
+    pub field: Option<String>,
+}
+
+/// How much of the work of an operation has been completed, e.g. in
+/// documents or bytes.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    #[serde(default, deserialize_with = "deserialize_int64")]
+    pub estimated_work: u64,
+    #[serde(default, deserialize_with = "deserialize_int64")]
+    pub completed_work: u64,
+}
+
+/// Why an [`Operation`] failed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OperationError {
+    code: i32,
+    pub message: String,
+}
+
+impl OperationError {
+    /// The gRPC status code of the error, e.g.
+    /// [`NotFound`](tonic::Code::NotFound) when importing an export that
+    /// doesn't exist, or
+    /// [`PermissionDenied`](tonic::Code::PermissionDenied) when the service
+    /// account can't access the bucket.
+    pub fn code(&self) -> tonic::Code {
+        tonic::Code::from_i32(self.code)
+    }
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?})", self.message, self.code())
+    }
+}
+
+/// The JSON mapping of protobuf represents 64-bit integers as strings.
+fn deserialize_int64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Int64 {
+        String(String),
+        Number(u64),
+    }
+
+    match Int64::deserialize(deserializer)? {
+        Int64::String(s) => s.parse().map_err(serde::de::Error::custom),
+        Int64::Number(n) => Ok(n),
+    }
+}
+
+async fn response_error(msg: &'static str, res: Response) -> FirebaseError {
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+
+    let err = anyhow::anyhow!("{} (status: {}): {}", msg, status, body).into();
+
+    tracing::error!("{:?}'", &err);
+
+    err
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_export_options() {
+        let options = ExportOptions::new("gs://backups/today").collection_ids(["users"]);
+
+        assert_eq!(
+            serde_json::to_value(options).unwrap(),
+            serde_json::json!({
+                "outputUriPrefix": "gs://backups/today",
+                "collectionIds": ["users"],
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(ExportOptions::new("gs://backups")).unwrap(),
+            serde_json::json!({ "outputUriPrefix": "gs://backups" })
+        );
+    }
+
+    #[test]
+    fn index_json_round_trip() {
+        let index = Index::new(QueryScope::CollectionGroup)
+            .field(IndexField::ArrayContains("tags".to_string()))
+            .field(IndexField::Descending("rating".to_string()));
+
+        assert_eq!(
+            serde_json::to_value(&index).unwrap(),
+            serde_json::json!({
+                "queryScope": "COLLECTION_GROUP",
+                "fields": [
+                    { "fieldPath": "tags", "arrayConfig": "CONTAINS" },
+                    { "fieldPath": "rating", "order": "DESCENDING" },
+                ],
+            })
+        );
+
+        let listed: Index = serde_json::from_value(serde_json::json!({
+            "name": "projects/p/databases/(default)/collectionGroups/landmarks/indexes/abc",
+            "queryScope": "COLLECTION_GROUP",
+            "fields": [
+                { "fieldPath": "tags", "arrayConfig": "CONTAINS" },
+                { "fieldPath": "rating", "order": "DESCENDING" },
+                { "fieldPath": "__name__", "order": "DESCENDING" },
+            ],
+            "state": "READY",
+        }))
+        .unwrap();
+
+        assert_eq!(listed.collection_group(), Some("landmarks"));
+        assert_eq!(listed.state.as_deref(), Some("READY"));
+        assert!(listed.has_same_fields(&index));
+        assert!(!listed.has_same_fields(&Index::new(QueryScope::CollectionGroup)));
+    }
+
+    #[test]
+    fn vector_index_json_round_trip() {
+        let index = Index::new(QueryScope::Collection)
+            .field(IndexField::Ascending("genre".to_string()))
+            .field(IndexField::Vector {
+                field_path: "embedding".to_string(),
+                dimension: 768,
+            });
+
+        let json = serde_json::json!({
+            "queryScope": "COLLECTION",
+            "fields": [
+                { "fieldPath": "genre", "order": "ASCENDING" },
+                { "fieldPath": "embedding", "vectorConfig": { "dimension": 768, "flat": {} } },
+            ],
+        });
+
+        assert_eq!(serde_json::to_value(&index).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Index>(json).unwrap(), index);
+    }
+
+    #[test]
+    fn database_json_round_trip() {
+        let database = Database::new("eur3", DatabaseType::FirestoreNative);
+
+        assert_eq!(
+            serde_json::to_value(&database).unwrap(),
+            serde_json::json!({ "locationId": "eur3", "type": "FIRESTORE_NATIVE" })
+        );
+
+        let listed: Database = serde_json::from_value(serde_json::json!({
+            "name": "projects/p/databases/test-run-42",
+            "uid": "abc",
+            "createTime": "2022-10-01T12:00:00.000000Z",
+            "locationId": "eur3",
+            "type": "FIRESTORE_NATIVE",
+            "concurrencyMode": "PESSIMISTIC",
+        }))
+        .unwrap();
+
+        assert_eq!(listed.database_id(), Some("test-run-42"));
+        assert_eq!(listed.database_type, DatabaseType::FirestoreNative);
+    }
+
+    #[test]
+    fn deserializes_ttl_policy() {
+        let policy: TtlPolicy = serde_json::from_value(serde_json::json!({
+            "name": "projects/p/databases/(default)/collectionGroups/sessions/fields/expireAt",
+            "indexConfig": { "usesAncestorConfig": true },
+            "ttlConfig": { "state": "ACTIVE" },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            policy,
+            TtlPolicy {
+                collection_group: "sessions".to_string(),
+                field_path: "expireAt".to_string(),
+                state: Some("ACTIVE".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn serializes_import_options() {
+        let options = ImportOptions::new("gs://backups/today").collection_ids(["users"]);
+
+        assert_eq!(
+            serde_json::to_value(options).unwrap(),
+            serde_json::json!({
+                "inputUriPrefix": "gs://backups/today",
+                "collectionIds": ["users"],
+            })
+        );
+    }
+
+    #[test]
+    fn deserializes_failed_operation() {
+        let operation: Operation = serde_json::from_value(serde_json::json!({
+            "name": "projects/p/databases/(default)/operations/abc",
+            "done": true,
+            "error": { "code": 5, "message": "No such object: backups/missing" }
+        }))
+        .unwrap();
+
+        let error = operation.error.unwrap();
+        assert_eq!(error.code(), tonic::Code::NotFound);
+        assert_eq!(
+            error.to_string(),
+            "No such object: backups/missing (NotFound)"
+        );
+    }
+
+    #[test]
+    fn deserializes_export_operation() {
+        let operation: Operation = serde_json::from_value(serde_json::json!({
+            "name": "projects/p/databases/(default)/operations/abc",
+            "metadata": {
+                "@type": "type.googleapis.com/google.firestore.admin.v1.ExportDocumentsMetadata",
+                "startTime": "2022-10-01T12:00:00.000000Z",
+                "operationState": "SUCCESSFUL",
+                "progressDocuments": { "estimatedWork": "120", "completedWork": "120" },
+                "collectionIds": ["users"],
+                "outputUriPrefix": "gs://backups/today"
+            },
+            "done": true,
+            "response": {
+                "@type": "type.googleapis.com/google.firestore.admin.v1.ExportDocumentsResponse",
+                "outputUriPrefix": "gs://backups/today"
+            }
+        }))
+        .unwrap();
+
+        assert!(operation.done);
+        assert_eq!(operation.output_uri_prefix(), Some("gs://backups/today"));
+        let metadata = operation.metadata.unwrap();
+        assert_eq!(metadata.progress_documents.unwrap().completed_work, 120);
+        assert!(metadata.progress_bytes.is_none());
+    }
+}
@@ -0,0 +1,294 @@
+//! Running several aggregations over the results of a query in a single
+//! request. See [`FirestoreClient::aggregate`](super::client::FirestoreClient::aggregate).
+
+use std::collections::HashMap;
+
+use firestore_grpc::v1::{
+    structured_aggregation_query::{aggregation, Aggregation as GrpcAggregation},
+    structured_query::FieldReference,
+    value::ValueType,
+    Value,
+};
+
+use crate::error::FirebaseError;
+
+/// An aggregation over the results of a query, created with [`count`],
+/// [`sum`] or [`avg`]. Its value is returned under its alias, which defaults
+/// to e.g. `count`, `sum_price` or `avg_price`, and can be changed with
+/// [`alias`](Self::alias).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregation {
+    alias: String,
+    kind: AggregationKind,
+    up_to: Option<u64>,
+}
+
+/// The most aggregations Firestore runs in a single request.
+pub const MAX_AGGREGATIONS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AggregationKind {
+    Count,
+    Sum(String),
+    Avg(String),
+}
+
+/// Counts the results of the query. Use [`up_to`](Aggregation::up_to) to
+/// stop counting early.
+pub fn count() -> Aggregation {
+    Aggregation {
+        alias: "count".to_string(),
+        kind: AggregationKind::Count,
+        up_to: None,
+    }
+}
+
+/// Sums the numeric field across the results of the query, ignoring results
+/// where it isn't a number. The sum is an integer if all values are integers
+/// and it doesn't overflow, and a double otherwise.
+pub fn sum(field: impl Into<String>) -> Aggregation {
+    let field = field.into();
+    Aggregation {
+        alias: format!("sum_{}", field),
+        kind: AggregationKind::Sum(field),
+        up_to: None,
+    }
+}
+
+/// Averages the numeric field across the results of the query, ignoring
+/// results where it isn't a number. The average is a double, or null if no
+/// result has a numeric value.
+pub fn avg(field: impl Into<String>) -> Aggregation {
+    let field = field.into();
+    Aggregation {
+        alias: format!("avg_{}", field),
+        kind: AggregationKind::Avg(field),
+        up_to: None,
+    }
+}
+
+impl Aggregation {
+    /// Return the value under the given alias instead of the default one.
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.alias = alias.into();
+        self
+    }
+
+    /// Stop counting once `up_to` results have been counted, which is
+    /// cheaper when you only need to know whether there are at least that
+    /// many results. Only applies to a [`count`], and must be greater than
+    /// zero.
+    pub fn up_to(mut self, up_to: u64) -> Self {
+        self.up_to = Some(up_to);
+        self
+    }
+
+    pub(crate) fn alias_ref(&self) -> &str {
+        &self.alias
+    }
+
+    pub(crate) fn into_grpc(self) -> GrpcAggregation {
+        let field = |field_path| Some(FieldReference { field_path });
+
+        let operator = match self.kind {
+            AggregationKind::Count => aggregation::Operator::Count(aggregation::Count {
+                up_to: self
+                    .up_to
+                    .map(|up_to| i64::try_from(up_to).unwrap_or(i64::MAX)),
+            }),
+            AggregationKind::Sum(path) => {
+                aggregation::Operator::Sum(aggregation::Sum { field: field(path) })
+            }
+            AggregationKind::Avg(path) => {
+                aggregation::Operator::Avg(aggregation::Avg { field: field(path) })
+            }
+        };
+
+        GrpcAggregation {
+            alias: self.alias,
+            operator: Some(operator),
+        }
+    }
+}
+
+/// Checks the aggregations of a request before it is sent, as Firestore's
+/// errors for these don't say which aggregation is at fault.
+pub(crate) fn validate_aggregations(aggregations: &[Aggregation]) -> Result<(), FirebaseError> {
+    if aggregations.is_empty() || aggregations.len() > MAX_AGGREGATIONS {
+        return Err(anyhow::anyhow!(
+            "Between 1 and {} aggregations can be run at once, but {} were given",
+            MAX_AGGREGATIONS,
+            aggregations.len()
+        )
+        .into());
+    }
+
+    for (i, aggregation) in aggregations.iter().enumerate() {
+        // The values are returned by alias, so they must be unique
+        let alias = aggregation.alias_ref();
+        if aggregations[..i].iter().any(|a| a.alias_ref() == alias) {
+            return Err(anyhow::anyhow!("Duplicate aggregation alias '{}'", alias).into());
+        }
+
+        match (&aggregation.kind, aggregation.up_to) {
+            (_, None) => {}
+            (AggregationKind::Count, Some(up_to)) if up_to > 0 => {}
+            (AggregationKind::Count, Some(_)) => {
+                return Err(anyhow::anyhow!(
+                    "The count limit of aggregation '{}' must be greater than zero",
+                    alias
+                )
+                .into());
+            }
+            (_, Some(_)) => {
+                return Err(anyhow::anyhow!(
+                    "Aggregation '{}' has a limit, but only counts can be limited",
+                    alias
+                )
+                .into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The value of an [`Aggregation`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateValue {
+    Integer(i64),
+    Double(f64),
+    /// E.g. the average of a field that no result has a numeric value for.
+    Null,
+}
+
+impl AggregateValue {
+    /// The value as an integer, if it is one.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value as a double, converting integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Integer(value) => Some(*value as f64),
+            Self::Double(value) => Some(*value),
+            Self::Null => None,
+        }
+    }
+}
+
+impl TryFrom<Value> for AggregateValue {
+    type Error = FirebaseError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.value_type {
+            Some(ValueType::IntegerValue(value)) => Ok(Self::Integer(value)),
+            Some(ValueType::DoubleValue(value)) => Ok(Self::Double(value)),
+            Some(ValueType::NullValue(_)) => Ok(Self::Null),
+            v => Err(anyhow::anyhow!("Unexpected value type for aggregation: {v:?}").into()),
+        }
+    }
+}
+
+/// The values of the aggregations run with
+/// [`FirestoreClient::aggregate`](super::client::FirestoreClient::aggregate),
+/// by their alias.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AggregationResult {
+    values: HashMap<String, AggregateValue>,
+}
+
+impl AggregationResult {
+    pub(crate) fn from_values(values: HashMap<String, Value>) -> Result<Self, FirebaseError> {
+        let values = values
+            .into_iter()
+            .map(|(alias, value)| Ok((alias, AggregateValue::try_from(value)?)))
+            .collect::<Result<_, FirebaseError>>()?;
+
+        Ok(Self { values })
+    }
+
+    /// The value of the aggregation with the given alias.
+    pub fn get(&self, alias: &str) -> Option<AggregateValue> {
+        self.values.get(alias).copied()
+    }
+
+    /// The value of a [`count`] with the given alias.
+    pub fn count(&self, alias: &str) -> Option<u64> {
+        self.get(alias)?.as_i64().map(|count| count as u64)
+    }
+
+    /// The value of an aggregation with the given alias as a double, e.g. a
+    /// [`sum`] or [`avg`].
+    pub fn f64(&self, alias: &str) -> Option<f64> {
+        self.get(alias)?.as_f64()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, AggregateValue)> {
+        self.values
+            .iter()
+            .map(|(alias, value)| (alias.as_str(), *value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_aliases() {
+        assert_eq!(count().alias_ref(), "count");
+        assert_eq!(sum("price").alias_ref(), "sum_price");
+        assert_eq!(avg("price").alias("mean").alias_ref(), "mean");
+    }
+
+    #[test]
+    fn validates_aggregations() {
+        assert!(validate_aggregations(&[count(), sum("price")]).is_ok());
+        assert!(validate_aggregations(&[count().up_to(10)]).is_ok());
+
+        assert!(validate_aggregations(&[]).is_err());
+        let too_many: Vec<_> = (0..=MAX_AGGREGATIONS)
+            .map(|i| count().alias(format!("count_{}", i)))
+            .collect();
+        assert!(validate_aggregations(&too_many).is_err());
+
+        assert!(validate_aggregations(&[count(), count()]).is_err());
+        assert!(validate_aggregations(&[count().up_to(0)]).is_err());
+        assert!(validate_aggregations(&[sum("price").up_to(10)]).is_err());
+    }
+
+    #[test]
+    fn count_up_to_is_sent_as_limit() {
+        let operator = count().up_to(10).into_grpc().operator;
+        assert_eq!(
+            operator,
+            Some(aggregation::Operator::Count(aggregation::Count {
+                up_to: Some(10)
+            }))
+        );
+    }
+
+    #[test]
+    fn reads_typed_values() {
+        let value = |value_type| Value {
+            value_type: Some(value_type),
+        };
+        let result = AggregationResult::from_values(HashMap::from([
+            ("n".to_string(), value(ValueType::IntegerValue(3))),
+            ("total".to_string(), value(ValueType::DoubleValue(10.5))),
+            ("mean".to_string(), value(ValueType::NullValue(0))),
+        ]))
+        .unwrap();
+
+        assert_eq!(result.count("n"), Some(3));
+        assert_eq!(result.f64("n"), Some(3.0));
+        assert_eq!(result.f64("total"), Some(10.5));
+        assert_eq!(result.get("mean"), Some(AggregateValue::Null));
+        assert_eq!(result.get("missing"), None);
+    }
+}
@@ -0,0 +1,161 @@
+//! Atomic writes to several documents. See [`WriteBatch`].
+
+use serde::Serialize;
+
+use crate::error::FirebaseError;
+
+use super::{
+    client::FirestoreClient,
+    reference::DocumentReference,
+    write::{delete_write, serialize_write, Write, WriteKind, WriteResult, MAX_BATCH_WRITE_SIZE},
+};
+
+/// A batch of writes that are committed atomically: either all of them are
+/// applied, or none of them are. Create one with
+/// [`FirestoreClient::batch`].
+///
+/// A batch may contain at most 500 writes. If any of the preconditions of the
+/// writes fail, e.g. because a document to be created already exists, the
+/// commit fails with the corresponding error, such as
+/// [`DocumentAlreadyExists`](FirebaseError::DocumentAlreadyExists) or
+/// [`DocumentNotfound`](FirebaseError::DocumentNotfound).
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::{error::FirebaseError, firestore::collection};
+/// use ulid::Ulid;
+///
+/// let order_ref = collection("orders").doc(Ulid::new());
+/// let cart_ref = collection("carts").doc(Ulid::new());
+/// client
+///     .set_document(&cart_ref, &serde_json::json!({ "items": ["pizza"] }))
+///     .await?;
+///
+/// let mut batch = client.batch();
+/// batch.create_document(&order_ref, &serde_json::json!({ "items": ["pizza"] }))?;
+/// batch.delete_document(&cart_ref);
+/// batch.commit().await?;
+///
+/// // Creating the same order again fails, so the cart is not deleted either
+/// client
+///     .set_document(&cart_ref, &serde_json::json!({ "items": [] }))
+///     .await?;
+///
+/// let mut batch = client.batch();
+/// batch.create_document(&order_ref, &serde_json::json!({ "items": [] }))?;
+/// batch.delete_document(&cart_ref);
+/// let result = batch.commit().await;
+///
+/// assert!(matches!(result, Err(FirebaseError::DocumentAlreadyExists(_))));
+/// assert!(client
+///     .get_document::<serde_json::Value>(&cart_ref)
+///     .await?
+///     .is_some());
+/// # Ok(())
+/// # }
+/// ```
+pub struct WriteBatch {
+    client: FirestoreClient,
+    writes: Vec<Write>,
+}
+
+impl WriteBatch {
+    pub(crate) fn new(client: FirestoreClient) -> Self {
+        Self {
+            client,
+            writes: vec![],
+        }
+    }
+
+    /// Add a write that overwrites the document, creating it if it doesn't
+    /// exist.
+    pub fn set_document<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        self.add_serialized(doc_ref, document, WriteKind::Set)
+    }
+
+    /// Add a write that creates the document. The commit fails if the
+    /// document already exists.
+    pub fn create_document<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        self.add_serialized(doc_ref, document, WriteKind::Create)
+    }
+
+    /// Add a write that overwrites the document. The commit fails if the
+    /// document doesn't exist.
+    pub fn update_document<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        self.add_serialized(doc_ref, document, WriteKind::Update)
+    }
+
+    /// Add a write that only updates the given fields of the document. The
+    /// commit fails if the document doesn't exist.
+    pub fn update_document_merge<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+        fields: &[&str],
+    ) -> Result<(), FirebaseError> {
+        self.add_serialized(doc_ref, document, WriteKind::UpdateMerge(fields))
+    }
+
+    /// Add a write that deletes the document, if it exists.
+    pub fn delete_document(&mut self, doc_ref: &DocumentReference) {
+        let write = delete_write(self.client.root_resource_path(), doc_ref);
+        self.writes.push(write);
+    }
+
+    /// Add a write that was created with e.g.
+    /// [`FirestoreClient::to_write`].
+    pub fn add_write(&mut self, write: Write) {
+        self.writes.push(write);
+    }
+
+    /// The number of writes in the batch.
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Commit the writes atomically, returning the result of each write in
+    /// the order they were added.
+    pub async fn commit(mut self) -> Result<Vec<WriteResult>, FirebaseError> {
+        if self.writes.len() > MAX_BATCH_WRITE_SIZE {
+            return Err(anyhow::anyhow!(
+                "A batch may contain at most {} writes, but it contains {}",
+                MAX_BATCH_WRITE_SIZE,
+                self.writes.len()
+            )
+            .into());
+        }
+
+        self.client.commit(self.writes).await
+    }
+
+    fn add_serialized<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+        kind: WriteKind,
+    ) -> Result<(), FirebaseError> {
+        let write = serialize_write(self.client.root_resource_path(), doc_ref, document, kind)?;
+        self.writes.push(write);
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+//! Buffering writes in the background and flushing them in batches. See
+//! [`BufferedWriter`].
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::{cancel::CancellationToken, error::FirebaseError};
+
+use super::{
+    client::FirestoreClient,
+    reference::DocumentReference,
+    write::{
+        delete_write, serialize_write, split_into_batches, Write, WriteKind, MAX_BATCH_WRITE_SIZE,
+    },
+};
+
+/// Options for a [`BufferedWriter`].
+#[derive(Debug, Clone)]
+pub struct BufferedWriterOptions {
+    max_writes: usize,
+    max_delay: Duration,
+    cancellation_token: Option<CancellationToken>,
+}
+
+impl Default for BufferedWriterOptions {
+    fn default() -> Self {
+        Self {
+            max_writes: MAX_BATCH_WRITE_SIZE,
+            max_delay: Duration::from_secs(1),
+            cancellation_token: None,
+        }
+    }
+}
+
+impl BufferedWriterOptions {
+    /// Flush when 500 writes are buffered, or one second after the first
+    /// buffered write.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flush as soon as this many writes are buffered. Capped at 500, the
+    /// maximum size of a write batch.
+    pub fn max_writes(mut self, max_writes: usize) -> Self {
+        self.max_writes = max_writes.clamp(1, MAX_BATCH_WRITE_SIZE);
+        self
+    }
+
+    /// Flush at the latest this long after the first write was buffered, so
+    /// writes don't linger in the buffer when only a few arrive.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Stop the background task as soon as the token is cancelled, e.g. when
+    /// the service shuts down, instead of waiting for the remaining writes.
+    /// Writes that haven't been applied yet are dropped and counted as failed,
+    /// whether they were buffered, still queued or part of a flush that was
+    /// in progress. Writes of an interrupted flush may still have been
+    /// applied.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+}
+
+/// Counts of what a [`BufferedWriter`] has written, returned by
+/// [`BufferedWriter::shutdown`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferedWriteStats {
+    /// The number of writes that were applied.
+    pub written: u64,
+    /// The number of writes that failed. Each failure is logged as a warning.
+    pub failed: u64,
+    /// The number of times the buffer was flushed.
+    pub flushes: u64,
+}
+
+enum Command {
+    Write(Write),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Accumulates writes and applies them in batches from a background task, so
+/// high-frequency writes like telemetry events don't each pay for their own
+/// commit. The buffer is flushed when it holds
+/// [`max_writes`](BufferedWriterOptions::max_writes) writes or
+/// [`max_delay`](BufferedWriterOptions::max_delay) after the first buffered
+/// write, whichever comes first.
+///
+/// Like [`FirestoreClient::write_sink`], the writes are not applied
+/// atomically. Since they are applied in the background, failed writes can't
+/// be reported to the caller; they are logged as warnings and counted in the
+/// [`BufferedWriteStats`] instead.
+///
+/// Call [`shutdown`](Self::shutdown) to flush the remaining writes and wait
+/// for them to be applied. Dropping the writer also flushes the remaining
+/// writes in the background, but nothing waits for them, so they are lost if
+/// the runtime shuts down first.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let client = fireplace::firestore::test_helpers::initialise().await?;
+/// use std::time::Duration;
+///
+/// use fireplace::firestore::{
+///     buffered::{BufferedWriter, BufferedWriterOptions},
+///     collection,
+/// };
+/// use ulid::Ulid;
+///
+/// let writer = BufferedWriter::new(
+///     &client,
+///     BufferedWriterOptions::new()
+///         .max_writes(100)
+///         .max_delay(Duration::from_millis(500)),
+/// );
+///
+/// for page in ["home", "pricing", "signup"] {
+///     let event_ref = collection("page-views").doc(Ulid::new());
+///     writer
+///         .set_document(&event_ref, &serde_json::json!({ "page": page }))
+///         .await?;
+/// }
+///
+/// let stats = writer.shutdown().await?;
+/// assert_eq!(stats.written, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct BufferedWriter {
+    commands: mpsc::Sender<Command>,
+    root_resource_path: String,
+    task: JoinHandle<BufferedWriteStats>,
+}
+
+impl BufferedWriter {
+    /// Create a writer that applies its writes with a clone of the given
+    /// client, and start its background task. Must be called from within a
+    /// Tokio runtime.
+    pub fn new(client: &FirestoreClient, options: BufferedWriterOptions) -> Self {
+        // Writers only wait for room in the channel when the background task
+        // falls behind by more than a full buffer
+        let (commands, receiver) = mpsc::channel(options.max_writes);
+        let root_resource_path = client.root_resource_path().to_string();
+        let flusher = Flusher {
+            client: client.clone(),
+            options,
+            buffer: vec![],
+            deadline: None,
+            in_flight: 0,
+            stats: BufferedWriteStats::default(),
+        };
+
+        Self {
+            commands,
+            root_resource_path,
+            task: tokio::spawn(flusher.run(receiver)),
+        }
+    }
+
+    /// Buffer a write that overwrites the document, creating it if it
+    /// doesn't exist.
+    pub async fn set_document<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        let write = serialize_write(&self.root_resource_path, doc_ref, document, WriteKind::Set)?;
+        self.add_write(write).await
+    }
+
+    /// Buffer a write that creates the document. The write fails if the
+    /// document already exists.
+    pub async fn create_document<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        let write = serialize_write(
+            &self.root_resource_path,
+            doc_ref,
+            document,
+            WriteKind::Create,
+        )?;
+        self.add_write(write).await
+    }
+
+    /// Buffer a write that deletes the document, if it exists.
+    pub async fn delete_document(&self, doc_ref: &DocumentReference) -> Result<(), FirebaseError> {
+        let write = delete_write(&self.root_resource_path, doc_ref);
+        self.add_write(write).await
+    }
+
+    /// Buffer a write that was created with e.g.
+    /// [`FirestoreClient::to_write`]. Waits if the background task has fallen
+    /// behind by more than a full buffer.
+    pub async fn add_write(&self, write: Write) -> Result<(), FirebaseError> {
+        self.commands
+            .send(Command::Write(write))
+            .await
+            .map_err(|_| stopped_err())
+    }
+
+    /// Flush the writes buffered so far, and wait until they have been
+    /// applied.
+    pub async fn flush(&self) -> Result<(), FirebaseError> {
+        let (done, flushed) = oneshot::channel();
+        self.commands
+            .send(Command::Flush(done))
+            .await
+            .map_err(|_| stopped_err())?;
+        flushed.await.map_err(|_| stopped_err())
+    }
+
+    /// Flush the remaining writes, wait until they have been applied, and
+    /// stop the background task.
+    pub async fn shutdown(self) -> Result<BufferedWriteStats, FirebaseError> {
+        let Self { commands, task, .. } = self;
+
+        // The background task flushes and stops once the channel is closed
+        drop(commands);
+
+        task.await
+            .map_err(|err| anyhow::anyhow!("The buffered writer failed: {}", err).into())
+    }
+}
+
+fn stopped_err() -> FirebaseError {
+    anyhow::anyhow!("The buffered writer has stopped").into()
+}
+
+/// The background task of a [`BufferedWriter`].
+struct Flusher {
+    client: FirestoreClient,
+    options: BufferedWriterOptions,
+    buffer: Vec<Write>,
+    /// When the buffer must be flushed at the latest, if it isn't empty.
+    deadline: Option<Instant>,
+    /// The number of writes of the current flush that haven't been applied
+    /// yet.
+    in_flight: usize,
+    stats: BufferedWriteStats,
+}
+
+impl Flusher {
+    async fn run(mut self, mut commands: mpsc::Receiver<Command>) -> BufferedWriteStats {
+        match self.options.cancellation_token.clone() {
+            Some(token) => tokio::select! {
+                biased;
+                _ = token.cancelled() => self.drop_remaining(&mut commands),
+                _ = self.process(&mut commands) => {}
+            },
+            None => self.process(&mut commands).await,
+        }
+
+        self.stats
+    }
+
+    /// Counts the writes that are buffered, in flight or still queued as
+    /// failed, after the writer was cancelled.
+    fn drop_remaining(&mut self, commands: &mut mpsc::Receiver<Command>) {
+        let mut dropped = self.buffer.len() + self.in_flight;
+        self.buffer.clear();
+        self.in_flight = 0;
+
+        commands.close();
+        while let Ok(command) = commands.try_recv() {
+            if let Command::Write(_) = command {
+                dropped += 1;
+            }
+        }
+
+        if dropped > 0 {
+            tracing::warn!("Buffered writer was cancelled, dropping {} writes", dropped);
+            self.stats.failed += dropped as u64;
+        }
+    }
+
+    async fn process(&mut self, commands: &mut mpsc::Receiver<Command>) {
+        loop {
+            let command = match self.deadline {
+                Some(deadline) => tokio::select! {
+                    command = commands.recv() => command,
+                    _ = tokio::time::sleep_until(deadline) => {
+                        self.flush().await;
+                        continue;
+                    }
+                },
+                None => commands.recv().await,
+            };
+
+            match command {
+                Some(Command::Write(write)) => {
+                    if self.buffer.is_empty() {
+                        self.deadline = Some(Instant::now() + self.options.max_delay);
+                    }
+                    self.buffer.push(write);
+                    if self.buffer.len() >= self.options.max_writes {
+                        self.flush().await;
+                    }
+                }
+                Some(Command::Flush(done)) => {
+                    self.flush().await;
+                    let _ = done.send(());
+                }
+                None => {
+                    self.flush().await;
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn flush(&mut self) {
+        self.deadline = None;
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let writes = std::mem::take(&mut self.buffer);
+        self.in_flight = writes.len();
+        self.stats.flushes += 1;
+
+        for batch in split_into_batches(writes) {
+            for result in self.client.batch_write(batch).await {
+                self.in_flight -= 1;
+                match result {
+                    Ok(_) => self.stats.written += 1,
+                    Err(err) => {
+                        tracing::warn!("Buffered write failed: {}", err);
+                        self.stats.failed += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_writes_is_capped_at_batch_size() {
+        assert_eq!(BufferedWriterOptions::new().max_writes(0).max_writes, 1);
+        assert_eq!(
+            BufferedWriterOptions::new().max_writes(10_000).max_writes,
+            MAX_BATCH_WRITE_SIZE
+        );
+    }
+}
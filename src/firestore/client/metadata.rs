@@ -0,0 +1,96 @@
+use firestore_grpc::tonic::metadata::{Ascii, MetadataKey, MetadataMap, MetadataValue};
+
+use crate::error::FirebaseError;
+
+/// Extra gRPC metadata (headers) to send with requests, e.g. routing headers
+/// or tags for quality of service. Attach it to a client with
+/// [`FirestoreClient::with_request_metadata`](super::FirestoreClient::with_request_metadata).
+///
+/// The metadata is added after the authorization header, and cannot replace
+/// it.
+#[derive(Debug, Clone, Default)]
+pub struct RequestMetadata {
+    entries: Vec<(MetadataKey<Ascii>, MetadataValue<Ascii>)>,
+}
+
+impl RequestMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a header. Fails if the key or value is not a valid ASCII header,
+    /// or if the key is `authorization`.
+    pub fn header(mut self, key: &str, value: &str) -> Result<Self, FirebaseError> {
+        let key = MetadataKey::from_bytes(key.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Invalid metadata key '{}'", key))?;
+
+        if key.as_str() == "authorization" {
+            return Err(anyhow::anyhow!("The authorization header cannot be overridden").into());
+        }
+
+        let value = MetadataValue::from_str(value)
+            .map_err(|_| anyhow::anyhow!("Invalid value for metadata key '{}'", key))?;
+
+        self.entries.push((key, value));
+        Ok(self)
+    }
+
+    /// Set the `x-goog-request-params` header, which Google APIs use to route
+    /// requests, from the given key-value pairs.
+    pub fn request_params(self, params: &[(&str, &str)]) -> Result<Self, FirebaseError> {
+        let value = params
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        self.header("x-goog-request-params", &value)
+    }
+
+    pub(crate) fn extend(&mut self, other: RequestMetadata) {
+        self.entries.extend(other.entries);
+    }
+
+    pub(crate) fn extended(mut self, other: RequestMetadata) -> Self {
+        self.extend(other);
+        self
+    }
+
+    pub(crate) fn apply(&self, metadata: &mut MetadataMap) {
+        for (key, value) in &self.entries {
+            metadata.append(key.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_headers() {
+        let request_metadata = RequestMetadata::new()
+            .header("x-priority", "low")
+            .unwrap()
+            .request_params(&[("project_id", "p"), ("database_id", "(default)")])
+            .unwrap();
+
+        let mut metadata = MetadataMap::new();
+        request_metadata.apply(&mut metadata);
+
+        assert_eq!(metadata.get("x-priority").unwrap(), "low");
+        assert_eq!(
+            metadata.get("x-goog-request-params").unwrap(),
+            "project_id=p&database_id=(default)"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_and_authorization_headers() {
+        assert!(RequestMetadata::new().header("bad key", "value").is_err());
+        assert!(RequestMetadata::new().header("x-ok", "bad\nvalue").is_err());
+        assert!(RequestMetadata::new()
+            .header("Authorization", "Bearer nope")
+            .is_err());
+    }
+}
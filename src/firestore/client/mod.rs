@@ -1,6 +1,9 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
-use std::future;
+use std::future::{self, Future};
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use anyhow::{anyhow, Context};
 use firestore_grpc::tonic;
@@ -8,12 +11,19 @@ use firestore_grpc::v1::firestore_client::FirestoreClient as GrpcFirestoreClient
 use firestore_grpc::v1::precondition::ConditionType;
 use firestore_grpc::v1::run_query_request::QueryType;
 use firestore_grpc::v1::structured_aggregation_query::aggregation;
-use firestore_grpc::v1::structured_query::CollectionSelector;
+use firestore_grpc::v1::structured_query::{
+    CollectionSelector, Direction, FieldReference, Order, Projection,
+};
 use firestore_grpc::v1::value::ValueType;
+use firestore_grpc::v1::write::Operation;
 use firestore_grpc::v1::{
-    run_aggregation_query_request, structured_aggregation_query, CreateDocumentRequest,
-    DeleteDocumentRequest, DocumentMask, Precondition, RunAggregationQueryRequest, RunQueryRequest,
-    StructuredAggregationQuery, StructuredQuery, UpdateDocumentRequest,
+    batch_get_documents_response, listen_response, partition_query_request,
+    run_aggregation_query_request, structured_aggregation_query, transaction_options,
+    BatchGetDocumentsRequest, BatchWriteRequest, BeginTransactionRequest, CommitRequest,
+    CreateDocumentRequest, Cursor as GrpcCursor, Document, DocumentMask, ListCollectionIdsRequest,
+    ListDocumentsRequest, ListenRequest, ListenResponse, PartitionQueryRequest, Precondition,
+    RollbackRequest, RunAggregationQueryRequest, RunQueryRequest, StructuredAggregationQuery,
+    StructuredQuery, TransactionOptions, UpdateDocumentRequest, Value,
 };
 use firestore_grpc::{
     tonic::{
@@ -29,19 +39,61 @@ use crate::error::FirebaseError;
 use crate::firestore::serde::deserialize_firestore_document_fields;
 use crate::ServiceAccount;
 
-use super::query::{try_into_grpc_filter, ApiQueryOptions, Filter, FirestoreQuery};
+use self::options::UserInterceptor;
+use self::rate_limit::{RateLimiter, RpcPermit};
+use self::stats::StatsCounters;
+use super::aggregation::{validate_aggregations, Aggregation, AggregationResult};
+use super::batch::WriteBatch;
+use super::consistency::{system_time, ConsistencyToken, ReadConsistency};
+use super::cursor::Cursor;
+use super::listen::{
+    document_change_kind, is_transient, query_changes, DocumentChange, DocumentSnapshot,
+    ListenCheckpoint, QuerySnapshot, RawSnapshot, Watch, WatchEvent,
+};
+use super::partition::QueryPartition;
+use super::query::{
+    into_grpc_order, try_into_grpc_filter, ApiQueryOptions, CollectionGroupQuery, Filter,
+    FirestoreQuery, OrderBy, QueryBound,
+};
 use super::reference::{CollectionReference, DocumentReference};
 use super::serde::{strip_reference_prefix, DocumentSerializer};
+use super::session::Session;
 use super::token_provider::FirestoreTokenProvider;
+use super::transaction::{
+    is_aborted, retry_delay, ReadOnlyTransaction, Transaction, MAX_TRANSACTION_ATTEMPTS,
+};
+use super::validate::{validate, Validate};
+use super::write::{
+    delete_write, delete_write_by_name, serialize_write, split_into_batches, transform_write,
+    DeleteFailure, DeleteSummary, FieldTransforms, Write, WriteKind, WriteResult,
+    MAX_BATCH_WRITE_SIZE,
+};
 
+mod metadata;
 mod options;
+mod rate_limit;
+mod stats;
+
+pub use metadata::RequestMetadata;
+use options::DEFAULT_DATABASE_ID;
+pub use options::{CopyOptions, FirestoreClientOptions};
+pub use rate_limit::RateLimit;
+pub use stats::ClientStats;
 
-pub use options::FirestoreClientOptions;
+pub(crate) type FirebaseStream<'i, T, E> = Pin<Box<dyn Stream<Item = Result<T, E>> + Send + 'i>>;
 
-type FirebaseStream<'i, T, E> = Pin<Box<dyn Stream<Item = Result<T, E>> + Send + 'i>>;
+const LIST_COLLECTION_IDS_PAGE_SIZE: i32 = 300;
+const LIST_DOCUMENTS_PAGE_SIZE: i32 = 300;
+
+/// The special field path that refers to the name of a document.
+const DOCUMENT_NAME_FIELD: &str = "__name__";
 
 type InterceptorFunction = Box<dyn FnMut(Request<()>) -> Result<Request<()>, Status> + Send>;
 
+/// The generated gRPC client for Firestore, with the same channel and
+/// authentication as a [`FirestoreClient`]. See [`FirestoreClient::raw`].
+pub type RawFirestoreClient = GrpcFirestoreClient<InterceptedService<Channel, InterceptorFunction>>;
+
 pub struct FirestoreClient {
     options: FirestoreClientOptions,
     client: GrpcFirestoreClient<InterceptedService<Channel, InterceptorFunction>>,
@@ -49,9 +101,37 @@ pub struct FirestoreClient {
     project_id: String,
     token_provider: FirestoreTokenProvider,
     root_resource_path: String,
+    stats: Arc<StatsCounters>,
+    rate_limiter: Arc<RateLimiter>,
+    /// Makes all reads of the client consistent with each other, e.g. as part
+    /// of a transaction.
+    read_consistency: Option<ReadConsistency>,
+    /// Extra metadata sent with every request.
+    request_metadata: RequestMetadata,
+    /// The routing header for databases other than the default one, which is
+    /// sent before the extra metadata.
+    routing_metadata: RequestMetadata,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+/// A document along with its metadata.
+///
+/// The document can be (de)serialized with serde, e.g. to JSON, which allows
+/// caching query results and later rehydrating them into the same type. The
+/// JSON shape looks like this:
+///
+/// ```json
+/// {
+///   "id": "projects/{project_id}/databases/{database_id}/documents/{document_path}",
+///   "data": { ... },
+///   "create_time": 1663061252,
+///   "update_time": 1663061252,
+///   "create_timestamp": { "secs_since_epoch": 1663061252, "nanos_since_epoch": 123456000 },
+///   "update_timestamp": { "secs_since_epoch": 1663061252, "nanos_since_epoch": 123456000 }
+/// }
+/// ```
+///
+/// The timestamps may be omitted or `null`.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct FirestoreDocument<T> {
     /// The resource name of the document, for example
     /// `projects/{project_id}/databases/{database_id}/documents/{document_path}`.
@@ -62,19 +142,65 @@ pub struct FirestoreDocument<T> {
     pub create_time: Option<i64>,
     /// The time at which the document was last updated, in seconds of UTC time since Unix epoch.
     pub update_time: Option<i64>,
+    /// The exact time at which the document was created, with the
+    /// nanosecond precision of Firestore.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_timestamp: Option<SystemTime>,
+    /// The exact time at which the document was last updated, with the
+    /// nanosecond precision of Firestore.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_timestamp: Option<SystemTime>,
 }
 
 impl Clone for FirestoreClient {
     fn clone(&self) -> Self {
-        Self::from_channel(
-            self.grpc_channel.clone(),
-            self.token_provider.clone(),
-            &self.project_id,
-            self.options.clone(),
-        )
+        Self {
+            options: self.options.clone(),
+            client: self.raw(),
+            grpc_channel: self.grpc_channel.clone(),
+            project_id: self.project_id.clone(),
+            token_provider: self.token_provider.clone(),
+            root_resource_path: self.root_resource_path.clone(),
+            // Clones count towards the same stats and share the same rate limit
+            stats: self.stats.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            read_consistency: self.read_consistency.clone(),
+            request_metadata: self.request_metadata.clone(),
+            routing_metadata: self.routing_metadata.clone(),
+        }
     }
 }
 
+/// The documents fetched with
+/// [`FirestoreClient::get_documents_by_refs`].
+#[derive(Debug)]
+pub struct DocumentsByRefs<T> {
+    /// The documents that exist, in the order they were requested.
+    pub found: Vec<FirestoreDocument<T>>,
+    /// The references to documents that don't exist, in the order they were
+    /// requested.
+    pub missing: Vec<DocumentReference>,
+}
+
+/// A single page of the documents in a collection, fetched with
+/// [`FirestoreClient::list_documents_paging`].
+#[derive(Debug)]
+pub struct DocumentsPage<T> {
+    pub documents: Vec<FirestoreDocument<T>>,
+    /// The token for the next page, or `None` if this is the last page.
+    pub next_page_token: Option<String>,
+}
+
+/// The outcome of [`FirestoreClient::create_document_if_absent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateOutcome {
+    /// The document was created.
+    Created,
+    /// The document already existed and was left untouched, e.g. because
+    /// this is a retry of a write that already succeeded.
+    AlreadyExists,
+}
+
 impl<T> FirestoreDocument<T> {
     /// Obtain a document reference to this document. May fail if the resource
     /// path is invalid.
@@ -85,7 +211,53 @@ impl<T> FirestoreDocument<T> {
     }
 }
 
-fn create_auth_interceptor(mut token_provider: FirestoreTokenProvider) -> InterceptorFunction {
+async fn connect(options: &FirestoreClientOptions) -> Result<Channel, FirebaseError> {
+    let channel = Channel::from_shared(options.host_url.clone())
+        .context("Failed to create gRPC channel")?
+        .connect()
+        .await
+        .context("Failed to create channel to endpoint")?;
+
+    Ok(channel)
+}
+
+fn create_grpc_client(
+    channel: Channel,
+    interceptor: InterceptorFunction,
+    options: &FirestoreClientOptions,
+) -> GrpcFirestoreClient<InterceptedService<Channel, InterceptorFunction>> {
+    let client = GrpcFirestoreClient::with_interceptor(channel, interceptor);
+
+    // Only settable with the `compression` feature, without which tonic
+    // panics when enabling gzip
+    if options.gzip {
+        client.send_gzip().accept_gzip()
+    } else {
+        client
+    }
+}
+
+/// The routing header that Firestore needs to find a database other than the
+/// default one. Fails if the database ID can't be sent in a header.
+fn routing_metadata(
+    project_id: &str,
+    options: &FirestoreClientOptions,
+) -> Result<RequestMetadata, FirebaseError> {
+    if options.database_id == DEFAULT_DATABASE_ID {
+        return Ok(RequestMetadata::default());
+    }
+
+    RequestMetadata::new().request_params(&[
+        ("project_id", project_id),
+        ("database_id", &options.database_id),
+    ])
+}
+
+fn create_auth_interceptor(
+    mut token_provider: FirestoreTokenProvider,
+    request_metadata: RequestMetadata,
+    interceptors: Vec<UserInterceptor>,
+) -> InterceptorFunction {
     Box::new(move |mut req: Request<()>| {
         let token = token_provider
             .get_token()
@@ -98,8 +270,11 @@ fn create_auth_interceptor(mut token_provider: FirestoreTokenProvider) -> Interc
         header_value.set_sensitive(true);
 
         req.metadata_mut().insert("authorization", header_value);
+        request_metadata.apply(req.metadata_mut());
 
-        Ok(req)
+        interceptors
+            .iter()
+            .try_fold(req, |req, interceptor| interceptor(req))
     })
 }
 
@@ -110,21 +285,65 @@ impl FirestoreClient {
         service_account: ServiceAccount,
         options: FirestoreClientOptions,
     ) -> Result<Self, FirebaseError> {
-        let channel = Channel::from_shared(options.host_url.clone())
-            .context("Failed to create gRPC channel")?
-            .connect()
-            .await
-            .context("Failed to create channel to endpoint")?;
+        let channel = connect(&options).await?;
 
         let project_id = service_account.project_id.clone();
         let token_provider = FirestoreTokenProvider::new(service_account);
 
-        Ok(Self::from_channel(
+        Self::from_channel(
             channel,
             token_provider,
             &project_id,
             options,
-        ))
+            RequestMetadata::default(),
+        )
+    }
+
+    /// Initialise a client that authenticates as an end user with the given
+    /// Firebase ID token, instead of as a service account. Requests made with
+    /// the client are therefore subject to the project's security rules,
+    /// which makes it possible to test the rules through the same API, e.g.
+    /// against the Firestore emulator.
+    ///
+    /// The ID token is used as-is, so requests will fail once it expires.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use fireplace::firestore::{
+    ///     client::{FirestoreClient, FirestoreClientOptions},
+    ///     collection,
+    /// };
+    ///
+    /// let id_token = "an ID token obtained by signing in as the user";
+    /// let options = FirestoreClientOptions::default().host_url("http://localhost:8080");
+    ///
+    /// let mut client = FirestoreClient::initialise_as_user("my-project", id_token, options).await?;
+    ///
+    /// // Fails if the security rules don't allow the user to read the document
+    /// let result = client
+    ///     .get_document::<serde_json::Value>(&collection("private").doc("secret"))
+    ///     .await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn initialise_as_user(
+        project_id: impl AsRef<str>,
+        id_token: impl Into<String>,
+        options: FirestoreClientOptions,
+    ) -> Result<Self, FirebaseError> {
+        let channel = connect(&options).await?;
+        let token_provider = FirestoreTokenProvider::from_id_token(id_token);
+
+        Self::from_channel(
+            channel,
+            token_provider,
+            project_id.as_ref(),
+            options,
+            RequestMetadata::default(),
+        )
     }
 
     fn from_channel(
@@ -132,24 +351,184 @@ impl FirestoreClient {
         token_provider: FirestoreTokenProvider,
         project_id: &str,
         options: FirestoreClientOptions,
-    ) -> Self {
+        request_metadata: RequestMetadata,
+    ) -> Result<Self, FirebaseError> {
+        let routing_metadata = routing_metadata(project_id, &options)?;
+
         // Cloning a channel is supposedly very cheap and encouraged be tonic's
         // documentation.
-        let service = GrpcFirestoreClient::with_interceptor(
+        let service = create_grpc_client(
             channel.clone(),
-            create_auth_interceptor(token_provider.clone()),
+            create_auth_interceptor(
+                token_provider.clone(),
+                routing_metadata.clone().extended(request_metadata.clone()),
+                options.interceptors.clone(),
+            ),
+            &options,
         );
 
-        let resource_path = format!("projects/{}/databases/(default)/documents", project_id);
+        let resource_path = format!(
+            "projects/{}/databases/{}/documents",
+            project_id, options.database_id
+        );
 
-        Self {
+        Ok(Self {
             client: service,
             project_id: project_id.to_string(),
             token_provider,
             grpc_channel: channel,
             root_resource_path: resource_path,
+            stats: Arc::new(StatsCounters::new(options.collect_stats)),
+            rate_limiter: Arc::new(RateLimiter::new(&options.rate_limit)),
+            read_consistency: None,
+            request_metadata,
+            routing_metadata,
             options,
-        }
+        })
+    }
+
+    /// Returns the underlying gRPC client, for calling RPCs that fireplace
+    /// doesn't wrap. It shares the connection of this client and
+    /// authenticates the same way, including any
+    /// [`RequestMetadata`] and interceptors.
+    ///
+    /// Requests sent with the raw client bypass the client's rate limit and
+    /// aren't counted in its [`stats`](Self::stats).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use firestore_grpc::v1::ListCollectionIdsRequest;
+    ///
+    /// let mut raw = client.raw();
+    ///
+    /// let response = raw
+    ///     .list_collection_ids(ListCollectionIdsRequest {
+    ///         parent: format!("{}/documents", client.database_path()),
+    ///         page_size: 10,
+    ///         ..Default::default()
+    ///     })
+    ///     .await?
+    ///     .into_inner();
+    ///
+    /// println!("Collections: {:?}", response.collection_ids);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw(&self) -> RawFirestoreClient {
+        create_grpc_client(
+            self.grpc_channel.clone(),
+            create_auth_interceptor(
+                self.token_provider.clone(),
+                self.routing_metadata
+                    .clone()
+                    .extended(self.request_metadata.clone()),
+                self.options.interceptors.clone(),
+            ),
+            &self.options,
+        )
+    }
+
+    /// Creates a clone of the client that sends the given metadata with every
+    /// request, in addition to the metadata of this client. Useful for
+    /// tagging individual calls, e.g. with routing or priority headers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{client::RequestMetadata, collection};
+    ///
+    /// let metadata = RequestMetadata::new().header("x-request-priority", "low")?;
+    ///
+    /// let doc = client
+    ///     .with_request_metadata(metadata)
+    ///     .get_document::<serde_json::Value>(&collection("reports").doc("nightly"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_request_metadata(&self, metadata: RequestMetadata) -> Self {
+        let mut client = self.clone();
+        client.request_metadata.extend(metadata);
+        client.client = client.raw();
+        client
+    }
+
+    /// Limits how fast this client and its future clones may send requests
+    /// to Firestore, replacing any previous limit. See [`RateLimit`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{client::RateLimit, collection};
+    ///
+    /// let mut client = client.with_rate_limit(RateLimit::new().max_writes_per_second(10));
+    ///
+    /// // The first second's worth of writes go through right away, and the
+    /// // rest are spread out to stay within the limit
+    /// for i in 0..20 {
+    ///     client
+    ///         .set_document(
+    ///             &collection("rate-limited").doc(i.to_string()),
+    ///             &serde_json::json!({ "i": i }),
+    ///         )
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(&rate_limit));
+        self.options.rate_limit = rate_limit;
+        self
+    }
+
+    /// Returns the number of documents read, written and deleted by this
+    /// client and its clones, along with the number of RPCs issued.
+    ///
+    /// Stats are only collected when enabled with
+    /// [`FirestoreClientOptions::collect_stats`], otherwise all counts are
+    /// zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use fireplace::{
+    ///     firestore::{
+    ///         client::{FirestoreClient, FirestoreClientOptions},
+    ///         collection,
+    ///     },
+    ///     ServiceAccount,
+    /// };
+    ///
+    /// let service_account = ServiceAccount::from_file("./test-service-account.json")?;
+    /// let options = FirestoreClientOptions::default().collect_stats(true);
+    /// let mut client = FirestoreClient::initialise(service_account, options).await?;
+    ///
+    /// let doc_ref = collection("greetings").doc("stats");
+    /// client.set_document(&doc_ref, &serde_json::json!({ "message": "Hi" })).await?;
+    /// client.get_document::<serde_json::Value>(&doc_ref).await?;
+    ///
+    /// let stats = client.stats();
+    /// assert_eq!(stats.documents_written, 1);
+    /// assert_eq!(stats.documents_read, 1);
+    /// assert_eq!(stats.rpcs, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
     }
 
     /// Retrieve a document from Firestore at the given document reference.
@@ -202,20 +581,228 @@ impl FirestoreClient {
         &mut self,
         doc_ref: &DocumentReference,
     ) -> Result<Option<T>, FirebaseError> {
+        match self.get_raw_document(doc_ref).await? {
+            Some(doc) => {
+                let deserialized = deserialize_firestore_document_fields::<T>(doc.fields)
+                    .map_err(|e| serde_err_with_doc(e, &doc.name))?;
+                Ok(Some(deserialized))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Like [`get_document`](Self::get_document), but also returns the
+    /// document's resource name and timestamps as a [`FirestoreDocument`],
+    /// e.g. for building document references or preconditions from a read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{client::FirestoreDocument, collection};
+    ///
+    /// let doc_ref = collection("greetings").doc("with-metadata");
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "message": "Hi Mom!" }))
+    ///     .await?;
+    ///
+    /// let doc: FirestoreDocument<serde_json::Value> = client
+    ///     .get_document_with_metadata(&doc_ref)
+    ///     .await?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(doc.data, serde_json::json!({ "message": "Hi Mom!" }));
+    /// assert_eq!(doc.document_reference()?, doc_ref);
+    /// assert!(doc.update_time.is_some());
+    /// assert!(doc.update_timestamp.is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_document_with_metadata<'de, T: Deserialize<'de>>(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<Option<FirestoreDocument<T>>, FirebaseError> {
+        self.get_raw_document(doc_ref)
+            .await?
+            .map(into_firestore_document)
+            .transpose()
+    }
+
+    /// Checks whether a document exists, without downloading or deserializing
+    /// its fields. Only the document's name is requested, so this is cheaper
+    /// than [`get_document`](Self::get_document) for large documents, though
+    /// it is still billed as a read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use ulid::Ulid;
+    ///
+    /// let doc_ref = collection("greetings").doc(Ulid::new());
+    /// assert!(!client.document_exists(&doc_ref).await?);
+    ///
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "message": "Hi Mom!" }))
+    ///     .await?;
+    /// assert!(client.document_exists(&doc_ref).await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn document_exists(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<bool, FirebaseError> {
+        let mask = DocumentMask {
+            field_paths: vec!["__name__".to_string()],
+        };
+        let doc = self.get_raw_document_with_mask(doc_ref, Some(mask)).await?;
+
+        Ok(doc.is_some())
+    }
+
+    /// Fetches many documents in a single round trip, which is a lot faster
+    /// than calling [`get_document`](Self::get_document) for each of them.
+    /// References that appear more than once are only fetched once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use ulid::Ulid;
+    ///
+    /// let luke_ref = collection("people").doc(Ulid::new());
+    /// let leia_ref = collection("people").doc(Ulid::new());
+    /// let han_ref = collection("people").doc(Ulid::new());
+    ///
+    /// client
+    ///     .set_document(&luke_ref, &serde_json::json!({ "name": "Luke" }))
+    ///     .await?;
+    /// client
+    ///     .set_document(&leia_ref, &serde_json::json!({ "name": "Leia" }))
+    ///     .await?;
+    ///
+    /// let docs = client
+    ///     .get_documents_by_refs::<serde_json::Value>(&[
+    ///         luke_ref.clone(),
+    ///         han_ref.clone(),
+    ///         leia_ref.clone(),
+    ///     ])
+    ///     .await?;
+    ///
+    /// let names: Vec<_> = docs.found.iter().map(|doc| &doc.data["name"]).collect();
+    /// assert_eq!(names, vec!["Luke", "Leia"]);
+    /// assert_eq!(docs.missing.len(), 1);
+    /// assert_eq!(docs.missing[0].id(), han_ref.id());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_documents_by_refs<T: DeserializeOwned>(
+        &mut self,
+        doc_refs: &[DocumentReference],
+    ) -> Result<DocumentsByRefs<T>, FirebaseError> {
+        // No request is sent when there is nothing to fetch
+        if doc_refs.is_empty() {
+            return Ok(DocumentsByRefs {
+                found: vec![],
+                missing: vec![],
+            });
+        }
+
+        let mut names = doc_refs
+            .iter()
+            .map(|doc_ref| self.get_name_with(doc_ref))
+            .collect::<Vec<_>>();
+        let requested_names = names.clone();
+        names.sort();
+        names.dedup();
+
+        let request = BatchGetDocumentsRequest {
+            database: self.database_path(),
+            documents: names,
+            mask: None,
+            consistency_selector: self
+                .read_consistency
+                .as_ref()
+                .map(ReadConsistency::batch_get_documents_selector),
+        };
+
+        let _permit = self.begin_rpc(0).await;
+        let mut res = self
+            .client
+            .batch_get_documents(request)
+            .await
+            .context("Failed to get documents")?
+            .into_inner();
+
+        let mut found_docs = HashMap::new();
+        while let Some(item) = res.message().await.context("Failed to get documents")? {
+            if let Some(batch_get_documents_response::Result::Found(doc)) = item.result {
+                found_docs.insert(doc.name.clone(), doc);
+            }
+        }
+        self.stats.record_reads(found_docs.len() as u64);
+
+        let mut documents = DocumentsByRefs {
+            found: vec![],
+            missing: vec![],
+        };
+
+        let mut seen_names = HashSet::new();
+        for (doc_ref, name) in doc_refs.iter().zip(requested_names) {
+            if !seen_names.insert(name.clone()) {
+                continue;
+            }
+
+            match found_docs.remove(&name) {
+                Some(doc) => documents.found.push(into_firestore_document(doc)?),
+                None => documents.missing.push(doc_ref.clone()),
+            }
+        }
+
+        Ok(documents)
+    }
+
+    /// Gets a document as it comes from the gRPC API, without deserializing
+    /// it.
+    async fn get_raw_document(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<Option<Document>, FirebaseError> {
+        self.get_raw_document_with_mask(doc_ref, None).await
+    }
+
+    /// Like [`get_raw_document`](Self::get_raw_document), but only returns
+    /// the fields in the given mask.
+    async fn get_raw_document_with_mask(
+        &mut self,
+        doc_ref: &DocumentReference,
+        mask: Option<DocumentMask>,
+    ) -> Result<Option<Document>, FirebaseError> {
         let request = GetDocumentRequest {
             name: self.get_name_with(doc_ref),
-            mask: None,
-            consistency_selector: None,
+            mask,
+            consistency_selector: self
+                .read_consistency
+                .as_ref()
+                .map(ReadConsistency::get_document_selector),
         };
 
+        let _permit = self.begin_rpc(0).await;
         let res = self.client.get_document(request).await;
 
         match res {
             Ok(res) => {
-                let doc = res.into_inner();
-                let deserialized = deserialize_firestore_document_fields::<T>(doc.fields)
-                    .map_err(|e| serde_err_with_doc(e, &doc.name))?;
-                Ok(Some(deserialized))
+                self.stats.record_reads(1);
+                Ok(Some(res.into_inner()))
             }
             Err(err) if err.code() == tonic::Code::NotFound => Ok(None),
             Err(err) => Err(anyhow!(err).into()),
@@ -256,6 +843,19 @@ impl FirestoreClient {
             .await
     }
 
+    /// Like [`create_document`](Self::create_document), but runs the
+    /// document's [`Validate`] implementation first. Invalid documents are not
+    /// created, and an [`InvalidDocument`](FirebaseError::InvalidDocument)
+    /// error lists everything wrong with them.
+    pub async fn create_document_validated<T: Serialize + Validate>(
+        &mut self,
+        collection_ref: &CollectionReference,
+        document: &T,
+    ) -> Result<String, FirebaseError> {
+        validate(document)?;
+        self.create_document(collection_ref, document).await
+    }
+
     /// Creates a document in Firestore at the given document reference.
     /// Returns the ID of the created document.
     ///
@@ -297,16 +897,122 @@ impl FirestoreClient {
             .await
     }
 
-    async fn create_document_internal<T: Serialize>(
-        &mut self,
-        collection_ref: &CollectionReference,
-        document_id: Option<String>,
-        document: &T,
-    ) -> Result<String, FirebaseError> {
-        // We should provide no name or timestamps when creating a document
-        // according to Google's Firestore API reference.
-        let doc = self.serializer().serialize(document)?;
-
+    /// Creates a document at the given document reference unless it already
+    /// exists, in which case the existing document is left untouched. Unlike
+    /// [`create_document_at_ref`](Self::create_document_at_ref), an existing
+    /// document is not an error but a
+    /// [`CreateOutcome::AlreadyExists`] outcome.
+    ///
+    /// Together with IDs derived from an idempotency key, this lets consumers
+    /// of at-least-once pipelines retry safely without duplicating documents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{client::CreateOutcome, collection};
+    ///
+    /// let event_id = ulid::Ulid::new().to_string();
+    /// let doc_ref = collection("processed-events").doc_from_key(&event_id);
+    /// let doc = serde_json::json!({ "event_id": event_id });
+    ///
+    /// let first = client.create_document_if_absent(&doc_ref, &doc).await?;
+    /// assert_eq!(first, CreateOutcome::Created);
+    ///
+    /// // Processing the same event again doesn't create a duplicate
+    /// let retry = client.create_document_if_absent(&doc_ref, &doc).await?;
+    /// assert_eq!(retry, CreateOutcome::AlreadyExists);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_document_if_absent<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<CreateOutcome, FirebaseError> {
+        match self.create_document_at_ref(doc_ref, document).await {
+            Ok(_) => Ok(CreateOutcome::Created),
+            Err(FirebaseError::DocumentAlreadyExists(_)) => Ok(CreateOutcome::AlreadyExists),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Creates a document in Firestore in the given collection, letting
+    /// Firestore generate the ID for you. Unlike
+    /// [`create_document`](Self::create_document), the whole created document
+    /// is returned, including the timestamps set by Firestore.
+    ///
+    /// Returns an error if the document already exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    ///
+    /// let doc_to_create = serde_json::json!({ "message": "Hi Mom!" });
+    ///
+    /// let created = client
+    ///     .create_document_full(&collection("greetings"), &doc_to_create)
+    ///     .await?;
+    ///
+    /// assert_eq!(created.data, doc_to_create);
+    /// assert!(created.create_time.is_some());
+    /// assert_eq!(created.create_time, created.update_time);
+    /// println!("Created document {}", created.document_reference()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_document_full<T: Serialize + DeserializeOwned>(
+        &mut self,
+        collection_ref: &CollectionReference,
+        document: &T,
+    ) -> Result<FirestoreDocument<T>, FirebaseError> {
+        // Without a mask, Firestore responds with all fields of the document
+        let doc = self
+            .create_raw_document(collection_ref, None, document, None)
+            .await?;
+
+        into_firestore_document(doc)
+    }
+
+    async fn create_document_internal<T: Serialize>(
+        &mut self,
+        collection_ref: &CollectionReference,
+        document_id: Option<String>,
+        document: &T,
+    ) -> Result<String, FirebaseError> {
+        let mask = DocumentMask {
+            field_paths: vec![],
+        };
+        let created_doc = self
+            .create_raw_document(collection_ref, document_id, document, Some(mask))
+            .await?;
+
+        let created_doc_id = created_doc
+            .name
+            .rsplit_once('/')
+            .map(|(_, id)| id.to_string())
+            .context("Could not get document ID from resource path")?;
+
+        Ok(created_doc_id)
+    }
+
+    async fn create_raw_document<T: Serialize>(
+        &mut self,
+        collection_ref: &CollectionReference,
+        document_id: Option<String>,
+        document: &T,
+        mask: Option<DocumentMask>,
+    ) -> Result<Document, FirebaseError> {
+        // We should provide no name or timestamps when creating a document
+        // according to Google's Firestore API reference.
+        let doc = self.serializer().serialize(document)?;
+
         let (parent, collection_name) = self.split_collection_parent_and_name(collection_ref);
         let request = CreateDocumentRequest {
             parent,
@@ -315,22 +1021,16 @@ impl FirestoreClient {
             // document ID for us.
             document_id: document_id.unwrap_or_default(),
             document: Some(doc),
-            mask: Some(DocumentMask {
-                field_paths: vec![],
-            }),
+            mask,
         };
 
+        let _permit = self.begin_rpc(1).await;
         let res = self.client.create_document(request).await;
 
         match res {
             Ok(r) => {
-                let created_doc = r.into_inner();
-                let created_doc_id = created_doc
-                    .name
-                    .rsplit_once('/')
-                    .map(|(_, id)| id.to_string())
-                    .context("Could not get document ID from resource path")?;
-                Ok(created_doc_id)
+                self.stats.record_writes(1);
+                Ok(r.into_inner())
             }
             Err(err) if err.code() == tonic::Code::AlreadyExists => Err(
                 FirebaseError::DocumentAlreadyExists(err.message().to_string()),
@@ -341,6 +1041,7 @@ impl FirestoreClient {
 
     /// Sets a document at the given document reference. If it doesn't already,
     /// exist, it is created - and if it does exist already, it is overwritten.
+    /// Returns the [`WriteResult`] with the time the document was written.
     ///
     /// # Examples
     ///
@@ -353,8 +1054,10 @@ impl FirestoreClient {
     /// let doc_ref = collection("greetings").doc("some-doc-id-to-set");
     /// let doc = serde_json::json!({ "message": "Hello, world!".to_string() });
     ///
-    /// // We can upsert the document in the database
-    /// client.set_document(&doc_ref, &doc).await.unwrap();
+    /// // We can upsert the document in the database, and get the time it was
+    /// // written
+    /// let result = client.set_document(&doc_ref, &doc).await.unwrap();
+    /// assert!(result.update_time.is_some());
     ///
     /// // We can write to the same document reference again, and it will overwrite
     /// // the existing value document
@@ -365,7 +1068,7 @@ impl FirestoreClient {
         &mut self,
         doc_ref: &DocumentReference,
         document: &T,
-    ) -> Result<(), FirebaseError> {
+    ) -> Result<WriteResult, FirebaseError> {
         let name = self.get_name_with(doc_ref);
         let doc = self.serializer().name(name).serialize(document)?;
 
@@ -378,12 +1081,64 @@ impl FirestoreClient {
             current_document: None,
         };
 
-        self.client
+        let _permit = self.begin_rpc(1).await;
+        let res = self
+            .client
             .update_document(request)
             .await
             .map_err(|err| anyhow!(err))?;
+        self.stats.record_writes(1);
 
-        Ok(())
+        Ok(updated_document_result(res.into_inner()))
+    }
+
+    /// Like [`set_document`](Self::set_document), but runs the document's
+    /// [`Validate`] implementation first. Invalid documents are not written,
+    /// and an [`InvalidDocument`](FirebaseError::InvalidDocument) error lists
+    /// everything wrong with them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use fireplace::{
+    /// #     error::FirebaseError,
+    /// #     firestore::{collection, validate::{Validate, Violations}},
+    /// # };
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await.unwrap();
+    /// #
+    /// #[derive(serde::Serialize)]
+    /// struct Greeting {
+    ///     message: String,
+    /// }
+    ///
+    /// impl Validate for Greeting {
+    ///     fn validate(&self, violations: &mut Violations) {
+    ///         violations.check(!self.message.is_empty(), "message", "must not be empty");
+    ///     }
+    /// }
+    ///
+    /// let doc_ref = collection("greetings").doc("validated-greeting");
+    ///
+    /// let result = client
+    ///     .set_document_validated(&doc_ref, &Greeting { message: String::new() })
+    ///     .await;
+    /// assert!(matches!(result, Err(FirebaseError::InvalidDocument(_))));
+    ///
+    /// client
+    ///     .set_document_validated(&doc_ref, &Greeting { message: "Hi!".to_string() })
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn set_document_validated<T: Serialize + Validate>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<WriteResult, FirebaseError> {
+        validate(document)?;
+        self.set_document(doc_ref, document).await
     }
 
     /// Similar to [`set_document`](Self::set_document) but only upserts the
@@ -513,11 +1268,13 @@ impl FirestoreClient {
             current_document: current_document_precondition,
         };
 
+        let _permit = self.begin_rpc(1).await;
         let res = self
             .client
             .update_document(request)
             .await
             .map_err(not_found_err())?;
+        self.stats.record_writes(1);
 
         let doc = res.into_inner();
         let deserialized = deserialize_firestore_document_fields::<O>(doc.fields)
@@ -530,7 +1287,8 @@ impl FirestoreClient {
     /// [`set_document`](Self::set_document), in that this function assumes
     /// that the document already exists, and will return a
     /// [`DocumentNotfound`](FirebaseError::DocumentNotfound) error
-    /// if it cannot be found.
+    /// if it cannot be found. Returns the [`WriteResult`] with the time the
+    /// document was written.
     ///
     /// # Examples
     /// ```
@@ -582,7 +1340,7 @@ impl FirestoreClient {
         &mut self,
         doc_ref: &DocumentReference,
         document: &T,
-    ) -> Result<(), FirebaseError> {
+    ) -> Result<WriteResult, FirebaseError> {
         let name = self.get_name_with(doc_ref);
         let doc = self.serializer().name(name).serialize(document)?;
 
@@ -595,12 +1353,130 @@ impl FirestoreClient {
             current_document: document_exists_precondition(),
         };
 
-        self.client
+        let _permit = self.begin_rpc(1).await;
+        let res = self
+            .client
             .update_document(request)
             .await
             .map_err(not_found_err())?;
+        self.stats.record_writes(1);
 
-        Ok(())
+        Ok(updated_document_result(res.into_inner()))
+    }
+
+    /// Replaces the document at the given reference with `new`, but only if
+    /// it currently equals `expected`. This makes it possible to safely
+    /// update e.g. counters or state machines without a transaction.
+    ///
+    /// The document is read and compared client-side, and then written with
+    /// a precondition on the update time that was read. If the document
+    /// doesn't equal `expected`, or it was changed by someone else in the
+    /// meantime, a [`Conflict`](FirebaseError::Conflict) error is returned.
+    /// If the document doesn't exist, a
+    /// [`DocumentNotfound`](FirebaseError::DocumentNotfound) error is
+    /// returned. Otherwise, the result of the write is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::{error::FirebaseError, firestore::collection};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    /// enum OrderState {
+    ///     Placed,
+    ///     Shipped,
+    ///     Cancelled,
+    /// }
+    ///
+    /// #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    /// struct Order {
+    ///     state: OrderState,
+    /// }
+    ///
+    /// let doc_ref = collection("orders").doc("compare-and-set");
+    /// let placed = Order { state: OrderState::Placed };
+    /// client.set_document(&doc_ref, &placed).await?;
+    ///
+    /// let shipped = Order { state: OrderState::Shipped };
+    /// let result = client.compare_and_set(&doc_ref, &placed, &shipped).await?;
+    /// assert!(result.consistency_token().is_some());
+    ///
+    /// // The order can no longer be cancelled, as it isn't placed anymore
+    /// let cancelled = Order { state: OrderState::Cancelled };
+    /// let result = client.compare_and_set(&doc_ref, &placed, &cancelled).await;
+    /// assert!(matches!(result, Err(FirebaseError::Conflict(_))));
+    ///
+    /// assert_eq!(client.get_document(&doc_ref).await?, Some(shipped));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn compare_and_set<T: Serialize + DeserializeOwned + PartialEq>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        expected: &T,
+        new: &T,
+    ) -> Result<WriteResult, FirebaseError> {
+        let name = self.get_name_with(doc_ref);
+
+        let request = GetDocumentRequest {
+            name: name.clone(),
+            mask: None,
+            consistency_selector: None,
+        };
+
+        let permit = self.begin_rpc(0).await;
+        let current_doc = self
+            .client
+            .get_document(request)
+            .await
+            .map_err(not_found_err())?
+            .into_inner();
+        self.stats.record_reads(1);
+        // Release the permit before the write, which needs one of its own
+        drop(permit);
+
+        let current = deserialize_firestore_document_fields::<T>(current_doc.fields)
+            .map_err(|e| serde_err_with_doc(e, &current_doc.name))?;
+
+        if &current != expected {
+            return Err(FirebaseError::Conflict(format!(
+                "Document '{}' does not have the expected value",
+                doc_ref
+            )));
+        }
+
+        let doc = self.serializer().name(name).serialize(new)?;
+
+        let request = UpdateDocumentRequest {
+            document: Some(doc),
+            update_mask: None,
+            mask: Some(DocumentMask {
+                field_paths: vec![],
+            }),
+            current_document: Some(Precondition {
+                condition_type: current_doc.update_time.map(ConditionType::UpdateTime),
+            }),
+        };
+
+        let _permit = self.begin_rpc(1).await;
+        let res = self
+            .client
+            .update_document(request)
+            .await
+            .map_err(|err| match err.code() {
+                tonic::Code::FailedPrecondition => FirebaseError::Conflict(format!(
+                    "Document '{}' was changed concurrently",
+                    doc_ref
+                )),
+                _ => not_found_err()(err),
+            })?;
+        self.stats.record_writes(1);
+
+        Ok(updated_document_result(res.into_inner()))
     }
 
     /// Similar to [`update_document`](Self::update_document) but only updates
@@ -710,8 +1586,46 @@ impl FirestoreClient {
             .await
     }
 
+    /// Applies server-side transforms, such as increments, to the fields of a
+    /// document. The transforms are applied atomically by Firestore, so
+    /// concurrent updates don't overwrite each other the way a
+    /// read-modify-write would. Creates the document if it doesn't exist, and
+    /// leaves other fields untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{collection, transforms};
+    /// use ulid::Ulid;
+    ///
+    /// let doc_ref = collection("pages").doc(Ulid::new());
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "title": "Home", "views": 41 }))
+    ///     .await?;
+    ///
+    /// client
+    ///     .update_transform(&doc_ref, transforms().increment("views", 1))
+    ///     .await?;
+    ///
+    /// let page: serde_json::Value = client.get_document(&doc_ref).await?.unwrap();
+    /// assert_eq!(page, serde_json::json!({ "title": "Home", "views": 42 }));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn update_transform(
+        &mut self,
+        doc_ref: &DocumentReference,
+        transforms: FieldTransforms,
+    ) -> Result<WriteResult, FirebaseError> {
+        let write = transform_write(&self.root_resource_path, doc_ref, transforms);
+        self.commit_single(write).await
+    }
+
     /// Deletes a document from the database. Whether the document exists or not
-    /// makes no difference.
+    /// makes no difference. Returns the [`WriteResult`] of the delete.
     ///
     /// # Examples
     ///
@@ -745,27 +1659,18 @@ impl FirestoreClient {
     pub async fn delete_document(
         &mut self,
         doc_ref: &DocumentReference,
-    ) -> Result<(), FirebaseError> {
-        let name = self.get_name_with(doc_ref);
-
-        let request = DeleteDocumentRequest {
-            name,
-            current_document: None,
-        };
-
-        self.client
-            .delete_document(request)
-            .await
-            .context("Failed to delete document")?;
-
-        Ok(())
+    ) -> Result<WriteResult, FirebaseError> {
+        // Deleting through a commit, unlike `DeleteDocument`, returns the
+        // time of the write
+        let write = delete_write(&self.root_resource_path, doc_ref);
+        self.commit_single(write).await
     }
 
     /// Deletes a document at the given document reference. Differs from
     /// [delete_document](Self::delete_document), in that this function assumes
     /// that the document already exists, and will return a
     /// [`DocumentNotfound`](FirebaseError::DocumentNotfound) error
-    /// if it cannot be found.
+    /// if it cannot be found. Returns the [`WriteResult`] of the delete.
     ///
     /// # Examples
     /// ```
@@ -806,165 +1711,1133 @@ impl FirestoreClient {
     pub async fn delete_existing_document(
         &mut self,
         doc_ref: &DocumentReference,
-    ) -> Result<(), FirebaseError> {
-        let name = self.get_name_with(doc_ref);
+    ) -> Result<WriteResult, FirebaseError> {
+        let mut write = delete_write(&self.root_resource_path, doc_ref);
+        write.current_document = document_exists_precondition();
+        self.commit_single(write).await
+    }
 
-        let request = DeleteDocumentRequest {
-            name,
-            current_document: document_exists_precondition(),
-        };
+    /// Deletes a document along with all documents in its subcollections, and
+    /// in their subcollections, and so on. Unlike
+    /// [`delete_document`](Self::delete_document), this doesn't leave orphaned
+    /// subcollections behind. Returns a [`DeleteSummary`] of how many
+    /// documents were deleted, not counting documents that didn't exist, and
+    /// which failed to be deleted. Only the names of the documents are read.
+    ///
+    /// The deletes are sent in batches and are not atomic. Descendants are
+    /// deleted before the document itself, so if the delete fails midway,
+    /// calling this again picks up the remaining documents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use ulid::Ulid;
+    ///
+    /// let city_ref = collection("cities").doc(Ulid::new());
+    /// let landmark_ref = city_ref.collection("landmarks").doc("city-hall");
+    /// let review_ref = landmark_ref.collection("reviews").doc(Ulid::new());
+    ///
+    /// client
+    ///     .set_document(&city_ref, &serde_json::json!({ "name": "Oslo" }))
+    ///     .await?;
+    /// client
+    ///     .set_document(&review_ref, &serde_json::json!({ "stars": 5 }))
+    ///     .await?;
+    ///
+    /// // The landmark document doesn't exist, but its reviews are still found
+    /// let summary = client.delete_document_recursive(&city_ref).await?;
+    ///
+    /// assert!(summary.is_complete());
+    /// assert_eq!(summary.documents_deleted, 2);
+    /// assert!(client.list_subcollection_ids(&city_ref).await?.is_empty());
+    ///
+    /// // Nothing is left to delete the second time
+    /// let summary = client.delete_document_recursive(&city_ref).await?;
+    /// assert_eq!(summary.documents_deleted, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn delete_document_recursive(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<DeleteSummary, FirebaseError> {
+        let name = self.get_name_with(doc_ref);
+        let mut summary = DeleteSummary::default();
+        self.delete_descendants(&name, &mut summary).await?;
 
-        self.client
-            .delete_document(request)
-            .await
-            .map_err(not_found_err())?;
+        // Deleting a document that doesn't exist succeeds, so the delete
+        // requires the document to exist to tell whether it counts
+        summary.batches_committed += 1;
+        match self.delete_existing_document(doc_ref).await {
+            Ok(_) => summary.documents_deleted += 1,
+            Err(FirebaseError::DocumentNotfound(_)) => {}
+            Err(error) => summary.failures.push(DeleteFailure {
+                path: doc_ref.to_string(),
+                error,
+            }),
+        }
 
-        Ok(())
+        Ok(summary)
     }
 
-    /// Query a collection for documents that fulfill the given criteria.
+    /// Deletes all documents in a collection along with all documents in
+    /// their subcollections, recursively. This includes documents in
+    /// subcollections of documents that don't exist themselves. Returns a
+    /// [`DeleteSummary`] like
+    /// [`delete_document_recursive`](Self::delete_document_recursive).
     ///
-    /// Returns a [`Stream`](futures::stream::Stream) of query results,
-    /// allowing you to process results as they are coming in.
+    /// The deletes are not atomic, and a failed delete can be resumed by
+    /// calling this again.
     ///
     /// # Examples
     ///
     /// ```
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// # use fireplace::firestore::collection;
-    /// # use serde::{Deserialize, Serialize};
     /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
-    /// #
-    /// use fireplace::firestore::query::{filter, ArrayContains, EqualTo};
+    /// use fireplace::firestore::collection;
     /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
     ///
-    /// #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-    /// struct Pizza {
-    ///     name: String,
-    ///     toppings: Vec<String>,
-    /// }
-    ///
-    /// // Instantiate our example pizzas
-    /// let pepperoni = Pizza {
-    ///     name: "Pepperoni".into(),
-    ///     toppings: vec!["pepperoni".into(), "cheese".into()],
-    /// };
-    /// let hawaii = Pizza {
-    ///     name: "Hawaii".into(),
-    ///     toppings: vec!["pineapple".into(), "ham".into(), "cheese".into()],
-    /// };
+    /// let sessions_ref = collection("users").doc(Ulid::new()).collection("sessions");
+    /// let session_ref = sessions_ref.doc(Ulid::new());
     ///
-    /// // Create the pizzas in the database
     /// client
-    ///     .set_document(&collection("pizzas").doc("pepperoni"), &pepperoni)
+    ///     .set_document(&session_ref, &serde_json::json!({ "device": "phone" }))
     ///     .await?;
     /// client
-    ///     .set_document(&collection("pizzas").doc("hawaii"), &hawaii)
+    ///     .set_document(
+    ///         &session_ref.collection("events").doc(Ulid::new()),
+    ///         &serde_json::json!({ "type": "login" }),
+    ///     )
     ///     .await?;
     ///
-    /// // Query for pizzas whose name field is "Hawaii"
-    /// let hawaii_results: Vec<Pizza> = client
-    ///     .query(&collection("pizzas"), filter("name", EqualTo("Hawaii")))
+    /// let summary = client.delete_collection_recursive(&sessions_ref).await?;
+    ///
+    /// assert_eq!(summary.documents_deleted, 2);
+    /// let remaining: Vec<serde_json::Value> = client
+    ///     .get_documents(&sessions_ref)
     ///     .await?
     ///     .try_collect()
     ///     .await?;
-    ///
-    /// // We expect a single search hit - the hawaii pizza.
-    /// assert_eq!(hawaii_results, vec![hawaii.clone()]);
-    ///
-    /// // Query for pizzas that have a "cheese" entry in the toppings list.
-    /// let mut cheese_results: Vec<Pizza> = client
-    ///     .query(
-    ///         &collection("pizzas"),
-    ///         filter("toppings", ArrayContains("cheese")),
-    ///     )
+    /// assert!(remaining.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn delete_collection_recursive(
+        &mut self,
+        collection_ref: &CollectionReference,
+    ) -> Result<DeleteSummary, FirebaseError> {
+        let mut summary = DeleteSummary::default();
+        let mut page_token = String::new();
+
+        // The collection is deleted a page of documents at a time, so only a
+        // page of names is held in memory
+        loop {
+            let (page, next_page_token) = self
+                .list_document_names_page(
+                    collection_ref,
+                    LIST_DOCUMENTS_PAGE_SIZE,
+                    page_token,
+                    true,
+                )
+                .await?;
+
+            let mut existing = Vec::new();
+            for doc in page {
+                self.delete_descendants(&doc.name, &mut summary).await?;
+
+                // Documents that are only listed because they have
+                // subcollections don't have a create time
+                if doc.create_time.is_some() {
+                    existing.push(doc.name);
+                }
+            }
+            self.apply_deletes(existing, &mut summary).await;
+
+            if next_page_token.is_empty() {
+                break;
+            }
+            page_token = next_page_token;
+        }
+
+        Ok(summary)
+    }
+
+    /// Deletes the documents in the subcollections of the document with the
+    /// given resource name, at any depth, one batch at a time. Only the names
+    /// of the documents are read. Fails only if the documents can't be
+    /// listed; failed deletes are added to the summary.
+    async fn delete_descendants(
+        &mut self,
+        name: &str,
+        summary: &mut DeleteSummary,
+    ) -> Result<(), FirebaseError> {
+        let mut start_after = None;
+
+        loop {
+            let names: Vec<String> = self
+                .query_internal_raw(ApiQueryOptions {
+                    parent: name.to_string(),
+                    // Without a collection ID, the query matches the
+                    // documents of all collections under the parent
+                    collection_name: String::new(),
+                    should_search_descendants: true,
+                    names_only: true,
+                    limit: Some(MAX_BATCH_WRITE_SIZE as i32),
+                    start_after: start_after.take(),
+                    ..Default::default()
+                })
+                .await?
+                .map_ok(|doc| doc.name)
+                .try_collect()
+                .await?;
+
+            let last = match names.last() {
+                Some(last) => last.clone(),
+                None => break,
+            };
+            let is_last_page = names.len() < MAX_BATCH_WRITE_SIZE;
+
+            // Failed deletes are skipped by paging past them rather than
+            // querying from the start again
+            self.apply_deletes(names, summary).await;
+
+            if is_last_page {
+                break;
+            }
+            start_after = Some(last);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the documents with the given resource names in batch writes,
+    /// adding the results to the summary.
+    async fn apply_deletes(&mut self, names: Vec<String>, summary: &mut DeleteSummary) {
+        for names in names.chunks(MAX_BATCH_WRITE_SIZE) {
+            let writes = names.iter().cloned().map(delete_write_by_name).collect();
+            summary.batches_committed += 1;
+
+            for (name, result) in names.iter().zip(self.batch_write(writes).await) {
+                match result {
+                    Ok(_) => summary.documents_deleted += 1,
+                    Err(error) => summary.failures.push(DeleteFailure {
+                        path: strip_reference_prefix(name),
+                        error,
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Gets a page of the documents in a collection, along with the token for
+    /// the next page, which is empty on the last page. Only the names and
+    /// metadata of the documents are read, not their fields.
+    async fn list_document_names_page(
+        &mut self,
+        collection_ref: &CollectionReference,
+        page_size: i32,
+        page_token: String,
+        show_missing: bool,
+    ) -> Result<(Vec<Document>, String), FirebaseError> {
+        let (parent, collection_id) = self.split_collection_parent_and_name(collection_ref);
+        let request = ListDocumentsRequest {
+            parent,
+            collection_id,
+            page_size,
+            page_token,
+            mask: Some(DocumentMask {
+                field_paths: vec![DOCUMENT_NAME_FIELD.to_string()],
+            }),
+            show_missing,
+            ..Default::default()
+        };
+
+        let _permit = self.begin_rpc(0).await;
+        let res = self
+            .client
+            .list_documents(request)
+            .await
+            .context("Failed to list documents")?
+            .into_inner();
+
+        self.stats.record_reads(res.documents.len() as u64);
+
+        Ok((res.documents, res.next_page_token))
+    }
+
+    /// Deletes all documents in a collection, `batch_size` documents at a
+    /// time. Only the document IDs are read, so it is cheap even for large
+    /// documents. Subcollections are not deleted; use
+    /// [`delete_collection_recursive`](Self::delete_collection_recursive)
+    /// for that.
+    ///
+    /// The returned stream yields the total number of documents deleted so
+    /// far after each batch, and ends when the collection is empty. The
+    /// documents are only deleted while the stream is polled, and it stops at
+    /// the first error. Batches are capped at 500 documents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
+    ///
+    /// let events = collection(format!("events-{}", Ulid::new()));
+    /// for i in 0..5 {
+    ///     client
+    ///         .set_document(&events.doc(Ulid::new()), &serde_json::json!({ "i": i }))
+    ///         .await?;
+    /// }
+    ///
+    /// let progress: Vec<u64> = client.delete_collection(&events, 2).try_collect().await?;
+    ///
+    /// assert_eq!(progress, vec![2, 4, 5]);
+    /// assert!(client
+    ///     .get_documents::<serde_json::Value>(&events)
     ///     .await?
-    ///     .try_collect()
+    ///     .try_collect::<Vec<_>>()
+    ///     .await?
+    ///     .is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_collection<'a>(
+        &self,
+        collection_ref: &CollectionReference,
+        batch_size: u32,
+    ) -> FirebaseStream<'a, u64, FirebaseError> {
+        let batch_size = batch_size.clamp(1, MAX_BATCH_WRITE_SIZE as u32) as i32;
+        let state = (self.clone(), collection_ref.clone(), 0, false);
+
+        futures::stream::try_unfold(
+            state,
+            move |(mut client, collection_ref, deleted, done)| async move {
+                if done {
+                    return Ok(None);
+                }
+
+                // The deleted documents are gone from the next listing, so
+                // every batch is the first page of what is left
+                let (docs, _) = client
+                    .list_document_names_page(&collection_ref, batch_size, String::new(), false)
+                    .await?;
+                let names: Vec<String> = docs.into_iter().map(|doc| doc.name).collect();
+
+                if names.is_empty() {
+                    return Ok(None);
+                }
+
+                let done = names.len() < batch_size as usize;
+                let mut summary = DeleteSummary::default();
+                client.apply_deletes(names, &mut summary).await;
+
+                // A document that fails to delete would be listed again, so
+                // the stream stops at the first failure
+                if let Some(failure) = summary.failures.into_iter().next() {
+                    return Err(failure.error);
+                }
+                let deleted = deleted + summary.documents_deleted;
+
+                Ok(Some((deleted, (client, collection_ref, deleted, done))))
+            },
+        )
+        .boxed()
+    }
+
+    /// Copies a document to another location. The fields are copied as they
+    /// are stored in Firestore, so the document doesn't need to be
+    /// deserializable into any particular type.
+    ///
+    /// Returns a [`DocumentNotfound`](FirebaseError::DocumentNotfound) error
+    /// if the source document doesn't exist, and a
+    /// [`DocumentAlreadyExists`](FirebaseError::DocumentAlreadyExists) error
+    /// if the destination already exists, unless
+    /// [`overwrite`](CopyOptions::overwrite) is set. With
+    /// [`recursive`](CopyOptions::recursive), the documents in the
+    /// subcollections are copied too, one document at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{client::CopyOptions, collection, reference::DocumentReference};
+    /// use ulid::Ulid;
+    ///
+    /// let src_ref = collection("cities").doc(Ulid::new());
+    /// let dst_ref = collection("cities").doc(Ulid::new());
+    ///
+    /// client
+    ///     .set_document(&src_ref, &serde_json::json!({ "name": "Oslo" }))
+    ///     .await?;
+    /// client
+    ///     .set_document(
+    ///         &src_ref.collection("landmarks").doc("opera"),
+    ///         &serde_json::json!({ "name": "Oslo Opera House" }),
+    ///     )
     ///     .await?;
     ///
-    /// // We don't have a guaranteed ordering of the query results, so we sort
-    /// // them by name to make sure our equality check works.
-    /// cheese_results.sort_by(|a, b| a.name.cmp(&b.name));
+    /// // The city hall document doesn't exist, but its reviews are still copied
+    /// let review_ref = |city_ref: &DocumentReference| {
+    ///     city_ref.collection("landmarks").doc("city-hall").collection("reviews").doc("first")
+    /// };
+    /// client
+    ///     .set_document(&review_ref(&src_ref), &serde_json::json!({ "stars": 5 }))
+    ///     .await?;
     ///
-    /// // We expect both pizzas to be found
-    /// assert_eq!(cheese_results, vec![hawaii, pepperoni]);
+    /// client
+    ///     .copy_document(&src_ref, &dst_ref, &CopyOptions::new().recursive(true))
+    ///     .await?;
     ///
-    /// // Query for pizzas with the name "pasta salad".
-    /// let mut pasta_salad_results: Vec<Pizza> = client
-    ///     .query(&collection("pizzas"), filter("name", EqualTo("pasta salad")))
-    ///     .await?
-    ///     .try_collect()
+    /// assert_eq!(
+    ///     client
+    ///         .get_document::<serde_json::Value>(&dst_ref.collection("landmarks").doc("opera"))
+    ///         .await?,
+    ///     Some(serde_json::json!({ "name": "Oslo Opera House" }))
+    /// );
+    /// assert_eq!(
+    ///     client
+    ///         .get_document::<serde_json::Value>(&review_ref(&dst_ref))
+    ///         .await?,
+    ///     Some(serde_json::json!({ "stars": 5 }))
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn copy_document(
+        &mut self,
+        src_ref: &DocumentReference,
+        dst_ref: &DocumentReference,
+        options: &CopyOptions,
+    ) -> Result<(), FirebaseError> {
+        self.copy_document_internal(src_ref, dst_ref, options, false)
+            .await
+    }
+
+    /// Moves a document to another location. Works like
+    /// [`copy_document`](Self::copy_document), except that each source
+    /// document is deleted in the same atomic commit as its copy is written.
+    /// The source is only deleted if it hasn't changed since it was read,
+    /// otherwise a [`Conflict`](FirebaseError::Conflict) error is returned.
+    ///
+    /// With [`recursive`](CopyOptions::recursive), the documents are moved one
+    /// at a time, so a failure may leave some of them moved and others not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{client::CopyOptions, collection};
+    /// use ulid::Ulid;
+    ///
+    /// let src_ref = collection("cities").doc(Ulid::new());
+    /// let dst_ref = collection("cities").doc(Ulid::new());
+    ///
+    /// client
+    ///     .set_document(&src_ref, &serde_json::json!({ "name": "Bergen" }))
     ///     .await?;
     ///
-    /// // We expect no results
-    /// assert_eq!(pasta_salad_results, vec![]);
+    /// client
+    ///     .move_document(&src_ref, &dst_ref, &CopyOptions::new())
+    ///     .await?;
+    ///
+    /// assert_eq!(client.get_document::<serde_json::Value>(&src_ref).await?, None);
+    /// assert_eq!(
+    ///     client.get_document::<serde_json::Value>(&dst_ref).await?,
+    ///     Some(serde_json::json!({ "name": "Bergen" }))
+    /// );
     /// # Ok(())
     /// # }
-    pub async fn query<'de, 'a, T: Deserialize<'de> + 'a>(
-        &'a mut self,
-        collection: &CollectionReference,
-        filter: Filter<'a>,
-    ) -> Result<FirebaseStream<T, FirebaseError>, FirebaseError> {
-        let (parent, collection_name) = self.split_collection_parent_and_name(collection);
+    /// ```
+    pub async fn move_document(
+        &mut self,
+        src_ref: &DocumentReference,
+        dst_ref: &DocumentReference,
+        options: &CopyOptions,
+    ) -> Result<(), FirebaseError> {
+        self.copy_document_internal(src_ref, dst_ref, options, true)
+            .await
+    }
 
-        self.query_internal(ApiQueryOptions {
-            parent,
-            collection_name,
-            filter: Some(filter),
-            limit: None,
-            offset: None,
-            should_search_descendants: false,
-        })
-        .await
+    async fn copy_document_internal(
+        &mut self,
+        src_ref: &DocumentReference,
+        dst_ref: &DocumentReference,
+        options: &CopyOptions,
+        delete_source: bool,
+    ) -> Result<(), FirebaseError> {
+        let src_doc = self
+            .get_raw_document(src_ref)
+            .await?
+            .ok_or_else(|| FirebaseError::DocumentNotfound(src_ref.to_string()))?;
+
+        // The descendants are read before the source document might be
+        // deleted, though deleting a document doesn't delete them anyway
+        let src_name = self.get_name_with(src_ref);
+        let descendants: Vec<Document> = if options.recursive {
+            self.query_internal_raw(ApiQueryOptions {
+                parent: src_name.clone(),
+                // Without a collection ID, the query matches the documents of
+                // all collections under the parent, including the ones under
+                // documents that don't exist themselves
+                collection_name: String::new(),
+                should_search_descendants: true,
+                ..Default::default()
+            })
+            .await?
+            .try_collect()
+            .await?
+        } else {
+            Vec::new()
+        };
+
+        self.copy_single_document(src_ref, dst_ref, src_doc, options, delete_source)
+            .await?;
+
+        for descendant in descendants {
+            let relative_path = descendant
+                .name
+                .strip_prefix(&src_name)
+                .unwrap_or_default()
+                .trim_start_matches('/');
+
+            let mut child_src = src_ref.clone();
+            let mut child_dst = dst_ref.clone();
+            for segments in relative_path.split('/').collect::<Vec<_>>().chunks(2) {
+                if let [collection_id, doc_id] = *segments {
+                    child_src = child_src.collection(collection_id).doc(doc_id);
+                    child_dst = child_dst.collection(collection_id).doc(doc_id);
+                }
+            }
+
+            self.copy_single_document(&child_src, &child_dst, descendant, options, delete_source)
+                .await?;
+        }
+
+        Ok(())
     }
 
-    /// The same as [`query`](Self::query), but only returns the first result.
+    async fn copy_single_document(
+        &mut self,
+        src_ref: &DocumentReference,
+        dst_ref: &DocumentReference,
+        src_doc: Document,
+        options: &CopyOptions,
+        delete_source: bool,
+    ) -> Result<(), FirebaseError> {
+        let mut writes = vec![Write {
+            operation: Some(Operation::Update(Document {
+                name: self.get_name_with(dst_ref),
+                fields: src_doc.fields,
+                ..Default::default()
+            })),
+            update_mask: None,
+            update_transforms: vec![],
+            current_document: if options.overwrite {
+                None
+            } else {
+                Some(Precondition {
+                    condition_type: Some(ConditionType::Exists(false)),
+                })
+            },
+        }];
+
+        if delete_source {
+            let mut delete = delete_write(&self.root_resource_path, src_ref);
+            delete.current_document = Some(Precondition {
+                condition_type: src_doc.update_time.map(ConditionType::UpdateTime),
+            });
+            writes.push(delete);
+        }
+
+        self.commit(writes).await?;
+
+        Ok(())
+    }
+
+    /// Serializes a document into a gRPC [`Write`] without sending it to
+    /// Firestore. This is useful for systems that commit writes through a
+    /// separate pipeline but still want to use the same serialization as the
+    /// rest of the client.
+    ///
+    /// The [`WriteKind`] decides which update mask and preconditions are set
+    /// on the write.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{collection, write::WriteKind};
+    ///
+    /// let write = client.to_write(
+    ///     &collection("greetings").doc("first"),
+    ///     &serde_json::json!({ "message": "Hi Mom" }),
+    ///     WriteKind::Create,
+    /// )?;
+    ///
+    /// // The write will only succeed if the document doesn't exist already
+    /// assert!(write.current_document.is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_write<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+        kind: WriteKind,
+    ) -> Result<Write, FirebaseError> {
+        let write = serialize_write(&self.root_resource_path, doc_ref, document, kind)?;
+        Ok(write)
+    }
+
+    /// Creates a gRPC [`Write`] that deletes the document at the given
+    /// reference. See [`to_write`](Self::to_write).
+    pub fn to_delete_write(&self, doc_ref: &DocumentReference) -> Write {
+        delete_write(&self.root_resource_path, doc_ref)
+    }
+
+    /// Creates an empty [`WriteBatch`], whose writes are committed atomically
+    /// when calling [`WriteBatch::commit`].
+    pub fn batch(&self) -> WriteBatch {
+        WriteBatch::new(self.clone())
+    }
+
+    /// Applies a stream of writes to Firestore, returning a stream with the
+    /// result of each write, in the same order as the writes.
+    ///
+    /// Writes that are ready are grouped into batches which are sent one at a
+    /// time, so the input stream is only polled as fast as Firestore keeps up.
+    /// The writes are not applied atomically, and a failing write doesn't
+    /// stop the remaining writes from being applied - its error is simply
+    /// yielded in its place in the output stream.
+    ///
+    /// Writes can be created with [`to_write`](Self::to_write) and
+    /// [`to_delete_write`](Self::to_delete_write).
     ///
     /// # Examples
     ///
     /// ```
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// # use fireplace::firestore::collection;
-    /// # use serde::{Deserialize, Serialize};
     /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
-    /// #
-    /// use fireplace::firestore::query::{filter, EqualTo};
+    /// use fireplace::{
+    ///     error::FirebaseError,
+    ///     firestore::{collection, write::WriteKind},
+    /// };
+    /// use futures::StreamExt;
     ///
-    /// #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-    /// struct Pizza {
-    ///     name: String,
+    /// let doc_ref = collection("greetings").doc("sink");
+    /// client.delete_document(&doc_ref).await?;
+    ///
+    /// let writes = vec![
+    ///     client.to_write(&doc_ref, &serde_json::json!({ "n": 1 }), WriteKind::Create)?,
+    ///     // This write fails, as the document was just created
+    ///     client.to_write(&doc_ref, &serde_json::json!({ "n": 2 }), WriteKind::Create)?,
+    ///     client.to_delete_write(&doc_ref),
+    /// ];
+    ///
+    /// let results: Vec<_> = client
+    ///     .write_sink(futures::stream::iter(writes))
+    ///     .collect()
+    ///     .await;
+    ///
+    /// assert!(results[0].is_ok());
+    /// assert!(matches!(
+    ///     results[1],
+    ///     Err(FirebaseError::DocumentAlreadyExists(_))
+    /// ));
+    /// assert!(results[2].is_ok());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_sink<'a>(
+        &self,
+        writes: impl Stream<Item = Write> + Send + 'a,
+    ) -> FirebaseStream<'a, WriteResult, FirebaseError> {
+        let batches = writes
+            .ready_chunks(MAX_BATCH_WRITE_SIZE)
+            .flat_map(|writes| futures::stream::iter(split_into_batches(writes)))
+            .boxed();
+
+        futures::stream::unfold(
+            (self.clone(), batches),
+            |(mut client, mut batches)| async move {
+                let batch = batches.next().await?;
+                let results = client.batch_write(batch).await;
+                Some((futures::stream::iter(results), (client, batches)))
+            },
+        )
+        .flatten()
+        .boxed()
+    }
+
+    /// Sends the writes in a single batch write request, returning a result
+    /// for each of the writes.
+    pub(crate) async fn batch_write(
+        &mut self,
+        writes: Vec<Write>,
+    ) -> Vec<Result<WriteResult, FirebaseError>> {
+        let is_delete: Vec<bool> = writes
+            .iter()
+            .map(|w| matches!(w.operation, Some(Operation::Delete(_))))
+            .collect();
+        let write_count = writes.len();
+        let request = BatchWriteRequest {
+            database: self.database_path(),
+            writes,
+            labels: Default::default(),
+        };
+
+        let _permit = self.begin_rpc(write_count as u32).await;
+        let response = match self.client.batch_write(request).await {
+            Ok(res) => res.into_inner(),
+            Err(err) => {
+                // The batch failed as a whole, so every write in it failed
+                return (0..write_count)
+                    .map(|_| Err(anyhow!("Failed to apply batch write: {}", err).into()))
+                    .collect();
+            }
+        };
+
+        // Results are matched to writes by position, so a response with a
+        // different number of them can't be trusted for any write
+        if response.write_results.len() != write_count || response.status.len() != write_count {
+            let (results, statuses) = (response.write_results.len(), response.status.len());
+            return (0..write_count)
+                .map(|_| {
+                    Err(anyhow!(
+                        "Batch write returned {} results and {} statuses for {} writes",
+                        results,
+                        statuses,
+                        write_count
+                    )
+                    .into())
+                })
+                .collect();
+        }
+
+        response
+            .write_results
+            .into_iter()
+            .zip(response.status)
+            .zip(is_delete)
+            .map(
+                |((result, status), is_delete)| match tonic::Code::from_i32(status.code) {
+                    tonic::Code::Ok => {
+                        if is_delete {
+                            self.stats.record_deletes(1);
+                        } else {
+                            self.stats.record_writes(1);
+                        }
+                        Ok(result.into())
+                    }
+                    code => Err(write_status_err(Status::new(code, status.message))),
+                },
+            )
+            .collect()
+    }
+
+    /// Applies the writes atomically in a single commit. Either all of them
+    /// succeed, or none of them are applied.
+    pub(crate) async fn commit(
+        &mut self,
+        writes: Vec<Write>,
+    ) -> Result<Vec<WriteResult>, FirebaseError> {
+        self.commit_internal(writes, vec![]).await
+    }
+
+    /// Commits a single write and returns its result.
+    async fn commit_single(&mut self, write: Write) -> Result<WriteResult, FirebaseError> {
+        let mut results = self.commit(vec![write]).await?;
+
+        results
+            .pop()
+            .ok_or_else(|| anyhow!("Missing write result in commit response").into())
+    }
+
+    /// Like [`commit`](Self::commit), but commits the given transaction if
+    /// the ID is not empty.
+    async fn commit_internal(
+        &mut self,
+        writes: Vec<Write>,
+        transaction: Vec<u8>,
+    ) -> Result<Vec<WriteResult>, FirebaseError> {
+        let deletes = writes
+            .iter()
+            .filter(|w| matches!(w.operation, Some(Operation::Delete(_))))
+            .count();
+        let write_count = writes.len();
+        let request = CommitRequest {
+            database: self.database_path(),
+            writes,
+            transaction,
+        };
+
+        let _permit = self.begin_rpc(write_count as u32).await;
+        let response = self
+            .client
+            .commit(request)
+            .await
+            .map_err(write_status_err)?
+            .into_inner();
+        self.stats.record_writes((write_count - deletes) as u64);
+        self.stats.record_deletes(deletes as u64);
+
+        let commit_time = response.commit_time;
+        Ok(response
+            .write_results
+            .into_iter()
+            .map(|result| WriteResult::from(result).or_commit_time(commit_time.as_ref()))
+            .collect())
+    }
+
+    /// Runs the given function in a transaction, and commits the writes it
+    /// queued on the [`Transaction`] if it succeeds. If the function returns
+    /// an error, the transaction is rolled back and the error is returned.
+    ///
+    /// Documents read in the transaction are locked until it completes, so if
+    /// another transaction changes them in the meantime, Firestore aborts one
+    /// of the transactions. Aborted transactions are retried by running the
+    /// function again, up to 5 attempts in total. The function should
+    /// therefore not have side effects other than queueing writes on the
+    /// transaction.
+    ///
+    /// All reads must be done before any writes are queued, and reads don't
+    /// see the writes queued in the same transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use serde::{Deserialize, Serialize};
+    /// use ulid::Ulid;
+    ///
+    /// #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    /// struct Account {
+    ///     balance: i64,
     /// }
     ///
-    /// let margherita = Pizza {
-    ///     name: "Margherita".into(),
-    /// };
+    /// let from_ref = collection("accounts").doc(Ulid::new());
+    /// let to_ref = collection("accounts").doc(Ulid::new());
+    /// client.set_document(&from_ref, &Account { balance: 100 }).await?;
+    /// client.set_document(&to_ref, &Account { balance: 0 }).await?;
+    ///
+    /// let new_balance = client
+    ///     .run_transaction(|mut txn| {
+    ///         let (from_ref, to_ref) = (from_ref.clone(), to_ref.clone());
+    ///         async move {
+    ///             let from: Account = txn.get_document(&from_ref).await?.unwrap();
+    ///             let to: Account = txn.get_document(&to_ref).await?.unwrap();
+    ///
+    ///             let from = Account { balance: from.balance - 30 };
+    ///             txn.set_document(&from_ref, &from)?;
+    ///             txn.set_document(&to_ref, &Account { balance: to.balance + 30 })?;
+    ///
+    ///             Ok(from.balance)
+    ///         }
+    ///     })
+    ///     .await?;
     ///
+    /// assert_eq!(new_balance, 70);
+    /// assert_eq!(
+    ///     client.get_document(&to_ref).await?,
+    ///     Some(Account { balance: 30 })
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_transaction<T, F, Fut>(&mut self, mut f: F) -> Result<T, FirebaseError>
+    where
+        F: FnMut(Transaction) -> Fut,
+        Fut: Future<Output = Result<T, FirebaseError>>,
+    {
+        let mut retry_transaction = vec![];
+        let mut attempt = 1;
+
+        loop {
+            let id = self
+                .begin_transaction(TransactionOptions {
+                    mode: Some(transaction_options::Mode::ReadWrite(
+                        transaction_options::ReadWrite { retry_transaction },
+                    )),
+                })
+                .await?;
+
+            let transaction = Transaction::new(
+                self.with_read_consistency(ReadConsistency::Transaction(id.clone())),
+            );
+
+            let result = match f(transaction.clone()).await {
+                Ok(value) => self
+                    .commit_internal(transaction.take_writes(), id.clone())
+                    .await
+                    .map(|_| value),
+                Err(err) => {
+                    self.rollback(id.clone()).await;
+                    Err(err)
+                }
+            };
+
+            match result {
+                Err(err) if is_aborted(&err) && attempt < MAX_TRANSACTION_ATTEMPTS => {
+                    tracing::debug!("Transaction aborted on attempt {}, retrying", attempt);
+                    tokio::time::sleep(retry_delay(attempt)).await;
+                    retry_transaction = id;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Starts a read-only transaction, which reads documents at a single
+    /// consistent snapshot of the database, taken when the transaction
+    /// starts. Unlike [`run_transaction`](Self::run_transaction), it doesn't
+    /// lock any documents and never needs to be retried or committed.
+    ///
+    /// Firestore expires read-only transactions after a while (currently 270
+    /// seconds), after which reads will fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use futures::TryStreamExt;
+    ///
+    /// let doc_ref = collection("snapshots").doc("read-only-transaction");
     /// client
-    ///     .set_document(&collection("pizzas").doc("margherita"), &margherita)
+    ///     .set_document(&doc_ref, &serde_json::json!({ "version": 1 }))
     ///     .await?;
     ///
-    /// // Query for the Margherita pizza by name
-    /// let mut margherita_result: Option<Pizza> = client
-    ///     .query_one(
-    ///         &collection("pizzas"),
-    ///         filter("name", EqualTo("Margherita")),
-    ///     )
+    /// let mut txn = client.read_only_transaction().await?;
+    ///
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "version": 2 }))
     ///     .await?;
     ///
-    /// // We expect a single search hit - the margherita pizza.
-    /// assert_eq!(margherita_result, Some(margherita.clone()));
+    /// // The transaction still sees the database as it was when it started
+    /// let doc: Option<serde_json::Value> = txn.get_document(&doc_ref).await?;
+    /// assert_eq!(doc, Some(serde_json::json!({ "version": 1 })));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_only_transaction(&mut self) -> Result<ReadOnlyTransaction, FirebaseError> {
+        let id = self
+            .begin_transaction(TransactionOptions {
+                mode: Some(transaction_options::Mode::ReadOnly(
+                    transaction_options::ReadOnly {
+                        consistency_selector: None,
+                    },
+                )),
+            })
+            .await?;
+
+        Ok(ReadOnlyTransaction::new(
+            self.with_read_consistency(ReadConsistency::Transaction(id)),
+        ))
+    }
+
+    /// Gets a token for the current state of the database, which includes all
+    /// writes that have completed before this call. Pass the token to
+    /// [`read_at`](Self::read_at), possibly in another process, to read the
+    /// database as of that time. See [`ConsistencyToken`].
+    ///
+    /// Getting a token costs one RPC, but no document reads or writes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{collection, consistency::ConsistencyToken};
+    ///
+    /// let doc_ref = collection("orders").doc("consistency-token");
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "state": "placed" }))
+    ///     .await?;
+    ///
+    /// // Send the token along to another service, e.g. in a message
+    /// let token = client.consistency_token().await?.to_string();
+    ///
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "state": "shipped" }))
+    ///     .await?;
+    ///
+    /// // The other service reads the database as it was when the token was taken
+    /// let token: ConsistencyToken = token.parse()?;
+    /// let order: Option<serde_json::Value> =
+    ///     client.read_at(&token).get_document(&doc_ref).await?;
+    /// assert_eq!(order, Some(serde_json::json!({ "state": "placed" })));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn consistency_token(&mut self) -> Result<ConsistencyToken, FirebaseError> {
+        // An empty commit doesn't change anything, but tells us the current
+        // time according to the database
+        let request = CommitRequest {
+            database: self.database_path(),
+            writes: vec![],
+            transaction: vec![],
+        };
+
+        let _permit = self.begin_rpc(0).await;
+        let commit_time = self
+            .client
+            .commit(request)
+            .await
+            .context("Failed to get consistency token")?
+            .into_inner()
+            .commit_time
+            .context("No commit time returned")?;
+
+        Ok(ConsistencyToken::from_timestamp(commit_time))
+    }
+
+    /// Creates a clone of the client whose reads all see the database as of
+    /// the time of the given token. Writes are not affected.
+    ///
+    /// This applies to documents fetched with e.g.
+    /// [`get_document`](Self::get_document) as well as to queries, so a
+    /// token created with [`ConsistencyToken::at`] can be used to read a
+    /// fixed snapshot of the database, e.g. in a backfill job.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// use fireplace::firestore::{collection, consistency::ConsistencyToken};
+    /// use futures::TryStreamExt;
+    ///
+    /// let snapshot = ConsistencyToken::at(SystemTime::now() - Duration::from_secs(30))?;
+    /// let mut snapshot_client = client.read_at(&snapshot);
+    ///
+    /// let orders: Vec<serde_json::Value> = snapshot_client
+    ///     .get_documents(&collection("orders"))
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_at(&self, token: &ConsistencyToken) -> Self {
+        self.with_read_consistency(ReadConsistency::ReadTime(token.timestamp()))
+    }
+
+    async fn begin_transaction(
+        &mut self,
+        options: TransactionOptions,
+    ) -> Result<Vec<u8>, FirebaseError> {
+        let request = BeginTransactionRequest {
+            database: self.database_path(),
+            options: Some(options),
+        };
+
+        let _permit = self.begin_rpc(0).await;
+        let res = self
+            .client
+            .begin_transaction(request)
+            .await
+            .context("Failed to begin transaction")?;
+
+        Ok(res.into_inner().transaction)
+    }
+
+    /// Rolls back the transaction. Failing to do so only means that the locks
+    /// of the transaction are held until it times out, so errors are logged
+    /// rather than returned.
+    async fn rollback(&mut self, transaction: Vec<u8>) {
+        let request = RollbackRequest {
+            database: self.database_path(),
+            transaction,
+        };
+
+        let _permit = self.begin_rpc(0).await;
+        if let Err(err) = self.client.rollback(request).await {
+            tracing::warn!("Failed to roll back transaction: {}", err);
+        }
+    }
+
+    /// Starts a [`Session`] on a clone of the client, whose reads always see
+    /// the writes made through the session. See [`Session`].
+    pub fn session(&self) -> Session {
+        Session::new(self.clone())
+    }
+
+    /// Creates a clone of the client whose reads see at least the database as
+    /// of the given token. Reads that already see the latest state of the
+    /// database, or that are part of a transaction, are left as they are,
+    /// while reads at an earlier time are moved up to the token.
+    pub(crate) fn read_no_earlier_than(&self, token: &ConsistencyToken) -> Self {
+        match &self.read_consistency {
+            Some(ReadConsistency::ReadTime(time))
+                if ConsistencyToken::from_timestamp(time.clone()) < *token =>
+            {
+                self.read_at(token)
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Creates a clone of the client whose reads all use the given
+    /// consistency.
+    fn with_read_consistency(&self, read_consistency: ReadConsistency) -> Self {
+        let mut client = self.clone();
+        client.read_consistency = Some(read_consistency);
+        client
+    }
+
+    /// Query a collection for documents that fulfill the given filter.
     ///
-    /// // Query for pizzas with the name "pasta salad".
-    /// let mut pasta_salad_result: Option<Pizza> = client
-    ///     .query_one(&collection("pizzas"), filter("name", EqualTo("pasta salad")))
-    ///     .await?;
+    /// Prefer [`run_query`](Self::run_query), which takes the same query
+    /// built with [`with_filter`](CollectionReference::with_filter) and also
+    /// supports limits, offsets, ordering and cursors.
+    #[deprecated(note = "use `run_query(collection.with_filter(filter))` instead")]
+    pub async fn query<'de, 'a, T: Deserialize<'de> + 'a>(
+        &'a mut self,
+        collection: &CollectionReference,
+        filter: Filter<'a>,
+    ) -> Result<FirebaseStream<T, FirebaseError>, FirebaseError> {
+        let (parent, collection_name) = self.split_collection_parent_and_name(collection);
+
+        self.query_internal(ApiQueryOptions {
+            parent,
+            collection_name,
+            filter: Some(filter),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// The same as [`query`](Self::query), but only returns the first result.
     ///
-    /// // We expect no results
-    /// assert_eq!(pasta_salad_result, None);
-    /// # Ok(())
-    /// # }
+    /// Prefer [`run_query_one`](Self::run_query_one), which takes any query.
+    #[deprecated(note = "use `run_query_one(collection.with_filter(filter))` instead")]
     pub async fn query_one<'de, 'a, T: Deserialize<'de>>(
         &mut self,
         collection: &CollectionReference,
@@ -978,8 +2851,7 @@ impl FirestoreClient {
                 collection_name,
                 filter: Some(filter),
                 limit: Some(1),
-                offset: None,
-                should_search_descendants: false,
+                ..Default::default()
             })
             .await?;
 
@@ -1002,39 +2874,672 @@ impl FirestoreClient {
         &mut self,
         options: ApiQueryOptions<'a>,
     ) -> Result<FirebaseStream<FirestoreDocument<T>, FirebaseError>, FirebaseError> {
+        let doc_stream = self
+            .query_internal_raw(options)
+            .await?
+            .map(|doc_res| into_firestore_document(doc_res?));
+
+        Ok(doc_stream.boxed())
+    }
+
+    /// Runs a query and returns the documents as they come from the gRPC API,
+    /// without deserializing them.
+    pub(crate) async fn query_raw<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<FirebaseStream<'static, Document, FirebaseError>, FirebaseError> {
+        let options = ApiQueryOptions::from_query(self, query);
+        self.query_internal_raw(options).await
+    }
+
+    /// Lists a single page of the documents in a collection, ordered by
+    /// document ID. Pass the
+    /// [`next_page_token`](DocumentsPage::next_page_token) of a page to fetch
+    /// the following page.
+    ///
+    /// Unlike a query, which streams all results in a single long-running
+    /// request, every page is fetched with its own request. The page token can
+    /// be stored to continue listing a very large collection later, e.g. after
+    /// a failure or in another process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use fireplace::firestore::{collection, test_helpers::Landmark};
+    ///
+    /// let landmarks = collection("cities").doc("SF").collection("landmarks");
+    ///
+    /// let mut count = 0;
+    /// let mut page_token = None;
+    /// loop {
+    ///     let page = client
+    ///         .list_documents_paging::<Landmark>(&landmarks, 2, page_token.as_deref())
+    ///         .await?;
+    ///     count += page.documents.len();
+    ///
+    ///     // The token could be saved here to continue later
+    ///     page_token = page.next_page_token;
+    ///     if page_token.is_none() {
+    ///         break;
+    ///     }
+    /// }
+    /// assert_eq!(count, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_documents_paging<T: DeserializeOwned>(
+        &mut self,
+        collection_ref: &CollectionReference,
+        page_size: u32,
+        page_token: Option<&str>,
+    ) -> Result<DocumentsPage<T>, FirebaseError> {
+        let (parent, collection_id) = self.split_collection_parent_and_name(collection_ref);
+        let request = ListDocumentsRequest {
+            parent,
+            collection_id,
+            page_size: page_size.min(i32::MAX as u32) as i32,
+            page_token: page_token.unwrap_or_default().to_string(),
+            consistency_selector: self
+                .read_consistency
+                .as_ref()
+                .map(ReadConsistency::list_documents_selector),
+            ..Default::default()
+        };
+
+        let _permit = self.begin_rpc(0).await;
+        let res = self
+            .client
+            .list_documents(request)
+            .await
+            .context("Failed to list documents")?
+            .into_inner();
+
+        self.stats.record_reads(res.documents.len() as u64);
+        let documents = res
+            .documents
+            .into_iter()
+            .map(into_firestore_document)
+            .collect::<Result<_, _>>()?;
+
+        Ok(DocumentsPage {
+            documents,
+            next_page_token: Some(res.next_page_token).filter(|token| !token.is_empty()),
+        })
+    }
+
+    /// Gets a page of the documents in a collection, ordered by document ID,
+    /// starting after the document with the given ID.
+    pub(crate) async fn list_documents_page(
+        &mut self,
+        collection_ref: &CollectionReference,
+        start_after_id: Option<&str>,
+        page_size: u32,
+    ) -> Result<Vec<Document>, FirebaseError> {
+        let (parent, collection_name) = self.split_collection_parent_and_name(collection_ref);
+        let start_after = start_after_id.map(|id| self.get_name_with(collection_ref.doc(id)));
+
+        self.query_internal_raw(ApiQueryOptions {
+            parent,
+            collection_name,
+            limit: Some(page_size as i32),
+            start_after,
+            ..Default::default()
+        })
+        .await?
+        .try_collect()
+        .await
+    }
+
+    async fn query_internal_raw(
+        &mut self,
+        options: ApiQueryOptions<'_>,
+    ) -> Result<FirebaseStream<'static, Document, FirebaseError>, FirebaseError> {
         let parent = options.parent.clone();
+        let limit_to_last = options.limit_to_last;
         let structured_query = self.structured_query_from_options(options)?;
+        let doc_stream = self
+            .run_structured_query_raw(parent, structured_query)
+            .await?;
+
+        if !limit_to_last {
+            return Ok(doc_stream);
+        }
+
+        // The query ran in the reverse order, so the results are reversed
+        // back once they have all been received
+        let mut docs: Vec<Document> = doc_stream.try_collect().await?;
+        docs.reverse();
+        Ok(futures::stream::iter(docs.into_iter().map(Ok)).boxed())
+    }
 
+    async fn run_structured_query_raw(
+        &mut self,
+        parent: String,
+        structured_query: StructuredQuery,
+    ) -> Result<FirebaseStream<'static, Document, FirebaseError>, FirebaseError> {
         let request = RunQueryRequest {
             parent,
             query_type: Some(QueryType::StructuredQuery(structured_query)),
-            consistency_selector: None,
+            consistency_selector: self
+                .read_consistency
+                .as_ref()
+                .map(ReadConsistency::run_query_selector),
         };
 
+        let _permit = self.begin_rpc(0).await;
         let res = self
             .client
             .run_query(request)
             .await
             .context("Failed to run query")?;
 
+        let stats = self.stats.clone();
+
         let doc_stream = res
             .into_inner()
             // Some of the "results" coming from the gRPC stream don't represent
             // search hits but rather information about query progress. We just
             // ignore those items.
             .filter_map(|res| future::ready(res.map(|inner| inner.document).transpose()))
-            .map(|doc_res| {
+            .map(move |doc_res| {
                 let doc = doc_res.map_err(|e| anyhow!(e))?;
-                Ok(FirestoreDocument {
-                    data: deserialize_firestore_document_fields::<T>(doc.fields)
-                        .map_err(|e| serde_err_with_doc(e, &doc.name))?,
-                    id: doc.name,
-                    create_time: doc.create_time.map(|t| t.seconds),
-                    update_time: doc.update_time.map(|t| t.seconds),
-                })
+                stats.record_reads(1);
+                Ok(doc)
             });
 
-        Ok(doc_stream.boxed())
+        Ok(doc_stream.boxed())
+    }
+
+    /// Splits a collection group query into at most `partition_count`
+    /// partitions of roughly equal size, which can be run in parallel with
+    /// [`run_partition`](Self::run_partition), e.g. by separate worker tasks.
+    /// Firestore may return fewer partitions than requested, e.g. for small
+    /// collection groups.
+    ///
+    /// Partitioning only supports plain collection group queries, so the
+    /// query must not have a filter, limit, offset, ordering or cursor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use fireplace::firestore::{collection_group, test_helpers::Landmark};
+    /// use futures::TryStreamExt;
+    ///
+    /// let partitions = client
+    ///     .partition_query(collection_group("landmarks"), 4)
+    ///     .await?;
+    ///
+    /// let mut workers = Vec::new();
+    /// for partition in partitions {
+    ///     let mut client = client.clone();
+    ///     workers.push(tokio::spawn(async move {
+    ///         client
+    ///             .run_partition::<Landmark>(&partition)
+    ///             .await?
+    ///             .try_fold(0, |count, _| async move { Ok(count + 1) })
+    ///             .await
+    ///     }));
+    /// }
+    ///
+    /// let mut total = 0;
+    /// for worker in workers {
+    ///     total += worker.await??;
+    /// }
+    /// assert!(total >= 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn partition_query(
+        &mut self,
+        query: CollectionGroupQuery<'_>,
+        partition_count: u32,
+    ) -> Result<Vec<QueryPartition>, FirebaseError> {
+        if query.filter_ref().is_some()
+            || query.limit().is_some()
+            || query.offset().is_some()
+            || !query.ordering().is_empty()
+            || query.start().is_some()
+            || query.end().is_some()
+        {
+            return Err(anyhow!(
+                "Partitioned queries can't have a filter, limit, offset, ordering or cursor"
+            )
+            .into());
+        }
+
+        let collection_id = query.collection_name().to_string();
+        let mut split_points = Vec::new();
+        let mut page_token = String::new();
+
+        // Firestore counts the split points between partitions, so there is
+        // nothing to ask for when a single partition is wanted
+        let split_point_count = partition_count.saturating_sub(1);
+
+        while split_point_count > 0 {
+            let request = PartitionQueryRequest {
+                parent: self.root_resource_path.clone(),
+                query_type: Some(partition_query_request::QueryType::StructuredQuery(
+                    partition_structured_query(&collection_id, None, None),
+                )),
+                partition_count: split_point_count as i64,
+                page_token,
+                ..Default::default()
+            };
+
+            let _permit = self.begin_rpc(0).await;
+            let res = self
+                .client
+                .partition_query(request)
+                .await
+                .context("Failed to partition query")?
+                .into_inner();
+
+            for cursor in res.partitions {
+                split_points.push(split_point_document(cursor)?);
+            }
+
+            if res.next_page_token.is_empty() {
+                break;
+            }
+            page_token = res.next_page_token;
+        }
+
+        Ok(QueryPartition::from_split_points(
+            collection_id,
+            split_points,
+        ))
+    }
+
+    /// Runs the collection group query for the documents in one of the
+    /// partitions returned by [`partition_query`](Self::partition_query).
+    /// The documents are ordered by their path.
+    pub async fn run_partition<'de, 'a, T: Deserialize<'de> + 'a>(
+        &'a mut self,
+        partition: &QueryPartition,
+    ) -> Result<FirebaseStream<'a, FirestoreDocument<T>, FirebaseError>, FirebaseError> {
+        let start_at = partition
+            .start_at()
+            .map(|doc_ref| self.get_name_with(doc_ref));
+        let end_before = partition
+            .end_before()
+            .map(|doc_ref| self.get_name_with(doc_ref));
+        let structured_query =
+            partition_structured_query(partition.collection_id(), start_at, end_before);
+
+        let doc_stream = self
+            .run_structured_query_raw(self.root_resource_path.clone(), structured_query)
+            .await?
+            .map(|doc_res| into_firestore_document(doc_res?));
+
+        Ok(doc_stream.boxed())
+    }
+
+    /// Listens to changes of a document in real time. The returned stream
+    /// yields a snapshot of the document whenever it is created, changed or
+    /// deleted, starting with its current state. The first snapshot is
+    /// [`Added`](super::listen::ChangeKind::Added) if the document exists, and
+    /// [`Removed`](super::listen::ChangeKind::Removed) if it doesn't.
+    ///
+    /// Snapshots are consistent, but several quick changes may be reported
+    /// as a single snapshot. If the connection is lost, the client reconnects
+    /// with backoff and resumes where it left off, so no change is missed.
+    /// The stream only ends with an error that can't be recovered from, such
+    /// as missing permissions, or when it is dropped.
+    ///
+    /// Each snapshot has a [`ListenCheckpoint`], which can be stored to resume
+    /// listening after a restart with
+    /// [`resume_listen_document`](Self::resume_listen_document).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{collection, listen::ChangeKind};
+    /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
+    ///
+    /// let doc_ref = collection("scoreboards").doc(Ulid::new());
+    /// let mut snapshots = client.listen_document::<serde_json::Value>(&doc_ref);
+    ///
+    /// let first = snapshots.try_next().await?.unwrap();
+    /// assert_eq!(first.kind, ChangeKind::Removed);
+    ///
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "score": 1 }))
+    ///     .await?;
+    ///
+    /// let created = snapshots.try_next().await?.unwrap();
+    /// assert_eq!(created.kind, ChangeKind::Added);
+    /// assert_eq!(
+    ///     created.document.unwrap().data,
+    ///     serde_json::json!({ "score": 1 })
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn listen_document<T: DeserializeOwned + Send + 'static>(
+        &self,
+        doc_ref: &DocumentReference,
+    ) -> FirebaseStream<'static, DocumentSnapshot<T>, FirebaseError> {
+        let watch = Watch::document(self.get_name_with(doc_ref));
+        self.listen_document_with(watch)
+    }
+
+    /// Resumes listening to a document where a previous listener left off,
+    /// e.g. before the service was restarted. Only changes since the
+    /// checkpoint are reported, so nothing is yielded until the document
+    /// changes. See [`ListenCheckpoint`].
+    ///
+    /// Returns an error if the checkpoint is from a query listener.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{collection, listen::{ChangeKind, ListenCheckpoint}};
+    /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
+    ///
+    /// let doc_ref = collection("scoreboards").doc(Ulid::new());
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "score": 1 }))
+    ///     .await?;
+    ///
+    /// let mut snapshots = client.listen_document::<serde_json::Value>(&doc_ref);
+    /// let first = snapshots.try_next().await?.unwrap();
+    ///
+    /// // Store the checkpoint somewhere durable before shutting down
+    /// let stored = first.checkpoint.encode();
+    /// drop(snapshots);
+    ///
+    /// client
+    ///     .set_document(&doc_ref, &serde_json::json!({ "score": 2 }))
+    ///     .await?;
+    ///
+    /// // After restarting, only the change made in the meantime is reported
+    /// let checkpoint = ListenCheckpoint::decode(&stored)?;
+    /// let mut snapshots = client.resume_listen_document::<serde_json::Value>(checkpoint)?;
+    /// let change = snapshots.try_next().await?.unwrap();
+    /// assert_eq!(change.kind, ChangeKind::Modified);
+    /// assert_eq!(
+    ///     change.document.unwrap().data,
+    ///     serde_json::json!({ "score": 2 })
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume_listen_document<T: DeserializeOwned + Send + 'static>(
+        &self,
+        checkpoint: ListenCheckpoint,
+    ) -> Result<FirebaseStream<'static, DocumentSnapshot<T>, FirebaseError>, FirebaseError> {
+        if !checkpoint.is_document_target() {
+            return Err(anyhow!("The checkpoint is not from a document listener").into());
+        }
+
+        Ok(self.listen_document_with(Watch::resume(checkpoint)))
+    }
+
+    fn listen_document_with<T: DeserializeOwned + Send + 'static>(
+        &self,
+        watch: Watch,
+    ) -> FirebaseStream<'static, DocumentSnapshot<T>, FirebaseError> {
+        self.listen_raw(watch)
+            .map(|snapshot| {
+                let snapshot = snapshot?;
+                let kind = document_change_kind(snapshot.previous.as_deref(), &snapshot.current);
+                let document = snapshot
+                    .current
+                    .into_iter()
+                    .next()
+                    .map(into_firestore_document::<T>)
+                    .transpose()?;
+                Ok(DocumentSnapshot {
+                    kind,
+                    document,
+                    checkpoint: snapshot.checkpoint,
+                })
+            })
+            .boxed()
+    }
+
+    /// Listens to the results of a query in real time. The returned stream
+    /// yields a [`QuerySnapshot`] with all matching documents whenever any of
+    /// them are added, changed or removed, starting with the current results.
+    /// Each snapshot also lists the [`DocumentChange`]s since the previous one,
+    /// with the indices of the changed documents before and after the change,
+    /// so a sorted view of the results can be kept up to date.
+    ///
+    /// Documents are ordered by their path. Like
+    /// [`listen_document`](Self::listen_document), snapshots are consistent,
+    /// and the client reconnects and resumes when the connection is lost.
+    ///
+    /// Returns an error if the query has an offset, which listening doesn't
+    /// support.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{
+    ///     collection,
+    ///     listen::ChangeKind,
+    ///     query::{filter, CollectionQuery, EqualTo},
+    /// };
+    /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
+    ///
+    /// let players = collection("leagues").doc(Ulid::new()).collection("players");
+    /// let query = CollectionQuery::new(players.clone())
+    ///     .with_filter(filter("active", EqualTo(true)));
+    /// let mut snapshots = client.listen_query::<serde_json::Value>(query)?;
+    ///
+    /// let first = snapshots.try_next().await?.unwrap();
+    /// assert!(first.documents.is_empty());
+    ///
+    /// client
+    ///     .set_document(&players.doc("mario"), &serde_json::json!({ "active": true }))
+    ///     .await?;
+    ///
+    /// let snapshot = snapshots.try_next().await?.unwrap();
+    /// assert_eq!(snapshot.documents.len(), 1);
+    /// assert_eq!(snapshot.changes[0].kind, ChangeKind::Added);
+    /// assert_eq!(snapshot.changes[0].new_index, Some(0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn listen_query<'a, T: DeserializeOwned + Send + 'static>(
+        &self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<FirebaseStream<'static, QuerySnapshot<T>, FirebaseError>, FirebaseError> {
+        let options = ApiQueryOptions::from_query(self, query);
+        if options.offset.is_some() {
+            return Err(anyhow!("Listening to a query with an offset is not supported").into());
+        }
+        // Snapshots are ordered by document path, which would hide the order
+        if !options.order_by.is_empty() {
+            return Err(anyhow!("Listening to an ordered query is not supported").into());
+        }
+
+        let parent = options.parent.clone();
+        let structured_query = self.structured_query_from_options(options)?;
+        let watch = Watch::query(parent, structured_query);
+
+        Ok(self.listen_query_with(watch))
+    }
+
+    /// Resumes listening to a query where a previous listener left off, e.g.
+    /// before the service was restarted. Only changes since the checkpoint
+    /// are reported, so nothing is yielded until the results change, and the
+    /// results aren't read again. See [`ListenCheckpoint`] and
+    /// [`resume_listen_document`](Self::resume_listen_document).
+    ///
+    /// Returns an error if the checkpoint is from a document listener.
+    pub fn resume_listen_query<T: DeserializeOwned + Send + 'static>(
+        &self,
+        checkpoint: ListenCheckpoint,
+    ) -> Result<FirebaseStream<'static, QuerySnapshot<T>, FirebaseError>, FirebaseError> {
+        if checkpoint.is_document_target() {
+            return Err(anyhow!("The checkpoint is not from a query listener").into());
+        }
+
+        Ok(self.listen_query_with(Watch::resume(checkpoint)))
+    }
+
+    fn listen_query_with<T: DeserializeOwned + Send + 'static>(
+        &self,
+        watch: Watch,
+    ) -> FirebaseStream<'static, QuerySnapshot<T>, FirebaseError> {
+        self.listen_raw(watch)
+            .map(|snapshot| {
+                let RawSnapshot {
+                    previous,
+                    current,
+                    checkpoint,
+                } = snapshot?;
+
+                let changes = query_changes(previous.as_deref().unwrap_or_default(), &current)
+                    .into_iter()
+                    .map(|(kind, document, old_index, new_index)| {
+                        Ok(DocumentChange {
+                            kind,
+                            document: into_firestore_document::<T>(document)?,
+                            old_index,
+                            new_index,
+                        })
+                    })
+                    .collect::<Result<_, FirebaseError>>()?;
+                let documents = current
+                    .into_iter()
+                    .map(into_firestore_document::<T>)
+                    .collect::<Result<_, _>>()?;
+
+                Ok(QuerySnapshot {
+                    documents,
+                    changes,
+                    checkpoint,
+                })
+            })
+            .boxed()
+    }
+
+    /// Listens to the target of the given watch, yielding a snapshot of its
+    /// documents whenever they change. Reconnects with backoff on transient
+    /// errors.
+    fn listen_raw(&self, watch: Watch) -> FirebaseStream<'static, RawSnapshot, FirebaseError> {
+        struct ListenState {
+            client: FirestoreClient,
+            watch: Watch,
+            responses: Option<tonic::Streaming<ListenResponse>>,
+            /// The number of reconnection attempts since the last response.
+            attempt: u32,
+            done: bool,
+        }
+
+        let state = ListenState {
+            client: self.clone(),
+            watch,
+            responses: None,
+            attempt: 0,
+            done: false,
+        };
+
+        futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if state.done {
+                    return None;
+                }
+
+                let responses = match &mut state.responses {
+                    Some(responses) => responses,
+                    None => {
+                        if state.attempt > 0 {
+                            tokio::time::sleep(retry_delay(state.attempt)).await;
+                        }
+
+                        let request = state.watch.add_target_request(state.client.database_path());
+                        match state.client.open_listen(request).await {
+                            Ok(responses) => state.responses = Some(responses),
+                            Err(status) if is_transient(status.code()) => {
+                                tracing::debug!("Failed to listen, retrying: {}", status);
+                                state.attempt += 1;
+                            }
+                            Err(status) => {
+                                state.done = true;
+                                return Some((Err(anyhow!(status).into()), state));
+                            }
+                        }
+                        continue;
+                    }
+                };
+
+                let response = match responses.message().await {
+                    Ok(Some(response)) => response,
+                    Ok(None) => {
+                        tracing::debug!("Listen stream ended, reconnecting");
+                        state.responses = None;
+                        state.watch.reconnect();
+                        state.attempt += 1;
+                        continue;
+                    }
+                    Err(status) if is_transient(status.code()) => {
+                        tracing::debug!("Listen stream failed, reconnecting: {}", status);
+                        state.responses = None;
+                        state.watch.reconnect();
+                        state.attempt += 1;
+                        continue;
+                    }
+                    Err(status) => {
+                        state.done = true;
+                        return Some((Err(anyhow!(status).into()), state));
+                    }
+                };
+
+                state.attempt = 0;
+                if let Some(listen_response::ResponseType::DocumentChange(_)) =
+                    &response.response_type
+                {
+                    state.client.stats.record_reads(1);
+                }
+
+                match state.watch.handle(response) {
+                    WatchEvent::None => {}
+                    WatchEvent::Snapshot(snapshot) => return Some((Ok(snapshot), state)),
+                    WatchEvent::Reset => state.responses = None,
+                    WatchEvent::Error(err) => {
+                        state.done = true;
+                        return Some((Err(err), state));
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Opens a `Listen` stream that sends the given request and then stays
+    /// open until the responses are dropped.
+    async fn open_listen(
+        &mut self,
+        request: ListenRequest,
+    ) -> Result<tonic::Streaming<ListenResponse>, Status> {
+        let requests =
+            futures::stream::once(future::ready(request)).chain(futures::stream::pending());
+
+        let _permit = self.begin_rpc(0).await;
+        Ok(self.client.listen(requests).await?.into_inner())
     }
 
     /// Fetch all documents from any collection with the given name.
@@ -1121,10 +3626,8 @@ impl FirestoreClient {
         self.query_internal(ApiQueryOptions {
             parent: self.root_resource_path.clone(),
             collection_name: collection_name.into(),
-            filter: None,
-            limit: None,
-            offset: None,
             should_search_descendants: true,
+            ..Default::default()
         })
         .await
     }
@@ -1213,9 +3716,8 @@ impl FirestoreClient {
             parent: self.root_resource_path.clone(),
             collection_name: collection_name.into(),
             filter: Some(filter),
-            limit: None,
-            offset: None,
             should_search_descendants: true,
+            ..Default::default()
         })
         .await
     }
@@ -1302,9 +3804,8 @@ impl FirestoreClient {
             parent: self.root_resource_path.clone(),
             collection_name: collection_name.into(),
             filter: Some(filter),
-            limit: None,
-            offset: None,
             should_search_descendants: true,
+            ..Default::default()
         })
         .await
     }
@@ -1369,35 +3870,426 @@ impl FirestoreClient {
         self.query_internal(ApiQueryOptions {
             parent,
             collection_name,
-            filter: None,
-            limit: None,
-            offset: None,
-            should_search_descendants: false,
+            ..Default::default()
         })
         .await
     }
 
-    pub async fn run_query<'de, 'a, T: Deserialize<'de> + 'a>(
+    /// Run a query, returning a [`Stream`](futures::stream::Stream) of the
+    /// results, which allows you to process results as they are coming in.
+    ///
+    /// The query is built fluently from a [`CollectionReference`], e.g.
+    /// `collection("pizzas").with_filter(..).order_by(..).with_limit(..)`, or
+    /// from a [`collection_group`](super::collection_group). A plain
+    /// collection reference returns all documents of the collection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use fireplace::firestore::collection;
+    /// # use serde::{Deserialize, Serialize};
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// #
+    /// use fireplace::firestore::query::{filter, ArrayContains, EqualTo};
+    /// use futures::TryStreamExt;
+    ///
+    /// #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+    /// struct Pizza {
+    ///     name: String,
+    ///     toppings: Vec<String>,
+    /// }
+    ///
+    /// // Instantiate our example pizzas
+    /// let pepperoni = Pizza {
+    ///     name: "Pepperoni".into(),
+    ///     toppings: vec!["pepperoni".into(), "cheese".into()],
+    /// };
+    /// let hawaii = Pizza {
+    ///     name: "Hawaii".into(),
+    ///     toppings: vec!["pineapple".into(), "ham".into(), "cheese".into()],
+    /// };
+    ///
+    /// // Create the pizzas in the database
+    /// client
+    ///     .set_document(&collection("pizzas").doc("pepperoni"), &pepperoni)
+    ///     .await?;
+    /// client
+    ///     .set_document(&collection("pizzas").doc("hawaii"), &hawaii)
+    ///     .await?;
+    ///
+    /// // Query for pizzas whose name field is "Hawaii"
+    /// let hawaii_results: Vec<Pizza> = client
+    ///     .run_query(collection("pizzas").with_filter(filter("name", EqualTo("Hawaii"))))
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// // We expect a single search hit - the hawaii pizza.
+    /// assert_eq!(hawaii_results, vec![hawaii.clone()]);
+    ///
+    /// // Query for pizzas that have a "cheese" entry in the toppings list.
+    /// let mut cheese_results: Vec<Pizza> = client
+    ///     .run_query(
+    ///         collection("pizzas").with_filter(filter("toppings", ArrayContains("cheese"))),
+    ///     )
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// // We don't have a guaranteed ordering of the query results, so we sort
+    /// // them by name to make sure our equality check works.
+    /// cheese_results.sort_by(|a, b| a.name.cmp(&b.name));
+    ///
+    /// // We expect both pizzas to be found
+    /// assert_eq!(cheese_results, vec![hawaii, pepperoni]);
+    ///
+    /// // Query for pizzas with the name "pasta salad".
+    /// let mut pasta_salad_results: Vec<Pizza> = client
+    ///     .run_query(collection("pizzas").with_filter(filter("name", EqualTo("pasta salad"))))
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// // We expect no results
+    /// assert_eq!(pasta_salad_results, vec![]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_query<'de, 'a, T: Deserialize<'de> + 'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<FirebaseStream<T, FirebaseError>, FirebaseError> {
+        let options = ApiQueryOptions::from_query(self, query);
+        self.query_internal(options).await
+    }
+
+    /// The same as [`run_query`](Self::run_query), but only returns the first
+    /// result. The query's limit is replaced with a limit of one, so only a
+    /// single document is read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use fireplace::firestore::collection;
+    /// # use serde::{Deserialize, Serialize};
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// #
+    /// use fireplace::firestore::query::{filter, EqualTo};
+    ///
+    /// #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+    /// struct Pizza {
+    ///     name: String,
+    /// }
+    ///
+    /// let margherita = Pizza {
+    ///     name: "Margherita".into(),
+    /// };
+    ///
+    /// client
+    ///     .set_document(&collection("pizzas").doc("margherita"), &margherita)
+    ///     .await?;
+    ///
+    /// // Query for the Margherita pizza by name
+    /// let mut margherita_result: Option<Pizza> = client
+    ///     .run_query_one(collection("pizzas").with_filter(filter("name", EqualTo("Margherita"))))
+    ///     .await?;
+    ///
+    /// // We expect a single search hit - the margherita pizza.
+    /// assert_eq!(margherita_result, Some(margherita.clone()));
+    ///
+    /// // Query for pizzas with the name "pasta salad".
+    /// let mut pasta_salad_result: Option<Pizza> = client
+    ///     .run_query_one(collection("pizzas").with_filter(filter("name", EqualTo("pasta salad"))))
+    ///     .await?;
+    ///
+    /// // We expect no results
+    /// assert_eq!(pasta_salad_result, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_query_one<'de, 'a, T: Deserialize<'de> + 'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<Option<T>, FirebaseError> {
+        let mut options = ApiQueryOptions::from_query(self, query);
+        options.limit = Some(1);
+
+        let mut stream = self.query_internal(options).await?;
+        stream.try_next().await
+    }
+
+    pub async fn run_query_with_metadata<'de, 'a, T: Deserialize<'de> + 'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<FirebaseStream<FirestoreDocument<T>, FirebaseError>, FirebaseError> {
+        let options = ApiQueryOptions::from_query(self, query);
+        self.query_internal_with_metadata(options).await
+    }
+
+    /// Counts the number of documents that would be returned by the given query.
+    ///
+    /// The counting itself is done server-side by Firestore, so using this
+    /// function will be more efficient than executing the query and counting
+    /// how many documents were returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{
+    ///     collection, collection_group,
+    ///     query::{filter, EqualTo},
+    /// };
+    ///
+    /// let landmarks = vec![
+    ///     (
+    ///         ("SF", "golden-gate"),
+    ///         serde_json::json!({ "name": "Golden Gate Bridge", "type": "bridge" }),
+    ///     ),
+    ///     (
+    ///         ("SF", "legion-honor"),
+    ///         serde_json::json!({ "name": "Legion of Honor", "type": "museum" }),
+    ///     ),
+    ///     (
+    ///         ("TOK", "national-science-museum"),
+    ///         serde_json::json!({ "name": "National Museum of Nature and Science", "type": "museum" }),
+    ///     ),
+    /// ];
+    ///
+    /// for ((city, landmark_id), landmark_data) in landmarks {
+    ///     client
+    ///         .set_document(
+    ///             &collection("cities")
+    ///                 .doc(city)
+    ///                 .collection("landmarks")
+    ///                 .doc(landmark_id),
+    ///             &landmark_data,
+    ///         )
+    ///         .await?;
+    /// }
+    ///
+    /// let number_of_museums = client
+    ///     .count(collection_group("landmarks").with_filter(filter("type", EqualTo("museum"))))
+    ///     .await?;
+    ///
+    /// assert_eq!(number_of_museums, 2);
+    ///
+    /// let number_of_landmarks_in_san_francisco = client
+    ///     .count(collection("cities").doc("SF").collection("landmarks"))
+    ///     .await?;
+    ///
+    /// assert_eq!(number_of_landmarks_in_san_francisco, 2);
+    ///
+    /// let number_of_museums_in_san_francisco = client
+    ///     .count(
+    ///         collection("cities")
+    ///             .doc("SF")
+    ///             .collection("landmarks")
+    ///             .with_filter(filter("type", EqualTo("museum"))),
+    ///     )
+    ///     .await?;
+    ///
+    /// assert_eq!(number_of_museums_in_san_francisco, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn count<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<u64, FirebaseError> {
+        let options = ApiQueryOptions::from_query(self, query);
+
+        self.count_internal(options, None).await
+    }
+
+    /// Counts the documents returned by the query, but stops counting once
+    /// `up_to` documents have been counted. Cheaper than [`count`](Self::count)
+    /// when you only need to know whether there are at least `up_to` results,
+    /// e.g. to check that a collection isn't empty, since Firestore bills
+    /// aggregations by the number of index entries read.
+    ///
+    /// Returns an error if `up_to` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use fireplace::firestore::collection;
+    ///
+    /// let landmarks = collection("cities").doc("SF").collection("landmarks");
+    ///
+    /// let has_landmarks = client.count_up_to(landmarks.clone(), 1).await? > 0;
+    /// assert!(has_landmarks);
+    ///
+    /// let at_most_ten = client.count_up_to(landmarks, 10).await?;
+    /// assert_eq!(at_most_ten, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn count_up_to<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+        up_to: u64,
+    ) -> Result<u64, FirebaseError> {
+        if up_to == 0 {
+            return Err(anyhow!("The count limit must be greater than zero").into());
+        }
+
+        let options = ApiQueryOptions::from_query(self, query);
+        let up_to = i64::try_from(up_to).unwrap_or(i64::MAX);
+
+        self.count_internal(options, Some(up_to)).await
+    }
+
+    async fn count_internal<'a>(
+        &'a mut self,
+        options: ApiQueryOptions<'a>,
+        up_to: Option<i64>,
+    ) -> Result<u64, FirebaseError> {
+        let mut results = self
+            .run_aggregations(
+                options,
+                vec![structured_aggregation_query::Aggregation {
+                    alias: "doc_count".to_string(),
+                    operator: Some(aggregation::Operator::Count(aggregation::Count { up_to })),
+                }],
+            )
+            .await?;
+
+        let doc_count_value = results
+            .remove("doc_count")
+            .context("Failed to get count from response")?;
+
+        let doc_count = match doc_count_value.value_type {
+            Some(ValueType::IntegerValue(doc_count)) if doc_count >= 0 => doc_count as u64,
+            ref v => {
+                return Err(FirebaseError::Other(anyhow::anyhow!(
+                    "Unexpected value type for count: {v:?}"
+                )))
+            }
+        };
+
+        Ok(doc_count)
+    }
+
+    /// Sums the integer field with the given name across the documents
+    /// returned by the query. Documents where the field isn't an integer are
+    /// ignored.
+    pub(crate) async fn sum_integers<'a>(
         &'a mut self,
         query: impl FirestoreQuery<'a>,
-    ) -> Result<FirebaseStream<T, FirebaseError>, FirebaseError> {
+        field: &str,
+    ) -> Result<i64, FirebaseError> {
         let options = ApiQueryOptions::from_query(self, query);
-        self.query_internal(options).await
+
+        let mut results = self
+            .run_aggregations(
+                options,
+                vec![structured_aggregation_query::Aggregation {
+                    alias: "sum".to_string(),
+                    operator: Some(aggregation::Operator::Sum(aggregation::Sum {
+                        field: Some(FieldReference {
+                            field_path: field.to_string(),
+                        }),
+                    })),
+                }],
+            )
+            .await?;
+
+        let sum_value = results
+            .remove("sum")
+            .context("Failed to get sum from response")?;
+
+        match sum_value.value_type {
+            Some(ValueType::IntegerValue(sum)) => Ok(sum),
+            // Firestore returns a double if the sum overflows, which we treat
+            // as an error along with any other unexpected type
+            ref v => Err(FirebaseError::Other(anyhow::anyhow!(
+                "Unexpected value type for sum: {v:?}"
+            ))),
+        }
     }
 
-    pub async fn run_query_with_metadata<'de, 'a, T: Deserialize<'de> + 'a>(
+    /// Averages the numeric field with the given name across the documents
+    /// returned by the query. Documents where the field isn't a number are
+    /// ignored, and `None` is returned if no document has a numeric value.
+    ///
+    /// Like [`count`](Self::count), the aggregation is done server-side. The
+    /// average is always a double, even if the values are integers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use ulid::Ulid;
+    ///
+    /// let ratings = collection("products").doc(Ulid::new()).collection("ratings");
+    /// for stars in [3, 4, 4] {
+    ///     client
+    ///         .set_document(&ratings.doc(Ulid::new()), &serde_json::json!({ "stars": stars }))
+    ///         .await?;
+    /// }
+    ///
+    /// let average = client.avg(ratings.clone(), "stars").await?;
+    /// assert!((average.unwrap() - 11.0 / 3.0).abs() < 1e-9);
+    ///
+    /// let no_values = client.avg(ratings, "missing").await?;
+    /// assert_eq!(no_values, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn avg<'a>(
         &'a mut self,
         query: impl FirestoreQuery<'a>,
-    ) -> Result<FirebaseStream<FirestoreDocument<T>, FirebaseError>, FirebaseError> {
+        field: &str,
+    ) -> Result<Option<f64>, FirebaseError> {
         let options = ApiQueryOptions::from_query(self, query);
-        self.query_internal_with_metadata(options).await
+
+        let mut results = self
+            .run_aggregations(
+                options,
+                vec![structured_aggregation_query::Aggregation {
+                    alias: "avg".to_string(),
+                    operator: Some(aggregation::Operator::Avg(aggregation::Avg {
+                        field: Some(FieldReference {
+                            field_path: field.to_string(),
+                        }),
+                    })),
+                }],
+            )
+            .await?;
+
+        let avg_value = results
+            .remove("avg")
+            .context("Failed to get average from response")?;
+
+        match avg_value.value_type {
+            Some(ValueType::DoubleValue(avg)) => Ok(Some(avg)),
+            // The average of no values is null rather than zero
+            Some(ValueType::NullValue(_)) => Ok(None),
+            ref v => Err(FirebaseError::Other(anyhow::anyhow!(
+                "Unexpected value type for average: {v:?}"
+            ))),
+        }
     }
 
-    /// Counts the number of documents that would be returned by the given query.
-    ///
-    /// The counting itself is done server-side by Firestore, so using this
-    /// function will be more efficient than executing the query and counting
-    /// how many documents were returned.
+    /// Runs several aggregations over the results of a query in a single
+    /// request, returning their values by alias. Firestore allows up to
+    /// [`MAX_AGGREGATIONS`](super::aggregation::MAX_AGGREGATIONS)
+    /// aggregations per request, with unique aliases, which is checked before
+    /// the request is sent.
     ///
     /// # Examples
     ///
@@ -1406,75 +4298,60 @@ impl FirestoreClient {
     /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
     /// use fireplace::firestore::{
-    ///     collection, collection_group,
-    ///     query::{filter, EqualTo},
+    ///     aggregation::{avg, count, sum},
+    ///     collection,
     /// };
+    /// use ulid::Ulid;
     ///
-    /// let landmarks = vec![
-    ///     (
-    ///         ("SF", "golden-gate"),
-    ///         serde_json::json!({ "name": "Golden Gate Bridge", "type": "bridge" }),
-    ///     ),
-    ///     (
-    ///         ("SF", "legion-honor"),
-    ///         serde_json::json!({ "name": "Legion of Honor", "type": "museum" }),
-    ///     ),
-    ///     (
-    ///         ("TOK", "national-science-museum"),
-    ///         serde_json::json!({ "name": "National Museum of Nature and Science", "type": "museum" }),
-    ///     ),
-    /// ];
-    ///
-    /// for ((city, landmark_id), landmark_data) in landmarks {
+    /// let orders = collection("customers").doc(Ulid::new()).collection("orders");
+    /// for price in [10, 20, 60] {
     ///     client
-    ///         .set_document(
-    ///             &collection("cities")
-    ///                 .doc(city)
-    ///                 .collection("landmarks")
-    ///                 .doc(landmark_id),
-    ///             &landmark_data,
-    ///         )
+    ///         .set_document(&orders.doc(Ulid::new()), &serde_json::json!({ "price": price }))
     ///         .await?;
     /// }
     ///
-    /// let number_of_museums = client
-    ///     .count(collection_group("landmarks").with_filter(filter("type", EqualTo("museum"))))
-    ///     .await?;
-    ///
-    /// assert_eq!(number_of_museums, 2);
-    ///
-    /// let number_of_landmarks_in_san_francisco = client
-    ///     .count(collection("cities").doc("SF").collection("landmarks"))
-    ///     .await?;
-    ///
-    /// assert_eq!(number_of_landmarks_in_san_francisco, 2);
-    ///
-    /// let number_of_museums_in_san_francisco = client
-    ///     .count(
-    ///         collection("cities")
-    ///             .doc("SF")
-    ///             .collection("landmarks")
-    ///             .with_filter(filter("type", EqualTo("museum"))),
+    /// let stats = client
+    ///     .aggregate(
+    ///         orders,
+    ///         [count().alias("n"), sum("price").alias("total"), avg("price")],
     ///     )
     ///     .await?;
     ///
-    /// assert_eq!(number_of_museums_in_san_francisco, 1);
+    /// assert_eq!(stats.count("n"), Some(3));
+    /// assert_eq!(stats.f64("total"), Some(90.0));
+    /// assert_eq!(stats.f64("avg_price"), Some(30.0));
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn count<'a>(
+    pub async fn aggregate<'a>(
         &'a mut self,
         query: impl FirestoreQuery<'a>,
-    ) -> Result<u64, FirebaseError> {
+        aggregations: impl IntoIterator<Item = Aggregation>,
+    ) -> Result<AggregationResult, FirebaseError> {
+        let aggregations: Vec<Aggregation> = aggregations.into_iter().collect();
+        validate_aggregations(&aggregations)?;
+
         let options = ApiQueryOptions::from_query(self, query);
+        let values = self
+            .run_aggregations(
+                options,
+                aggregations
+                    .into_iter()
+                    .map(Aggregation::into_grpc)
+                    .collect(),
+            )
+            .await?;
 
-        self.count_internal(options).await
+        AggregationResult::from_values(values)
     }
 
-    async fn count_internal<'a>(
+    /// Runs the given aggregations over the results of a query, returning the
+    /// aggregated values by their alias.
+    async fn run_aggregations<'a>(
         &'a mut self,
         options: ApiQueryOptions<'a>,
-    ) -> Result<u64, FirebaseError> {
+        aggregations: Vec<structured_aggregation_query::Aggregation>,
+    ) -> Result<HashMap<String, Value>, FirebaseError> {
         let parent = options.parent.clone();
         let structured_query = self.structured_query_from_options(options)?;
 
@@ -1486,50 +4363,125 @@ impl FirestoreClient {
                         query_type: Some(structured_aggregation_query::QueryType::StructuredQuery(
                             structured_query,
                         )),
-                        aggregations: vec![structured_aggregation_query::Aggregation {
-                            alias: "doc_count".to_string(),
-                            operator: Some(aggregation::Operator::Count(aggregation::Count {
-                                up_to: None,
-                            })),
-                        }],
+                        aggregations,
                     },
                 ),
             ),
-            consistency_selector: None,
+            consistency_selector: self
+                .read_consistency
+                .as_ref()
+                .map(ReadConsistency::run_aggregation_query_selector),
         };
 
+        let _permit = self.begin_rpc(0).await;
         let res = self
             .client
             .run_aggregation_query(aggregation_request)
             .await
-            .context("Failed to run count aggregation query")?;
+            .context("Failed to run aggregation query")?;
 
-        let count = res
+        let result = res
             .into_inner()
             .filter_map(|res| future::ready(res.map(|inner| inner.result).transpose()))
-            .map(|agg_res| -> Result<u64, FirebaseError> {
-                let agg = agg_res.map_err(|e| anyhow!(e))?;
-                let doc_count_value = agg
-                    .aggregate_fields
-                    .get("doc_count")
-                    .context("Failed to get count from response")?;
-
-                let doc_count = match doc_count_value.value_type {
-                    Some(ValueType::IntegerValue(doc_count)) if doc_count >= 0 => doc_count as u64,
-                    ref v => {
-                        return Err(FirebaseError::Other(anyhow::anyhow!(
-                            "Unexpected value type for count: {v:?}"
-                        )))
-                    }
-                };
-
-                Ok(doc_count)
-            })
             .next()
             .await
-            .context("No count returned from aggregation query")??;
+            .context("No result returned from aggregation query")?
+            .map_err(|e| anyhow!(e))?;
+
+        Ok(result.aggregate_fields)
+    }
+
+    /// Lists the IDs of the root collections of the database, fetching all
+    /// pages of results. Useful for discovering collections in admin tooling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    ///
+    /// client
+    ///     .set_document(
+    ///         &collection("cities").doc("SF"),
+    ///         &serde_json::json!({ "name": "San Francisco" }),
+    ///     )
+    ///     .await?;
+    ///
+    /// let collection_ids = client.list_collection_ids().await?;
+    /// assert!(collection_ids.contains(&"cities".to_string()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_collection_ids(&mut self) -> Result<Vec<String>, FirebaseError> {
+        let root = self.root_resource_path.clone();
+        self.list_collection_ids_at(root).await
+    }
+
+    /// Lists the IDs of the subcollections directly under the given document,
+    /// fetching all pages of results. The document itself doesn't need to
+    /// exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use fireplace::firestore::collection;
+    ///
+    /// let collection_ids = client
+    ///     .list_subcollection_ids(&collection("cities").doc("SF"))
+    ///     .await?;
+    ///
+    /// assert_eq!(collection_ids, vec!["landmarks".to_string()]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_subcollection_ids(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<Vec<String>, FirebaseError> {
+        let name = self.get_name_with(doc_ref);
+        self.list_collection_ids_at(name).await
+    }
+
+    /// Lists the IDs of the collections directly under the given parent, which
+    /// is either a document resource name or the root resource path.
+    pub(crate) async fn list_collection_ids_at(
+        &mut self,
+        parent: String,
+    ) -> Result<Vec<String>, FirebaseError> {
+        let mut collection_ids = Vec::new();
+        let mut page_token = String::new();
+
+        loop {
+            let request = ListCollectionIdsRequest {
+                parent: parent.clone(),
+                page_size: LIST_COLLECTION_IDS_PAGE_SIZE,
+                page_token,
+                ..Default::default()
+            };
+
+            let _permit = self.begin_rpc(0).await;
+            let res = self
+                .client
+                .list_collection_ids(request)
+                .await
+                .context("Failed to list collection IDs")?
+                .into_inner();
+
+            collection_ids.extend(res.collection_ids);
+
+            if res.next_page_token.is_empty() {
+                break;
+            }
+            page_token = res.next_page_token;
+        }
 
-        Ok(count)
+        Ok(collection_ids)
     }
 
     fn structured_query_from_options(
@@ -1541,16 +4493,61 @@ impl FirestoreClient {
             .map(|f| try_into_grpc_filter(f, &self.root_resource_path))
             .transpose()?;
 
+        if options.start_after.is_some()
+            && (!options.order_by.is_empty() || options.start.is_some() || options.end.is_some())
+        {
+            return Err(anyhow!(
+                "Paging by document name can't be combined with ordering or cursors"
+            )
+            .into());
+        }
+
+        if options.limit_to_last && options.order_by.is_empty() {
+            return Err(anyhow!("Queries with limit_to_last must be ordered").into());
+        }
+
+        // Paging by document name requires the results to be ordered by it
+        let (order_by, start_at, end_at) = match options.start_after {
+            Some(name) => (
+                vec![Order {
+                    field: Some(FieldReference {
+                        field_path: DOCUMENT_NAME_FIELD.to_string(),
+                    }),
+                    direction: Direction::Ascending as i32,
+                }],
+                Some(GrpcCursor {
+                    values: vec![Value {
+                        value_type: Some(ValueType::ReferenceValue(name)),
+                    }],
+                    before: false,
+                }),
+                None,
+            ),
+            None => grpc_order_and_cursors(
+                &self.root_resource_path,
+                options.order_by,
+                options.start,
+                options.end,
+                options.limit_to_last,
+            )?,
+        };
+
+        let select = options.names_only.then(|| Projection {
+            fields: vec![FieldReference {
+                field_path: DOCUMENT_NAME_FIELD.to_string(),
+            }],
+        });
+
         let structured_query = StructuredQuery {
-            select: None,
+            select,
             from: vec![CollectionSelector {
                 collection_id: options.collection_name,
                 all_descendants: options.should_search_descendants,
             }],
             r#where: grpc_filter,
-            order_by: vec![],
-            start_at: None,
-            end_at: None,
+            order_by,
+            start_at,
+            end_at,
             offset: options.offset.unwrap_or(0),
             limit: options.limit,
         };
@@ -1575,6 +4572,25 @@ impl FirestoreClient {
         (parent, name)
     }
 
+    /// Records an RPC with the given number of writes in the stats, and waits
+    /// until the rate limit allows it to be sent. The returned permit should
+    /// be held until the RPC has completed.
+    async fn begin_rpc(&self, writes: u32) -> RpcPermit {
+        self.stats.record_rpc();
+        self.rate_limiter.acquire(writes).await
+    }
+
+    /// The resource name of the database, e.g.
+    /// `projects/my-project/databases/(default)`, as used in requests sent
+    /// with the [`raw`](Self::raw) client. Document names are the database
+    /// name followed by `/documents/` and the path of the document.
+    pub fn database_path(&self) -> String {
+        format!(
+            "projects/{}/databases/{}",
+            self.project_id, self.options.database_id
+        )
+    }
+
     pub(crate) fn root_resource_path(&self) -> &str {
         &self.root_resource_path
     }
@@ -1584,6 +4600,64 @@ impl FirestoreClient {
     }
 }
 
+/// A collection group query ordered by document name, optionally limited to
+/// the documents from `start_at` and before `end_before`, given as resource
+/// names. This is the only shape of query that Firestore can partition.
+fn partition_structured_query(
+    collection_id: &str,
+    start_at: Option<String>,
+    end_before: Option<String>,
+) -> StructuredQuery {
+    let name_cursor = |name: String| GrpcCursor {
+        values: vec![Value {
+            value_type: Some(ValueType::ReferenceValue(name)),
+        }],
+        // Both cursors are positioned before the named document
+        before: true,
+    };
+
+    StructuredQuery {
+        from: vec![CollectionSelector {
+            collection_id: collection_id.to_string(),
+            all_descendants: true,
+        }],
+        order_by: vec![Order {
+            field: Some(FieldReference {
+                field_path: DOCUMENT_NAME_FIELD.to_string(),
+            }),
+            direction: Direction::Ascending as i32,
+        }],
+        start_at: start_at.map(name_cursor),
+        end_at: end_before.map(name_cursor),
+        ..Default::default()
+    }
+}
+
+/// The document that a cursor returned by a partition query points at.
+fn split_point_document(cursor: GrpcCursor) -> Result<DocumentReference, FirebaseError> {
+    match cursor.values.into_iter().next().and_then(|v| v.value_type) {
+        Some(ValueType::ReferenceValue(name)) => {
+            Ok(DocumentReference::try_from(strip_reference_prefix(&name))?)
+        }
+        _ => Err(anyhow!("Unexpected cursor in partition query response").into()),
+    }
+}
+
+/// Deserializes a document from the gRPC API along with its metadata.
+fn into_firestore_document<'de, T: Deserialize<'de>>(
+    doc: Document,
+) -> Result<FirestoreDocument<T>, FirebaseError> {
+    Ok(FirestoreDocument {
+        data: deserialize_firestore_document_fields::<T>(doc.fields)
+            .map_err(|e| serde_err_with_doc(e, &doc.name))?,
+        id: doc.name,
+        create_time: doc.create_time.as_ref().map(|t| t.seconds),
+        update_time: doc.update_time.as_ref().map(|t| t.seconds),
+        create_timestamp: doc.create_time.as_ref().map(system_time),
+        update_timestamp: doc.update_time.as_ref().map(system_time),
+    })
+}
+
 fn serde_err_with_doc(err: crate::firestore::serde::Error, doc: impl AsRef<str>) -> FirebaseError {
     FirebaseError::FirestoreSerdeError {
         source: err,
@@ -1597,6 +4671,12 @@ fn document_exists_precondition() -> Option<Precondition> {
     })
 }
 
+/// `UpdateDocument` responds with the written document, whose update time is
+/// the time of the write.
+fn updated_document_result(doc: Document) -> WriteResult {
+    doc.update_time.into()
+}
+
 fn not_found_err() -> fn(Status) -> FirebaseError {
     |err| {
         if err.code() == tonic::Code::NotFound {
@@ -1607,11 +4687,347 @@ fn not_found_err() -> fn(Status) -> FirebaseError {
     }
 }
 
+fn write_status_err(err: Status) -> FirebaseError {
+    match err.code() {
+        tonic::Code::NotFound => FirebaseError::DocumentNotfound(err.message().to_string()),
+        tonic::Code::AlreadyExists => {
+            FirebaseError::DocumentAlreadyExists(err.message().to_string())
+        }
+        tonic::Code::FailedPrecondition => FirebaseError::Conflict(err.message().to_string()),
+        _ => anyhow!(err).into(),
+    }
+}
+
+/// Converts the ordering and cursors of a query to gRPC. Queries with
+/// `limit_to_last` are run in the reverse order, with the cursors swapped.
+fn grpc_order_and_cursors(
+    root_resource_path: &str,
+    order_by: Vec<OrderBy>,
+    start: Option<QueryBound>,
+    end: Option<QueryBound>,
+    limit_to_last: bool,
+) -> Result<(Vec<Order>, Option<GrpcCursor>, Option<GrpcCursor>), FirebaseError> {
+    let mut order_by: Vec<Order> = order_by.into_iter().map(into_grpc_order).collect();
+
+    // The last results are fetched by running the query in the reverse
+    // order, with the cursors swapped
+    let (start, end) = if limit_to_last {
+        for order in &mut order_by {
+            order.direction = match Direction::from_i32(order.direction) {
+                Some(Direction::Descending) => Direction::Ascending as i32,
+                _ => Direction::Descending as i32,
+            };
+        }
+        (end, start)
+    } else {
+        (start, end)
+    };
+
+    // A start cursor is positioned before its document when it includes it,
+    // an end cursor when it doesn't
+    let start_at = start
+        .map(|start| {
+            grpc_cursor(
+                root_resource_path,
+                start.cursor,
+                start.inclusive,
+                &mut order_by,
+            )
+        })
+        .transpose()?;
+    let end_at = end
+        .map(|end| {
+            grpc_cursor(
+                root_resource_path,
+                end.cursor,
+                !end.inclusive,
+                &mut order_by,
+            )
+        })
+        .transpose()?;
+
+    Ok((order_by, start_at, end_at))
+}
+
+/// Converts a cursor of a query to a gRPC cursor. If the cursor has a
+/// document, the results are also ordered by document name, in the direction
+/// of the last ordering, so the cursor can point at it.
+fn grpc_cursor(
+    root_resource_path: &str,
+    cursor: Cursor,
+    before: bool,
+    order_by: &mut Vec<Order>,
+) -> Result<GrpcCursor, FirebaseError> {
+    let is_name_order = |order: &Order| {
+        order.field.as_ref().map(|f| f.field_path.as_str()) == Some(DOCUMENT_NAME_FIELD)
+    };
+
+    // Each value is compared to the ordering at the same position, so values
+    // without an ordering would be rejected by Firestore
+    if cursor.values().len() > order_by.len() {
+        return Err(anyhow!(
+            "Cursor has {} values, but the query is only ordered by {} fields",
+            cursor.values().len(),
+            order_by.len()
+        )
+        .into());
+    }
+
+    // The document is compared to the document name, which comes after the
+    // ordered fields, so a cursor made with `start_after_doc` before
+    // `order_by` was called would be compared to the wrong fields
+    let field_orders = order_by
+        .iter()
+        .filter(|order| !is_name_order(order))
+        .count();
+    if cursor.document().is_some() && cursor.values().len() != field_orders {
+        return Err(anyhow!(
+            "Cursor at a document has {} values, but the query is ordered by {} fields; \
+             call start_after_doc after order_by",
+            cursor.values().len(),
+            field_orders
+        )
+        .into());
+    }
+
+    // Cursors are created without a client, so document references in them
+    // are relative to the database and must be made absolute
+    let mut values: Vec<Value> = cursor
+        .values()
+        .iter()
+        .cloned()
+        .map(|value| match value.value_type {
+            Some(ValueType::ReferenceValue(path)) if path.starts_with('/') => Value {
+                value_type: Some(ValueType::ReferenceValue(format!(
+                    "{}{}",
+                    root_resource_path, path
+                ))),
+            },
+            _ => value,
+        })
+        .collect();
+
+    if let Some(document) = cursor.document() {
+        values.push(Value {
+            value_type: Some(ValueType::ReferenceValue(format!(
+                "{}/{}",
+                root_resource_path, document
+            ))),
+        });
+
+        if !order_by.iter().any(is_name_order) {
+            let direction = order_by
+                .last()
+                .map(|order| order.direction)
+                .unwrap_or(Direction::Ascending as i32);
+            order_by.push(Order {
+                field: Some(FieldReference {
+                    field_path: DOCUMENT_NAME_FIELD.to_string(),
+                }),
+                direction,
+            });
+        }
+    }
+
+    Ok(GrpcCursor { values, before })
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn implements_send() {
         fn assert_send<T: Send>() {}
         assert_send::<super::FirestoreClient>();
     }
+
+    #[test]
+    fn routing_metadata_rejects_invalid_database_ids() {
+        let options =
+            |database_id: &str| FirestoreClientOptions::default().database_id(database_id);
+
+        assert!(routing_metadata("p", &options(DEFAULT_DATABASE_ID)).is_ok());
+        assert!(routing_metadata("p", &options("analytics")).is_ok());
+        assert!(routing_metadata("p", &options("bad\nid")).is_err());
+    }
+
+    #[test]
+    fn deserialize_firestore_document_from_json() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Landmark {
+            name: String,
+        }
+
+        let doc: FirestoreDocument<Landmark> = serde_json::from_value(serde_json::json!({
+            "id": "projects/p/databases/(default)/documents/landmarks/golden-gate",
+            "data": { "name": "Golden Gate Bridge" },
+            "create_time": 1663061252,
+        }))
+        .unwrap();
+
+        assert_eq!(
+            doc,
+            FirestoreDocument {
+                id: "projects/p/databases/(default)/documents/landmarks/golden-gate".to_string(),
+                data: Landmark {
+                    name: "Golden Gate Bridge".to_string()
+                },
+                create_time: Some(1663061252),
+                update_time: None,
+                create_timestamp: None,
+                update_timestamp: None,
+            }
+        );
+        assert_eq!(
+            doc.document_reference().unwrap(),
+            crate::firestore::collection("landmarks").doc("golden-gate")
+        );
+    }
+
+    #[test]
+    fn firestore_document_json_round_trip() {
+        let doc = FirestoreDocument {
+            id: "projects/p/databases/(default)/documents/greetings/first".to_string(),
+            data: serde_json::json!({ "message": "Hi Mom" }),
+            create_time: Some(1),
+            update_time: Some(2),
+            create_timestamp: Some(std::time::UNIX_EPOCH + std::time::Duration::new(1, 5)),
+            update_timestamp: Some(std::time::UNIX_EPOCH + std::time::Duration::new(2, 5)),
+        };
+
+        let json = serde_json::to_string(&doc).unwrap();
+        let rehydrated: FirestoreDocument<serde_json::Value> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(doc, rehydrated);
+    }
+
+    const ROOT: &str = "projects/p/databases/(default)/documents";
+
+    fn order_by(field: &str, direction: crate::firestore::query::Direction) -> OrderBy {
+        OrderBy {
+            field: field.to_string(),
+            direction,
+        }
+    }
+
+    fn field_paths(order_by: &[Order]) -> Vec<(&str, i32)> {
+        order_by
+            .iter()
+            .map(|order| {
+                let field = order.field.as_ref().unwrap();
+                (field.field_path.as_str(), order.direction)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn limit_to_last_reverses_ordering_and_swaps_cursors() {
+        use crate::firestore::query::Direction::{Ascending, Descending};
+
+        let (order_by, start_at, end_at) = grpc_order_and_cursors(
+            ROOT,
+            vec![
+                order_by("population", Descending),
+                order_by("name", Ascending),
+            ],
+            Some(QueryBound {
+                cursor: Cursor::new()
+                    .with_value(&100)
+                    .unwrap()
+                    .with_value(&"a")
+                    .unwrap(),
+                inclusive: true,
+            }),
+            Some(QueryBound {
+                cursor: Cursor::new()
+                    .with_value(&10)
+                    .unwrap()
+                    .with_value(&"z")
+                    .unwrap(),
+                inclusive: false,
+            }),
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            field_paths(&order_by),
+            vec![
+                ("population", Direction::Ascending as i32),
+                ("name", Direction::Descending as i32),
+            ]
+        );
+
+        // The exclusive end becomes a start after its values, and the
+        // inclusive start becomes an end that includes its values
+        let start_at = start_at.unwrap();
+        assert!(!start_at.before);
+        assert_eq!(
+            start_at.values[0].value_type,
+            Some(ValueType::IntegerValue(10))
+        );
+        let end_at = end_at.unwrap();
+        assert!(!end_at.before);
+        assert_eq!(
+            end_at.values[0].value_type,
+            Some(ValueType::IntegerValue(100))
+        );
+    }
+
+    #[test]
+    fn cursor_at_document_orders_by_document_name() {
+        use crate::firestore::query::Direction::Descending;
+
+        let cursor = Cursor::new()
+            .with_value(&100)
+            .unwrap()
+            .with_document(crate::firestore::collection("cities").doc("SF"));
+
+        let (order_by, start_at, _) = grpc_order_and_cursors(
+            ROOT,
+            vec![order_by("population", Descending)],
+            Some(QueryBound {
+                cursor,
+                inclusive: false,
+            }),
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            field_paths(&order_by),
+            vec![
+                ("population", Direction::Descending as i32),
+                (DOCUMENT_NAME_FIELD, Direction::Descending as i32),
+            ]
+        );
+        assert_eq!(
+            start_at.unwrap().values[1].value_type,
+            Some(ValueType::ReferenceValue(format!("{}/cities/SF", ROOT)))
+        );
+    }
+
+    #[test]
+    fn cursor_with_more_values_than_orderings_is_rejected() {
+        use crate::firestore::query::Direction::Ascending;
+
+        let result = grpc_order_and_cursors(
+            ROOT,
+            vec![order_by("population", Ascending)],
+            Some(QueryBound {
+                cursor: Cursor::new()
+                    .with_value(&1)
+                    .unwrap()
+                    .with_value(&2)
+                    .unwrap(),
+                inclusive: true,
+            }),
+            None,
+            false,
+        );
+
+        assert!(result.is_err());
+    }
 }
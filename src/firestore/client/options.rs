@@ -1,12 +1,37 @@
+use std::sync::Arc;
+
+use firestore_grpc::tonic::{Request, Status};
+
+use super::RateLimit;
+
+/// A user-provided interceptor that runs on every request. See
+/// [`FirestoreClientOptions::interceptor`].
+pub(crate) type UserInterceptor =
+    Arc<dyn Fn(Request<()>) -> Result<Request<()>, Status> + Send + Sync>;
+
+/// The ID of the database every project has.
+pub(crate) const DEFAULT_DATABASE_ID: &str = "(default)";
+
 #[derive(Clone)]
 pub struct FirestoreClientOptions {
     pub host_url: String,
+    /// The ID of the database to connect to. Defaults to `(default)`.
+    pub database_id: String,
+    pub collect_stats: bool,
+    pub rate_limit: RateLimit,
+    pub(crate) interceptors: Vec<UserInterceptor>,
+    pub(crate) gzip: bool,
 }
 
 impl Default for FirestoreClientOptions {
     fn default() -> Self {
         Self {
             host_url: "https://firestore.googleapis.com".to_string(),
+            database_id: DEFAULT_DATABASE_ID.to_string(),
+            collect_stats: false,
+            rate_limit: RateLimit::default(),
+            interceptors: vec![],
+            gzip: false,
         }
     }
 }
@@ -16,4 +41,118 @@ impl FirestoreClientOptions {
         self.host_url = host_url.into();
         self
     }
+
+    /// Connect to the database with the given ID instead of the `(default)`
+    /// database, e.g. one created with
+    /// [`FirestoreAdmin::create_database`](crate::firestore::admin::FirestoreAdmin::create_database).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fireplace::firestore::client::FirestoreClientOptions;
+    ///
+    /// let options = FirestoreClientOptions::default().database_id("analytics");
+    /// assert_eq!(options.database_id, "analytics");
+    /// ```
+    pub fn database_id(mut self, database_id: impl Into<String>) -> Self {
+        self.database_id = database_id.into();
+        self
+    }
+
+    /// Count the documents read, written and deleted by the client, as well
+    /// as the RPCs it issues. The counts can be retrieved with
+    /// [`FirestoreClient::stats`](super::FirestoreClient::stats).
+    pub fn collect_stats(mut self, collect_stats: bool) -> Self {
+        self.collect_stats = collect_stats;
+        self
+    }
+
+    /// Limit how fast the client may send requests to Firestore. See
+    /// [`RateLimit`].
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Add an interceptor that is run on every request, after the
+    /// authorization header and any
+    /// [`RequestMetadata`](super::RequestMetadata) have been added.
+    /// Interceptors run in the order they were added, and returning an error
+    /// fails the request with that status without sending it. Useful for e.g.
+    /// custom headers, deadline propagation or injecting failures in tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use firestore_grpc::tonic::Status;
+    /// use fireplace::firestore::client::FirestoreClientOptions;
+    ///
+    /// let options = FirestoreClientOptions::default()
+    ///     .interceptor(|mut req| {
+    ///         req.set_timeout(Duration::from_secs(5));
+    ///         Ok(req)
+    ///     })
+    ///     .interceptor(|req| {
+    ///         if std::env::var("CHAOS").is_ok() {
+    ///             return Err(Status::unavailable("chaos testing"));
+    ///         }
+    ///         Ok(req)
+    ///     });
+    /// ```
+    pub fn interceptor(
+        mut self,
+        interceptor: impl Fn(Request<()>) -> Result<Request<()>, Status> + Send + Sync + 'static,
+    ) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Compress requests with gzip and accept gzip-compressed responses.
+    /// This trades CPU time for less data on the wire, which pays off for
+    /// large documents sent across regions, where egress is billed per byte,
+    /// but costs latency for small documents on fast links. Run the
+    /// `compression` example against your own documents to measure the
+    /// tradeoff, and the `compression` benchmark for the CPU cost and the
+    /// compression ratio of documents of different shapes.
+    ///
+    /// Requires the `compression` feature.
+    #[cfg(feature = "compression")]
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+}
+
+/// Options for copying and moving documents with
+/// [`FirestoreClient::copy_document`](super::FirestoreClient::copy_document)
+/// and [`FirestoreClient::move_document`](super::FirestoreClient::move_document).
+///
+/// By default only the document itself is copied, and the copy fails if the
+/// destination already exists.
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    pub(crate) overwrite: bool,
+    pub(crate) recursive: bool,
+}
+
+impl CopyOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overwrite destination documents that already exist instead of failing.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Also copy the documents in all subcollections of the document, at any
+    /// depth. This includes documents in subcollections of documents that
+    /// don't exist themselves, though the missing documents aren't created.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
 }
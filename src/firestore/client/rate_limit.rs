@@ -0,0 +1,165 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Limits on how fast a [`FirestoreClient`](super::FirestoreClient) and its
+/// clones may send requests to Firestore. By default, nothing is limited.
+///
+/// This is useful for keeping batch jobs within Firestore's
+/// [guidance](https://firebase.google.com/docs/firestore/best-practices) of at
+/// most 10,000 writes per second per database, without writing any throttling
+/// code yourself.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::firestore::client::{FirestoreClientOptions, RateLimit};
+///
+/// let options = FirestoreClientOptions::default().rate_limit(
+///     RateLimit::new()
+///         .max_writes_per_second(500)
+///         .max_concurrent_rpcs(50),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RateLimit {
+    max_writes_per_second: Option<u32>,
+    max_concurrent_rpcs: Option<usize>,
+}
+
+impl RateLimit {
+    /// Create a rate limit that doesn't limit anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of document writes per second, including deletes.
+    /// Short bursts of up to one second's worth of writes are allowed.
+    pub fn max_writes_per_second(mut self, max_writes_per_second: u32) -> Self {
+        self.max_writes_per_second = Some(max_writes_per_second.max(1));
+        self
+    }
+
+    /// Limit the number of RPCs that may be in flight at the same time. For
+    /// queries, only the initial request is counted, not the streaming of
+    /// the results.
+    pub fn max_concurrent_rpcs(mut self, max_concurrent_rpcs: usize) -> Self {
+        self.max_concurrent_rpcs = Some(max_concurrent_rpcs.max(1));
+        self
+    }
+}
+
+/// Enforces a [`RateLimit`]. Shared between a client and its clones.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    concurrency: Option<Arc<Semaphore>>,
+    write_bucket: Option<Mutex<TokenBucket>>,
+}
+
+/// Keeps the permit for a concurrent RPC until dropped.
+pub(crate) struct RpcPermit(#[allow(dead_code)] Option<OwnedSemaphorePermit>);
+
+impl RateLimiter {
+    pub fn new(rate_limit: &RateLimit) -> Self {
+        Self {
+            concurrency: rate_limit
+                .max_concurrent_rpcs
+                .map(|max| Arc::new(Semaphore::new(max))),
+            write_bucket: rate_limit
+                .max_writes_per_second
+                .map(|rate| Mutex::new(TokenBucket::new(rate as f64))),
+        }
+    }
+
+    /// Waits until an RPC with the given number of writes may be sent.
+    pub async fn acquire(&self, writes: u32) -> RpcPermit {
+        if let Some(bucket) = &self.write_bucket {
+            if writes > 0 {
+                let wait = bucket
+                    .lock()
+                    .expect("token bucket lock poisoned")
+                    .reserve(writes as f64);
+                tokio::time::sleep(wait).await;
+            }
+        }
+
+        let permit = match &self.concurrency {
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("rate limit semaphore is never closed"),
+            ),
+            None => None,
+        };
+
+        RpcPermit(permit)
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes the given number of tokens from the bucket, returning how long
+    /// to wait before they are actually available. The bucket may go into
+    /// debt, so requests larger than the bucket itself are also served.
+    fn reserve(&mut self, tokens: f64) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+
+        self.tokens -= tokens;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_allows_burst_then_waits() {
+        let mut bucket = TokenBucket::new(100.0);
+
+        assert_eq!(bucket.reserve(100.0), Duration::ZERO);
+
+        let wait = bucket.reserve(50.0);
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn limits_concurrent_rpcs() {
+        let limiter = RateLimiter::new(&RateLimit::new().max_concurrent_rpcs(1));
+
+        let permit = limiter.acquire(0).await;
+        let blocked = tokio::time::timeout(Duration::from_millis(10), limiter.acquire(0)).await;
+        assert!(blocked.is_err());
+
+        drop(permit);
+        let unblocked = tokio::time::timeout(Duration::from_millis(10), limiter.acquire(0)).await;
+        assert!(unblocked.is_ok());
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of the operations performed by a
+/// [`FirestoreClient`](super::FirestoreClient), which can be used to estimate
+/// the billing impact of e.g. a load test or a batch job.
+///
+/// The counts only include documents that were actually returned or changed,
+/// so minimum charges (such as a query without results being billed as one
+/// read) are not accounted for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientStats {
+    /// The number of documents returned by reads and queries.
+    pub documents_read: u64,
+    /// The number of documents created, set or updated.
+    pub documents_written: u64,
+    /// The number of deletes applied.
+    pub documents_deleted: u64,
+    /// The number of RPCs issued to Firestore.
+    pub rpcs: u64,
+}
+
+/// Counters that are shared between a client and its clones. Recording is a
+/// no-op unless stats collection has been enabled.
+#[derive(Debug, Default)]
+pub(crate) struct StatsCounters {
+    enabled: bool,
+    documents_read: AtomicU64,
+    documents_written: AtomicU64,
+    documents_deleted: AtomicU64,
+    rpcs: AtomicU64,
+}
+
+impl StatsCounters {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_rpc(&self) {
+        self.add(&self.rpcs, 1);
+    }
+
+    pub fn record_reads(&self, count: u64) {
+        self.add(&self.documents_read, count);
+    }
+
+    pub fn record_writes(&self, count: u64) {
+        self.add(&self.documents_written, count);
+    }
+
+    pub fn record_deletes(&self, count: u64) {
+        self.add(&self.documents_deleted, count);
+    }
+
+    pub fn snapshot(&self) -> ClientStats {
+        ClientStats {
+            documents_read: self.documents_read.load(Ordering::Relaxed),
+            documents_written: self.documents_written.load(Ordering::Relaxed),
+            documents_deleted: self.documents_deleted.load(Ordering::Relaxed),
+            rpcs: self.rpcs.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(&self, counter: &AtomicU64, count: u64) {
+        if self.enabled {
+            counter.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_counts_when_enabled() {
+        let disabled = StatsCounters::new(false);
+        disabled.record_rpc();
+        disabled.record_reads(3);
+        assert_eq!(disabled.snapshot(), ClientStats::default());
+
+        let enabled = StatsCounters::new(true);
+        enabled.record_rpc();
+        enabled.record_rpc();
+        enabled.record_reads(3);
+        enabled.record_writes(2);
+        enabled.record_deletes(1);
+        assert_eq!(
+            enabled.snapshot(),
+            ClientStats {
+                documents_read: 3,
+                documents_written: 2,
+                documents_deleted: 1,
+                rpcs: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Consistency of reads across operations, clients and processes.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+use firestore_grpc::v1::{
+    batch_get_documents_request, get_document_request, list_documents_request,
+    run_aggregation_query_request, run_query_request,
+};
+use prost_types::Timestamp;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::FirebaseError;
+
+/// Makes reads consistent with each other, either by performing them as part
+/// of the same transaction, which may be read-only, or by reading at the same
+/// point in time.
+#[derive(Debug, Clone)]
+pub(crate) enum ReadConsistency {
+    Transaction(Vec<u8>),
+    ReadTime(Timestamp),
+}
+
+impl ReadConsistency {
+    pub(crate) fn get_document_selector(&self) -> get_document_request::ConsistencySelector {
+        match self {
+            Self::Transaction(id) => {
+                get_document_request::ConsistencySelector::Transaction(id.clone())
+            }
+            Self::ReadTime(time) => {
+                get_document_request::ConsistencySelector::ReadTime(time.clone())
+            }
+        }
+    }
+
+    pub(crate) fn batch_get_documents_selector(
+        &self,
+    ) -> batch_get_documents_request::ConsistencySelector {
+        match self {
+            Self::Transaction(id) => {
+                batch_get_documents_request::ConsistencySelector::Transaction(id.clone())
+            }
+            Self::ReadTime(time) => {
+                batch_get_documents_request::ConsistencySelector::ReadTime(time.clone())
+            }
+        }
+    }
+
+    pub(crate) fn run_query_selector(&self) -> run_query_request::ConsistencySelector {
+        match self {
+            Self::Transaction(id) => {
+                run_query_request::ConsistencySelector::Transaction(id.clone())
+            }
+            Self::ReadTime(time) => run_query_request::ConsistencySelector::ReadTime(time.clone()),
+        }
+    }
+
+    pub(crate) fn list_documents_selector(&self) -> list_documents_request::ConsistencySelector {
+        match self {
+            Self::Transaction(id) => {
+                list_documents_request::ConsistencySelector::Transaction(id.clone())
+            }
+            Self::ReadTime(time) => {
+                list_documents_request::ConsistencySelector::ReadTime(time.clone())
+            }
+        }
+    }
+
+    pub(crate) fn run_aggregation_query_selector(
+        &self,
+    ) -> run_aggregation_query_request::ConsistencySelector {
+        match self {
+            Self::Transaction(id) => {
+                run_aggregation_query_request::ConsistencySelector::Transaction(id.clone())
+            }
+            Self::ReadTime(time) => {
+                run_aggregation_query_request::ConsistencySelector::ReadTime(time.clone())
+            }
+        }
+    }
+}
+
+/// A point in time of the database, which can be passed between services to
+/// make sure that one service sees the writes made by another.
+///
+/// Obtain a token with
+/// [`FirestoreClient::consistency_token`](super::client::FirestoreClient::consistency_token)
+/// after writing, send it along (it can be converted to and from a string, or
+/// serialized with serde), and read at the token with
+/// [`FirestoreClient::read_at`](super::client::FirestoreClient::read_at).
+///
+/// Firestore only allows reading at times within the last hour, so tokens
+/// should be used shortly after they have been obtained.
+///
+/// A token can also be created for any point in time with
+/// [`at`](Self::at), for point-in-time reads. To read at least a given
+/// document, use its exact
+/// [`update_timestamp`](super::client::FirestoreDocument::update_timestamp)
+/// or [`WriteResult::consistency_token`](super::write::WriteResult::consistency_token),
+/// not the whole seconds of its `update_time`, which are before the write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConsistencyToken {
+    seconds: i64,
+    nanos: i32,
+}
+
+impl ConsistencyToken {
+    /// A token for the given point in time, e.g. for reading a fixed snapshot
+    /// of the database in a backfill job. The time is truncated to
+    /// microseconds, which is the precision Firestore reads at.
+    ///
+    /// Firestore only allows reading at times within the last hour, or at
+    /// whole minutes within the last 7 days if point-in-time recovery is
+    /// enabled for the database.
+    pub fn at(time: SystemTime) -> Result<Self, FirebaseError> {
+        let since_epoch = time
+            .duration_since(UNIX_EPOCH)
+            .context("Read time is before the Unix epoch")?;
+
+        Ok(Self {
+            seconds: since_epoch.as_secs() as i64,
+            nanos: (since_epoch.subsec_micros() * 1000) as i32,
+        })
+    }
+
+    pub(crate) fn from_timestamp(timestamp: Timestamp) -> Self {
+        Self {
+            seconds: timestamp.seconds,
+            nanos: timestamp.nanos,
+        }
+    }
+
+    pub(crate) fn timestamp(&self) -> Timestamp {
+        Timestamp {
+            seconds: self.seconds,
+            nanos: self.nanos,
+        }
+    }
+}
+
+/// Converts a timestamp of the gRPC API to a [`SystemTime`] without losing
+/// precision.
+pub(crate) fn system_time(timestamp: &Timestamp) -> SystemTime {
+    let nanos = Duration::from_nanos(timestamp.nanos as u64);
+    if timestamp.seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(timestamp.seconds as u64) + nanos
+    } else {
+        UNIX_EPOCH - Duration::from_secs(timestamp.seconds.unsigned_abs()) + nanos
+    }
+}
+
+impl Display for ConsistencyToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:09}", self.seconds, self.nanos)
+    }
+}
+
+impl FromStr for ConsistencyToken {
+    type Err = FirebaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::anyhow!("Invalid consistency token '{}'", s);
+
+        let (seconds, nanos) = s.split_once('.').ok_or_else(invalid)?;
+        let seconds = seconds.parse().map_err(|_| invalid())?;
+
+        // Tokens always have nine digits of nanoseconds, so e.g. `1.5` is
+        // rejected rather than read as five nanoseconds
+        if nanos.len() != 9 || !nanos.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid().into());
+        }
+        let nanos = nanos.parse().map_err(|_| invalid())?;
+
+        Ok(Self { seconds, nanos })
+    }
+}
+
+impl Serialize for ConsistencyToken {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ConsistencyToken {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_string_round_trip() {
+        let token = ConsistencyToken::from_timestamp(Timestamp {
+            seconds: 1663061252,
+            nanos: 1234,
+        });
+
+        assert_eq!(token.to_string(), "1663061252.000001234");
+        assert_eq!(
+            token.to_string().parse::<ConsistencyToken>().unwrap(),
+            token
+        );
+        assert_eq!(
+            serde_json::from_value::<ConsistencyToken>(serde_json::to_value(token).unwrap())
+                .unwrap(),
+            token
+        );
+    }
+
+    #[test]
+    fn token_at_time_is_truncated_to_micros() {
+        let time = UNIX_EPOCH + Duration::new(1663061252, 123_456_789);
+
+        assert_eq!(
+            ConsistencyToken::at(time).unwrap().to_string(),
+            "1663061252.123456000"
+        );
+        assert!(ConsistencyToken::at(UNIX_EPOCH - Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn converts_timestamps_with_nanos() {
+        let timestamp = |seconds, nanos| Timestamp { seconds, nanos };
+
+        assert_eq!(
+            system_time(&timestamp(1663061252, 123_456_789)),
+            UNIX_EPOCH + Duration::new(1663061252, 123_456_789)
+        );
+        assert_eq!(
+            system_time(&timestamp(-2, 500)),
+            UNIX_EPOCH - Duration::new(1, 999_999_500)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_tokens() {
+        assert!("".parse::<ConsistencyToken>().is_err());
+        assert!("1663061252".parse::<ConsistencyToken>().is_err());
+        assert!("1663061252.1000000000".parse::<ConsistencyToken>().is_err());
+        assert!("1663061252.5".parse::<ConsistencyToken>().is_err());
+        assert!("1663061252.+00000005".parse::<ConsistencyToken>().is_err());
+        assert!("abc.123".parse::<ConsistencyToken>().is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! A counter that supports a high rate of increments by spreading them across
+//! several shards, following the
+//! [distributed counter pattern](https://firebase.google.com/docs/firestore/solutions/counters).
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use anyhow::Context;
+
+use crate::error::FirebaseError;
+
+use super::{client::FirestoreClient, reference::DocumentReference, write::increment_write};
+
+const SHARDS_COLLECTION: &str = "shards";
+const COUNT_FIELD: &str = "count";
+
+/// A sharded counter. A single Firestore document can only sustain about one
+/// write per second, so the count is spread across a number of shard
+/// documents in a `shards` subcollection of the counter's document. Each
+/// increment goes to a random shard, and the total is the sum of all shards.
+///
+/// More shards allow more increments per second, at the cost of slightly more
+/// expensive reads of the total.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::firestore::{collection, counter::DistributedCounter};
+/// use ulid::Ulid;
+///
+/// let counter = DistributedCounter::new(collection("counters").doc(Ulid::new()), 10);
+///
+/// counter.increment(&mut client, 1).await?;
+/// counter.increment(&mut client, 2).await?;
+/// counter.increment(&mut client, -1).await?;
+///
+/// assert_eq!(counter.get_total(&mut client).await?, 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DistributedCounter {
+    doc_ref: DocumentReference,
+    shards: u32,
+}
+
+impl DistributedCounter {
+    /// Create a counter stored at the given document with the given number
+    /// of shards. At least one shard is always used.
+    pub fn new(doc_ref: DocumentReference, shards: u32) -> Self {
+        Self {
+            doc_ref,
+            shards: shards.max(1),
+        }
+    }
+
+    /// Add the given amount to the counter. Pass a negative amount to
+    /// decrement it.
+    pub async fn increment(
+        &self,
+        client: &mut FirestoreClient,
+        by: i64,
+    ) -> Result<(), FirebaseError> {
+        let shard_ref = self
+            .doc_ref
+            .collection(SHARDS_COLLECTION)
+            .doc(random_shard(self.shards).to_string());
+        let write = increment_write(client.root_resource_path(), &shard_ref, COUNT_FIELD, by);
+
+        client
+            .batch_write(vec![write])
+            .await
+            .pop()
+            .context("No result returned for counter increment")??;
+
+        Ok(())
+    }
+
+    /// Get the current value of the counter by summing up all shards.
+    pub async fn get_total(&self, client: &mut FirestoreClient) -> Result<i64, FirebaseError> {
+        client
+            .sum_integers(self.doc_ref.collection(SHARDS_COLLECTION), COUNT_FIELD)
+            .await
+    }
+}
+
+fn random_shard(shards: u32) -> u32 {
+    // Good enough randomness for spreading writes, without pulling in a
+    // dependency for random numbers
+    let random = RandomState::new().build_hasher().finish();
+    (random % shards as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_shard_is_in_range() {
+        for _ in 0..100 {
+            assert!(random_shard(3) < 3);
+        }
+        assert_eq!(random_shard(1), 0);
+    }
+}
@@ -0,0 +1,302 @@
+//! Cursors describe a position in the results of an ordered query, and can be
+//! encoded into opaque page tokens for stateless pagination.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use firestore_grpc::v1::{value::ValueType, ArrayValue, MapValue, Value};
+use prost::Message;
+use serde::Serialize;
+
+use crate::error::FirebaseError;
+
+use super::{
+    client::FirestoreDocument,
+    query::{OrderBy, DOCUMENT_ID},
+    reference::DocumentReference,
+    serde::serialize_to_value_type,
+    write::split_field_path,
+};
+
+const VALUES_KEY: &str = "v";
+const DOCUMENT_KEY: &str = "d";
+
+/// A position in the results of a query, made up of the values of the fields
+/// the query is ordered by and, optionally, the document at that position.
+///
+/// A cursor can be encoded into an opaque, URL-safe page token with
+/// [`encode`](Self::encode) and recovered with [`decode`](Self::decode). The
+/// token only contains the order values and the document path, never the rest
+/// of the document's contents.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), fireplace::error::FirebaseError> {
+/// use fireplace::firestore::{collection, cursor::Cursor};
+///
+/// let cursor = Cursor::new()
+///     .with_value(&"Golden Gate Bridge")?
+///     .with_value(&1937)?
+///     .with_document(collection("landmarks").doc("golden-gate"));
+///
+/// let page_token = cursor.encode();
+///
+/// assert_eq!(Cursor::decode(&page_token)?, cursor);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Cursor {
+    values: Vec<Value>,
+    document: Option<DocumentReference>,
+}
+
+impl Cursor {
+    /// Create an empty cursor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the value of the next field the query is ordered by. Fails if the
+    /// value cannot be serialized to a Firestore value.
+    pub fn with_value<T: Serialize>(mut self, value: &T) -> Result<Self, FirebaseError> {
+        let value_type = serialize_to_value_type(value, "")?;
+        self.values.push(Value {
+            value_type: Some(value_type),
+        });
+        Ok(self)
+    }
+
+    /// Set the document at the position of the cursor, which breaks ties
+    /// between documents with the same values. The cursor must then have a
+    /// value for each field the query is ordered by.
+    pub fn with_document(mut self, document: DocumentReference) -> Self {
+        self.document = Some(document);
+        self
+    }
+
+    /// The values of the fields the query is ordered by.
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// The document at the position of the cursor, if any.
+    pub fn document(&self) -> Option<&DocumentReference> {
+        self.document.as_ref()
+    }
+
+    /// Create a cursor at the position of the document in the results of a
+    /// query with the given ordering, taking the values of the ordered
+    /// fields from the document. Fails if the document doesn't have one of
+    /// the fields, as it then can't be in the results of the query.
+    ///
+    /// The values are serialized from the document's data, so they are only
+    /// exact if the data holds them exactly, e.g. timestamps rather than
+    /// seconds.
+    pub(crate) fn from_document<T: Serialize>(
+        document: &FirestoreDocument<T>,
+        ordering: &[OrderBy],
+    ) -> Result<Self, FirebaseError> {
+        let doc_ref = document.document_reference()?;
+        let fields = match serialize_to_value_type(&document.data, "")? {
+            ValueType::MapValue(map) => map.fields,
+            _ => return Err(anyhow::anyhow!("Document data must serialize to a map").into()),
+        };
+
+        let mut cursor = Self::new();
+        let mut is_ordered_by_id = false;
+        for order in ordering {
+            if order.field == DOCUMENT_ID {
+                cursor = cursor.with_value(&doc_ref)?;
+                is_ordered_by_id = true;
+                continue;
+            }
+
+            let value = field_value(&fields, &order.field).with_context(|| {
+                format!(
+                    "Document {} has no value for ordered field '{}'",
+                    doc_ref, order.field
+                )
+            })?;
+            cursor.values.push(value.clone());
+        }
+
+        // Without an explicit ordering by ID, the document breaks ties
+        // between documents with the same values of the ordered fields
+        if !is_ordered_by_id {
+            cursor = cursor.with_document(doc_ref);
+        }
+
+        Ok(cursor)
+    }
+
+    /// Encode the cursor into an opaque, URL-safe token.
+    pub fn encode(&self) -> String {
+        let mut fields = HashMap::from([(
+            VALUES_KEY.to_string(),
+            Value {
+                value_type: Some(ValueType::ArrayValue(ArrayValue {
+                    values: self.values.clone(),
+                })),
+            },
+        )]);
+
+        if let Some(document) = &self.document {
+            fields.insert(
+                DOCUMENT_KEY.to_string(),
+                Value {
+                    value_type: Some(ValueType::StringValue(document.to_string())),
+                },
+            );
+        }
+
+        let token = Value {
+            value_type: Some(ValueType::MapValue(MapValue { fields })),
+        };
+
+        URL_SAFE_NO_PAD.encode(token.encode_to_vec())
+    }
+
+    /// Decode a token created with [`encode`](Self::encode). Fails if the
+    /// token is malformed.
+    pub fn decode(token: &str) -> Result<Self, FirebaseError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(token)
+            .context("Cursor token is not valid base64")?;
+        let token = Value::decode(bytes.as_slice()).context("Cursor token is malformed")?;
+
+        let mut fields = match token.value_type {
+            Some(ValueType::MapValue(map)) => map.fields,
+            _ => return Err(anyhow::anyhow!("Cursor token is malformed").into()),
+        };
+
+        let values = match fields.remove(VALUES_KEY).and_then(|v| v.value_type) {
+            Some(ValueType::ArrayValue(array)) => array.values,
+            _ => return Err(anyhow::anyhow!("Cursor token is missing its values").into()),
+        };
+
+        let document = match fields.remove(DOCUMENT_KEY).and_then(|v| v.value_type) {
+            Some(ValueType::StringValue(path)) => Some(
+                DocumentReference::try_from(path)
+                    .context("Cursor token contains an invalid document path")?,
+            ),
+            None => None,
+            Some(_) => return Err(anyhow::anyhow!("Cursor token is malformed").into()),
+        };
+
+        Ok(Self { values, document })
+    }
+}
+
+/// Looks up the value at the field path, descending into maps.
+fn field_value<'a>(fields: &'a HashMap<String, Value>, field_path: &str) -> Option<&'a Value> {
+    let mut fields = fields;
+    let mut segments = split_field_path(field_path).into_iter().peekable();
+
+    while let Some(segment) = segments.next() {
+        let value = fields.get(&segment)?;
+        if segments.peek().is_none() {
+            return Some(value);
+        }
+        fields = match &value.value_type {
+            Some(ValueType::MapValue(map)) => &map.fields,
+            _ => return None,
+        };
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::firestore::{collection, query::Direction};
+
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips_through_token() {
+        let cursor = Cursor::new()
+            .with_value(&"Tokyo")
+            .unwrap()
+            .with_value(&42)
+            .unwrap()
+            .with_document(collection("cities").doc("TOK"));
+
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+
+        assert_eq!(decoded, cursor);
+        assert_eq!(
+            decoded.values()[0].value_type,
+            Some(ValueType::StringValue("Tokyo".to_string()))
+        );
+        assert_eq!(decoded.document(), Some(&collection("cities").doc("TOK")));
+    }
+
+    #[test]
+    fn cursor_without_document_round_trips() {
+        let cursor = Cursor::new().with_value(&true).unwrap();
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    }
+
+    #[test]
+    fn cursor_from_document_takes_ordered_values() {
+        let document = FirestoreDocument {
+            id: "projects/p/databases/(default)/documents/cities/SF".to_string(),
+            data: serde_json::json!({ "name": "San Francisco", "stats": { "population": 808437 } }),
+            create_time: None,
+            update_time: None,
+            create_timestamp: None,
+            update_timestamp: None,
+        };
+        let order = |field: &str| OrderBy {
+            field: field.to_string(),
+            direction: Direction::Ascending,
+        };
+
+        let cursor =
+            Cursor::from_document(&document, &[order("stats.population"), order("name")]).unwrap();
+        assert_eq!(
+            cursor.values()[0].value_type,
+            Some(ValueType::IntegerValue(808437))
+        );
+        assert_eq!(cursor.document(), Some(&collection("cities").doc("SF")));
+
+        let by_id = Cursor::from_document(&document, &[order(DOCUMENT_ID)]).unwrap();
+        assert_eq!(by_id.values().len(), 1);
+        assert_eq!(by_id.document(), None);
+
+        assert!(Cursor::from_document(&document, &[order("missing")]).is_err());
+    }
+
+    #[test]
+    fn cursor_from_document_uses_current_data() {
+        let mut document = FirestoreDocument {
+            id: "projects/p/databases/(default)/documents/cities/SF".to_string(),
+            data: serde_json::json!({ "population": 808437 }),
+            create_time: None,
+            update_time: None,
+            create_timestamp: None,
+            update_timestamp: None,
+        };
+        document.data["population"] = serde_json::json!(815201);
+        let ordering = [OrderBy {
+            field: "population".to_string(),
+            direction: Direction::Descending,
+        }];
+
+        let cursor = Cursor::from_document(&document, &ordering).unwrap();
+
+        assert_eq!(
+            cursor.values()[0].value_type,
+            Some(ValueType::IntegerValue(815201))
+        );
+    }
+
+    #[test]
+    fn decoding_invalid_token_fails() {
+        assert!(Cursor::decode("not a token!").is_err());
+        assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode(b"garbage")).is_err());
+    }
+}
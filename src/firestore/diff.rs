@@ -0,0 +1,334 @@
+//! Field-level differences between documents, e.g. for printing useful
+//! messages when documents don't match in tests or migration checks. See
+//! [`diff_documents`].
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use firestore_grpc::v1::{value::ValueType, Value};
+use serde::Serialize;
+
+use crate::error::FirebaseError;
+
+use super::{
+    client::FirestoreDocument,
+    serde::{serialize_to_value_type, RenderValue},
+};
+
+/// A difference in a single field between two documents. Values are compared
+/// as Firestore stores them, so e.g. an integer differs from the equal
+/// double, and timestamps are compared to the nanosecond. Nested maps are
+/// compared field by field, while arrays are compared as a whole, the same
+/// way Firestore treats them in field paths.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldDiff {
+    /// The field only exists in the second document.
+    Added { field_path: String, value: Value },
+    /// The field only exists in the first document.
+    Removed { field_path: String, value: Value },
+    /// The field exists in both documents, with different values.
+    Changed {
+        field_path: String,
+        old_value: Value,
+        new_value: Value,
+    },
+}
+
+impl FieldDiff {
+    pub fn field_path(&self) -> &str {
+        match self {
+            Self::Added { field_path, .. }
+            | Self::Removed { field_path, .. }
+            | Self::Changed { field_path, .. } => field_path,
+        }
+    }
+}
+
+impl Display for FieldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added { field_path, value } => {
+                write!(f, "+ {}: {}", field_path, render(value))
+            }
+            Self::Removed { field_path, value } => {
+                write!(f, "- {}: {}", field_path, render(value))
+            }
+            Self::Changed {
+                field_path,
+                old_value,
+                new_value,
+            } => write!(
+                f,
+                "~ {}: {} -> {}",
+                field_path,
+                render(old_value),
+                render(new_value)
+            ),
+        }
+    }
+}
+
+/// The differences between the data of two documents, ordered by field path.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DocumentDiff {
+    pub fields: Vec<FieldDiff>,
+}
+
+impl DocumentDiff {
+    /// Whether the documents have the same data.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl Display for DocumentDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for field in &self.fields {
+            writeln!(f, "{}", field)?;
+        }
+        Ok(())
+    }
+}
+
+fn render(value: &Value) -> String {
+    match &value.value_type {
+        Some(value_type) => RenderValue(value_type).to_string(),
+        None => "?".to_string(),
+    }
+}
+
+/// Compares the data of two documents field by field. Document names and
+/// timestamps are not compared.
+///
+/// The data is serialized the way it would be written to Firestore, so a
+/// fetched document can be compared to a modified copy of it.
+///
+/// Field paths use Firestore's syntax, so segments that aren't simple
+/// identifiers are quoted with backticks.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::firestore::{
+///     client::FirestoreDocument,
+///     diff::{diff_documents, FieldDiff},
+/// };
+///
+/// let document = |data| FirestoreDocument {
+///     id: "projects/p/databases/(default)/documents/people/luke".to_string(),
+///     data,
+///     create_time: None,
+///     update_time: None,
+///     create_timestamp: None,
+///     update_timestamp: None,
+/// };
+///
+/// let before = document(serde_json::json!({ "name": "Luke", "address": { "planet": "Tatooine" } }));
+/// let after = document(serde_json::json!({ "name": "Luke", "address": { "planet": "Yavin 4" }, "rank": "Commander" }));
+///
+/// let diff = diff_documents(&before, &after).unwrap();
+///
+/// let paths: Vec<&str> = diff.fields.iter().map(FieldDiff::field_path).collect();
+/// assert_eq!(paths, vec!["address.planet", "rank"]);
+/// assert_eq!(
+///     diff.to_string(),
+///     "~ address.planet: \"Tatooine\" -> \"Yavin 4\"\n+ rank: \"Commander\"\n"
+/// );
+/// ```
+pub fn diff_documents<T: Serialize>(
+    a: &FirestoreDocument<T>,
+    b: &FirestoreDocument<T>,
+) -> Result<DocumentDiff, FirebaseError> {
+    let mut diff = DocumentDiff::default();
+    diff_fields(
+        None,
+        document_fields(a)?,
+        document_fields(b)?,
+        &mut diff.fields,
+    );
+    Ok(diff)
+}
+
+/// The fields of the document's data as they would be written to Firestore.
+fn document_fields<T: Serialize>(
+    document: &FirestoreDocument<T>,
+) -> Result<HashMap<String, Value>, FirebaseError> {
+    // References are written relative to the database of the document, like
+    // the ones read from Firestore
+    let root_resource_path = document.id.split('/').take(5).collect::<Vec<_>>().join("/");
+
+    match serialize_to_value_type(&document.data, &root_resource_path)? {
+        ValueType::MapValue(map) => Ok(map.fields),
+        _ => Err(anyhow::anyhow!("Document data must serialize to a map").into()),
+    }
+}
+
+fn diff_fields(
+    field_path: Option<&str>,
+    mut a: HashMap<String, Value>,
+    mut b: HashMap<String, Value>,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    let mut keys: Vec<String> = a.keys().chain(b.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        let segment = quote_segment(&key);
+        let path = match field_path {
+            Some(parent) => format!("{}.{}", parent, segment),
+            None => segment,
+        };
+
+        match (a.remove(&key), b.remove(&key)) {
+            (Some(a), Some(b)) => diff_values(path, a, b, diffs),
+            (Some(value), None) => diffs.push(FieldDiff::Removed {
+                field_path: path,
+                value,
+            }),
+            (None, Some(value)) => diffs.push(FieldDiff::Added {
+                field_path: path,
+                value,
+            }),
+            (None, None) => {}
+        }
+    }
+}
+
+fn diff_values(field_path: String, a: Value, b: Value, diffs: &mut Vec<FieldDiff>) {
+    match (a.value_type, b.value_type) {
+        (Some(ValueType::MapValue(a)), Some(ValueType::MapValue(b))) => {
+            diff_fields(Some(&field_path), a.fields, b.fields, diffs)
+        }
+        (a, b) if a != b => diffs.push(FieldDiff::Changed {
+            field_path,
+            old_value: Value { value_type: a },
+            new_value: Value { value_type: b },
+        }),
+        _ => {}
+    }
+}
+
+/// Quotes a field path segment with backticks unless it's a simple
+/// identifier.
+fn quote_segment(segment: &str) -> String {
+    let is_simple = segment
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if is_simple {
+        segment.to_string()
+    } else {
+        format!("`{}`", segment.replace('\\', "\\\\").replace('`', "\\`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(data: serde_json::Value) -> HashMap<String, Value> {
+        match serialize_to_value_type(&data, "").unwrap() {
+            ValueType::MapValue(map) => map.fields,
+            _ => panic!("not a map"),
+        }
+    }
+
+    fn value(value_type: ValueType) -> Value {
+        Value {
+            value_type: Some(value_type),
+        }
+    }
+
+    fn diff(a: serde_json::Value, b: serde_json::Value) -> Vec<FieldDiff> {
+        let mut diffs = vec![];
+        diff_fields(None, fields(a), fields(b), &mut diffs);
+        diffs
+    }
+
+    #[test]
+    fn equal_documents_have_no_diff() {
+        let doc = serde_json::json!({ "name": "Luke", "friends": ["Han", "Leia"] });
+        assert!(diff(doc.clone(), doc).is_empty());
+    }
+
+    #[test]
+    fn diffs_nested_fields_and_quotes_paths() {
+        let diffs = diff(
+            serde_json::json!({ "ships": { "x-wing": 1 }, "friends": ["Han"], "age": 19 }),
+            serde_json::json!({ "ships": { "x-wing": 2 }, "friends": ["Han", "Leia"] }),
+        );
+
+        assert_eq!(
+            diffs,
+            vec![
+                FieldDiff::Removed {
+                    field_path: "age".to_string(),
+                    value: value(ValueType::IntegerValue(19)),
+                },
+                FieldDiff::Changed {
+                    field_path: "friends".to_string(),
+                    old_value: fields(serde_json::json!({ "v": ["Han"] }))
+                        .remove("v")
+                        .unwrap(),
+                    new_value: fields(serde_json::json!({ "v": ["Han", "Leia"] }))
+                        .remove("v")
+                        .unwrap(),
+                },
+                FieldDiff::Changed {
+                    field_path: "ships.`x-wing`".to_string(),
+                    old_value: value(ValueType::IntegerValue(1)),
+                    new_value: value(ValueType::IntegerValue(2)),
+                },
+            ]
+        );
+        assert_eq!(diffs[2].to_string(), "~ ships.`x-wing`: 1 -> 2");
+    }
+
+    #[test]
+    fn compares_values_as_stored_in_firestore() {
+        let diffs = diff(
+            serde_json::json!({ "rating": 4, "name": "Luke" }),
+            serde_json::json!({ "rating": 4.0, "name": "Luke" }),
+        );
+
+        // Equal in JSON, but an integer and a double in Firestore
+        assert_eq!(
+            diffs,
+            vec![FieldDiff::Changed {
+                field_path: "rating".to_string(),
+                old_value: value(ValueType::IntegerValue(4)),
+                new_value: value(ValueType::DoubleValue(4.0)),
+            }]
+        );
+    }
+
+    #[test]
+    fn diffs_modified_copy_of_document() {
+        let before = FirestoreDocument {
+            id: "projects/p/databases/(default)/documents/people/luke".to_string(),
+            data: serde_json::json!({ "name": "Luke", "friend": { "name": "Han" } }),
+            create_time: None,
+            update_time: None,
+            create_timestamp: None,
+            update_timestamp: None,
+        };
+        let mut after = FirestoreDocument {
+            id: before.id.clone(),
+            data: before.data.clone(),
+            create_time: None,
+            update_time: None,
+            create_timestamp: None,
+            update_timestamp: None,
+        };
+        after.data["friend"]["name"] = serde_json::json!("Leia");
+
+        let diff = diff_documents(&before, &after).unwrap();
+
+        assert_eq!(diff.to_string(), "~ friend.name: \"Han\" -> \"Leia\"\n");
+    }
+}
@@ -0,0 +1,166 @@
+//! Field name constants that are checked against a struct at compile time,
+//! so a typo in a [`filter`](super::query::filter) or
+//! [ordering](super::query::CollectionQuery::order_by) fails to compile
+//! instead of silently matching no documents.
+
+/// Declares a module of field name constants for a struct, and checks at
+/// compile time that the struct has each of the fields.
+///
+/// Each constant is named after its field and holds the field's name. The
+/// name in Firestore can be given after a `=` when it differs from the Rust
+/// name, e.g. because of `#[serde(rename)]` or a raw identifier like
+/// `r#type`. The renamed field is still checked to exist in the struct.
+///
+/// The names given after `=` can't be checked against serde at compile
+/// time. The module also has an `ALL` constant with every name, so a unit
+/// test can check them against a serialized value with
+/// [`check_field_paths`](crate::firestore::write::check_field_paths):
+///
+/// ```
+/// use fireplace::{firestore::write::check_field_paths, firestore_fields};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "camelCase")]
+/// struct Order {
+///     total_price: f64,
+/// }
+///
+/// firestore_fields! {
+///     mod order_fields for Order {
+///         total_price = "totalPrcie",
+///     }
+/// }
+///
+/// let order = Order { total_price: 9.5 };
+/// let err = check_field_paths(&order, order_fields::ALL).unwrap_err();
+/// assert!(err.to_string().contains("did you mean 'totalPrice'?"));
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+/// use fireplace::{
+///     firestore::{
+///         collection,
+///         query::{filter, EqualTo},
+///     },
+///     firestore_fields,
+/// };
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Landmark {
+///     name: String,
+///     r#type: String,
+/// }
+///
+/// firestore_fields! {
+///     mod landmark_fields for Landmark {
+///         name,
+///         r#type = "type",
+///     }
+/// }
+///
+/// let landmark: Option<Landmark> = client
+///     .run_query_one(
+///         collection("cities")
+///             .doc("SF")
+///             .collection("landmarks")
+///             .with_filter(filter(landmark_fields::r#type, EqualTo("bridge"))),
+///     )
+///     .await?;
+///
+/// assert_eq!(landmark.unwrap().name, "Golden Gate Bridge");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Misspelled fields don't compile:
+///
+/// ```compile_fail
+/// use fireplace::firestore_fields;
+///
+/// struct Pizza {
+///     name: String,
+/// }
+///
+/// firestore_fields! {
+///     mod pizza_fields for Pizza {
+///         nmae,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! firestore_fields {
+    (@name $field:ident) => {
+        stringify!($field)
+    };
+    (@name $field:ident $name:literal) => {
+        $name
+    };
+    (
+        $vis:vis mod $module:ident for $ty:ty {
+            $($field:ident $(= $name:literal)?),* $(,)?
+        }
+    ) => {
+        #[allow(non_upper_case_globals)]
+        $vis mod $module {
+            $(
+                pub const $field: &str = $crate::firestore_fields!(@name $field $($name)?);
+            )*
+
+            /// The names of all the fields above.
+            pub const ALL: &[&str] = &[$($field),*];
+        }
+
+        // Never called, it only makes the compiler check that the fields exist
+        const _: fn(&$ty) = |value| {
+            $(
+                let _ = &value.$field;
+            )*
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use crate::firestore::write::check_field_paths;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Order {
+        id: String,
+        total_price: f64,
+    }
+
+    crate::firestore_fields! {
+        mod order_fields for Order {
+            id,
+            total_price = "totalPrice",
+        }
+    }
+
+    #[test]
+    fn constants_hold_field_names() {
+        assert_eq!(order_fields::id, "id");
+        assert_eq!(order_fields::total_price, "totalPrice");
+    }
+
+    #[test]
+    fn names_match_serialized_fields() {
+        let order = Order {
+            id: "first".to_string(),
+            total_price: 9.5,
+        };
+
+        assert_eq!(order_fields::ALL, ["id", "totalPrice"]);
+        assert!(check_field_paths(&order, order_fields::ALL).is_ok());
+    }
+}
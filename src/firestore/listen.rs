@@ -0,0 +1,794 @@
+//! Real-time updates of documents and queries through Firestore's `Listen`
+//! RPC. See
+//! [`FirestoreClient::listen_document`](super::client::FirestoreClient::listen_document)
+//! and [`FirestoreClient::listen_query`](super::client::FirestoreClient::listen_query).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use firestore_grpc::{
+    tonic,
+    v1::{
+        listen_request, listen_response::ResponseType, target, target_change::TargetChangeType,
+        value::ValueType, ArrayValue, Document, ListenRequest, ListenResponse, MapValue,
+        StructuredQuery, Target, TargetChange, Value,
+    },
+};
+use prost::Message;
+
+use crate::error::FirebaseError;
+
+use super::client::FirestoreDocument;
+
+/// The ID of the only target of a listener.
+const TARGET_ID: i32 = 1;
+
+const CHECKPOINT_TARGET_KEY: &str = "t";
+const CHECKPOINT_DOCUMENTS_KEY: &str = "d";
+
+/// How a listened-to document changed. See [`DocumentSnapshot`] and
+/// [`DocumentChange`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The document exists, and either didn't exist before or this is the
+    /// first snapshot.
+    Added,
+    /// The document was changed.
+    Modified,
+    /// The document doesn't exist, and either existed before or this is the
+    /// first snapshot.
+    Removed,
+}
+
+/// The state of a listened-to document after a change, as returned by
+/// [`listen_document`](super::client::FirestoreClient::listen_document).
+#[derive(Debug, PartialEq)]
+pub struct DocumentSnapshot<T> {
+    pub kind: ChangeKind,
+    /// The document, or `None` if it doesn't exist.
+    pub document: Option<FirestoreDocument<T>>,
+    /// Where to resume listening from after this snapshot.
+    pub checkpoint: ListenCheckpoint,
+}
+
+/// The results of a listened-to query after a change, as returned by
+/// [`listen_query`](super::client::FirestoreClient::listen_query).
+#[derive(Debug, PartialEq)]
+pub struct QuerySnapshot<T> {
+    /// All documents matching the query, ordered by document path.
+    pub documents: Vec<FirestoreDocument<T>>,
+    /// The changes since the previous snapshot, or all documents as
+    /// [`Added`](ChangeKind::Added) for the first snapshot.
+    pub changes: Vec<DocumentChange<T>>,
+    /// Where to resume listening from after this snapshot.
+    pub checkpoint: ListenCheckpoint,
+}
+
+/// A change of a single document in a [`QuerySnapshot`].
+///
+/// The changes of a snapshot are ordered so that applying them one after the
+/// other to the documents of the previous snapshot gives the documents of the
+/// new one: removed documents come first, and the indices of every change
+/// take the changes before it into account.
+#[derive(Debug, PartialEq)]
+pub struct DocumentChange<T> {
+    pub kind: ChangeKind,
+    /// The document after the change, or before it if it was removed.
+    pub document: FirestoreDocument<T>,
+    /// The index of the document in the previous snapshot, or `None` if it
+    /// was added.
+    pub old_index: Option<usize>,
+    /// The index of the document in the new snapshot, or `None` if it was
+    /// removed.
+    pub new_index: Option<usize>,
+}
+
+/// Where a listener left off, for resuming it after e.g. a restart of the
+/// service with
+/// [`resume_listen_document`](super::client::FirestoreClient::resume_listen_document)
+/// or [`resume_listen_query`](super::client::FirestoreClient::resume_listen_query).
+/// The resumed listener only receives the changes since the checkpoint
+/// rather than reading every document again.
+///
+/// A checkpoint contains the resume token of the listen target along with the
+/// documents as of the snapshot, so it is about as large as the documents.
+/// Store it with [`encode`](Self::encode) and recover it with
+/// [`decode`](Self::decode).
+///
+/// Firestore only keeps resume tokens for a limited time. Resuming from an
+/// expired checkpoint still works, but reads the full results again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenCheckpoint {
+    target: target::TargetType,
+    resume_token: Vec<u8>,
+    documents: Vec<Document>,
+}
+
+impl ListenCheckpoint {
+    /// Encode the checkpoint into an opaque, URL-safe token.
+    pub fn encode(&self) -> String {
+        let target = Target {
+            target_type: Some(self.target.clone()),
+            resume_type: Some(target::ResumeType::ResumeToken(self.resume_token.clone())),
+            ..Default::default()
+        };
+        let documents = self
+            .documents
+            .iter()
+            .map(|doc| Value {
+                value_type: Some(ValueType::BytesValue(doc.encode_to_vec())),
+            })
+            .collect();
+
+        let fields = HashMap::from([
+            (
+                CHECKPOINT_TARGET_KEY.to_string(),
+                Value {
+                    value_type: Some(ValueType::BytesValue(target.encode_to_vec())),
+                },
+            ),
+            (
+                CHECKPOINT_DOCUMENTS_KEY.to_string(),
+                Value {
+                    value_type: Some(ValueType::ArrayValue(ArrayValue { values: documents })),
+                },
+            ),
+        ]);
+        let checkpoint = Value {
+            value_type: Some(ValueType::MapValue(MapValue { fields })),
+        };
+
+        URL_SAFE_NO_PAD.encode(checkpoint.encode_to_vec())
+    }
+
+    /// Decode a token created with [`encode`](Self::encode). Fails if the
+    /// token is malformed.
+    pub fn decode(token: &str) -> Result<Self, FirebaseError> {
+        let malformed = || anyhow::anyhow!("Listen checkpoint is malformed");
+
+        let bytes = URL_SAFE_NO_PAD
+            .decode(token)
+            .context("Listen checkpoint is not valid base64")?;
+        let checkpoint =
+            Value::decode(bytes.as_slice()).context("Listen checkpoint is malformed")?;
+
+        let mut fields = match checkpoint.value_type {
+            Some(ValueType::MapValue(map)) => map.fields,
+            _ => return Err(malformed().into()),
+        };
+
+        let target = match fields
+            .remove(CHECKPOINT_TARGET_KEY)
+            .and_then(|v| v.value_type)
+        {
+            Some(ValueType::BytesValue(bytes)) => {
+                Target::decode(bytes.as_slice()).context("Listen checkpoint is malformed")?
+            }
+            _ => return Err(malformed().into()),
+        };
+        let resume_token = match target.resume_type {
+            Some(target::ResumeType::ResumeToken(token)) => token,
+            _ => return Err(malformed().into()),
+        };
+        let target_type = target.target_type.ok_or_else(malformed)?;
+
+        let documents = match fields
+            .remove(CHECKPOINT_DOCUMENTS_KEY)
+            .and_then(|v| v.value_type)
+        {
+            Some(ValueType::ArrayValue(array)) => array
+                .values
+                .into_iter()
+                .map(|value| match value.value_type {
+                    Some(ValueType::BytesValue(bytes)) => {
+                        Document::decode(bytes.as_slice()).context("Listen checkpoint is malformed")
+                    }
+                    _ => Err(malformed()),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err(malformed().into()),
+        };
+
+        Ok(Self {
+            target: target_type,
+            resume_token,
+            documents,
+        })
+    }
+
+    /// Whether the checkpoint is of a document listener rather than a query
+    /// listener.
+    pub(crate) fn is_document_target(&self) -> bool {
+        matches!(self.target, target::TargetType::Documents(_))
+    }
+}
+
+/// A consistent snapshot of the documents of a listen target.
+#[derive(Debug)]
+pub(crate) struct RawSnapshot {
+    /// The documents of the previous snapshot, if any, ordered by document
+    /// path.
+    pub(crate) previous: Option<Vec<Document>>,
+    /// The current documents, ordered by document path.
+    pub(crate) current: Vec<Document>,
+    pub(crate) checkpoint: ListenCheckpoint,
+}
+
+/// What happened as a result of a response from the `Listen` RPC.
+#[derive(Debug)]
+pub(crate) enum WatchEvent {
+    /// Nothing to report yet.
+    None,
+    /// The documents changed as of a consistent snapshot.
+    Snapshot(RawSnapshot),
+    /// The local state was found to be out of sync, so the listen must be
+    /// restarted from scratch.
+    Reset,
+    /// The server stopped listening, e.g. because of missing permissions.
+    Error(FirebaseError),
+}
+
+/// Orders documents the way Firestore does by default: by their path,
+/// segment by segment.
+type DocumentKey = Vec<String>;
+
+fn document_key(name: &str) -> DocumentKey {
+    name.split('/').map(str::to_string).collect()
+}
+
+/// Tracks the state of a single target of the `Listen` RPC, and decides when
+/// the changes add up to a consistent snapshot.
+#[derive(Debug)]
+pub(crate) struct Watch {
+    target: target::TargetType,
+    /// The documents according to the changes received so far, which are
+    /// not necessarily consistent yet.
+    pending: BTreeMap<DocumentKey, Document>,
+    /// The documents as of the last snapshot, or `None` before the first one.
+    emitted: Option<BTreeMap<DocumentKey, Document>>,
+    /// Whether the target has caught up with the server.
+    current: bool,
+    /// The latest resume token received, which only becomes safe to resume
+    /// from once the documents are consistent as of it.
+    pending_resume_token: Vec<u8>,
+    /// Where to resume listening after reconnecting, as of the last
+    /// snapshot.
+    resume_token: Vec<u8>,
+}
+
+impl Watch {
+    /// Watches the document with the given resource name.
+    pub(crate) fn document(name: String) -> Self {
+        Self::new(target::TargetType::Documents(target::DocumentsTarget {
+            documents: vec![name],
+        }))
+    }
+
+    /// Watches the results of the query on the given parent resource.
+    pub(crate) fn query(parent: String, structured_query: StructuredQuery) -> Self {
+        Self::new(target::TargetType::Query(target::QueryTarget {
+            parent,
+            query_type: Some(target::query_target::QueryType::StructuredQuery(
+                structured_query,
+            )),
+        }))
+    }
+
+    /// Continues watching where the checkpoint left off. Only changes since
+    /// the checkpoint result in snapshots.
+    pub(crate) fn resume(checkpoint: ListenCheckpoint) -> Self {
+        let documents: BTreeMap<_, _> = checkpoint
+            .documents
+            .into_iter()
+            .map(|doc| (document_key(&doc.name), doc))
+            .collect();
+
+        Self {
+            target: checkpoint.target,
+            pending: documents.clone(),
+            emitted: Some(documents),
+            current: false,
+            pending_resume_token: checkpoint.resume_token.clone(),
+            resume_token: checkpoint.resume_token,
+        }
+    }
+
+    fn new(target: target::TargetType) -> Self {
+        Self {
+            target,
+            pending: BTreeMap::new(),
+            emitted: None,
+            current: false,
+            pending_resume_token: Vec::new(),
+            resume_token: Vec::new(),
+        }
+    }
+
+    /// The request that starts listening to the target, resuming from the
+    /// last consistent snapshot if any.
+    pub(crate) fn add_target_request(&self, database: String) -> ListenRequest {
+        let resume_type = (!self.resume_token.is_empty())
+            .then(|| target::ResumeType::ResumeToken(self.resume_token.clone()));
+
+        ListenRequest {
+            database,
+            labels: HashMap::new(),
+            target_change: Some(listen_request::TargetChange::AddTarget(Target {
+                target_id: TARGET_ID,
+                target_type: Some(self.target.clone()),
+                resume_type,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Prepares for listening again after the connection was lost. The server
+    /// only sends the changes since the resume token of the last snapshot, so
+    /// the documents are reset to that snapshot, and changes received since
+    /// are received again.
+    pub(crate) fn reconnect(&mut self) {
+        self.pending = self.emitted.clone().unwrap_or_default();
+        self.current = false;
+        self.pending_resume_token = self.resume_token.clone();
+    }
+
+    /// Forgets everything but the last snapshot, so the next listen receives
+    /// the full state of the target again.
+    pub(crate) fn reset(&mut self) {
+        self.pending.clear();
+        self.current = false;
+        self.pending_resume_token.clear();
+        self.resume_token.clear();
+    }
+
+    pub(crate) fn handle(&mut self, response: ListenResponse) -> WatchEvent {
+        match response.response_type {
+            Some(ResponseType::TargetChange(change)) => self.handle_target_change(change),
+            Some(ResponseType::DocumentChange(change)) => {
+                if let Some(document) = change.document {
+                    let key = document_key(&document.name);
+                    if change.target_ids.contains(&TARGET_ID) {
+                        self.pending.insert(key, document);
+                    } else if change.removed_target_ids.contains(&TARGET_ID) {
+                        self.pending.remove(&key);
+                    }
+                }
+                WatchEvent::None
+            }
+            Some(ResponseType::DocumentDelete(delete)) => {
+                self.pending.remove(&document_key(&delete.document));
+                WatchEvent::None
+            }
+            Some(ResponseType::DocumentRemove(remove)) => {
+                self.pending.remove(&document_key(&remove.document));
+                WatchEvent::None
+            }
+            Some(ResponseType::Filter(filter)) => {
+                // The server counts the documents it thinks we have. If that
+                // doesn't match, some change was missed.
+                if filter.target_id == TARGET_ID && filter.count as usize != self.pending.len() {
+                    self.reset();
+                    WatchEvent::Reset
+                } else {
+                    WatchEvent::None
+                }
+            }
+            None => WatchEvent::None,
+        }
+    }
+
+    fn handle_target_change(&mut self, change: TargetChange) -> WatchEvent {
+        let affects_target = change.target_ids.is_empty() || change.target_ids.contains(&TARGET_ID);
+        if !affects_target {
+            return WatchEvent::None;
+        }
+
+        match TargetChangeType::from_i32(change.target_change_type) {
+            Some(TargetChangeType::Current) => {
+                self.current = true;
+                self.record_resume_token(change.resume_token);
+                WatchEvent::None
+            }
+            Some(TargetChangeType::Reset) => {
+                self.pending.clear();
+                self.current = false;
+                WatchEvent::None
+            }
+            Some(TargetChangeType::Remove) => {
+                let message = change
+                    .cause
+                    .map(|cause| {
+                        tonic::Status::new(tonic::Code::from_i32(cause.code), cause.message)
+                    })
+                    .unwrap_or_else(|| tonic::Status::cancelled("Listen target was removed"));
+                WatchEvent::Error(anyhow::anyhow!(message).into())
+            }
+            Some(TargetChangeType::NoChange) => {
+                self.record_resume_token(change.resume_token);
+
+                // A change without target IDs marks a consistent snapshot of
+                // all targets
+                if change.target_ids.is_empty() {
+                    self.snapshot()
+                } else {
+                    WatchEvent::None
+                }
+            }
+            Some(TargetChangeType::Add) | None => WatchEvent::None,
+        }
+    }
+
+    /// Resume tokens are only safe to use once the target is current, as
+    /// resuming from an earlier token would skip the initial state. They are
+    /// kept pending until the next consistent snapshot.
+    fn record_resume_token(&mut self, resume_token: Vec<u8>) {
+        if self.current && !resume_token.is_empty() {
+            self.pending_resume_token = resume_token;
+        }
+    }
+
+    fn snapshot(&mut self) -> WatchEvent {
+        if !self.current {
+            return WatchEvent::None;
+        }
+
+        // The documents are consistent as of the pending token, so resuming
+        // from it can't skip changes that weren't emitted
+        self.resume_token = self.pending_resume_token.clone();
+        if self.emitted.as_ref() == Some(&self.pending) {
+            return WatchEvent::None;
+        }
+
+        let previous = self
+            .emitted
+            .replace(self.pending.clone())
+            .map(|docs| docs.into_values().collect());
+        let current: Vec<_> = self.pending.values().cloned().collect();
+        let checkpoint = ListenCheckpoint {
+            target: self.target.clone(),
+            resume_token: self.resume_token.clone(),
+            documents: current.clone(),
+        };
+
+        WatchEvent::Snapshot(RawSnapshot {
+            previous,
+            current,
+            checkpoint,
+        })
+    }
+}
+
+/// How a single listened-to document changed between two snapshots, given
+/// the documents of the previous snapshot if any and the current ones.
+pub(crate) fn document_change_kind(
+    previous: Option<&[Document]>,
+    current: &[Document],
+) -> ChangeKind {
+    match (previous.map(<[Document]>::is_empty), current.is_empty()) {
+        (Some(false), false) => ChangeKind::Modified,
+        (_, false) => ChangeKind::Added,
+        (_, true) => ChangeKind::Removed,
+    }
+}
+
+/// The changes that turn the previous documents of a query into the current
+/// ones, both ordered by document path, as `(kind, document, old_index,
+/// new_index)`. Removals come first, followed by additions and modifications
+/// in document order, each with indices as of the changes before it.
+pub(crate) fn query_changes(
+    previous: &[Document],
+    current: &[Document],
+) -> Vec<(ChangeKind, Document, Option<usize>, Option<usize>)> {
+    let previous_by_name: HashMap<&str, &Document> = previous
+        .iter()
+        .map(|doc| (doc.name.as_str(), doc))
+        .collect();
+    let current_names: HashSet<&str> = current.iter().map(|doc| doc.name.as_str()).collect();
+
+    // The document keys as the changes so far have left them
+    let mut keys: Vec<DocumentKey> = previous.iter().map(|doc| document_key(&doc.name)).collect();
+    let mut changes = Vec::new();
+
+    for doc in previous {
+        if !current_names.contains(doc.name.as_str()) {
+            let key = document_key(&doc.name);
+            let old_index = keys.binary_search(&key).ok();
+            if let Some(index) = old_index {
+                keys.remove(index);
+            }
+            changes.push((ChangeKind::Removed, doc.clone(), old_index, None));
+        }
+    }
+
+    for doc in current {
+        let key = document_key(&doc.name);
+        let old_index = match previous_by_name.get(doc.name.as_str()) {
+            Some(previous_doc) if *previous_doc == doc => continue,
+            Some(_) => {
+                let index = keys.binary_search(&key).ok();
+                if let Some(index) = index {
+                    keys.remove(index);
+                }
+                index
+            }
+            None => None,
+        };
+        let new_index = keys.binary_search(&key).unwrap_or_else(|index| index);
+        keys.insert(new_index, key);
+
+        let kind = if old_index.is_some() {
+            ChangeKind::Modified
+        } else {
+            ChangeKind::Added
+        };
+        changes.push((kind, doc.clone(), old_index, Some(new_index)));
+    }
+
+    changes
+}
+
+/// Whether a failed listen is worth retrying. Errors like missing
+/// permissions or an invalid document name are not.
+pub(crate) fn is_transient(code: tonic::Code) -> bool {
+    matches!(
+        code,
+        tonic::Code::Unavailable
+            | tonic::Code::Unknown
+            | tonic::Code::Internal
+            | tonic::Code::DeadlineExceeded
+            | tonic::Code::ResourceExhausted
+            | tonic::Code::Aborted
+            | tonic::Code::Cancelled
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use firestore_grpc::v1::{value::ValueType, DocumentDelete, Value};
+
+    use super::*;
+
+    fn target_change(change_type: TargetChangeType, target_ids: Vec<i32>) -> ListenResponse {
+        ListenResponse {
+            response_type: Some(ResponseType::TargetChange(TargetChange {
+                target_change_type: change_type as i32,
+                target_ids,
+                resume_token: b"token".to_vec(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn snapshot_point() -> ListenResponse {
+        target_change(TargetChangeType::NoChange, vec![])
+    }
+
+    fn doc(name: &str, version: i64) -> Document {
+        Document {
+            name: name.to_string(),
+            fields: HashMap::from([(
+                "version".to_string(),
+                Value {
+                    value_type: Some(ValueType::IntegerValue(version)),
+                },
+            )]),
+            ..Default::default()
+        }
+    }
+
+    fn document_change(name: &str, version: i64) -> ListenResponse {
+        ListenResponse {
+            response_type: Some(ResponseType::DocumentChange(
+                firestore_grpc::v1::DocumentChange {
+                    document: Some(doc(name, version)),
+                    target_ids: vec![TARGET_ID],
+                    removed_target_ids: vec![],
+                },
+            )),
+        }
+    }
+
+    fn document_delete(name: &str) -> ListenResponse {
+        ListenResponse {
+            response_type: Some(ResponseType::DocumentDelete(DocumentDelete {
+                document: name.to_string(),
+                removed_target_ids: vec![TARGET_ID],
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn kind(event: WatchEvent) -> Option<ChangeKind> {
+        match event {
+            WatchEvent::Snapshot(snapshot) => Some(document_change_kind(
+                snapshot.previous.as_deref(),
+                &snapshot.current,
+            )),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn emits_snapshots_only_when_current_and_changed() {
+        let mut watch = Watch::document("a".to_string());
+
+        assert_eq!(kind(watch.handle(document_change("a", 1))), None);
+        // Not current yet, so there's no consistent snapshot
+        assert_eq!(kind(watch.handle(snapshot_point())), None);
+
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+        assert_eq!(
+            kind(watch.handle(snapshot_point())),
+            Some(ChangeKind::Added)
+        );
+        assert_eq!(kind(watch.handle(snapshot_point())), None);
+
+        watch.handle(document_change("a", 2));
+        assert_eq!(
+            kind(watch.handle(snapshot_point())),
+            Some(ChangeKind::Modified)
+        );
+
+        watch.handle(document_delete("a"));
+        assert_eq!(
+            kind(watch.handle(snapshot_point())),
+            Some(ChangeKind::Removed)
+        );
+        assert_eq!(watch.resume_token, b"token".to_vec());
+    }
+
+    #[test]
+    fn first_snapshot_of_missing_document_is_removed() {
+        let mut watch = Watch::document("a".to_string());
+
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+
+        assert_eq!(
+            kind(watch.handle(snapshot_point())),
+            Some(ChangeKind::Removed)
+        );
+    }
+
+    #[test]
+    fn reconnecting_keeps_last_snapshot() {
+        let mut watch = Watch::document("a".to_string());
+        watch.handle(document_change("a", 1));
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+        watch.handle(snapshot_point());
+
+        watch.reconnect();
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+
+        // Nothing changed while disconnected
+        assert_eq!(kind(watch.handle(snapshot_point())), None);
+    }
+
+    #[test]
+    fn reconnecting_before_snapshot_receives_changes_again() {
+        let mut watch = Watch::document("a".to_string());
+        watch.handle(document_change("a", 1));
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+
+        // The stream is dropped before the snapshot point, so the document
+        // was never emitted and must not be skipped by resuming
+        watch.reconnect();
+        assert_eq!(
+            watch.add_target_request("db".to_string()).target_change,
+            Some(listen_request::TargetChange::AddTarget(Target {
+                target_id: TARGET_ID,
+                target_type: Some(watch.target.clone()),
+                resume_type: None,
+                ..Default::default()
+            }))
+        );
+
+        watch.handle(document_change("a", 1));
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+        assert_eq!(
+            kind(watch.handle(snapshot_point())),
+            Some(ChangeKind::Added)
+        );
+        assert_eq!(watch.resume_token, b"token".to_vec());
+    }
+
+    #[test]
+    fn query_snapshots_are_ordered_by_document_path() {
+        let mut watch = Watch::query("parent".to_string(), StructuredQuery::default());
+        watch.handle(document_change("c/b", 1));
+        watch.handle(document_change("c/a-b", 1));
+        watch.handle(document_change("c/a", 1));
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+
+        let names = match watch.handle(snapshot_point()) {
+            WatchEvent::Snapshot(RawSnapshot {
+                previous: None,
+                current,
+                ..
+            }) => current.into_iter().map(|doc| doc.name).collect::<Vec<_>>(),
+            event => panic!("expected first snapshot, got {:?}", event),
+        };
+
+        assert_eq!(names, vec!["c/a", "c/a-b", "c/b"]);
+    }
+
+    #[test]
+    fn resuming_from_checkpoint_only_reports_later_changes() {
+        let mut watch = Watch::query("parent".to_string(), StructuredQuery::default());
+        watch.handle(document_change("c/a", 1));
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+        let checkpoint = match watch.handle(snapshot_point()) {
+            WatchEvent::Snapshot(snapshot) => snapshot.checkpoint,
+            event => panic!("expected snapshot, got {:?}", event),
+        };
+
+        let checkpoint = ListenCheckpoint::decode(&checkpoint.encode()).unwrap();
+        let mut watch = Watch::resume(checkpoint);
+        assert_eq!(
+            watch.add_target_request("db".to_string()).target_change,
+            Some(listen_request::TargetChange::AddTarget(Target {
+                target_id: TARGET_ID,
+                target_type: Some(watch.target.clone()),
+                resume_type: Some(target::ResumeType::ResumeToken(b"token".to_vec())),
+                ..Default::default()
+            }))
+        );
+
+        watch.handle(target_change(TargetChangeType::Current, vec![TARGET_ID]));
+        assert!(matches!(watch.handle(snapshot_point()), WatchEvent::None));
+
+        watch.handle(document_change("c/b", 1));
+        let changes = match watch.handle(snapshot_point()) {
+            WatchEvent::Snapshot(snapshot) => {
+                query_changes(snapshot.previous.as_deref().unwrap(), &snapshot.current)
+            }
+            event => panic!("expected snapshot, got {:?}", event),
+        };
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].1.name, "c/b");
+    }
+
+    #[test]
+    fn rejects_malformed_checkpoints() {
+        assert!(ListenCheckpoint::decode("not a checkpoint").is_err());
+        assert!(ListenCheckpoint::decode("").is_err());
+    }
+
+    #[test]
+    fn query_changes_have_indices_as_of_previous_changes() {
+        let previous = vec![doc("c/a", 1), doc("c/b", 1), doc("c/c", 1), doc("c/d", 1)];
+        let current = vec![doc("c/a", 1), doc("c/b", 2), doc("c/bb", 1), doc("c/d", 1)];
+
+        let changes: Vec<_> = query_changes(&previous, &current)
+            .into_iter()
+            .map(|(kind, doc, old_index, new_index)| (kind, doc.name, old_index, new_index))
+            .collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                (ChangeKind::Removed, "c/c".to_string(), Some(2), None),
+                (ChangeKind::Modified, "c/b".to_string(), Some(1), Some(1)),
+                (ChangeKind::Added, "c/bb".to_string(), None, Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_query_changes_add_every_document() {
+        let current = vec![doc("c/a", 1), doc("c/b", 1)];
+
+        let indices: Vec<_> = query_changes(&[], &current)
+            .into_iter()
+            .map(|(kind, _, old_index, new_index)| (kind, old_index, new_index))
+            .collect();
+
+        assert_eq!(
+            indices,
+            vec![
+                (ChangeKind::Added, None, Some(0)),
+                (ChangeKind::Added, None, Some(1)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Migrating the documents of a collection to another collection, for example
+//! when renaming a collection or changing the shape of its documents.
+
+use futures::StreamExt;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use firestore_grpc::v1::Document;
+
+use crate::error::FirebaseError;
+
+use super::{
+    client::{FirebaseStream, FirestoreClient},
+    reference::CollectionReference,
+    serde::{deserialize_firestore_document_fields, strip_reference_prefix},
+    write::{serialize_write, Write, WriteKind, MAX_BATCH_WRITE_SIZE},
+};
+
+const DEFAULT_PAGE_SIZE: u32 = 300;
+
+/// Options for [`migrate_collection`].
+#[derive(Debug, Clone)]
+pub struct MigrationOptions {
+    page_size: u32,
+    resume_after: Option<String>,
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            resume_after: None,
+        }
+    }
+}
+
+impl MigrationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many documents to read and write at a time. Progress is reported
+    /// once per page. Capped at 500, the maximum size of a write batch.
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size.clamp(1, MAX_BATCH_WRITE_SIZE as u32);
+        self
+    }
+
+    /// Continue an earlier migration, starting after the document with the
+    /// given ID. Use the [`last_document_id`](MigrationProgress::last_document_id)
+    /// of the last progress report of the earlier migration.
+    pub fn resume_after(mut self, document_id: impl Into<String>) -> Self {
+        self.resume_after = Some(document_id.into());
+        self
+    }
+}
+
+/// The progress of a migration, reported after each page of documents has
+/// been written. Can be serialized, e.g. to store it for resuming later.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationProgress {
+    /// The number of source documents read so far.
+    pub documents_read: u64,
+    /// The number of documents written to the destination so far. Documents
+    /// that the transform skipped are not counted.
+    pub documents_written: u64,
+    /// The ID of the last source document that has been migrated.
+    pub last_document_id: Option<String>,
+}
+
+/// Migrate the documents of the `src` collection to the `dst` collection,
+/// passing each document through `transform` on the way. Documents for which
+/// the transform returns `None` are skipped. Subcollections are not migrated,
+/// and the source documents are left untouched.
+///
+/// The documents are processed in pages, ordered by document ID. The returned
+/// stream yields the progress after each page and ends when all documents
+/// have been migrated. The migration only makes progress while the stream is
+/// polled, and stops at the first error.
+///
+/// Destination documents are overwritten, so a page can safely be migrated
+/// again. To resume an interrupted migration, pass the last reported
+/// [`last_document_id`](MigrationProgress::last_document_id) to
+/// [`MigrationOptions::resume_after`]. The counts in the progress reports
+/// start from zero when resuming.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::firestore::{
+///     collection,
+///     migration::{migrate_collection, MigrationOptions},
+/// };
+/// use futures::TryStreamExt;
+/// use serde::{Deserialize, Serialize};
+/// use ulid::Ulid;
+///
+/// #[derive(Deserialize)]
+/// struct OldUser {
+///     name: String,
+/// }
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// struct NewUser {
+///     first_name: String,
+///     last_name: String,
+/// }
+///
+/// let src = collection(format!("users-{}", Ulid::new()));
+/// let dst = collection(format!("users-{}", Ulid::new()));
+///
+/// for (id, name) in [("ada", "Ada Lovelace"), ("alan", "Alan Turing"), ("x", "")] {
+///     client
+///         .set_document(&src.doc(id), &serde_json::json!({ "name": name }))
+///         .await?;
+/// }
+///
+/// let progress: Vec<_> = migrate_collection(
+///     &client,
+///     &src,
+///     &dst,
+///     |user: OldUser| {
+///         let (first_name, last_name) = user.name.split_once(' ')?;
+///         Some(NewUser {
+///             first_name: first_name.to_string(),
+///             last_name: last_name.to_string(),
+///         })
+///     },
+///     MigrationOptions::new().page_size(2),
+/// )
+/// .try_collect()
+/// .await?;
+///
+/// let last = progress.last().unwrap();
+/// assert_eq!(last.documents_read, 3);
+/// assert_eq!(last.documents_written, 2);
+/// assert_eq!(last.last_document_id.as_deref(), Some("x"));
+///
+/// assert_eq!(
+///     client.get_document(&dst.doc("ada")).await?,
+///     Some(NewUser {
+///         first_name: "Ada".to_string(),
+///         last_name: "Lovelace".to_string(),
+///     })
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn migrate_collection<'a, I, O, F>(
+    client: &FirestoreClient,
+    src: &CollectionReference,
+    dst: &CollectionReference,
+    transform: F,
+    options: MigrationOptions,
+) -> FirebaseStream<'a, MigrationProgress, FirebaseError>
+where
+    I: DeserializeOwned + 'a,
+    O: Serialize + 'a,
+    F: FnMut(I) -> Option<O> + Send + 'a,
+{
+    let state = MigrationState {
+        client: client.clone(),
+        src: src.clone(),
+        dst: dst.clone(),
+        transform,
+        page_size: options.page_size,
+        progress: MigrationProgress {
+            last_document_id: options.resume_after,
+            ..Default::default()
+        },
+        done: false,
+    };
+
+    futures::stream::try_unfold(state, |mut state| async move {
+        if state.done {
+            return Ok(None);
+        }
+
+        let page = state
+            .client
+            .list_documents_page(
+                &state.src,
+                state.progress.last_document_id.as_deref(),
+                state.page_size,
+            )
+            .await?;
+
+        if page.is_empty() {
+            return Ok(None);
+        }
+
+        state.done = page.len() < state.page_size as usize;
+        let documents_read = page.len() as u64;
+        let last_document_id = page.last().map(|doc| document_id(doc).to_string());
+
+        let writes = transform_page(
+            &mut state.transform,
+            state.client.root_resource_path(),
+            &state.dst,
+            page,
+        )?;
+        let documents_written = writes.len() as u64;
+
+        if !writes.is_empty() {
+            for result in state.client.batch_write(writes).await {
+                result?;
+            }
+        }
+
+        state.progress.documents_read += documents_read;
+        state.progress.documents_written += documents_written;
+        state.progress.last_document_id = last_document_id;
+
+        Ok(Some((state.progress.clone(), state)))
+    })
+    .boxed()
+}
+
+struct MigrationState<F> {
+    client: FirestoreClient,
+    src: CollectionReference,
+    dst: CollectionReference,
+    transform: F,
+    page_size: u32,
+    progress: MigrationProgress,
+    done: bool,
+}
+
+/// Transforms a page of source documents into writes to the destination
+/// collection.
+fn transform_page<I, O, F>(
+    transform: &mut F,
+    root_resource_path: &str,
+    dst: &CollectionReference,
+    page: Vec<Document>,
+) -> Result<Vec<Write>, FirebaseError>
+where
+    I: DeserializeOwned,
+    O: Serialize,
+    F: FnMut(I) -> Option<O>,
+{
+    let mut writes = Vec::with_capacity(page.len());
+
+    for doc in page {
+        let dst_ref = dst.doc(document_id(&doc));
+        let input = deserialize_firestore_document_fields::<I>(doc.fields).map_err(|e| {
+            FirebaseError::FirestoreSerdeError {
+                source: e,
+                document: Some(strip_reference_prefix(&doc.name)),
+            }
+        })?;
+
+        if let Some(output) = transform(input) {
+            writes.push(serialize_write(
+                root_resource_path,
+                &dst_ref,
+                &output,
+                WriteKind::Set,
+            )?);
+        }
+    }
+
+    Ok(writes)
+}
+
+fn document_id(doc: &Document) -> &str {
+    doc.name.rsplit('/').next().unwrap_or_default()
+}
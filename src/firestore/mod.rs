@@ -278,11 +278,32 @@
 //! # }
 //! ```
 
+pub mod admin;
+pub mod aggregation;
+pub mod batch;
+pub mod buffered;
 pub mod client;
+pub mod consistency;
+pub mod counter;
+pub mod cursor;
+pub mod diff;
+pub mod fields;
+pub mod listen;
+pub mod migration;
+pub mod partition;
 pub mod query;
 pub mod reference;
 pub mod serde;
+pub mod session;
+pub mod settings;
+pub mod stream;
 mod token_provider;
+pub mod transaction;
+pub mod typed;
+pub mod usage;
+pub mod validate;
+pub mod vector;
+pub mod write;
 
 /// This module isn't really supposed to be exposed, but we are lacking
 /// `#[cfg(doctest)]`, and we can't make it private either since doctests are
@@ -293,3 +314,4 @@ pub mod test_helpers;
 
 pub use query::collection_group;
 pub use reference::collection;
+pub use write::transforms;
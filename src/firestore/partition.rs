@@ -0,0 +1,119 @@
+//! Splitting a collection group query into ranges that can be scanned in
+//! parallel. See
+//! [`FirestoreClient::partition_query`](super::client::FirestoreClient::partition_query).
+
+use super::reference::DocumentReference;
+
+/// A range of the documents in a collection group, ordered by document path.
+/// Together, the partitions returned by
+/// [`partition_query`](super::client::FirestoreClient::partition_query) cover
+/// every document of the collection group exactly once.
+///
+/// Partitions are cheap to clone and can be sent to other tasks, which run
+/// them with
+/// [`run_partition`](super::client::FirestoreClient::run_partition).
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPartition {
+    collection_id: String,
+    start_at: Option<DocumentReference>,
+    end_before: Option<DocumentReference>,
+}
+
+impl QueryPartition {
+    /// The ID of the collection group the partition belongs to.
+    pub fn collection_id(&self) -> &str {
+        &self.collection_id
+    }
+
+    /// The first document of the partition, or `None` if the partition
+    /// starts at the beginning of the collection group.
+    pub fn start_at(&self) -> Option<&DocumentReference> {
+        self.start_at.as_ref()
+    }
+
+    /// The first document after the partition, or `None` if the partition
+    /// extends to the end of the collection group.
+    pub fn end_before(&self) -> Option<&DocumentReference> {
+        self.end_before.as_ref()
+    }
+
+    /// Creates the partitions between the given split points, which don't
+    /// need to be ordered. Without split points, a single partition covers
+    /// the whole collection group.
+    pub(crate) fn from_split_points(
+        collection_id: String,
+        mut split_points: Vec<DocumentReference>,
+    ) -> Vec<Self> {
+        // Firestore orders documents by their path segment by segment, which
+        // differs from ordering the paths as strings when IDs contain e.g. `-`
+        split_points.sort_by_cached_key(|doc_ref| {
+            doc_ref
+                .to_string()
+                .split('/')
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        });
+        split_points.dedup();
+
+        let starts: Vec<_> = std::iter::once(None)
+            .chain(split_points.iter().cloned().map(Some))
+            .collect();
+        let ends = split_points
+            .into_iter()
+            .map(Some)
+            .chain(std::iter::once(None));
+
+        starts
+            .into_iter()
+            .zip(ends)
+            .map(|(start_at, end_before)| Self {
+                collection_id: collection_id.clone(),
+                start_at,
+                end_before,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::firestore::collection;
+
+    use super::*;
+
+    #[test]
+    fn partitions_cover_range_between_sorted_split_points() {
+        let a = collection("users").doc("a").collection("events").doc("1");
+        let a_b = collection("users").doc("a-b").collection("events").doc("1");
+        let b = collection("users").doc("b").collection("events").doc("1");
+
+        let partitions = QueryPartition::from_split_points(
+            "events".to_string(),
+            vec![b.clone(), a_b.clone(), a.clone(), b.clone()],
+        );
+
+        let ranges: Vec<_> = partitions
+            .iter()
+            .map(|p| (p.start_at().cloned(), p.end_before().cloned()))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (None, Some(a.clone())),
+                (Some(a), Some(a_b.clone())),
+                (Some(a_b), Some(b.clone())),
+                (Some(b), None),
+            ]
+        );
+        assert!(partitions.iter().all(|p| p.collection_id() == "events"));
+    }
+
+    #[test]
+    fn no_split_points_give_single_partition() {
+        let partitions = QueryPartition::from_split_points("events".to_string(), vec![]);
+
+        assert_eq!(partitions.len(), 1);
+        assert_eq!(partitions[0].start_at(), None);
+        assert_eq!(partitions[0].end_before(), None);
+    }
+}
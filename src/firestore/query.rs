@@ -14,12 +14,18 @@
 - [ ] not-in
 */
 
+use std::fmt::Display;
+
+use anyhow::anyhow;
 use firestore_grpc::v1::{
     structured_query::{
         composite_filter::Operator as CompositeFilterOperator,
-        field_filter::Operator as FieldFilterOperator, filter::FilterType as GrpcFilterType,
-        CompositeFilter as GrpcCompositeFilter, FieldFilter as GrpcFieldFilter, FieldReference,
-        Filter as GrpcFilter,
+        field_filter::Operator as FieldFilterOperator,
+        filter::FilterType as GrpcFilterType,
+        unary_filter::{OperandType, Operator as UnaryFilterOperator},
+        CompositeFilter as GrpcCompositeFilter, Direction as GrpcDirection,
+        FieldFilter as GrpcFieldFilter, FieldReference, Filter as GrpcFilter, Order as GrpcOrder,
+        UnaryFilter as GrpcUnaryFilter,
     },
     Value,
 };
@@ -28,7 +34,10 @@ use serde::Serialize;
 use crate::error::FirebaseError;
 
 use super::{
-    client::FirestoreClient, reference::CollectionReference, serde::serialize_to_value_type,
+    client::{FirestoreClient, FirestoreDocument},
+    cursor::Cursor,
+    reference::CollectionReference,
+    serde::serialize_to_value_type,
 };
 
 /// Represents a Firestore query operator used to test a field's value against
@@ -140,6 +149,41 @@ impl<T: Eq + Serialize> QueryOperator<T> for ArrayContains<T> {
     }
 }
 
+/// The field path of the document ID, for filtering and ordering by it. The
+/// values to filter by are
+/// [`DocumentReference`](super::reference::DocumentReference)s, and documents are ordered by
+/// their path, segment by segment.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+/// use fireplace::firestore::{
+///     collection,
+///     query::{filter, CollectionQuery, Direction, GreaterThan, DOCUMENT_ID},
+///     test_helpers::Landmark,
+/// };
+/// use futures::TryStreamExt;
+///
+/// let landmarks = collection("cities").doc("SF").collection("landmarks");
+///
+/// // The landmarks after "golden-gate", by document ID
+/// let query = CollectionQuery::new(landmarks.clone())
+///     .with_filter(filter(DOCUMENT_ID, GreaterThan(landmarks.doc("golden-gate"))))
+///     .order_by(DOCUMENT_ID, Direction::Ascending);
+///
+/// let after: Vec<Landmark> = client.run_query(query).await?.try_collect().await?;
+///
+/// assert_eq!(after.len(), 1);
+/// assert_eq!(after[0].name, "Legion of Honor");
+/// # Ok(())
+/// # }
+/// ```
+pub const DOCUMENT_ID: &str = "__name__";
+
 pub fn filter<'a, T: Serialize + 'a + Send>(
     field: impl Into<String> + 'a,
     check_against: impl QueryOperator<T> + 'a,
@@ -148,15 +192,113 @@ pub fn filter<'a, T: Serialize + 'a + Send>(
     Filter::Single(field_filter)
 }
 
+/// Matches documents where the field is `null`. Firestore doesn't compare
+/// with `null` in [`EqualTo`] filters, so this is the way to find such
+/// documents. Combine it with other filters with e.g. [`Filter::combine`].
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::firestore::{
+///     collection,
+///     query::{filter, is_null, CollectionQuery, EqualTo},
+/// };
+///
+/// let unassigned_tickets = CollectionQuery::new(collection("tickets"))
+///     .with_filter(is_null("assignee").combine(filter("status", EqualTo("open"))));
+/// ```
+pub fn is_null<'a>(field: impl Into<String>) -> Filter<'a> {
+    unary_filter(field.into(), UnaryFilterOperator::IsNull)
+}
+
+/// Matches documents where the field exists and is not `null`.
+pub fn is_not_null<'a>(field: impl Into<String>) -> Filter<'a> {
+    unary_filter(field.into(), UnaryFilterOperator::IsNotNull)
+}
+
+/// Matches documents where the field is `NaN`, which isn't equal to anything,
+/// not even itself.
+pub fn is_nan<'a>(field: impl Into<String>) -> Filter<'a> {
+    unary_filter(field.into(), UnaryFilterOperator::IsNan)
+}
+
+/// Matches documents where the field exists and is not `NaN`.
+pub fn is_not_nan<'a>(field: impl Into<String>) -> Filter<'a> {
+    unary_filter(field.into(), UnaryFilterOperator::IsNotNan)
+}
+
+fn unary_filter<'a>(field: String, op: UnaryFilterOperator) -> Filter<'a> {
+    Filter::Single(FieldFilter {
+        field,
+        check: FieldCheck::Unary(op),
+    })
+}
+
+/// Matches documents for which all of the filters match. Filters can be
+/// nested to any depth, e.g. to match documents that are either in stock or
+/// can be backordered, and cost at most 100:
+///
+/// ```
+/// use fireplace::firestore::query::{and, filter, or, EqualTo, LessThanOrEqual};
+///
+/// let affordable_and_available = and([
+///     or([
+///         filter("in_stock", EqualTo(true)),
+///         filter("backorderable", EqualTo(true)),
+///     ]),
+///     filter("price", LessThanOrEqual(100)),
+/// ]);
+/// ```
+///
+/// Queries with an empty group are rejected when they are run.
+pub fn and<'a>(filters: impl IntoIterator<Item = Filter<'a>>) -> Filter<'a> {
+    Filter::And(filters.into_iter().collect())
+}
+
+/// Matches documents for which at least one of the filters matches. See
+/// [`and`] for nesting filters. Queries with an empty group are rejected
+/// when they are run.
+pub fn or<'a>(filters: impl IntoIterator<Item = Filter<'a>>) -> Filter<'a> {
+    Filter::Or(filters.into_iter().collect())
+}
+
 pub enum Filter<'a> {
     Composite(Vec<FieldFilter<'a>>),
     Single(FieldFilter<'a>),
+    /// A group of filters that must all match, created with [`and`].
+    And(Vec<Filter<'a>>),
+    /// A group of filters of which at least one must match, created with
+    /// [`or`].
+    Or(Vec<Filter<'a>>),
 }
 
 pub struct FieldFilter<'a> {
     field: String,
-    op: FieldFilterOperator,
-    value: Box<dyn erased_serde::Serialize + 'a + Send>,
+    check: FieldCheck<'a>,
+}
+
+/// How a [`FieldFilter`] checks the value of its field.
+enum FieldCheck<'a> {
+    Compare {
+        op: FieldFilterOperator,
+        value: Box<dyn erased_serde::Serialize + 'a + Send>,
+    },
+    Unary(UnaryFilterOperator),
+}
+
+impl<'a> FieldFilter<'a> {
+    /// The comparison the filter amounts to, as far as Firestore's
+    /// restrictions on combining filters are concerned. Checking for `null`
+    /// or `NaN` counts as `==`, and checking for anything else as `!=`.
+    fn effective_op(&self) -> FieldFilterOperator {
+        match &self.check {
+            FieldCheck::Compare { op, .. } => *op,
+            FieldCheck::Unary(UnaryFilterOperator::IsNull | UnaryFilterOperator::IsNan) => {
+                FieldFilterOperator::Equal
+            }
+            FieldCheck::Unary(_) => FieldFilterOperator::NotEqual,
+        }
+    }
 }
 
 impl<'a> Filter<'a> {
@@ -177,26 +319,250 @@ impl<'a> Filter<'a> {
                 Filter::Composite(filters)
             }
             Filter::Single(filter) => Filter::Composite(vec![filter, other_field_filter]),
+            Filter::And(mut filters) => {
+                filters.push(Filter::Single(other_field_filter));
+                Filter::And(filters)
+            }
+            filter @ Filter::Or(_) => Filter::And(vec![filter, Filter::Single(other_field_filter)]),
         };
 
         new_filter
     }
 
     pub fn combine(self, other: Self) -> Self {
+        if self.is_group() || other.is_group() {
+            return Self::And(vec![self, other]);
+        }
+
         let (mut filters, other) = match (self, other) {
             (Self::Composite(filters), other) | (other, Self::Composite(filters)) => {
                 (filters, other)
             }
             (Self::Single(filter), other) => (vec![filter], other),
+            _ => unreachable!("groups are combined above"),
         };
 
         match other {
             Self::Composite(other_filters) => filters.extend(other_filters),
             Self::Single(other_filter) => filters.push(other_filter),
+            Self::And(_) | Self::Or(_) => unreachable!("groups are combined above"),
         }
 
         Self::Composite(filters)
     }
+
+    fn is_group(&self) -> bool {
+        matches!(self, Self::And(_) | Self::Or(_))
+    }
+
+    /// All field filters, including those nested in groups.
+    fn field_filters(&self) -> Vec<&FieldFilter<'a>> {
+        match self {
+            Self::Composite(filters) => filters.iter().collect(),
+            Self::Single(filter) => vec![filter],
+            Self::And(filters) | Self::Or(filters) => {
+                filters.iter().flat_map(Filter::field_filters).collect()
+            }
+        }
+    }
+}
+
+/// The direction in which query results are ordered by a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ascending,
+    Descending,
+}
+
+/// An ordering of query results by a field. See e.g.
+/// [`CollectionQuery::order_by`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderBy {
+    pub field: String,
+    pub direction: Direction,
+}
+
+/// Where the results of a query start or end. See e.g.
+/// [`CollectionQuery::start_after`] and [`CollectionQuery::end_before`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryBound {
+    pub cursor: Cursor,
+    /// Whether the document at the cursor is included in the results.
+    pub inclusive: bool,
+}
+
+pub(crate) fn into_grpc_order(order: OrderBy) -> GrpcOrder {
+    let direction = match order.direction {
+        Direction::Ascending => GrpcDirection::Ascending,
+        Direction::Descending => GrpcDirection::Descending,
+    };
+
+    GrpcOrder {
+        field: Some(FieldReference {
+            field_path: order.field,
+        }),
+        direction: direction as i32,
+    }
+}
+
+/// A problem with a query that makes Firestore reject it. See [`lint_query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryLint {
+    /// A filter has an empty field path.
+    EmptyFieldPath,
+    /// Inequality filters (`<`, `<=`, `>`, `>=`, `!=`) are used on more than
+    /// one field.
+    InequalityOnMultipleFields(Vec<String>),
+    /// More than one `!=` filter is used.
+    MultipleNotEqual,
+    /// More than one `array-contains` filter is used.
+    MultipleArrayContains,
+    /// The query has an inequality filter, but is first ordered by another
+    /// field.
+    FirstOrderByNotOnInequalityField {
+        inequality_field: String,
+        order_by_field: String,
+    },
+    /// A start or end cursor has values, but the query isn't ordered by any
+    /// field the values could be compared to.
+    CursorWithoutOrderBy,
+}
+
+impl Display for QueryLint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyFieldPath => write!(f, "A filter has an empty field path"),
+            Self::InequalityOnMultipleFields(fields) => write!(
+                f,
+                "Inequality filters are used on multiple fields ({}), but Firestore only \
+                 allows them on a single field. Keep one of them in the query and apply the \
+                 others to the results",
+                fields.join(", ")
+            ),
+            Self::MultipleNotEqual => write!(
+                f,
+                "A query may contain at most one != filter. Replace the others with a filter \
+                 applied to the results"
+            ),
+            Self::MultipleArrayContains => write!(
+                f,
+                "A query may contain at most one array-contains filter. Replace the others \
+                 with a filter applied to the results"
+            ),
+            Self::FirstOrderByNotOnInequalityField {
+                inequality_field,
+                order_by_field,
+            } => write!(
+                f,
+                "The query has an inequality filter on '{}', so it must first be ordered by \
+                 that field, but it is first ordered by '{}'",
+                inequality_field, order_by_field
+            ),
+            Self::CursorWithoutOrderBy => write!(
+                f,
+                "A cursor has values, but the query isn't ordered by any field. Order the \
+                 query by the fields of the cursor values"
+            ),
+        }
+    }
+}
+
+/// Checks a query for combinations of filters, orderings and cursors that
+/// Firestore rejects, without making any requests. Returns an empty list if no problems were
+/// found, which doesn't guarantee that Firestore accepts the query, e.g.
+/// because a composite index is missing.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::firestore::{
+///     collection,
+///     query::{filter, lint_query, CollectionQuery, GreaterThan, LessThan, QueryLint},
+/// };
+///
+/// let query = CollectionQuery::new(collection("landmarks"))
+///     .with_filter(filter("height", GreaterThan(100)).and("year_built", LessThan(1900)));
+///
+/// assert_eq!(
+///     lint_query(&query),
+///     vec![QueryLint::InequalityOnMultipleFields(vec![
+///         "height".to_string(),
+///         "year_built".to_string(),
+///     ])]
+/// );
+/// ```
+pub fn lint_query<'a>(query: &impl FirestoreQuery<'a>) -> Vec<QueryLint> {
+    let field_filters = query
+        .filter_ref()
+        .map(|filter| filter.field_filters())
+        .unwrap_or_default();
+
+    let mut lints = vec![];
+
+    if field_filters.iter().any(|f| f.field.is_empty()) {
+        lints.push(QueryLint::EmptyFieldPath);
+    }
+
+    let mut inequality_fields: Vec<String> = vec![];
+    for field_filter in &field_filters {
+        if is_inequality(field_filter.effective_op())
+            && !inequality_fields.contains(&field_filter.field)
+        {
+            inequality_fields.push(field_filter.field.clone());
+        }
+    }
+    if inequality_fields.len() > 1 {
+        lints.push(QueryLint::InequalityOnMultipleFields(
+            inequality_fields.clone(),
+        ));
+    }
+
+    let count_op = |op: FieldFilterOperator| {
+        field_filters
+            .iter()
+            .filter(|f| f.effective_op() == op)
+            .count()
+    };
+    if count_op(FieldFilterOperator::NotEqual) > 1 {
+        lints.push(QueryLint::MultipleNotEqual);
+    }
+    if count_op(FieldFilterOperator::ArrayContains) > 1 {
+        lints.push(QueryLint::MultipleArrayContains);
+    }
+
+    if let (Some(inequality_field), Some(first_order)) =
+        (inequality_fields.first(), query.ordering().first())
+    {
+        if inequality_fields.len() == 1 && first_order.field != *inequality_field {
+            lints.push(QueryLint::FirstOrderByNotOnInequalityField {
+                inequality_field: inequality_field.clone(),
+                order_by_field: first_order.field.clone(),
+            });
+        }
+    }
+
+    // A cursor with only a document is compared to the document name, which
+    // every query is ordered by last
+    let has_cursor_values = [query.start(), query.end()]
+        .into_iter()
+        .flatten()
+        .any(|bound| !bound.cursor.values().is_empty());
+    if has_cursor_values && query.ordering().is_empty() {
+        lints.push(QueryLint::CursorWithoutOrderBy);
+    }
+
+    lints
+}
+
+fn is_inequality(op: FieldFilterOperator) -> bool {
+    matches!(
+        op,
+        FieldFilterOperator::LessThan
+            | FieldFilterOperator::LessThanOrEqual
+            | FieldFilterOperator::GreaterThan
+            | FieldFilterOperator::GreaterThanOrEqual
+            | FieldFilterOperator::NotEqual
+    )
 }
 
 fn create_field_filter<'a, T, Q>(field: String, query_op: Q) -> FieldFilter<'a>
@@ -209,8 +575,10 @@ where
 
     FieldFilter {
         field,
-        op,
-        value: Box::new(value),
+        check: FieldCheck::Compare {
+            op,
+            value: Box::new(value),
+        },
     }
 }
 
@@ -219,9 +587,7 @@ pub(crate) fn try_into_grpc_filter(
     root_resource_path: &str,
 ) -> Result<GrpcFilter, FirebaseError> {
     let filter_type = match filter {
-        Filter::Single(filter) => {
-            GrpcFilterType::FieldFilter(try_into_grpc_field_filter(filter, root_resource_path)?)
-        }
+        Filter::Single(filter) => try_into_grpc_filter_type(filter, root_resource_path)?,
         Filter::Composite(filters) => {
             let f = filters
                 .into_iter()
@@ -236,6 +602,12 @@ pub(crate) fn try_into_grpc_filter(
                 filters: f,
             })
         }
+        Filter::And(filters) => {
+            try_into_grpc_group(CompositeFilterOperator::And, filters, root_resource_path)?
+        }
+        Filter::Or(filters) => {
+            try_into_grpc_group(CompositeFilterOperator::Or, filters, root_resource_path)?
+        }
     };
 
     Ok(GrpcFilter {
@@ -243,46 +615,57 @@ pub(crate) fn try_into_grpc_filter(
     })
 }
 
-fn try_into_grpc_filter_type(
-    field_filter: FieldFilter,
+fn try_into_grpc_group(
+    op: CompositeFilterOperator,
+    filters: Vec<Filter>,
     root_resource_path: &str,
 ) -> Result<GrpcFilterType, FirebaseError> {
-    let value = serialize_to_value_type(&field_filter.value, root_resource_path)?;
-    let firestore_value = Value {
-        value_type: Some(value),
-    };
+    // Firestore rejects composite filters without any filters, and it's
+    // ambiguous whether an empty group should match everything or nothing
+    if filters.is_empty() {
+        return Err(anyhow!("A filter group must contain at least one filter").into());
+    }
 
-    let filter_type = GrpcFilterType::FieldFilter(GrpcFieldFilter {
-        field: Some(firestore_grpc::v1::structured_query::FieldReference {
-            field_path: field_filter.field,
-        }),
-        op: field_filter.op as i32,
-        value: Some(firestore_value),
-    });
+    let filters = filters
+        .into_iter()
+        .map(|f| try_into_grpc_filter(f, root_resource_path))
+        .collect::<Result<Vec<_>, FirebaseError>>()?;
 
-    Ok(filter_type)
+    Ok(GrpcFilterType::CompositeFilter(GrpcCompositeFilter {
+        op: op as i32,
+        filters,
+    }))
 }
 
-fn try_into_grpc_field_filter(
+fn try_into_grpc_filter_type(
     field_filter: FieldFilter,
     root_resource_path: &str,
-) -> Result<GrpcFieldFilter, FirebaseError> {
-    let value_type = serialize_to_value_type(&field_filter.value, root_resource_path)?;
-    let value = Value {
-        value_type: Some(value_type),
+) -> Result<GrpcFilterType, FirebaseError> {
+    let field = FieldReference {
+        field_path: field_filter.field,
     };
 
-    let grpc_field_filter = GrpcFieldFilter {
-        field: Some(FieldReference {
-            field_path: field_filter.field,
+    let filter_type = match field_filter.check {
+        FieldCheck::Compare { op, value } => {
+            let value = serialize_to_value_type(&value, root_resource_path)?;
+            GrpcFilterType::FieldFilter(GrpcFieldFilter {
+                field: Some(field),
+                op: op as i32,
+                value: Some(Value {
+                    value_type: Some(value),
+                }),
+            })
+        }
+        FieldCheck::Unary(op) => GrpcFilterType::UnaryFilter(GrpcUnaryFilter {
+            op: op as i32,
+            operand_type: Some(OperandType::Field(field)),
         }),
-        op: field_filter.op as i32,
-        value: Some(value),
     };
 
-    Ok(grpc_field_filter)
+    Ok(filter_type)
 }
 
+#[derive(Default)]
 pub(crate) struct ApiQueryOptions<'a> {
     pub parent: String,
     pub collection_name: String,
@@ -291,6 +674,18 @@ pub(crate) struct ApiQueryOptions<'a> {
     pub offset: Option<i32>,
     /// Whether to search descendant collections with the same name
     pub should_search_descendants: bool,
+    /// Only return documents after the document with this resource name. The
+    /// results are ordered by document name when set, so it can't be combined
+    /// with `order_by` or cursors.
+    pub start_after: Option<String>,
+    pub order_by: Vec<OrderBy>,
+    pub start: Option<QueryBound>,
+    pub end: Option<QueryBound>,
+    /// Whether `limit` applies to the end of the results rather than the
+    /// start.
+    pub limit_to_last: bool,
+    /// Only read the names of the documents, not their fields.
+    pub names_only: bool,
 }
 
 impl<'a> ApiQueryOptions<'a> {
@@ -307,27 +702,42 @@ impl<'a> ApiQueryOptions<'a> {
             parent: parent_path,
             collection_name: query.collection_name().to_string(),
             limit: query.limit(),
+            limit_to_last: query.is_limit_to_last(),
             offset: query.offset(),
             should_search_descendants: query.should_search_descendants(),
+            order_by: query.ordering().to_vec(),
+            start: query.start().cloned(),
+            end: query.end().cloned(),
             filter: query.filter(),
+            start_after: None,
+            names_only: false,
         }
     }
 }
 
 pub trait FirestoreQuery<'a> {
     fn filter(self) -> Option<Filter<'a>>;
+    fn filter_ref(&self) -> Option<&Filter<'a>>;
     fn collection_name(&self) -> &str;
     fn parent_path(&self) -> Option<String>;
     fn should_search_descendants(&self) -> bool;
     fn limit(&self) -> Option<i32>;
+    fn is_limit_to_last(&self) -> bool;
     fn offset(&self) -> Option<i32>;
+    fn ordering(&self) -> &[OrderBy];
+    fn start(&self) -> Option<&QueryBound>;
+    fn end(&self) -> Option<&QueryBound>;
 }
 
 pub struct CollectionGroupQuery<'a> {
     collection_name: String,
     filter: Option<Filter<'a>>,
     limit: Option<i32>,
+    limit_to_last: bool,
     offset: Option<i32>,
+    order_by: Vec<OrderBy>,
+    start: Option<QueryBound>,
+    end: Option<QueryBound>,
 }
 
 pub fn collection_group<'a>(collection_name: impl Into<String>) -> CollectionGroupQuery<'a> {
@@ -340,7 +750,11 @@ impl<'a> CollectionGroupQuery<'a> {
             collection_name: collection_name.into(),
             filter: None,
             limit: None,
+            limit_to_last: false,
             offset: None,
+            order_by: vec![],
+            start: None,
+            end: None,
         }
     }
 
@@ -351,6 +765,15 @@ impl<'a> CollectionGroupQuery<'a> {
 
     pub fn with_limit(mut self, limit: u32) -> Self {
         self.limit = Some(limit as i32);
+        self.limit_to_last = false;
+        self
+    }
+
+    /// Only return the last `limit` results. See
+    /// [`CollectionQuery::limit_to_last`].
+    pub fn limit_to_last(mut self, limit: u32) -> Self {
+        self.limit = Some(limit as i32);
+        self.limit_to_last = true;
         self
     }
 
@@ -358,6 +781,70 @@ impl<'a> CollectionGroupQuery<'a> {
         self.offset = Some(offset as i32);
         self
     }
+
+    /// Order the results by the field. Call it again to order results with
+    /// the same value of the field by another field.
+    pub fn order_by(mut self, field: impl Into<String>, direction: Direction) -> Self {
+        self.order_by.push(OrderBy {
+            field: field.into(),
+            direction,
+        });
+        self
+    }
+
+    /// Start the results at the cursor, including the document at the
+    /// cursor. See [`CollectionQuery::start_after`].
+    pub fn start_at(mut self, cursor: Cursor) -> Self {
+        self.start = Some(QueryBound {
+            cursor,
+            inclusive: true,
+        });
+        self
+    }
+
+    /// Start the results after the cursor. See
+    /// [`CollectionQuery::start_after`].
+    pub fn start_after(mut self, cursor: Cursor) -> Self {
+        self.start = Some(QueryBound {
+            cursor,
+            inclusive: false,
+        });
+        self
+    }
+
+    /// Start the results after the document. Must be called after
+    /// [`order_by`](Self::order_by). See
+    /// [`CollectionQuery::start_after_doc`].
+    pub fn start_after_doc<T: Serialize>(
+        mut self,
+        document: &FirestoreDocument<T>,
+    ) -> Result<Self, FirebaseError> {
+        self.start = Some(QueryBound {
+            cursor: Cursor::from_document(document, &self.order_by)?,
+            inclusive: false,
+        });
+        Ok(self)
+    }
+
+    /// End the results at the cursor, including the document at the cursor.
+    /// See [`CollectionQuery::end_before`].
+    pub fn end_at(mut self, cursor: Cursor) -> Self {
+        self.end = Some(QueryBound {
+            cursor,
+            inclusive: true,
+        });
+        self
+    }
+
+    /// End the results before the cursor. See
+    /// [`CollectionQuery::end_before`].
+    pub fn end_before(mut self, cursor: Cursor) -> Self {
+        self.end = Some(QueryBound {
+            cursor,
+            inclusive: false,
+        });
+        self
+    }
 }
 
 impl<'a> FirestoreQuery<'a> for CollectionGroupQuery<'a> {
@@ -365,6 +852,10 @@ impl<'a> FirestoreQuery<'a> for CollectionGroupQuery<'a> {
         self.filter
     }
 
+    fn filter_ref(&self) -> Option<&Filter<'a>> {
+        self.filter.as_ref()
+    }
+
     fn collection_name(&self) -> &str {
         &self.collection_name
     }
@@ -381,9 +872,25 @@ impl<'a> FirestoreQuery<'a> for CollectionGroupQuery<'a> {
         self.limit
     }
 
+    fn is_limit_to_last(&self) -> bool {
+        self.limit_to_last
+    }
+
     fn offset(&self) -> Option<i32> {
         self.offset
     }
+
+    fn ordering(&self) -> &[OrderBy] {
+        &self.order_by
+    }
+
+    fn start(&self) -> Option<&QueryBound> {
+        self.start.as_ref()
+    }
+
+    fn end(&self) -> Option<&QueryBound> {
+        self.end.as_ref()
+    }
 }
 
 impl<'a> FirestoreQuery<'a> for CollectionReference {
@@ -391,6 +898,10 @@ impl<'a> FirestoreQuery<'a> for CollectionReference {
         None
     }
 
+    fn filter_ref(&self) -> Option<&Filter<'a>> {
+        None
+    }
+
     fn parent_path(&self) -> Option<String> {
         self.parent().map(|p| p.to_string())
     }
@@ -407,16 +918,36 @@ impl<'a> FirestoreQuery<'a> for CollectionReference {
         None
     }
 
+    fn is_limit_to_last(&self) -> bool {
+        false
+    }
+
     fn offset(&self) -> Option<i32> {
         None
     }
+
+    fn ordering(&self) -> &[OrderBy] {
+        &[]
+    }
+
+    fn start(&self) -> Option<&QueryBound> {
+        None
+    }
+
+    fn end(&self) -> Option<&QueryBound> {
+        None
+    }
 }
 
 pub struct CollectionQuery<'a> {
     collection: CollectionReference,
     filter: Option<Filter<'a>>,
     limit: Option<i32>,
+    limit_to_last: bool,
     offset: Option<i32>,
+    order_by: Vec<OrderBy>,
+    start: Option<QueryBound>,
+    end: Option<QueryBound>,
 }
 
 impl<'a> CollectionQuery<'a> {
@@ -425,7 +956,11 @@ impl<'a> CollectionQuery<'a> {
             collection,
             filter: None,
             limit: None,
+            limit_to_last: false,
             offset: None,
+            order_by: vec![],
+            start: None,
+            end: None,
         }
     }
 
@@ -436,6 +971,57 @@ impl<'a> CollectionQuery<'a> {
 
     pub fn with_limit(mut self, limit: u32) -> Self {
         self.limit = Some(limit as i32);
+        self.limit_to_last = false;
+        self
+    }
+
+    /// Only return the last `limit` results, e.g. the most recent entries of
+    /// a log ordered by time. The query must be [ordered](Self::order_by).
+    ///
+    /// Firestore runs the query in the reverse order with a regular limit,
+    /// so only the returned documents are read, and the results are reversed
+    /// again before they are returned. They are therefore only returned once
+    /// they have all been received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{
+    ///     collection,
+    ///     query::{CollectionQuery, Direction},
+    /// };
+    /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
+    ///
+    /// let log = collection("jobs").doc(Ulid::new()).collection("log");
+    /// for step in 0..5 {
+    ///     client
+    ///         .set_document(&log.doc(Ulid::new()), &serde_json::json!({ "step": step }))
+    ///         .await?;
+    /// }
+    ///
+    /// let last_steps: Vec<serde_json::Value> = client
+    ///     .run_query(
+    ///         CollectionQuery::new(log)
+    ///             .order_by("step", Direction::Ascending)
+    ///             .limit_to_last(2),
+    ///     )
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// // Still in ascending order
+    /// assert_eq!(last_steps[0]["step"], 3);
+    /// assert_eq!(last_steps[1]["step"], 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn limit_to_last(mut self, limit: u32) -> Self {
+        self.limit = Some(limit as i32);
+        self.limit_to_last = true;
         self
     }
 
@@ -443,6 +1029,243 @@ impl<'a> CollectionQuery<'a> {
         self.offset = Some(offset as i32);
         self
     }
+
+    /// Order the results by the field. Call it again to order results with
+    /// the same value of the field by another field. Firestore requires the
+    /// first ordering to be by the field of an inequality filter, if there is
+    /// one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use fireplace::firestore::{
+    ///     collection,
+    ///     query::{CollectionQuery, Direction},
+    ///     test_helpers::Landmark,
+    /// };
+    /// use futures::TryStreamExt;
+    ///
+    /// let query = CollectionQuery::new(collection("cities").doc("SF").collection("landmarks"))
+    ///     .order_by("name", Direction::Descending);
+    ///
+    /// let landmarks: Vec<Landmark> = client.run_query(query).await?.try_collect().await?;
+    ///
+    /// assert_eq!(landmarks[0].name, "Legion of Honor");
+    /// assert_eq!(landmarks[1].name, "Golden Gate Bridge");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn order_by(mut self, field: impl Into<String>, direction: Direction) -> Self {
+        self.order_by.push(OrderBy {
+            field: field.into(),
+            direction,
+        });
+        self
+    }
+
+    /// Start the results at the cursor, including the document at the
+    /// cursor. See [`start_after`](Self::start_after).
+    pub fn start_at(mut self, cursor: Cursor) -> Self {
+        self.start = Some(QueryBound {
+            cursor,
+            inclusive: true,
+        });
+        self
+    }
+
+    /// Start the results after the cursor, which holds the values of the
+    /// fields the query is [ordered by](Self::order_by), in order. Unlike an
+    /// offset, the documents before the cursor are neither read nor billed,
+    /// so it is the way to page through large results.
+    ///
+    /// If the cursor has a [document](Cursor::with_document), the results are
+    /// also ordered by document path, so results with the same values as the
+    /// cursor are split at that document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use fireplace::firestore::{
+    ///     collection,
+    ///     cursor::Cursor,
+    ///     query::{CollectionQuery, Direction},
+    ///     test_helpers::Landmark,
+    /// };
+    /// use futures::TryStreamExt;
+    ///
+    /// let landmarks = collection("cities").doc("SF").collection("landmarks");
+    ///
+    /// let first_page: Vec<Landmark> = client
+    ///     .run_query(
+    ///         CollectionQuery::new(landmarks.clone())
+    ///             .order_by("name", Direction::Ascending)
+    ///             .with_limit(1),
+    ///     )
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    /// assert_eq!(first_page[0].name, "Golden Gate Bridge");
+    ///
+    /// // Continue after the last landmark of the previous page
+    /// let cursor = Cursor::new().with_value(&first_page[0].name)?;
+    /// let second_page: Vec<Landmark> = client
+    ///     .run_query(
+    ///         CollectionQuery::new(landmarks)
+    ///             .order_by("name", Direction::Ascending)
+    ///             .start_after(cursor)
+    ///             .with_limit(1),
+    ///     )
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    /// assert_eq!(second_page[0].name, "Legion of Honor");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn start_after(mut self, cursor: Cursor) -> Self {
+        self.start = Some(QueryBound {
+            cursor,
+            inclusive: false,
+        });
+        self
+    }
+
+    /// Start the results after the document, e.g. the last document of the
+    /// previous page, taking the cursor values from the fields the query is
+    /// ordered by. It must be called after [`order_by`](Self::order_by), as
+    /// only the orderings added before it are used; running a query that is
+    /// ordered by more fields fails. The document's ID is included in the
+    /// cursor, so pages are stable even when several documents have the
+    /// same values.
+    ///
+    /// Fails if the document doesn't have one of the ordered fields.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use fireplace::firestore::{
+    ///     client::FirestoreDocument, collection, query::Direction, test_helpers::Landmark,
+    /// };
+    /// use futures::TryStreamExt;
+    ///
+    /// let landmarks = collection("cities").doc("SF").collection("landmarks");
+    ///
+    /// let page_one: Vec<FirestoreDocument<Landmark>> = client
+    ///     .run_query_with_metadata(
+    ///         landmarks
+    ///             .clone()
+    ///             .order_by("type", Direction::Ascending)
+    ///             .with_limit(1),
+    ///     )
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// let last = page_one.last().unwrap();
+    /// let page_two: Vec<Landmark> = client
+    ///     .run_query(
+    ///         landmarks
+    ///             .order_by("type", Direction::Ascending)
+    ///             .start_after_doc(last)?
+    ///             .with_limit(1),
+    ///     )
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// assert_eq!(
+    ///     page_two.into_iter().map(|l| l.name).collect::<Vec<_>>(),
+    ///     ["Legion of Honor"]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn start_after_doc<T: Serialize>(
+        mut self,
+        document: &FirestoreDocument<T>,
+    ) -> Result<Self, FirebaseError> {
+        self.start = Some(QueryBound {
+            cursor: Cursor::from_document(document, &self.order_by)?,
+            inclusive: false,
+        });
+        Ok(self)
+    }
+
+    /// End the results at the cursor, including the document at the cursor.
+    /// See [`end_before`](Self::end_before).
+    pub fn end_at(mut self, cursor: Cursor) -> Self {
+        self.end = Some(QueryBound {
+            cursor,
+            inclusive: true,
+        });
+        self
+    }
+
+    /// End the results before the cursor, which holds the values of the
+    /// fields the query is [ordered by](Self::order_by), in order. Together
+    /// with a start cursor, this selects a range of the ordered results, e.g.
+    /// a time window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{
+    ///     collection,
+    ///     cursor::Cursor,
+    ///     query::{CollectionQuery, Direction},
+    /// };
+    /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
+    ///
+    /// let readings = collection("sensors").doc(Ulid::new()).collection("readings");
+    /// for minute in 0..10 {
+    ///     client
+    ///         .set_document(
+    ///             &readings.doc(format!("reading-{minute}")),
+    ///             &serde_json::json!({ "minute": minute }),
+    ///         )
+    ///         .await?;
+    /// }
+    ///
+    /// // The readings from minute 3 up to, but not including, minute 6
+    /// let window: Vec<serde_json::Value> = client
+    ///     .run_query(
+    ///         CollectionQuery::new(readings)
+    ///             .order_by("minute", Direction::Ascending)
+    ///             .start_at(Cursor::new().with_value(&3)?)
+    ///             .end_before(Cursor::new().with_value(&6)?),
+    ///     )
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    ///
+    /// assert_eq!(window.len(), 3);
+    /// assert_eq!(window[0]["minute"], 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn end_before(mut self, cursor: Cursor) -> Self {
+        self.end = Some(QueryBound {
+            cursor,
+            inclusive: false,
+        });
+        self
+    }
 }
 
 impl<'a> FirestoreQuery<'a> for CollectionQuery<'a> {
@@ -450,6 +1273,10 @@ impl<'a> FirestoreQuery<'a> for CollectionQuery<'a> {
         self.filter
     }
 
+    fn filter_ref(&self) -> Option<&Filter<'a>> {
+        self.filter.as_ref()
+    }
+
     fn parent_path(&self) -> Option<String> {
         self.collection.parent_path()
     }
@@ -466,9 +1293,25 @@ impl<'a> FirestoreQuery<'a> for CollectionQuery<'a> {
         self.limit
     }
 
+    fn is_limit_to_last(&self) -> bool {
+        self.limit_to_last
+    }
+
     fn offset(&self) -> Option<i32> {
         self.offset
     }
+
+    fn ordering(&self) -> &[OrderBy] {
+        &self.order_by
+    }
+
+    fn start(&self) -> Option<&QueryBound> {
+        self.start.as_ref()
+    }
+
+    fn end(&self) -> Option<&QueryBound> {
+        self.end.as_ref()
+    }
 }
 
 #[cfg(test)]
@@ -537,6 +1380,239 @@ mod tests {
         assert_eq!(serialized, expected);
     }
 
+    #[test]
+    fn lint_accepts_valid_queries() {
+        let query = CollectionQuery::new(collection("landmarks")).with_filter(
+            filter("height", GreaterThan(100))
+                .and("height", LessThan(200))
+                .and("city", EqualTo("Paris"))
+                .and("tags", ArrayContains("tower")),
+        );
+
+        assert_eq!(lint_query(&query), vec![]);
+        assert_eq!(lint_query(&collection("landmarks")), vec![]);
+    }
+
+    #[test]
+    fn lint_flags_rejected_combinations() {
+        let query = collection_group("landmarks").with_filter(
+            filter("", EqualTo(1))
+                .and("city", NotEqual("Paris"))
+                .and("country", NotEqual("France"))
+                .and("tags", ArrayContains("tower"))
+                .and("materials", ArrayContains("iron")),
+        );
+
+        assert_eq!(
+            lint_query(&query),
+            vec![
+                QueryLint::EmptyFieldPath,
+                QueryLint::InequalityOnMultipleFields(vec![
+                    "city".to_string(),
+                    "country".to_string()
+                ]),
+                QueryLint::MultipleNotEqual,
+                QueryLint::MultipleArrayContains,
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_flags_first_order_by_on_other_field() {
+        let query = collection("landmarks")
+            .with_filter(filter("height", GreaterThan(100)))
+            .order_by("name", Direction::Ascending)
+            .order_by("height", Direction::Ascending);
+
+        assert_eq!(
+            lint_query(&query),
+            vec![QueryLint::FirstOrderByNotOnInequalityField {
+                inequality_field: "height".to_string(),
+                order_by_field: "name".to_string(),
+            }]
+        );
+
+        let query = collection("landmarks")
+            .with_filter(filter("height", GreaterThan(100)))
+            .order_by("height", Direction::Descending)
+            .order_by("name", Direction::Ascending);
+        assert_eq!(lint_query(&query), vec![]);
+    }
+
+    #[test]
+    fn lint_flags_cursor_values_without_order_by() {
+        let cursor = Cursor::new().with_value(&100).unwrap();
+
+        assert_eq!(
+            lint_query(&collection("landmarks").start_at(cursor.clone())),
+            vec![QueryLint::CursorWithoutOrderBy]
+        );
+        assert_eq!(
+            lint_query(&collection_group("landmarks").end_before(cursor.clone())),
+            vec![QueryLint::CursorWithoutOrderBy]
+        );
+        assert_eq!(
+            lint_query(
+                &collection("landmarks")
+                    .order_by("height", Direction::Ascending)
+                    .start_at(cursor)
+            ),
+            vec![]
+        );
+
+        let document_only = Cursor::new().with_document(collection("landmarks").doc("eiffel"));
+        assert_eq!(
+            lint_query(&collection("landmarks").start_after(document_only)),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn order_by_is_kept_in_order() {
+        let query = collection_group("landmarks")
+            .order_by("city", Direction::Ascending)
+            .order_by("rating", Direction::Descending);
+
+        let orders: Vec<_> = query
+            .ordering()
+            .iter()
+            .cloned()
+            .map(into_grpc_order)
+            .collect();
+
+        assert_eq!(
+            orders,
+            vec![
+                GrpcOrder {
+                    field: Some(FieldReference {
+                        field_path: "city".to_string(),
+                    }),
+                    direction: GrpcDirection::Ascending as i32,
+                },
+                GrpcOrder {
+                    field: Some(FieldReference {
+                        field_path: "rating".to_string(),
+                    }),
+                    direction: GrpcDirection::Descending as i32,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_groups() {
+        let query = and([
+            or([filter("age", LessThan(18)), filter("age", GreaterThan(65))]),
+            filter("name", EqualTo("Bob")),
+        ]);
+        let serialized = try_into_grpc_filter(query, "").unwrap();
+
+        let field_filter = |field: &str, op: FieldFilterOperator, value: ValueType| GrpcFilter {
+            filter_type: Some(GrpcFilterType::FieldFilter(GrpcFieldFilter {
+                field: Some(FieldReference {
+                    field_path: field.to_string(),
+                }),
+                op: op as i32,
+                value: Some(Value {
+                    value_type: Some(value),
+                }),
+            })),
+        };
+
+        let expected = GrpcFilter {
+            filter_type: Some(GrpcFilterType::CompositeFilter(GrpcCompositeFilter {
+                op: CompositeFilterOperator::And as i32,
+                filters: vec![
+                    GrpcFilter {
+                        filter_type: Some(GrpcFilterType::CompositeFilter(GrpcCompositeFilter {
+                            op: CompositeFilterOperator::Or as i32,
+                            filters: vec![
+                                field_filter(
+                                    "age",
+                                    FieldFilterOperator::LessThan,
+                                    ValueType::IntegerValue(18),
+                                ),
+                                field_filter(
+                                    "age",
+                                    FieldFilterOperator::GreaterThan,
+                                    ValueType::IntegerValue(65),
+                                ),
+                            ],
+                        })),
+                    },
+                    field_filter(
+                        "name",
+                        FieldFilterOperator::Equal,
+                        ValueType::StringValue("Bob".to_string()),
+                    ),
+                ],
+            })),
+        };
+
+        assert_eq!(serialized, expected);
+    }
+
+    #[test]
+    fn empty_groups_are_rejected() {
+        assert!(try_into_grpc_filter(and([]), "").is_err());
+        assert!(try_into_grpc_filter(or([]), "").is_err());
+
+        let nested = and([filter("age", LessThan(18)), or([])]);
+        assert!(try_into_grpc_filter(nested, "").is_err());
+    }
+
+    #[test]
+    fn unary_filters() {
+        let query = is_null("assignee").combine(filter("status", EqualTo("open")));
+        let serialized = try_into_grpc_filter(query, "").unwrap();
+
+        let expected = GrpcFilter {
+            filter_type: Some(GrpcFilterType::CompositeFilter(GrpcCompositeFilter {
+                op: CompositeFilterOperator::And as i32,
+                filters: vec![
+                    GrpcFilter {
+                        filter_type: Some(GrpcFilterType::UnaryFilter(GrpcUnaryFilter {
+                            op: UnaryFilterOperator::IsNull as i32,
+                            operand_type: Some(OperandType::Field(FieldReference {
+                                field_path: "assignee".to_string(),
+                            })),
+                        })),
+                    },
+                    GrpcFilter {
+                        filter_type: Some(GrpcFilterType::FieldFilter(GrpcFieldFilter {
+                            field: Some(FieldReference {
+                                field_path: "status".to_string(),
+                            }),
+                            op: FieldFilterOperator::Equal as i32,
+                            value: Some(Value {
+                                value_type: Some(ValueType::StringValue("open".to_string())),
+                            }),
+                        })),
+                    },
+                ],
+            })),
+        };
+
+        assert_eq!(serialized, expected);
+    }
+
+    #[test]
+    fn lint_counts_not_null_as_not_equal() {
+        let query = collection_group("tickets")
+            .with_filter(is_not_null("assignee").combine(filter("status", NotEqual("closed"))));
+
+        assert_eq!(
+            lint_query(&query),
+            vec![
+                QueryLint::InequalityOnMultipleFields(vec![
+                    "assignee".to_string(),
+                    "status".to_string()
+                ]),
+                QueryLint::MultipleNotEqual,
+            ]
+        );
+    }
+
     #[test]
     fn implements_send() {
         fn assert_send<T: Send>() {}
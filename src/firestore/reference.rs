@@ -1,6 +1,7 @@
 use std::{
     any::TypeId,
     hash::{Hash, Hasher},
+    ops::{Bound, RangeBounds},
     sync::Arc,
 };
 
@@ -8,7 +9,16 @@ use anyhow::Context;
 use once_cell::sync::OnceCell;
 use serde::{ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 
-use super::query::{CollectionQuery, Filter};
+use crate::error::FirebaseError;
+
+use super::{
+    client::FirestoreDocument,
+    cursor::Cursor,
+    query::{
+        filter, CollectionQuery, Direction, Filter, GreaterThan, GreaterThanOrEqual, LessThan,
+        LessThanOrEqual, QueryOperator, DOCUMENT_ID,
+    },
+};
 
 pub fn collection(name: impl Into<String>) -> CollectionReference {
     CollectionReference::new(name)
@@ -62,6 +72,12 @@ impl CollectionReference {
         COLLECTION_REF_TYPE_ID.get_or_init(hashed_type_id::<Self>)
     }
 
+    /// A reference to the document in this collection whose ID is derived from
+    /// the given idempotency key. See [`doc_id_from_key`].
+    pub fn doc_from_key(&self, key: impl AsRef<[u8]>) -> DocumentReference {
+        self.doc(doc_id_from_key(key))
+    }
+
     /// Create a Firestore query that filters documents from this collection.
     pub fn with_filter(self, filter: Filter<'_>) -> CollectionQuery<'_> {
         CollectionQuery::new(self).with_filter(filter)
@@ -76,6 +92,119 @@ impl CollectionReference {
     pub fn with_offset<'a>(self, offset: u32) -> CollectionQuery<'a> {
         CollectionQuery::new(self).with_offset(offset)
     }
+
+    /// Create a Firestore query that orders the documents of this collection.
+    /// See [`CollectionQuery::order_by`].
+    pub fn order_by<'a>(
+        self,
+        field: impl Into<String>,
+        direction: Direction,
+    ) -> CollectionQuery<'a> {
+        CollectionQuery::new(self).order_by(field, direction)
+    }
+
+    /// Create a Firestore query that only returns the last documents. See
+    /// [`CollectionQuery::limit_to_last`].
+    pub fn limit_to_last<'a>(self, limit: u32) -> CollectionQuery<'a> {
+        CollectionQuery::new(self).limit_to_last(limit)
+    }
+
+    /// Create a Firestore query that starts at the cursor. See
+    /// [`CollectionQuery::start_at`].
+    pub fn start_at<'a>(self, cursor: Cursor) -> CollectionQuery<'a> {
+        CollectionQuery::new(self).start_at(cursor)
+    }
+
+    /// Create a Firestore query that starts after the cursor. See
+    /// [`CollectionQuery::start_after`].
+    pub fn start_after<'a>(self, cursor: Cursor) -> CollectionQuery<'a> {
+        CollectionQuery::new(self).start_after(cursor)
+    }
+
+    /// Create a Firestore query that starts after the document. See
+    /// [`CollectionQuery::start_after_doc`].
+    pub fn start_after_doc<'a, T: Serialize>(
+        self,
+        document: &FirestoreDocument<T>,
+    ) -> Result<CollectionQuery<'a>, FirebaseError> {
+        CollectionQuery::new(self).start_after_doc(document)
+    }
+
+    /// Create a Firestore query that ends at the cursor. See
+    /// [`CollectionQuery::end_at`].
+    pub fn end_at<'a>(self, cursor: Cursor) -> CollectionQuery<'a> {
+        CollectionQuery::new(self).end_at(cursor)
+    }
+
+    /// Create a Firestore query that ends before the cursor. See
+    /// [`CollectionQuery::end_before`].
+    pub fn end_before<'a>(self, cursor: Cursor) -> CollectionQuery<'a> {
+        CollectionQuery::new(self).end_before(cursor)
+    }
+
+    /// Create a Firestore query for the documents of this collection whose
+    /// IDs are in the given range, ordered by ID. Useful for scanning a
+    /// collection in shards, e.g. one worker per range of IDs.
+    ///
+    /// IDs are compared as strings, so e.g. `"10"` comes before `"9"`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::collection;
+    /// use futures::TryStreamExt;
+    /// use ulid::Ulid;
+    ///
+    /// let shards = collection("shards").doc(Ulid::new()).collection("items");
+    /// for id in ["a1", "b1", "b2", "c1"] {
+    ///     client
+    ///         .set_document(&shards.doc(id), &serde_json::json!({ "id": id }))
+    ///         .await?;
+    /// }
+    ///
+    /// // Everything from "b" up to, but not including, "c"
+    /// let items: Vec<serde_json::Value> = client
+    ///     .run_query(shards.clone().id_range("b".."c"))
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    /// assert_eq!(items.len(), 2);
+    ///
+    /// let rest: Vec<serde_json::Value> = client
+    ///     .run_query(shards.id_range("b2"..))
+    ///     .await?
+    ///     .try_collect()
+    ///     .await?;
+    /// assert_eq!(rest.len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn id_range<'a, S, R>(self, range: R) -> CollectionQuery<'a>
+    where
+        S: AsRef<str>,
+        R: RangeBounds<S>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(id) => Some(filter_by_id(GreaterThanOrEqual(self.doc(id.as_ref())))),
+            Bound::Excluded(id) => Some(filter_by_id(GreaterThan(self.doc(id.as_ref())))),
+            Bound::Unbounded => None,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(id) => Some(filter_by_id(LessThanOrEqual(self.doc(id.as_ref())))),
+            Bound::Excluded(id) => Some(filter_by_id(LessThan(self.doc(id.as_ref())))),
+            Bound::Unbounded => None,
+        };
+
+        let query = CollectionQuery::new(self).order_by(DOCUMENT_ID, Direction::Ascending);
+        match (start, end) {
+            (Some(start), Some(end)) => query.with_filter(start.combine(end)),
+            (Some(filter), None) | (None, Some(filter)) => query.with_filter(filter),
+            (None, None) => query,
+        }
+    }
 }
 
 impl Serialize for CollectionReference {
@@ -231,7 +360,60 @@ impl PartialEq for DocumentReference {
     }
 }
 
-fn hashed_type_id<T: 'static>() -> String {
+impl Eq for DocumentReference {}
+
+/// Orders documents like Firestore does when ordering by document ID: by
+/// their path, segment by segment. A document comes right before the
+/// documents in its subcollections, which come before the next document in
+/// its collection, e.g. `cities/SF` < `cities/SF/landmarks/a` <
+/// `cities/SF-2`, even though `-` sorts before `/`.
+impl Ord for DocumentReference {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let (a, b) = (self.to_string(), other.to_string());
+        a.split('/').cmp(b.split('/'))
+    }
+}
+
+impl PartialOrd for DocumentReference {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Derives a deterministic document ID from an idempotency key, such as the
+/// ID of an event being consumed. The same key always gives the same ID, so a
+/// retried consumer writes to the same document instead of creating a
+/// duplicate. Combine it with
+/// [`create_document_if_absent`](crate::firestore::client::FirestoreClient::create_document_if_absent)
+/// to process each event at most once.
+///
+/// The ID is the hex-encoded first 16 bytes of the SHA-256 hash of the key,
+/// so it is safe to use keys that aren't valid document IDs themselves.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::firestore::reference::doc_id_from_key;
+///
+/// let id = doc_id_from_key("order-created/1234");
+///
+/// assert_eq!(id, doc_id_from_key("order-created/1234"));
+/// assert_ne!(id, doc_id_from_key("order-created/1235"));
+/// assert_eq!(id.len(), 32);
+/// ```
+pub fn doc_id_from_key(key: impl AsRef<[u8]>) -> String {
+    let hash = openssl::sha::sha256(key.as_ref());
+    hash[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn filter_by_id<'a>(check_against: impl QueryOperator<DocumentReference> + 'a) -> Filter<'a> {
+    filter(DOCUMENT_ID, check_against)
+}
+
+pub(crate) fn hashed_type_id<T: 'static>() -> String {
     let type_id = TypeId::of::<T>();
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
     type_id.hash(&mut hasher);
@@ -243,6 +425,16 @@ fn hashed_type_id<T: 'static>() -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn doc_id_from_key_is_stable() {
+        // Changing how IDs are derived would break idempotency for existing data
+        assert_eq!(doc_id_from_key("abc"), "ba7816bf8f01cfea414140de5dae2223");
+        assert_eq!(
+            collection("events").doc_from_key("abc").to_string(),
+            "events/ba7816bf8f01cfea414140de5dae2223"
+        );
+    }
+
     #[test]
     fn collection_reference() {
         assert_eq!(CollectionReference::new("users").to_string(), "users");
@@ -268,6 +460,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn document_references_are_ordered_by_segment() {
+        let cities = collection("cities");
+
+        assert!(cities.doc("SF") < cities.doc("TOK"));
+        // Segment by segment, rather than character by character, where '-'
+        // would sort before '/'
+        assert!(cities.doc("SF").collection("landmarks").doc("a") < cities.doc("SF-2"));
+        assert!(cities.doc("SF") < cities.doc("SF").collection("landmarks").doc("a"));
+    }
+
     #[test]
     fn deserialize_document_reference() {
         #[derive(Debug, Deserialize)]
@@ -275,7 +275,10 @@ impl<'de> de::Deserializer<'de> for FirestoreValueDeserializer {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        // Ignored values are never looked at, so there's no need to decode
+        // them. This also keeps unknown fields of types we can't deserialize,
+        // like bytes, from failing the whole document.
+        visitor.visit_unit()
     }
 }
 
@@ -287,6 +290,7 @@ pub(crate) fn strip_reference_prefix(reference: &str) -> String {
 struct MapDeserializer {
     fields: hash_map::IntoIter<String, firestore_grpc::v1::Value>,
     len: usize,
+    key: Option<String>,
     value: Option<ValueType>,
 }
 
@@ -295,6 +299,7 @@ impl MapDeserializer {
         Self {
             len: map.fields.len(),
             fields: map.fields.into_iter(),
+            key: None,
             value: None,
         }
     }
@@ -315,6 +320,7 @@ impl<'de> MapAccess<'de> for MapDeserializer {
                 };
 
                 self.len -= 1;
+                self.key = Some(key.clone());
                 self.value = Some(value);
 
                 let de = FirestoreValueDeserializer {
@@ -333,7 +339,10 @@ impl<'de> MapAccess<'de> for MapDeserializer {
     {
         let value = self.value.take().ok_or(Error::Eof)?;
         let de = FirestoreValueDeserializer { value };
-        seed.deserialize(de)
+        seed.deserialize(de).map_err(|e| match self.key.take() {
+            Some(key) => e.at_field(key),
+            None => e,
+        })
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -344,6 +353,7 @@ impl<'de> MapAccess<'de> for MapDeserializer {
 struct ArrayDeserializer {
     iter: vec::IntoIter<firestore_grpc::v1::Value>,
     len: usize,
+    index: usize,
 }
 
 impl ArrayDeserializer {
@@ -351,6 +361,7 @@ impl ArrayDeserializer {
         Self {
             len: arr.values.len(),
             iter: arr.values.into_iter(),
+            index: 0,
         }
     }
 }
@@ -371,9 +382,13 @@ impl<'de> SeqAccess<'de> for ArrayDeserializer {
                 };
 
                 self.len -= 1;
+                let index = self.index;
+                self.index += 1;
 
                 let de = FirestoreValueDeserializer { value };
-                seed.deserialize(de).map(Some)
+                seed.deserialize(de)
+                    .map(Some)
+                    .map_err(|e| e.at_index(index))
             }
         }
     }
@@ -389,9 +404,10 @@ mod tests {
 
     use firestore_grpc::v1::{value::ValueType, ArrayValue, Document, MapValue, Value};
     use prost_types::Timestamp;
-    use serde::Deserialize;
+    use serde::{Deserialize, Serialize};
 
     use super::deserialize_firestore_document_fields;
+    use crate::firestore::serde::DocumentSerializer;
 
     const RANDOM_TIMESTAMP: Option<Timestamp> = Some(Timestamp {
         seconds: 1663061252,
@@ -579,6 +595,158 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn deserialize_error_includes_field_path() {
+        let doc = create_simple_document(
+            "items",
+            ValueType::ArrayValue(ArrayValue {
+                values: vec![
+                    Value {
+                        value_type: Some(ValueType::MapValue(MapValue {
+                            fields: HashMap::from_iter(vec![(
+                                "price".to_string(),
+                                Value {
+                                    value_type: Some(ValueType::IntegerValue(10)),
+                                },
+                            )]),
+                        })),
+                    },
+                    Value {
+                        value_type: Some(ValueType::MapValue(MapValue {
+                            fields: HashMap::from_iter(vec![(
+                                "price".to_string(),
+                                Value {
+                                    value_type: Some(ValueType::StringValue("ten".to_string())),
+                                },
+                            )]),
+                        })),
+                    },
+                ],
+            }),
+        );
+
+        #[derive(Debug, Deserialize)]
+        struct Order {
+            #[allow(unused)]
+            items: Vec<Item>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Item {
+            #[allow(unused)]
+            price: i64,
+        }
+
+        let err = deserialize_firestore_document_fields::<Order>(doc.fields).unwrap_err();
+        assert_eq!(err.field_path().as_deref(), Some("items[1].price"));
+    }
+
+    #[test]
+    fn flattened_catch_all_map_round_trips() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Landmark {
+            name: String,
+            height: Option<u32>,
+            #[serde(flatten)]
+            extra: HashMap<String, serde_json::Value>,
+        }
+
+        let landmark = Landmark {
+            name: "Eiffel Tower".to_string(),
+            height: None,
+            extra: HashMap::from_iter([
+                ("city".to_string(), serde_json::json!("Paris")),
+                ("visitors".to_string(), serde_json::json!(7_000_000)),
+                ("rating".to_string(), serde_json::json!(4.5)),
+                ("closed".to_string(), serde_json::json!(null)),
+                (
+                    "architect".to_string(),
+                    serde_json::json!({ "name": "Gustave", "works": ["Garabit viaduct"] }),
+                ),
+            ]),
+        };
+
+        let doc = DocumentSerializer::new("").serialize(&landmark).unwrap();
+        assert_eq!(doc.fields.len(), 7);
+
+        let result: Landmark = deserialize_firestore_document_fields(doc.fields).unwrap();
+        assert_eq!(result, landmark);
+    }
+
+    #[test]
+    fn flattened_struct_round_trips() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Metadata {
+            version: u16,
+            score: f64,
+            owner: Option<String>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Pizza {
+            topping: String,
+            #[serde(flatten)]
+            metadata: Metadata,
+        }
+
+        let pizza = Pizza {
+            topping: "Pepperoni".to_string(),
+            metadata: Metadata {
+                version: 2,
+                score: 9.0,
+                owner: Some("Luigi".to_string()),
+            },
+        };
+
+        let doc = DocumentSerializer::new("").serialize(&pizza).unwrap();
+        let result: Pizza = deserialize_firestore_document_fields(doc.fields).unwrap();
+        assert_eq!(result, pizza);
+
+        // Integers are accepted for floats in flattened structs too
+        let mut doc = create_simple_document("topping", ValueType::StringValue("Ham".to_string()));
+        doc.fields.extend([
+            (
+                "version".to_string(),
+                Value {
+                    value_type: Some(ValueType::IntegerValue(1)),
+                },
+            ),
+            (
+                "score".to_string(),
+                Value {
+                    value_type: Some(ValueType::IntegerValue(7)),
+                },
+            ),
+        ]);
+        let result: Pizza = deserialize_firestore_document_fields(doc.fields).unwrap();
+        assert_eq!(result.metadata.score, 7.0);
+        assert_eq!(result.metadata.owner, None);
+    }
+
+    #[test]
+    fn unknown_fields_of_unsupported_types_are_ignored() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Person {
+            name: String,
+        }
+
+        let mut doc = create_simple_document("avatar", ValueType::BytesValue(vec![1, 2, 3]));
+        doc.fields.insert(
+            "name".to_string(),
+            Value {
+                value_type: Some(ValueType::StringValue("Luke".to_string())),
+            },
+        );
+
+        let result: Person = deserialize_firestore_document_fields(doc.fields).unwrap();
+        assert_eq!(
+            result,
+            Person {
+                name: "Luke".to_string()
+            }
+        );
+    }
+
     #[test]
     fn deserialize_field_not_present_yields_none() {
         let doc = Document {
@@ -0,0 +1,195 @@
+//! Client-side checks of the limits Firestore imposes on documents. The
+//! server rejects documents that exceed them with rather cryptic errors, so we
+//! check them up front to be able to point at the offending field.
+//!
+//! See <https://firebase.google.com/docs/firestore/quotas#collections_documents_and_fields>.
+
+use std::collections::HashMap;
+
+use firestore_grpc::v1::{value::ValueType, Value};
+use serde::Serialize;
+
+use super::{serialize_to_value_type, Error};
+
+/// The maximum depth of maps and arrays within a document.
+pub const MAX_NESTING_DEPTH: usize = 20;
+
+/// The maximum size of a field name in bytes.
+pub const MAX_FIELD_NAME_BYTES: usize = 1500;
+
+/// The maximum number of index entries for a single document.
+pub const MAX_INDEX_ENTRIES: usize = 40_000;
+
+/// Checks the fields of a document against the limits of Firestore that can
+/// be checked exactly. Every document is checked when it is serialized.
+pub(crate) fn validate_document_fields(fields: &HashMap<String, Value>) -> Result<(), Error> {
+    validate_map(fields, 0, None)
+}
+
+/// Checks a document against Firestore's limits, including an estimate of
+/// the number of index entries it creates, which is not checked when writing
+/// documents.
+///
+/// The number of index entries is estimated assuming the default single-field
+/// indexes: an ascending and a descending entry per field, plus an
+/// array-contains entry per array element. Index exemptions and composite
+/// indexes are not taken into account, so documents with exempted fields may
+/// be rejected even though Firestore accepts them.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::firestore::serde::{limits::check_document_limits, Error};
+///
+/// let readings = vec![0; 50_000];
+/// let result = check_document_limits(&serde_json::json!({ "readings": readings }));
+///
+/// assert!(matches!(result, Err(Error::TooManyIndexEntries { .. })));
+/// ```
+pub fn check_document_limits<T: Serialize>(document: &T) -> Result<(), Error> {
+    let fields = match serialize_to_value_type(document, "")? {
+        ValueType::MapValue(map) => map.fields,
+        _ => return Err(Error::InvalidDocument),
+    };
+
+    let mut index_entries = 0;
+    validate_map(&fields, 0, Some(&mut index_entries))?;
+
+    if index_entries > MAX_INDEX_ENTRIES {
+        return Err(Error::TooManyIndexEntries {
+            estimated: index_entries,
+            max: MAX_INDEX_ENTRIES,
+        });
+    }
+
+    Ok(())
+}
+
+/// Index entries are only counted when `index_entries` is given, as the
+/// values inside arrays are not indexed individually.
+fn validate_map(
+    fields: &HashMap<String, Value>,
+    depth: usize,
+    mut index_entries: Option<&mut usize>,
+) -> Result<(), Error> {
+    for (key, value) in fields {
+        if key.len() > MAX_FIELD_NAME_BYTES {
+            return Err(Error::FieldNameTooLong {
+                bytes: key.len(),
+                max: MAX_FIELD_NAME_BYTES,
+            }
+            .at_field(key.as_str()));
+        }
+
+        validate_value(value, depth, index_entries.as_deref_mut())
+            .map_err(|e| e.at_field(key.as_str()))?;
+    }
+
+    Ok(())
+}
+
+fn validate_value(
+    value: &Value,
+    depth: usize,
+    index_entries: Option<&mut usize>,
+) -> Result<(), Error> {
+    match &value.value_type {
+        Some(ValueType::MapValue(map)) => {
+            check_depth(depth + 1)?;
+            validate_map(&map.fields, depth + 1, index_entries)
+        }
+        Some(ValueType::ArrayValue(array)) => {
+            check_depth(depth + 1)?;
+
+            if let Some(index_entries) = index_entries {
+                *index_entries += 2 + array.values.len();
+            }
+
+            for (index, value) in array.values.iter().enumerate() {
+                validate_value(value, depth + 1, None).map_err(|e| e.at_index(index))?;
+            }
+
+            Ok(())
+        }
+        _ => {
+            if let Some(index_entries) = index_entries {
+                *index_entries += 2;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn check_depth(depth: usize) -> Result<(), Error> {
+    if depth > MAX_NESTING_DEPTH {
+        return Err(Error::NestingTooDeep {
+            max: MAX_NESTING_DEPTH,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firestore::serde::DocumentSerializer;
+
+    fn validate(json: serde_json::Value) -> Result<(), Error> {
+        let doc = DocumentSerializer::new("").serialize(&json)?;
+        validate_document_fields(&doc.fields)
+    }
+
+    fn nested(depth: usize) -> serde_json::Value {
+        (0..depth).fold(
+            serde_json::json!(1),
+            |inner, _| serde_json::json!({ "a": inner }),
+        )
+    }
+
+    #[test]
+    fn accepts_document_within_limits() {
+        validate(nested(MAX_NESTING_DEPTH + 1)).unwrap();
+        validate(serde_json::json!({ "tags": ["a", "b"], "name": "x" })).unwrap();
+    }
+
+    #[test]
+    fn rejects_too_deep_nesting() {
+        let err = validate(nested(MAX_NESTING_DEPTH + 2)).unwrap_err();
+
+        assert!(matches!(err.root_cause(), Error::NestingTooDeep { .. }));
+        assert_eq!(
+            err.field_path(),
+            Some(vec!["a"; MAX_NESTING_DEPTH + 1].join("."))
+        );
+    }
+
+    #[test]
+    fn rejects_too_long_field_name() {
+        let long_name = "x".repeat(MAX_FIELD_NAME_BYTES + 1);
+        let err = validate(serde_json::json!({ "outer": { long_name: true } })).unwrap_err();
+
+        assert!(matches!(
+            err.root_cause(),
+            Error::FieldNameTooLong { bytes, .. } if *bytes == MAX_FIELD_NAME_BYTES + 1
+        ));
+    }
+
+    #[test]
+    fn estimates_too_many_index_entries_without_failing_writes() {
+        let values = vec![0; MAX_INDEX_ENTRIES];
+        let document = serde_json::json!({ "values": values });
+
+        // The field may be exempted from indexing, so it can be written
+        validate(document.clone()).unwrap();
+
+        let err = check_document_limits(&document).unwrap_err();
+        assert!(matches!(err, Error::TooManyIndexEntries { .. }));
+    }
+
+    #[test]
+    fn checking_limits_includes_exact_limits() {
+        let err = check_document_limits(&nested(MAX_NESTING_DEPTH + 2)).unwrap_err();
+        assert!(matches!(err.root_cause(), Error::NestingTooDeep { .. }));
+    }
+}
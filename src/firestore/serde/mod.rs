@@ -1,15 +1,19 @@
 mod deserialize;
+pub mod limits;
+mod render;
 mod serialize;
+mod shape;
 
 pub(crate) use deserialize::*;
+pub(crate) use render::RenderValue;
 pub(crate) use serialize::*;
+pub(crate) use shape::{field_shape, FieldShape};
 
 use std::fmt;
 
 use firestore_grpc::v1::value::ValueType;
 use serde::{de, ser};
 
-#[derive(Debug)]
 pub enum Error {
     /// Any custom error message.
     Message(String),
@@ -20,6 +24,97 @@ pub enum Error {
     MissingValueType,
     InvalidKey(ValueType),
     InvalidDocument,
+    /// A [`DeleteField`](crate::firestore::write::DeleteField) was used
+    /// somewhere other than as the value of a field, e.g. in an array.
+    MisplacedDeleteField,
+    /// Maps and arrays are nested deeper than Firestore allows.
+    NestingTooDeep {
+        max: usize,
+    },
+    /// A field name is longer than Firestore allows.
+    FieldNameTooLong {
+        bytes: usize,
+        max: usize,
+    },
+    /// The document would likely create more index entries than Firestore
+    /// allows. The number of entries is an estimate, so this is only
+    /// returned by [`check_document_limits`](limits::check_document_limits).
+    TooManyIndexEntries {
+        estimated: usize,
+        max: usize,
+    },
+    /// An error that occurred while (de)serializing a nested field. The path
+    /// points at the offending field, e.g. `items[3].price`.
+    AtPath {
+        path: Vec<PathSegment>,
+        source: Box<Error>,
+    },
+}
+
+/// A single step in the path to a nested field of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl Error {
+    /// Returns the path of the field that caused the error, if known, for
+    /// example `items[3].price`.
+    pub fn field_path(&self) -> Option<String> {
+        match self {
+            Self::AtPath { path, .. } => Some(format_path(path)),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error without any path information.
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Self::AtPath { source, .. } => source.root_cause(),
+            other => other,
+        }
+    }
+
+    pub(crate) fn at_field(self, field: impl Into<String>) -> Self {
+        self.at_segment(PathSegment::Field(field.into()))
+    }
+
+    pub(crate) fn at_index(self, index: usize) -> Self {
+        self.at_segment(PathSegment::Index(index))
+    }
+
+    /// Errors bubble up from the innermost field, so each parent prepends its
+    /// own segment to the path.
+    fn at_segment(self, segment: PathSegment) -> Self {
+        match self {
+            Self::AtPath { mut path, source } => {
+                path.insert(0, segment);
+                Self::AtPath { path, source }
+            }
+            other => Self::AtPath {
+                path: vec![segment],
+                source: Box::new(other),
+            },
+        }
+    }
+}
+
+fn format_path(path: &[PathSegment]) -> String {
+    let mut formatted = String::new();
+
+    for segment in path {
+        match segment {
+            PathSegment::Field(name) if formatted.is_empty() => formatted.push_str(name),
+            PathSegment::Field(name) => {
+                formatted.push('.');
+                formatted.push_str(name);
+            }
+            PathSegment::Index(index) => formatted.push_str(&format!("[{index}]")),
+        }
+    }
+
+    formatted
 }
 
 impl ser::Error for Error {
@@ -40,12 +135,93 @@ impl fmt::Display for Error {
             Self::Message(msg) => formatter.write_str(msg),
             Self::Eof => formatter.write_str("end of content"),
             Self::MissingValueType => formatter.write_str("missing value type"),
-            Self::InvalidKey(item) => write!(formatter, "invalid key type: {:?}", item),
+            Self::InvalidKey(item) => write!(formatter, "invalid key type: {}", RenderValue(item)),
             Self::InvalidDocument => {
                 formatter.write_str("invalid document; must be a map-like type")
             }
+            Self::MisplacedDeleteField => {
+                formatter.write_str("DeleteField can only be used as the value of a field")
+            }
+            Self::NestingTooDeep { max } => write!(
+                formatter,
+                "maps and arrays are nested more than {} levels deep",
+                max
+            ),
+            Self::FieldNameTooLong { bytes, max } => write!(
+                formatter,
+                "field name is {} bytes long, exceeding the maximum of {} bytes",
+                bytes, max
+            ),
+            Self::TooManyIndexEntries { estimated, max } => write!(
+                formatter,
+                "document would create an estimated {} index entries, exceeding the maximum of {}",
+                estimated, max
+            ),
+            Self::AtPath { path, source } => {
+                write!(formatter, "{} at field '{}'", source, format_path(path))
+            }
+        }
+    }
+}
+
+// Written by hand so values are rendered compactly instead of as raw protobuf
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Message(msg) => f.debug_tuple("Message").field(msg).finish(),
+            Self::Eof => f.write_str("Eof"),
+            Self::MissingValueType => f.write_str("MissingValueType"),
+            Self::InvalidKey(item) => f
+                .debug_tuple("InvalidKey")
+                .field(&RenderValue(item))
+                .finish(),
+            Self::InvalidDocument => f.write_str("InvalidDocument"),
+            Self::MisplacedDeleteField => f.write_str("MisplacedDeleteField"),
+            Self::NestingTooDeep { max } => {
+                f.debug_struct("NestingTooDeep").field("max", max).finish()
+            }
+            Self::FieldNameTooLong { bytes, max } => f
+                .debug_struct("FieldNameTooLong")
+                .field("bytes", bytes)
+                .field("max", max)
+                .finish(),
+            Self::TooManyIndexEntries { estimated, max } => f
+                .debug_struct("TooManyIndexEntries")
+                .field("estimated", estimated)
+                .field("max", max)
+                .finish(),
+            Self::AtPath { path, source } => f
+                .debug_struct("AtPath")
+                .field("path", path)
+                .field("source", source)
+                .finish(),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_nested_field_path() {
+        let err = Error::Message("invalid type".to_string())
+            .at_field("price")
+            .at_index(3)
+            .at_field("items");
+
+        assert_eq!(err.field_path().as_deref(), Some("items[3].price"));
+        assert_eq!(err.to_string(), "invalid type at field 'items[3].price'");
+        assert!(matches!(err.root_cause(), Error::Message(_)));
+    }
+
+    #[test]
+    fn renders_invalid_keys_compactly() {
+        let err = Error::InvalidKey(ValueType::BytesValue(vec![0; 512]));
+
+        assert_eq!(err.to_string(), "invalid key type: <512 bytes>");
+        assert_eq!(format!("{:?}", err), "InvalidKey(<512 bytes>)");
+    }
+}
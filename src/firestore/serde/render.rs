@@ -0,0 +1,166 @@
+use std::fmt;
+
+use firestore_grpc::v1::value::ValueType;
+
+use super::strip_reference_prefix;
+
+/// Strings longer than this many characters are abbreviated.
+const MAX_STRING_CHARS: usize = 24;
+/// Only this many elements of arrays and entries of maps are shown.
+const MAX_ITEMS: usize = 4;
+/// Maps and arrays nested deeper than this are elided.
+const MAX_DEPTH: usize = 3;
+
+/// Renders a Firestore value compactly for error messages, unlike the
+/// protobuf `Debug` output. Long strings are abbreviated, bytes are only
+/// shown by length, and large or deeply nested maps and arrays are cut short.
+///
+/// For example, a map with a long string and bytes renders as
+/// `{avatar: <2048 bytes>, bio: "I am a Jedi, like my fat…" (53 chars)}`.
+pub(crate) struct RenderValue<'a>(pub &'a ValueType);
+
+impl fmt::Display for RenderValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        render(self.0, 0, f)
+    }
+}
+
+impl fmt::Debug for RenderValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+fn render(value: &ValueType, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match value {
+        ValueType::NullValue(_) => f.write_str("null"),
+        ValueType::BooleanValue(b) => write!(f, "{}", b),
+        ValueType::IntegerValue(i) => write!(f, "{}", i),
+        ValueType::DoubleValue(d) => write!(f, "{:?}", d),
+        ValueType::StringValue(s) => render_string(s, f),
+        ValueType::BytesValue(b) => write!(f, "<{} bytes>", b.len()),
+        ValueType::TimestampValue(t) => write!(f, "timestamp({}.{:09})", t.seconds, t.nanos),
+        ValueType::GeoPointValue(p) => write!(f, "geo({}, {})", p.latitude, p.longitude),
+        ValueType::ReferenceValue(r) => write!(f, "ref({})", strip_reference_prefix(r)),
+        ValueType::ArrayValue(a) if depth >= MAX_DEPTH && !a.values.is_empty() => {
+            f.write_str("[…]")
+        }
+        ValueType::ArrayValue(a) => {
+            f.write_str("[")?;
+            let values = a.values.iter().filter_map(|v| v.value_type.as_ref());
+            for (i, value) in values.take(MAX_ITEMS).enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                render(value, depth + 1, f)?;
+            }
+            render_remaining(a.values.len(), f)?;
+            f.write_str("]")
+        }
+        ValueType::MapValue(m) if depth >= MAX_DEPTH && !m.fields.is_empty() => f.write_str("{…}"),
+        ValueType::MapValue(m) => {
+            // Sort the keys so the output is stable
+            let mut fields: Vec<_> = m.fields.iter().collect();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            f.write_str("{")?;
+            for (i, (key, value)) in fields.into_iter().take(MAX_ITEMS).enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}: ", key)?;
+                match &value.value_type {
+                    Some(value) => render(value, depth + 1, f)?,
+                    None => f.write_str("?")?,
+                }
+            }
+            render_remaining(m.fields.len(), f)?;
+            f.write_str("}")
+        }
+    }
+}
+
+fn render_string(s: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let char_count = s.chars().count();
+
+    if char_count <= MAX_STRING_CHARS {
+        write!(f, "{:?}", s)
+    } else {
+        let abbreviated: String = s.chars().take(MAX_STRING_CHARS).collect();
+        write!(
+            f,
+            "\"{}…\" ({} chars)",
+            abbreviated.escape_debug(),
+            char_count
+        )
+    }
+}
+
+fn render_remaining(len: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if len > MAX_ITEMS {
+        write!(f, ", …{} more", len - MAX_ITEMS)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use firestore_grpc::v1::{ArrayValue, MapValue, Value};
+
+    use super::*;
+
+    fn value(value_type: ValueType) -> Value {
+        Value {
+            value_type: Some(value_type),
+        }
+    }
+
+    fn map(fields: Vec<(&str, ValueType)>) -> ValueType {
+        ValueType::MapValue(MapValue {
+            fields: fields
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), value(v)))
+                .collect::<HashMap<_, _>>(),
+        })
+    }
+
+    #[test]
+    fn abbreviates_long_strings_and_redacts_bytes() {
+        let rendered = RenderValue(&map(vec![
+            ("avatar", ValueType::BytesValue(vec![0; 2048])),
+            (
+                "bio",
+                ValueType::StringValue(
+                    "I am a Jedi, like my father before me. And my sister!".to_string(),
+                ),
+            ),
+            ("age", ValueType::IntegerValue(19)),
+            ("score", ValueType::DoubleValue(9.0)),
+        ]))
+        .to_string();
+
+        assert_eq!(
+            rendered,
+            "{age: 19, avatar: <2048 bytes>, bio: \"I am a Jedi, like my fat…\" (53 chars), score: 9.0}"
+        );
+    }
+
+    #[test]
+    fn cuts_large_and_deep_values_short() {
+        let array = ValueType::ArrayValue(ArrayValue {
+            values: (0..10).map(|i| value(ValueType::IntegerValue(i))).collect(),
+        });
+        assert_eq!(RenderValue(&array).to_string(), "[0, 1, 2, 3, …6 more]");
+
+        let deep = map(vec![(
+            "a",
+            map(vec![(
+                "b",
+                map(vec![("c", map(vec![("d", ValueType::NullValue(0))]))]),
+            )]),
+        )]);
+        assert_eq!(RenderValue(&deep).to_string(), "{a: {b: {c: {…}}}}");
+    }
+}
@@ -10,9 +10,12 @@ use serde::{
     Serialize, Serializer,
 };
 
-use crate::firestore::reference::{CollectionReference, DocumentReference};
+use crate::firestore::{
+    reference::{CollectionReference, DocumentReference},
+    write::DeleteField,
+};
 
-use super::Error;
+use super::{limits::validate_document_fields, Error};
 
 pub(crate) struct DocumentSerializer {
     root_resource_path: String,
@@ -40,12 +43,15 @@ impl DocumentSerializer {
         let value_type = serialize(value, &self.root_resource_path)?;
 
         match value_type {
-            ValueType::MapValue(map_value) => Ok(Document {
-                create_time: self.create_time,
-                update_time: self.update_time,
-                name: self.name.unwrap_or_default(),
-                fields: map_value.fields,
-            }),
+            ValueType::MapValue(map_value) => {
+                validate_document_fields(&map_value.fields)?;
+                Ok(Document {
+                    create_time: self.create_time,
+                    update_time: self.update_time,
+                    name: self.name.unwrap_or_default(),
+                    fields: map_value.fields,
+                })
+            }
             _ => Err(Error::InvalidDocument),
         }
     }
@@ -150,7 +156,13 @@ impl<'a> Serializer for FirestoreValueSerializer<'a> {
         Ok(ValueType::NullValue(0))
     }
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        // Fields with a delete sentinel are left out of the document by the
+        // map and struct serializers, which catch this error
+        if name == DeleteField::type_id() {
+            return Err(Error::MisplacedDeleteField);
+        }
+
         self.serialize_unit()
     }
 
@@ -290,7 +302,8 @@ impl<'a> SerializeSeq for ArraySerializer<'a> {
     type Error = Error;
 
     fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
-        let value_type = serialize(value, self.root_resource_path)?;
+        let value_type =
+            serialize(value, self.root_resource_path).map_err(|e| e.at_index(self.values.len()))?;
         self.values.push(Value {
             value_type: Some(value_type),
         });
@@ -337,7 +350,11 @@ impl<'a> SerializeMap for MapSerializer<'a> {
 
     fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
         let key = self.next_key.take().unwrap_or_default();
-        let value_type = serialize(value, self.root_resource_path)?;
+        let value_type = match serialize(value, self.root_resource_path) {
+            Ok(value_type) => value_type,
+            Err(Error::MisplacedDeleteField) => return Ok(()),
+            Err(e) => return Err(e.at_field(key)),
+        };
         self.fields.insert(
             key,
             Value {
@@ -405,7 +422,11 @@ impl<'a> SerializeStruct for StructSerializer<'a> {
         key: &'static str,
         value: &T,
     ) -> Result<(), Self::Error> {
-        let value_type = serialize(value, self.root_resource_path)?;
+        let value_type = match serialize(value, self.root_resource_path) {
+            Ok(value_type) => value_type,
+            Err(Error::MisplacedDeleteField) => return Ok(()),
+            Err(e) => return Err(e.at_field(key)),
+        };
         self.fields.insert(
             key.to_string(),
             Value {
@@ -497,7 +518,11 @@ impl<'a> SerializeStructVariant for StructVariantSerializer<'a> {
         key: &'static str,
         value: &T,
     ) -> Result<(), Self::Error> {
-        let value_type = serialize(value, self.root_resource_path)?;
+        let value_type = match serialize(value, self.root_resource_path) {
+            Ok(value_type) => value_type,
+            Err(Error::MisplacedDeleteField) => return Ok(()),
+            Err(e) => return Err(e.at_field(key)),
+        };
         self.fields.insert(
             key.to_string(),
             Value {
@@ -545,7 +570,8 @@ impl<'a> SerializeTupleVariant for TupleVariantSerializer<'a> {
     type Error = Error;
 
     fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
-        let value_type = serialize(value, self.root_resource_path)?;
+        let value_type =
+            serialize(value, self.root_resource_path).map_err(|e| e.at_index(self.values.len()))?;
         self.values.push(Value {
             value_type: Some(value_type),
         });
@@ -588,7 +614,8 @@ impl<'a> SerializeTupleStruct for TupleStructSerializer<'a> {
     type Error = Error;
 
     fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
-        let value_type = serialize(value, self.root_resource_path)?;
+        let value_type =
+            serialize(value, self.root_resource_path).map_err(|e| e.at_index(self.values.len()))?;
         self.values.push(Value {
             value_type: Some(value_type),
         });
@@ -621,7 +648,8 @@ impl<'a> SerializeTuple for TupleSerializer<'a> {
     type Error = Error;
 
     fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
-        let value_type = serialize(value, self.root_resource_path)?;
+        let value_type =
+            serialize(value, self.root_resource_path).map_err(|e| e.at_index(self.values.len()))?;
         self.values.push(Value {
             value_type: Some(value_type),
         });
@@ -645,7 +673,8 @@ mod tests {
     use crate::firestore::{
         collection,
         reference::{CollectionReference, DocumentReference},
-        serde::DocumentSerializer,
+        serde::{DocumentSerializer, Error},
+        write::DeleteField,
     };
 
     #[test]
@@ -759,6 +788,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn delete_field_is_left_out_of_document() {
+        #[derive(Serialize)]
+        struct Nested {
+            keep: i32,
+            remove: DeleteField,
+        }
+
+        #[derive(Serialize)]
+        struct Update {
+            remove: Option<DeleteField>,
+            nested: Nested,
+            map: HashMap<&'static str, DeleteField>,
+        }
+
+        let doc = DocumentSerializer::new("")
+            .serialize(&Update {
+                remove: Some(DeleteField),
+                nested: Nested {
+                    keep: 1,
+                    remove: DeleteField,
+                },
+                map: HashMap::from_iter([("remove", DeleteField)]),
+            })
+            .unwrap();
+
+        let nested_fields = match &doc.fields["nested"].value_type {
+            Some(ValueType::MapValue(map)) => map.fields.keys().collect::<Vec<_>>(),
+            other => panic!("Expected a map, got {:?}", other),
+        };
+
+        assert!(!doc.fields.contains_key("remove"));
+        assert_eq!(nested_fields, vec!["keep"]);
+        assert_eq!(
+            doc.fields["map"].value_type,
+            Some(ValueType::MapValue(MapValue {
+                fields: HashMap::new()
+            }))
+        );
+    }
+
+    #[test]
+    fn delete_field_outside_of_field_fails() {
+        let err = DocumentSerializer::new("")
+            .serialize(&HashMap::from_iter([("tags", vec![DeleteField])]))
+            .unwrap_err();
+
+        assert!(matches!(err.root_cause(), Error::MisplacedDeleteField));
+        assert_eq!(err.field_path().as_deref(), Some("tags[0]"));
+        assert!(matches!(
+            DocumentSerializer::new("").serialize(&DeleteField),
+            Err(Error::MisplacedDeleteField)
+        ));
+    }
+
     #[test]
     fn serialize_map() {
         let value: HashMap<&str, i32> = HashMap::from_iter([("Pep med drez", 65)]);
@@ -988,6 +1072,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn serialize_error_includes_field_path() {
+        #[derive(Serialize)]
+        struct TestStruct {
+            pizzas: Vec<Pizza>,
+        }
+
+        #[derive(Serialize)]
+        struct Pizza {
+            prices_by_size: HashMap<i32, i32>,
+        }
+
+        let value = TestStruct {
+            pizzas: vec![Pizza {
+                prices_by_size: HashMap::from_iter([(30, 65)]),
+            }],
+        };
+        let err = DocumentSerializer::new("").serialize(&value).unwrap_err();
+
+        assert_eq!(
+            err.field_path().as_deref(),
+            Some("pizzas[0].prices_by_size")
+        );
+    }
+
     #[test]
     fn serialize_document_reference() {
         #[derive(Serialize)]
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+
+use serde::{
+    ser::{
+        self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+    Serialize, Serializer,
+};
+
+use crate::firestore::reference::{CollectionReference, DocumentReference};
+
+use super::Error;
+
+/// The fields of a value as it would be written to Firestore, for checking
+/// field paths against a type. Unlike the serialized document, it includes
+/// fields that are left out of the write, such as
+/// [`DeleteField`](crate::firestore::write::DeleteField) sentinels and fields
+/// skipped with `skip_serializing_if`.
+#[derive(Debug, PartialEq)]
+pub(crate) enum FieldShape {
+    /// A value that isn't a map, e.g. a string or an array.
+    Value,
+    /// A map, or a struct, with the shapes of its fields.
+    Map(HashMap<String, FieldShape>),
+    /// A value whose shape isn't known, because it was skipped or is `None`,
+    /// so any path into it may be valid.
+    Unknown,
+}
+
+pub(crate) fn field_shape<T: ?Sized + Serialize>(value: &T) -> Result<FieldShape, Error> {
+    value.serialize(ShapeSerializer)
+}
+
+struct ShapeSerializer;
+
+impl Serializer for ShapeSerializer {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    type SerializeSeq = ValueShape;
+    type SerializeTuple = ValueShape;
+    type SerializeTupleStruct = ValueShape;
+    type SerializeTupleVariant = ValueShape;
+    type SerializeMap = MapShape;
+    type SerializeStruct = MapShape;
+    type SerializeStructVariant = MapShape;
+
+    fn serialize_bool(self, _: bool) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_char(self, _: char) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_str(self, _: &str) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_none(self) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Unknown)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<FieldShape, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    // Includes delete sentinels, whose fields are valid in a field mask
+    fn serialize_unit_struct(self, _: &'static str) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<FieldShape, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<FieldShape, Error> {
+        let fields = HashMap::from([(variant.to_string(), value.serialize(self)?)]);
+        Ok(FieldShape::Map(fields))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<ValueShape, Error> {
+        Ok(ValueShape)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<ValueShape, Error> {
+        Ok(ValueShape)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<ValueShape, Error> {
+        Ok(ValueShape)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<ValueShape, Error> {
+        Ok(ValueShape)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<MapShape, Error> {
+        Ok(MapShape::default())
+    }
+
+    fn serialize_struct(self, name: &'static str, _: usize) -> Result<MapShape, Error> {
+        // References are written as a single value
+        let is_reference =
+            name == DocumentReference::type_id() || name == CollectionReference::type_id();
+
+        Ok(MapShape {
+            is_value: is_reference,
+            ..Default::default()
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<MapShape, Error> {
+        Ok(MapShape {
+            variant: Some(variant),
+            ..Default::default()
+        })
+    }
+}
+
+/// Arrays are values as far as field paths are concerned, as paths can't
+/// point into them.
+struct ValueShape;
+
+impl SerializeSeq for ValueShape {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, _: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+}
+
+impl SerializeTuple for ValueShape {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, _: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+}
+
+impl SerializeTupleStruct for ValueShape {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+}
+
+impl SerializeTupleVariant for ValueShape {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _: &T) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldShape, Error> {
+        Ok(FieldShape::Value)
+    }
+}
+
+#[derive(Default)]
+struct MapShape {
+    fields: HashMap<String, FieldShape>,
+    next_key: Option<String>,
+    /// The variant a struct variant is nested under.
+    variant: Option<&'static str>,
+    /// Whether the struct is written as a single value rather than a map.
+    is_value: bool,
+}
+
+impl MapShape {
+    fn insert<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        let shape = field_shape(value).map_err(|e| e.at_field(key.clone()))?;
+        self.fields.insert(key, shape);
+        Ok(())
+    }
+
+    fn finish(self) -> FieldShape {
+        if self.is_value {
+            return FieldShape::Value;
+        }
+
+        let shape = FieldShape::Map(self.fields);
+        match self.variant {
+            Some(variant) => FieldShape::Map(HashMap::from([(variant.to_string(), shape)])),
+            None => shape,
+        }
+    }
+}
+
+impl SerializeMap for MapShape {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = match serde_json::to_value(key) {
+            Ok(serde_json::Value::String(key)) => Some(key),
+            _ => return Err(ser::Error::custom("map keys must be strings")),
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.next_key.take().unwrap_or_default();
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<FieldShape, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl SerializeStruct for MapShape {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    // Called for fields with a `skip_serializing_if` that applies
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+        self.fields.insert(key.to_string(), FieldShape::Unknown);
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldShape, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl SerializeStructVariant for MapShape {
+    type Ok = FieldShape;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+        self.fields.insert(key.to_string(), FieldShape::Unknown);
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldShape, Error> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use crate::firestore::write::DeleteField;
+
+    use super::*;
+
+    #[test]
+    fn includes_deleted_and_skipped_fields() {
+        #[derive(Serialize)]
+        struct Update {
+            debt: DeleteField,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            nickname: Option<String>,
+            address: Option<Address>,
+            tags: Vec<String>,
+        }
+
+        #[derive(Serialize)]
+        struct Address {
+            city: String,
+        }
+
+        let shape = field_shape(&Update {
+            debt: DeleteField,
+            nickname: None,
+            address: None,
+            tags: vec!["a".to_string()],
+        })
+        .unwrap();
+
+        assert_eq!(
+            shape,
+            FieldShape::Map(HashMap::from([
+                ("debt".to_string(), FieldShape::Value),
+                ("nickname".to_string(), FieldShape::Unknown),
+                ("address".to_string(), FieldShape::Unknown),
+                ("tags".to_string(), FieldShape::Value),
+            ]))
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Read-your-writes consistency for clients that read at an earlier point in
+//! time. See [`Session`].
+
+use serde::Serialize;
+
+use crate::error::FirebaseError;
+
+use super::{
+    client::FirestoreClient, consistency::ConsistencyToken, reference::DocumentReference,
+    write::WriteResult,
+};
+
+/// Keeps track of the writes made through it, so that reads in the session
+/// always see them, even if the client otherwise reads the database as of an
+/// earlier time with [`read_at`](FirestoreClient::read_at).
+///
+/// Reads that see the latest state of the database, which is the default,
+/// already see every completed write, so sessions matter for clients that
+/// read at a fixed or slightly stale time, e.g. to read from a consistent
+/// snapshot. Reads in a session use the later of that time and the time of
+/// the session's last write.
+///
+/// Start a session with [`FirestoreClient::session`], write through it, and
+/// read through the client returned by [`reader`](Self::reader). Writes made
+/// elsewhere can be added to the session with [`record`](Self::record) and
+/// [`observe`](Self::observe), e.g. with a [`ConsistencyToken`] received from
+/// another service.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let client = fireplace::firestore::test_helpers::initialise().await?;
+/// use std::time::{Duration, SystemTime};
+///
+/// use fireplace::firestore::{collection, consistency::ConsistencyToken};
+/// use ulid::Ulid;
+///
+/// // A client reading a snapshot of the database from a minute ago
+/// let snapshot = ConsistencyToken::at(SystemTime::now() - Duration::from_secs(60))?;
+/// let mut session = client.read_at(&snapshot).session();
+///
+/// let doc_ref = collection("carts").doc(Ulid::new());
+/// session
+///     .set_document(&doc_ref, &serde_json::json!({ "items": 1 }))
+///     .await?;
+///
+/// // Reads in the session see the write, even though it is newer than the
+/// // snapshot
+/// let cart: Option<serde_json::Value> = session.reader().get_document(&doc_ref).await?;
+/// assert_eq!(cart, Some(serde_json::json!({ "items": 1 })));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Session {
+    client: FirestoreClient,
+    last_write: Option<ConsistencyToken>,
+}
+
+impl Session {
+    pub fn new(client: FirestoreClient) -> Self {
+        Self {
+            client,
+            last_write: None,
+        }
+    }
+
+    /// A client whose reads see every write of the session. It should be
+    /// fetched again after writing, as it doesn't see later writes of the
+    /// session.
+    pub fn reader(&self) -> FirestoreClient {
+        match &self.last_write {
+            Some(token) => self.client.read_no_earlier_than(token),
+            None => self.client.clone(),
+        }
+    }
+
+    /// The time of the latest write of the session, or `None` if nothing has
+    /// been written yet. Pass it to another session with
+    /// [`observe`](Self::observe) to let it see the writes too.
+    pub fn token(&self) -> Option<ConsistencyToken> {
+        self.last_write
+    }
+
+    /// Makes reads in the session see the database as of at least the given
+    /// token, e.g. one received from another service after it wrote.
+    pub fn observe(&mut self, token: ConsistencyToken) {
+        self.last_write = self.last_write.max(Some(token));
+    }
+
+    /// Makes reads in the session see a write that was made outside of it,
+    /// e.g. with a [`WriteBatch`](super::batch::WriteBatch).
+    pub fn record(&mut self, result: &WriteResult) {
+        if let Some(token) = result.consistency_token() {
+            self.observe(token);
+        }
+    }
+
+    /// Sets a document like [`FirestoreClient::set_document`], and records
+    /// the write in the session.
+    pub async fn set_document<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<WriteResult, FirebaseError> {
+        let result = self.client.set_document(doc_ref, document).await?;
+        self.record(&result);
+        Ok(result)
+    }
+
+    /// Updates a document like [`FirestoreClient::update_document`], and
+    /// records the write in the session.
+    pub async fn update_document<T: Serialize>(
+        &mut self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<WriteResult, FirebaseError> {
+        let result = self.client.update_document(doc_ref, document).await?;
+        self.record(&result);
+        Ok(result)
+    }
+
+    /// Deletes a document like [`FirestoreClient::delete_document`], and
+    /// records the write in the session.
+    pub async fn delete_document(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<WriteResult, FirebaseError> {
+        let result = self.client.delete_document(doc_ref).await?;
+        self.record(&result);
+        Ok(result)
+    }
+}
@@ -0,0 +1,210 @@
+//! A small key-value store for typed settings, backed by a Firestore
+//! collection.
+//!
+//! Each setting is stored as a document in the collection, with the key as
+//! the document ID and the value in a `value` field. This means that values
+//! don't have to be maps - any serializable type will do.
+
+use std::future;
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::FirebaseError;
+
+use super::{
+    client::{FirebaseStream, FirestoreClient},
+    reference::{CollectionReference, DocumentReference},
+};
+
+/// Typed settings stored in a Firestore collection.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::firestore::{collection, settings::SettingsStore};
+/// use futures::TryStreamExt;
+///
+/// let mut settings = SettingsStore::new(client, collection("settings"));
+///
+/// settings.put("maintenance-mode", &false).await?;
+/// assert_eq!(settings.get::<bool>("maintenance-mode").await?, Some(false));
+///
+/// // Watching a setting yields its current value first, and then every
+/// // time it changes
+/// let mut changes = settings.watch::<bool>("maintenance-mode");
+/// assert_eq!(changes.try_next().await?, Some(Some(false)));
+///
+/// settings.put("maintenance-mode", &true).await?;
+/// assert_eq!(changes.try_next().await?, Some(Some(true)));
+/// # Ok(())
+/// # }
+/// ```
+pub struct SettingsStore {
+    client: FirestoreClient,
+    collection: CollectionReference,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Setting<T> {
+    value: T,
+}
+
+impl SettingsStore {
+    /// Create a store that keeps its settings in the given collection.
+    pub fn new(client: FirestoreClient, collection: CollectionReference) -> Self {
+        Self { client, collection }
+    }
+
+    /// Get the value of a setting, or `None` if it hasn't been set.
+    pub async fn get<T: DeserializeOwned>(
+        &mut self,
+        key: impl AsRef<str>,
+    ) -> Result<Option<T>, FirebaseError> {
+        let doc_ref = self.doc_ref(key);
+        let setting = self.client.get_document::<Setting<T>>(&doc_ref).await?;
+        Ok(setting.map(|s| s.value))
+    }
+
+    /// Get the value of a setting, or the default value for the type if it
+    /// hasn't been set.
+    pub async fn get_or_default<T: DeserializeOwned + Default>(
+        &mut self,
+        key: impl AsRef<str>,
+    ) -> Result<T, FirebaseError> {
+        Ok(self.get(key).await?.unwrap_or_default())
+    }
+
+    /// Set the value of a setting, overwriting any previous value.
+    pub async fn put<T: Serialize>(
+        &mut self,
+        key: impl AsRef<str>,
+        value: &T,
+    ) -> Result<(), FirebaseError> {
+        let doc_ref = self.doc_ref(key);
+        self.client
+            .set_document(&doc_ref, &Setting { value })
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a setting. Removing a setting that doesn't exist is not an
+    /// error.
+    pub async fn remove(&mut self, key: impl AsRef<str>) -> Result<(), FirebaseError> {
+        let doc_ref = self.doc_ref(key);
+        self.client.delete_document(&doc_ref).await?;
+        Ok(())
+    }
+
+    /// Get all settings in the store as key-value pairs. All settings must be
+    /// of the same type.
+    pub async fn get_all<T: DeserializeOwned + Send>(
+        &mut self,
+    ) -> Result<Vec<(String, T)>, FirebaseError> {
+        let collection = self.collection.clone();
+
+        self.client
+            .run_query_with_metadata::<Setting<T>>(collection)
+            .await?
+            .and_then(|doc| async move {
+                let key = doc.document_reference()?.id().to_string();
+                Ok((key, doc.data.value))
+            })
+            .try_collect()
+            .await
+    }
+
+    /// Watch a setting for changes. The returned stream yields the current
+    /// value of the setting right away, and then a new value every time the
+    /// setting changes. A value of `None` means that the setting was removed.
+    ///
+    /// The setting is listened to with
+    /// [`listen_document`](FirestoreClient::listen_document), so changes are
+    /// seen as soon as they are made. Writes that leave the value unchanged
+    /// are not yielded.
+    pub fn watch<T>(
+        &self,
+        key: impl AsRef<str>,
+    ) -> FirebaseStream<'static, Option<T>, FirebaseError>
+    where
+        T: DeserializeOwned + PartialEq + Clone + Send + 'static,
+    {
+        let values = self
+            .client
+            .listen_document::<Setting<T>>(&self.doc_ref(key))
+            .map_ok(|snapshot| snapshot.document.map(|doc| doc.data.value));
+
+        distinct_values(values).boxed()
+    }
+
+    fn doc_ref(&self, key: impl AsRef<str>) -> DocumentReference {
+        self.collection.doc(key.as_ref())
+    }
+}
+
+/// Skips values that are equal to the previous one, e.g. because the setting
+/// was written again with the same value.
+fn distinct_values<T, S>(values: S) -> impl Stream<Item = Result<Option<T>, FirebaseError>>
+where
+    T: PartialEq + Clone,
+    S: Stream<Item = Result<Option<T>, FirebaseError>>,
+{
+    let mut last_seen: Option<Option<T>> = None;
+
+    values.try_filter(move |value| {
+        let is_new = last_seen.as_ref() != Some(value);
+        if is_new {
+            last_seen = Some(value.clone());
+        }
+        future::ready(is_new)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn yields_only_changed_values() {
+        let values = futures::stream::iter(vec![
+            Ok(Some(1)),
+            Ok(Some(1)),
+            Ok(None),
+            Ok(None),
+            Ok(Some(1)),
+            Ok(Some(2)),
+        ]);
+
+        let distinct: Vec<Option<u32>> = distinct_values(values).try_collect().await.unwrap();
+
+        assert_eq!(distinct, vec![Some(1), None, Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn yields_missing_setting_first() {
+        let values = futures::stream::iter(vec![Ok(None), Ok(Some("on"))]);
+
+        let distinct: Vec<Option<&str>> = distinct_values(values).try_collect().await.unwrap();
+
+        assert_eq!(distinct, vec![None, Some("on")]);
+    }
+
+    #[tokio::test]
+    async fn passes_errors_through() {
+        let values = futures::stream::iter(vec![
+            Ok(Some(1)),
+            Err(FirebaseError::Cancelled),
+            Ok(Some(1)),
+        ]);
+
+        let results: Vec<_> = distinct_values(values).collect().await;
+
+        assert!(matches!(
+            results[..],
+            [Ok(Some(1)), Err(FirebaseError::Cancelled)]
+        ));
+    }
+}
@@ -0,0 +1,320 @@
+//! Helpers for consuming streams of documents, such as the ones returned by
+//! [`run_query_with_metadata`](super::client::FirestoreClient::run_query_with_metadata).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{future::BoxFuture, FutureExt, Stream, StreamExt, TryStreamExt};
+
+use crate::error::FirebaseError;
+
+use super::client::FirestoreDocument;
+
+/// Extension methods for streams of [`FirestoreDocument`]s.
+pub trait DocumentStreamExt<T>:
+    Stream<Item = Result<FirestoreDocument<T>, FirebaseError>> + Sized
+{
+    /// Collects the documents into a map from their short document ID, i.e.
+    /// the last segment of the document's path, to their data. Useful for
+    /// joining query results with other data.
+    ///
+    /// Fails if several documents have the same ID, as can happen in
+    /// collection group queries, rather than silently dropping all but one of
+    /// them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// # fireplace::firestore::test_helpers::setup_landmarks_example(&mut client).await?;
+    /// use std::collections::HashMap;
+    ///
+    /// use fireplace::firestore::{
+    ///     query::{filter, EqualTo},
+    ///     stream::DocumentStreamExt,
+    ///     test_helpers::Landmark,
+    /// };
+    ///
+    /// let landmarks: HashMap<String, Landmark> = client
+    ///     .collection_group_query_with_metadata("landmarks", filter("type", EqualTo("museum")))
+    ///     .await?
+    ///     .collect_map()
+    ///     .await?;
+    ///
+    /// assert_eq!(landmarks["legion-honor"].name, "Legion of Honor");
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn collect_map<'a>(self) -> BoxFuture<'a, Result<HashMap<String, T>, FirebaseError>>
+    where
+        Self: Send + 'a,
+        T: Send + 'a,
+    {
+        self.try_fold(HashMap::new(), |mut map, doc| async move {
+            let id = short_id(&doc.id);
+            if map.contains_key(id) {
+                return Err(duplicate_id_error(&doc.id));
+            }
+            map.insert(id.to_string(), doc.data);
+            Ok(map)
+        })
+        .boxed()
+    }
+
+    /// Like [`collect_map`](Self::collect_map), but collects into a
+    /// [`BTreeMap`] so the documents are ordered by ID.
+    fn collect_btree_map<'a>(self) -> BoxFuture<'a, Result<BTreeMap<String, T>, FirebaseError>>
+    where
+        Self: Send + 'a,
+        T: Send + 'a,
+    {
+        self.try_fold(BTreeMap::new(), |mut map, doc| async move {
+            let id = short_id(&doc.id);
+            if map.contains_key(id) {
+                return Err(duplicate_id_error(&doc.id));
+            }
+            map.insert(id.to_string(), doc.data);
+            Ok(map)
+        })
+        .boxed()
+    }
+}
+
+impl<T, S> DocumentStreamExt<T> for S where
+    S: Stream<Item = Result<FirestoreDocument<T>, FirebaseError>>
+{
+}
+
+/// How far a stream has been consumed. See
+/// [`ProgressStreamExt::with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of items that have been consumed successfully.
+    pub processed: u64,
+    /// The time since the first item was requested.
+    pub elapsed: Duration,
+    /// Whether the stream has ended.
+    pub done: bool,
+}
+
+impl Progress {
+    /// The average number of items consumed per second.
+    pub fn per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.processed as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Extension methods for reporting the progress of long-running streams.
+pub trait ProgressStreamExt<T>: Stream<Item = Result<T, FirebaseError>> + Sized {
+    /// Reports the progress of the stream every `every_n` items, and once more
+    /// when the stream ends, so e.g. hours-long backfills get visibility
+    /// without wiring up their own counters. Each report is also logged with
+    /// `tracing` at debug level. Errors are passed through without being
+    /// counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::firestore::{collection, stream::ProgressStreamExt};
+    /// use futures::TryStreamExt;
+    ///
+    /// let mut orders = client
+    ///     .get_documents::<serde_json::Value>(&collection("orders"))
+    ///     .await?
+    ///     .with_progress(1000, |progress| {
+    ///         println!(
+    ///             "Backfilled {} orders in {:?} ({:.0}/s)",
+    ///             progress.processed,
+    ///             progress.elapsed,
+    ///             progress.per_second()
+    ///         );
+    ///     });
+    ///
+    /// while let Some(_order) = orders.try_next().await? {
+    ///     // Backfill the order
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn with_progress<F: FnMut(Progress)>(self, every_n: u64, callback: F) -> WithProgress<Self, F>
+    where
+        Self: Unpin,
+    {
+        WithProgress {
+            stream: self,
+            every_n: every_n.max(1),
+            callback,
+            processed: 0,
+            started_at: None,
+            done: false,
+        }
+    }
+}
+
+impl<T, S> ProgressStreamExt<T> for S where S: Stream<Item = Result<T, FirebaseError>> {}
+
+/// The stream returned by [`ProgressStreamExt::with_progress`].
+pub struct WithProgress<S, F> {
+    stream: S,
+    every_n: u64,
+    callback: F,
+    processed: u64,
+    started_at: Option<Instant>,
+    done: bool,
+}
+
+impl<S, F> WithProgress<S, F>
+where
+    F: FnMut(Progress),
+{
+    fn report(&mut self) {
+        let progress = Progress {
+            processed: self.processed,
+            elapsed: self.started_at.map(|t| t.elapsed()).unwrap_or_default(),
+            done: self.done,
+        };
+
+        tracing::debug!(
+            processed = progress.processed,
+            elapsed_secs = progress.elapsed.as_secs_f64(),
+            done = progress.done,
+            "Stream progress"
+        );
+        (self.callback)(progress);
+    }
+}
+
+impl<T, S, F> Stream for WithProgress<S, F>
+where
+    S: Stream<Item = Result<T, FirebaseError>> + Unpin,
+    F: FnMut(Progress) + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        self.started_at.get_or_insert_with(Instant::now);
+
+        let item = match self.stream.poll_next_unpin(cx) {
+            Poll::Ready(item) => item,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        match &item {
+            Some(Ok(_)) => {
+                self.processed += 1;
+                if self.processed % self.every_n == 0 {
+                    self.report();
+                }
+            }
+            Some(Err(_)) => {}
+            None => {
+                self.done = true;
+                self.report();
+            }
+        }
+
+        Poll::Ready(item)
+    }
+}
+
+fn short_id(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or_default()
+}
+
+fn duplicate_id_error(name: &str) -> FirebaseError {
+    anyhow::anyhow!(
+        "Several documents have the ID '{}', e.g. '{}', so they can't be collected by ID",
+        short_id(name),
+        name
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, data: u32) -> Result<FirestoreDocument<u32>, FirebaseError> {
+        Ok(FirestoreDocument {
+            id: format!("projects/p/databases/(default)/documents/{}", id),
+            data,
+            create_time: None,
+            update_time: None,
+            create_timestamp: None,
+            update_timestamp: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn collects_documents_by_short_id() {
+        let docs = vec![doc("cities/SF", 1), doc("cities/TOK", 2)];
+
+        let map = futures::stream::iter(docs)
+            .collect_btree_map()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            vec![("SF".to_string(), 1), ("TOK".to_string(), 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_on_documents_with_the_same_id() {
+        let docs = vec![
+            doc("cities/SF/landmarks/museum", 1),
+            doc("cities/TOK/landmarks/museum", 2),
+        ];
+
+        let result = futures::stream::iter(docs).collect_map().await;
+
+        assert!(matches!(result, Err(FirebaseError::Other(_))));
+    }
+
+    #[tokio::test]
+    async fn reports_progress_every_n_items_and_at_end() {
+        let docs = (0..5).map(|i| doc("cities/SF", i));
+        let mut reports = vec![];
+
+        let count = futures::stream::iter(docs)
+            .with_progress(2, |progress| {
+                reports.push((progress.processed, progress.done))
+            })
+            .try_fold(0, |count, _| async move { Ok(count + 1) })
+            .await
+            .unwrap();
+
+        assert_eq!(count, 5);
+        assert_eq!(reports, vec![(2, false), (4, false), (5, true)]);
+    }
+
+    #[tokio::test]
+    async fn stops_at_first_error() {
+        let docs = vec![
+            doc("cities/SF", 1),
+            Err(FirebaseError::DocumentNotfound("cities/TOK".to_string())),
+        ];
+
+        let result = futures::stream::iter(docs).collect_map().await;
+
+        assert!(matches!(result, Err(FirebaseError::DocumentNotfound(_))));
+    }
+}
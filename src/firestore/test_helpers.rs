@@ -1,6 +1,6 @@
 use std::env;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     firestore::{client::FirestoreClient, collection},
@@ -26,7 +26,7 @@ pub async fn initialise() -> Result<FirestoreClient, anyhow::Error> {
     Ok(client)
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Landmark {
     pub name: String,
     pub r#type: String,
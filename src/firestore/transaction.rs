@@ -0,0 +1,296 @@
+//! Transactions, which read and write several documents atomically. See
+//! [`FirestoreClient::run_transaction`] and
+//! [`FirestoreClient::read_only_transaction`].
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use firestore_grpc::tonic;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::FirebaseError;
+
+use super::{
+    aggregation::{Aggregation, AggregationResult},
+    client::{FirebaseStream, FirestoreClient, FirestoreDocument},
+    query::FirestoreQuery,
+    reference::DocumentReference,
+    write::{delete_write, serialize_write, Write, WriteKind},
+};
+
+/// How many times a transaction is attempted before giving up when it keeps
+/// getting aborted due to contention.
+pub(crate) const MAX_TRANSACTION_ATTEMPTS: u32 = 5;
+
+/// A handle to a transaction in progress, given to the function passed to
+/// [`FirestoreClient::run_transaction`].
+///
+/// Reads go through the transaction right away, while writes are queued and
+/// only sent to Firestore when the transaction is committed. Clones of the
+/// handle refer to the same transaction.
+#[derive(Clone)]
+pub struct Transaction {
+    client: FirestoreClient,
+    writes: Arc<Mutex<Vec<Write>>>,
+}
+
+impl Transaction {
+    /// Create a transaction handle from a client whose reads are already
+    /// made in the transaction.
+    pub(crate) fn new(client: FirestoreClient) -> Self {
+        Self {
+            client,
+            writes: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Read a document in the transaction. Same semantics as
+    /// [`FirestoreClient::get_document`].
+    pub async fn get_document<T: DeserializeOwned>(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<Option<T>, FirebaseError> {
+        self.client.get_document(doc_ref).await
+    }
+
+    /// Run a query in the transaction. Same semantics as
+    /// [`FirestoreClient::run_query`].
+    pub async fn run_query<'a, T: DeserializeOwned + 'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<FirebaseStream<'a, T, FirebaseError>, FirebaseError> {
+        self.client.run_query(query).await
+    }
+
+    /// Count the results of a query in the transaction. Same semantics as
+    /// [`FirestoreClient::count`].
+    pub async fn count<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<u64, FirebaseError> {
+        self.client.count(query).await
+    }
+
+    /// Count the results of a query in the transaction, stopping at `up_to`.
+    /// Same semantics as [`FirestoreClient::count_up_to`].
+    pub async fn count_up_to<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+        up_to: u64,
+    ) -> Result<u64, FirebaseError> {
+        self.client.count_up_to(query, up_to).await
+    }
+
+    /// Average a field across the results of a query in the transaction.
+    /// Same semantics as [`FirestoreClient::avg`].
+    pub async fn avg<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+        field: &str,
+    ) -> Result<Option<f64>, FirebaseError> {
+        self.client.avg(query, field).await
+    }
+
+    /// Run several aggregations over the results of a query in the
+    /// transaction. Same semantics as [`FirestoreClient::aggregate`].
+    pub async fn aggregate<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+        aggregations: impl IntoIterator<Item = Aggregation>,
+    ) -> Result<AggregationResult, FirebaseError> {
+        self.client.aggregate(query, aggregations).await
+    }
+
+    /// Queue a write that overwrites the document, creating it if it doesn't
+    /// exist.
+    pub fn set_document<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        self.queue_serialized(doc_ref, document, WriteKind::Set)
+    }
+
+    /// Queue a write that creates the document. The transaction fails to
+    /// commit if the document already exists.
+    pub fn create_document<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        self.queue_serialized(doc_ref, document, WriteKind::Create)
+    }
+
+    /// Queue a write that overwrites the document. The transaction fails to
+    /// commit if the document doesn't exist.
+    pub fn update_document<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+    ) -> Result<(), FirebaseError> {
+        self.queue_serialized(doc_ref, document, WriteKind::Update)
+    }
+
+    /// Queue a write that only updates the given fields of the document. The
+    /// transaction fails to commit if the document doesn't exist.
+    pub fn update_document_merge<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+        fields: &[&str],
+    ) -> Result<(), FirebaseError> {
+        self.queue_serialized(doc_ref, document, WriteKind::UpdateMerge(fields))
+    }
+
+    /// Queue a write that deletes the document, if it exists.
+    pub fn delete_document(&self, doc_ref: &DocumentReference) {
+        self.queue(delete_write(self.client.root_resource_path(), doc_ref));
+    }
+
+    fn queue_serialized<T: Serialize>(
+        &self,
+        doc_ref: &DocumentReference,
+        document: &T,
+        kind: WriteKind,
+    ) -> Result<(), FirebaseError> {
+        let write = serialize_write(self.client.root_resource_path(), doc_ref, document, kind)?;
+        self.queue(write);
+        Ok(())
+    }
+
+    fn queue(&self, write: Write) {
+        self.writes.lock().unwrap().push(write);
+    }
+
+    /// Take the queued writes, leaving none behind.
+    pub(crate) fn take_writes(&self) -> Vec<Write> {
+        std::mem::take(&mut *self.writes.lock().unwrap())
+    }
+}
+
+/// A handle to a read-only transaction, created with
+/// [`FirestoreClient::read_only_transaction`]. All reads made through the
+/// handle see the database at the same point in time.
+#[derive(Clone)]
+pub struct ReadOnlyTransaction {
+    client: FirestoreClient,
+}
+
+impl ReadOnlyTransaction {
+    /// Create a transaction handle from a client whose reads are already
+    /// made in the transaction.
+    pub(crate) fn new(client: FirestoreClient) -> Self {
+        Self { client }
+    }
+
+    /// Read a document in the transaction. Same semantics as
+    /// [`FirestoreClient::get_document`].
+    pub async fn get_document<T: DeserializeOwned>(
+        &mut self,
+        doc_ref: &DocumentReference,
+    ) -> Result<Option<T>, FirebaseError> {
+        self.client.get_document(doc_ref).await
+    }
+
+    /// Run a query in the transaction. Same semantics as
+    /// [`FirestoreClient::run_query`].
+    pub async fn run_query<'a, T: DeserializeOwned + 'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<FirebaseStream<'a, T, FirebaseError>, FirebaseError> {
+        self.client.run_query(query).await
+    }
+
+    /// Run a query in the transaction, including document metadata. Same
+    /// semantics as [`FirestoreClient::run_query_with_metadata`].
+    pub async fn run_query_with_metadata<'a, T: DeserializeOwned + 'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<FirebaseStream<'a, FirestoreDocument<T>, FirebaseError>, FirebaseError> {
+        self.client.run_query_with_metadata(query).await
+    }
+
+    /// Count the results of a query in the transaction. Same semantics as
+    /// [`FirestoreClient::count`].
+    pub async fn count<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+    ) -> Result<u64, FirebaseError> {
+        self.client.count(query).await
+    }
+
+    /// Count the results of a query in the transaction, stopping at `up_to`.
+    /// Same semantics as [`FirestoreClient::count_up_to`].
+    pub async fn count_up_to<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+        up_to: u64,
+    ) -> Result<u64, FirebaseError> {
+        self.client.count_up_to(query, up_to).await
+    }
+
+    /// Average a field across the results of a query in the transaction.
+    /// Same semantics as [`FirestoreClient::avg`].
+    pub async fn avg<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+        field: &str,
+    ) -> Result<Option<f64>, FirebaseError> {
+        self.client.avg(query, field).await
+    }
+
+    /// Run several aggregations over the results of a query in the
+    /// transaction. Same semantics as [`FirestoreClient::aggregate`].
+    pub async fn aggregate<'a>(
+        &'a mut self,
+        query: impl FirestoreQuery<'a>,
+        aggregations: impl IntoIterator<Item = Aggregation>,
+    ) -> Result<AggregationResult, FirebaseError> {
+        self.client.aggregate(query, aggregations).await
+    }
+}
+
+/// Whether the error is caused by Firestore aborting a transaction, in which
+/// case it's worth retrying.
+pub(crate) fn is_aborted(err: &FirebaseError) -> bool {
+    match err {
+        FirebaseError::Other(err) => err
+            .downcast_ref::<tonic::Status>()
+            .map_or(false, |status| status.code() == tonic::Code::Aborted),
+        _ => false,
+    }
+}
+
+/// How long to wait before the given attempt is retried, doubling with each
+/// attempt.
+pub(crate) fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(100) * 2u32.pow(attempt.saturating_sub(1).min(5))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_aborted_errors_are_retried() {
+        let aborted = FirebaseError::Other(anyhow::anyhow!(tonic::Status::aborted("contention")));
+        let aborted_with_context = FirebaseError::Other(
+            anyhow::Error::new(tonic::Status::aborted("contention")).context("Failed to commit"),
+        );
+        let not_found = FirebaseError::Other(anyhow::anyhow!(tonic::Status::not_found("gone")));
+
+        assert!(is_aborted(&aborted));
+        assert!(is_aborted(&aborted_with_context));
+        assert!(!is_aborted(&not_found));
+        assert!(!is_aborted(&FirebaseError::UserNotFound));
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(1), Duration::from_millis(100));
+        assert_eq!(retry_delay(2), Duration::from_millis(200));
+        assert_eq!(retry_delay(4), Duration::from_millis(800));
+    }
+}
@@ -0,0 +1,271 @@
+//! Collections bound to the Rust type of their documents, so call sites don't
+//! have to repeat the collection path and type parameter.
+
+use std::{fmt, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::FirebaseError;
+
+use super::{
+    client::{FirebaseStream, FirestoreClient},
+    query::CollectionQuery,
+    reference::{CollectionReference, DocumentReference},
+    validate::{validate, Validate},
+    write::WriteResult,
+};
+
+/// A collection whose documents are all of type `T`. It's a thin wrapper
+/// around a [`CollectionReference`] that is cheap to clone, so it can be
+/// created once, e.g. as a field of a repository struct, and used wherever
+/// the collection is read or written.
+///
+/// Collections created with [`validated`](Self::validated) check documents
+/// with their [`Validate`] implementation before every write, like
+/// [`FirestoreClient::set_document_validated`].
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::firestore::{
+///     collection,
+///     query::{filter, Direction, EqualTo},
+///     typed::TypedCollection,
+/// };
+/// use futures::TryStreamExt;
+/// use serde::{Deserialize, Serialize};
+/// use ulid::Ulid;
+///
+/// #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// struct Book {
+///     title: String,
+///     year: u32,
+/// }
+///
+/// let books: TypedCollection<Book> =
+///     TypedCollection::new(collection("libraries").doc(Ulid::new()).collection("books"));
+///
+/// let dune = Book { title: "Dune".into(), year: 1965 };
+/// books.set(&mut client, "dune", &dune).await?;
+/// books
+///     .set(&mut client, "emma", &Book { title: "Emma".into(), year: 1815 })
+///     .await?;
+///
+/// assert_eq!(books.get(&mut client, "dune").await?, Some(dune.clone()));
+///
+/// let by_year: Vec<Book> = books
+///     .query(&mut client, |query| query.order_by("year", Direction::Descending))
+///     .await?
+///     .try_collect()
+///     .await?;
+/// assert_eq!(by_year[0], dune);
+///
+/// let emma = books
+///     .query_one(&mut client, |query| query.with_filter(filter("title", EqualTo("Emma"))))
+///     .await?;
+/// assert_eq!(emma.map(|book| book.year), Some(1815));
+/// # Ok(())
+/// # }
+/// ```
+pub struct TypedCollection<T> {
+    collection: CollectionReference,
+    /// Checks documents before they are written, if the collection is
+    /// validated.
+    validator: Option<fn(&T) -> Result<(), FirebaseError>>,
+    // `fn() -> T` keeps the handle `Send` and `Sync` regardless of `T`, as it
+    // never holds a `T`
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedCollection<T> {
+    pub fn new(collection: CollectionReference) -> Self {
+        Self {
+            collection,
+            validator: None,
+            _type: PhantomData,
+        }
+    }
+
+    /// A collection whose documents are validated before they are written
+    /// with [`set`](Self::set), [`create`](Self::create) and
+    /// [`update`](Self::update). Invalid documents fail with
+    /// [`InvalidDocument`](FirebaseError::InvalidDocument) without being
+    /// written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    /// use fireplace::{
+    ///     error::FirebaseError,
+    ///     firestore::{
+    ///         collection,
+    ///         typed::TypedCollection,
+    ///         validate::{Validate, Violations},
+    ///     },
+    /// };
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Review {
+    ///     stars: u8,
+    /// }
+    ///
+    /// impl Validate for Review {
+    ///     fn validate(&self, violations: &mut Violations) {
+    ///         violations.check((1..=5).contains(&self.stars), "stars", "must be 1 to 5");
+    ///     }
+    /// }
+    ///
+    /// let reviews: TypedCollection<Review> = TypedCollection::validated(collection("reviews"));
+    ///
+    /// let result = reviews.set(&mut client, "too-good", &Review { stars: 6 }).await;
+    /// assert!(matches!(result, Err(FirebaseError::InvalidDocument(_))));
+    /// assert!(reviews.get(&mut client, "too-good").await?.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn validated(collection: CollectionReference) -> Self
+    where
+        T: Validate,
+    {
+        Self {
+            validator: Some(validate::<T>),
+            ..Self::new(collection)
+        }
+    }
+
+    /// The untyped reference to the collection.
+    pub fn reference(&self) -> &CollectionReference {
+        &self.collection
+    }
+
+    /// A reference to the document with the given ID in the collection.
+    pub fn doc(&self, id: impl Into<String>) -> DocumentReference {
+        self.collection.doc(id)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> TypedCollection<T> {
+    /// Get the document with the given ID. See
+    /// [`FirestoreClient::get_document`].
+    pub async fn get(
+        &self,
+        client: &mut FirestoreClient,
+        id: impl Into<String>,
+    ) -> Result<Option<T>, FirebaseError> {
+        client.get_document(&self.doc(id)).await
+    }
+
+    /// Write the document with the given ID, overwriting it if it exists. See
+    /// [`FirestoreClient::set_document`].
+    pub async fn set(
+        &self,
+        client: &mut FirestoreClient,
+        id: impl Into<String>,
+        document: &T,
+    ) -> Result<WriteResult, FirebaseError> {
+        self.check(document)?;
+        client.set_document(&self.doc(id), document).await
+    }
+
+    /// Create a document with a generated ID, returning the ID. See
+    /// [`FirestoreClient::create_document`].
+    pub async fn create(
+        &self,
+        client: &mut FirestoreClient,
+        document: &T,
+    ) -> Result<String, FirebaseError> {
+        self.check(document)?;
+        client.create_document(&self.collection, document).await
+    }
+
+    /// Overwrite the document with the given ID, failing if it doesn't
+    /// exist. See [`FirestoreClient::update_document`].
+    pub async fn update(
+        &self,
+        client: &mut FirestoreClient,
+        id: impl Into<String>,
+        document: &T,
+    ) -> Result<WriteResult, FirebaseError> {
+        self.check(document)?;
+        client.update_document(&self.doc(id), document).await
+    }
+
+    /// Delete the document with the given ID. See
+    /// [`FirestoreClient::delete_document`].
+    pub async fn delete(
+        &self,
+        client: &mut FirestoreClient,
+        id: impl Into<String>,
+    ) -> Result<WriteResult, FirebaseError> {
+        client.delete_document(&self.doc(id)).await
+    }
+
+    /// Run a query over the collection, built by the given function from a
+    /// query for all documents of the collection. See
+    /// [`FirestoreClient::run_query`].
+    pub async fn query<'a>(
+        &self,
+        client: &'a mut FirestoreClient,
+        build: impl FnOnce(CollectionQuery<'a>) -> CollectionQuery<'a>,
+    ) -> Result<FirebaseStream<'a, T, FirebaseError>, FirebaseError>
+    where
+        T: 'a,
+    {
+        let query = build(CollectionQuery::new(self.collection.clone()));
+        client.run_query(query).await
+    }
+
+    /// Get the first result of a query over the collection, built like in
+    /// [`query`](Self::query). See [`FirestoreClient::run_query_one`].
+    pub async fn query_one<'a>(
+        &self,
+        client: &'a mut FirestoreClient,
+        build: impl FnOnce(CollectionQuery<'a>) -> CollectionQuery<'a>,
+    ) -> Result<Option<T>, FirebaseError>
+    where
+        T: 'a,
+    {
+        let query = build(CollectionQuery::new(self.collection.clone()));
+        client.run_query_one(query).await
+    }
+
+    fn check(&self, document: &T) -> Result<(), FirebaseError> {
+        match self.validator {
+            Some(validate) => validate(document),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> Clone for TypedCollection<T> {
+    fn clone(&self) -> Self {
+        Self {
+            collection: self.collection.clone(),
+            validator: self.validator,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TypedCollection<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedCollection")
+            .field("collection", &self.collection)
+            .field("type", &std::any::type_name::<T>())
+            .field("validated", &self.validator.is_some())
+            .finish()
+    }
+}
+
+impl<T> From<CollectionReference> for TypedCollection<T> {
+    fn from(collection: CollectionReference) -> Self {
+        Self::new(collection)
+    }
+}
@@ -0,0 +1,231 @@
+//! Helpers for inspecting how much data a database holds, e.g. for capacity
+//! planning.
+//!
+//! Document counts are exact and computed server-side, while sizes are
+//! estimated from a sample of documents in each collection using
+//! [Firestore's storage size rules](https://firebase.google.com/docs/firestore/storage-size).
+
+use std::collections::HashMap;
+
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use firestore_grpc::v1::{value::ValueType, Document, Value};
+
+use crate::error::FirebaseError;
+
+use super::{client::FirestoreClient, reference::collection, serde::strip_reference_prefix};
+
+/// Every document has 32 bytes of additional storage overhead.
+const DOCUMENT_OVERHEAD_BYTES: u64 = 32;
+
+/// Every document name has 16 bytes of additional storage overhead.
+const DOCUMENT_NAME_OVERHEAD_BYTES: u64 = 16;
+
+/// Usage of the root collections of a database.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub collections: Vec<CollectionUsage>,
+}
+
+impl UsageReport {
+    /// The total number of documents in the root collections.
+    pub fn total_document_count(&self) -> u64 {
+        self.collections.iter().map(|c| c.document_count).sum()
+    }
+
+    /// The estimated total size of the documents in the root collections, in
+    /// bytes.
+    pub fn estimated_total_size(&self) -> u64 {
+        self.collections
+            .iter()
+            .map(|c| c.estimated_total_size)
+            .sum()
+    }
+}
+
+/// Usage of a single collection. Subcollections are not included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionUsage {
+    pub collection_id: String,
+    /// The exact number of documents in the collection.
+    pub document_count: u64,
+    /// How many documents the size estimates are based on.
+    pub sampled_documents: u64,
+    /// The average size of the sampled documents, in bytes.
+    pub average_document_size: u64,
+    /// The average document size multiplied by the document count, in bytes.
+    pub estimated_total_size: u64,
+}
+
+/// Create a usage report for all root collections of the database, sampling
+/// up to `sample_size` documents per collection to estimate document sizes.
+///
+/// Counting documents is billed as one read per 1000 documents counted, and
+/// each sampled document is billed as a regular read.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::firestore::{collection, usage::collect_usage_report};
+///
+/// client
+///     .set_document(
+///         &collection("usage-report-example").doc("doc"),
+///         &serde_json::json!({ "name": "Hello" }),
+///     )
+///     .await?;
+///
+/// let report = collect_usage_report(&mut client, 10).await?;
+/// let usage = report
+///     .collections
+///     .iter()
+///     .find(|c| c.collection_id == "usage-report-example")
+///     .unwrap();
+///
+/// assert_eq!(usage.document_count, 1);
+/// assert!(usage.average_document_size > 0);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn collect_usage_report(
+    client: &mut FirestoreClient,
+    sample_size: u32,
+) -> Result<UsageReport, FirebaseError> {
+    let collection_ids = client.list_collection_ids().await?;
+
+    let mut collections = Vec::with_capacity(collection_ids.len());
+
+    for collection_id in collection_ids {
+        collections.push(collect_collection_usage(client, collection_id, sample_size).await?);
+    }
+
+    Ok(UsageReport { collections })
+}
+
+async fn collect_collection_usage(
+    client: &mut FirestoreClient,
+    collection_id: String,
+    sample_size: u32,
+) -> Result<CollectionUsage, FirebaseError> {
+    let document_count = client.count(collection(&collection_id)).await?;
+
+    let sample: Vec<Document> = if sample_size > 0 {
+        client
+            .query_raw(collection(&collection_id).with_limit(sample_size))
+            .await?
+            .try_collect()
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let sampled_documents = sample.len() as u64;
+    let sampled_size: u64 = sample.iter().map(document_size).sum();
+    let average_document_size = sampled_size.checked_div(sampled_documents).unwrap_or(0);
+
+    Ok(CollectionUsage {
+        collection_id,
+        document_count,
+        sampled_documents,
+        average_document_size,
+        estimated_total_size: average_document_size * document_count,
+    })
+}
+
+/// The storage size of a document in bytes, as calculated by Firestore.
+pub(crate) fn document_size(document: &Document) -> u64 {
+    document_name_size(&document.name) + fields_size(&document.fields) + DOCUMENT_OVERHEAD_BYTES
+}
+
+/// Only the path of the document within the database counts towards the size
+/// of a name, not the project and database prefix.
+fn document_name_size(name: &str) -> u64 {
+    document_path_size(&strip_reference_prefix(name))
+}
+
+fn document_path_size(path: &str) -> u64 {
+    let path_size: u64 = path.split('/').map(string_size).sum();
+
+    path_size + DOCUMENT_NAME_OVERHEAD_BYTES
+}
+
+fn fields_size(fields: &HashMap<String, Value>) -> u64 {
+    fields
+        .iter()
+        .map(|(name, value)| string_size(name) + value_size(value))
+        .sum()
+}
+
+fn value_size(value: &Value) -> u64 {
+    use ValueType::*;
+
+    match &value.value_type {
+        None | Some(NullValue(_)) | Some(BooleanValue(_)) => 1,
+        Some(IntegerValue(_)) | Some(DoubleValue(_)) | Some(TimestampValue(_)) => 8,
+        Some(GeoPointValue(_)) => 16,
+        Some(StringValue(s)) => string_size(s),
+        Some(BytesValue(b)) => b.len() as u64,
+        Some(ReferenceValue(r)) => document_name_size(r),
+        Some(ArrayValue(a)) => a.values.iter().map(value_size).sum(),
+        Some(MapValue(m)) => fields_size(&m.fields),
+    }
+}
+
+/// Strings are stored as UTF-8 plus one byte.
+fn string_size(s: &str) -> u64 {
+    s.len() as u64 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firestore::serde::DocumentSerializer;
+
+    fn document(name: &str, json: serde_json::Value) -> Document {
+        let mut doc = DocumentSerializer::new("projects/p/databases/(default)/documents")
+            .serialize(&json)
+            .unwrap();
+        doc.name = format!("projects/p/databases/(default)/documents/{name}");
+        doc
+    }
+
+    #[test]
+    fn document_size_follows_firestore_rules() {
+        // Example from the Firestore docs: the name `users/jeff/tasks/my_task_id`
+        // is 6 + 5 + 6 + 11 + 16 = 44 bytes
+        assert_eq!(document_path_size("users/jeff/tasks/my_task_id"), 44);
+
+        let doc = document(
+            "users/jeff/tasks/my_task_id",
+            serde_json::json!({
+                "type": "Personal",
+                "done": false,
+                "priority": 1,
+            }),
+        );
+
+        // 44 for the name, 5 + 9, 5 + 1 and 9 + 8 for the fields, and 32 of
+        // document overhead
+        assert_eq!(document_size(&doc), 44 + 14 + 6 + 17 + 32);
+    }
+
+    #[test]
+    fn nested_values_are_summed() {
+        let doc = document(
+            "c/d",
+            serde_json::json!({
+                "list": ["ab", 1.5],
+                "map": { "x": null },
+            }),
+        );
+
+        let name = 2 + 2 + 16;
+        let list = 5 + 3 + 8;
+        let map = 4 + 2 + 1;
+        assert_eq!(document_size(&doc), name + list + map + 32);
+    }
+}
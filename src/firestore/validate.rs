@@ -0,0 +1,224 @@
+//! Client-side validation of documents before they are written. See
+//! [`Validate`].
+
+use std::fmt;
+
+use crate::error::FirebaseError;
+
+/// Checks that a document is valid before it is written, so invalid documents
+/// are rejected with useful messages instead of ending up in the database.
+/// Write validated documents with e.g.
+/// [`set_document_validated`](super::client::FirestoreClient::set_document_validated),
+/// or call [`validate`] before any other write.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::{
+///     error::FirebaseError,
+///     firestore::validate::{validate, Validate, Violations},
+/// };
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Product {
+///     name: String,
+///     price_cents: i64,
+///     tags: Vec<String>,
+/// }
+///
+/// impl Validate for Product {
+///     fn validate(&self, violations: &mut Violations) {
+///         violations.check(!self.name.trim().is_empty(), "name", "must not be empty");
+///         violations.check(self.price_cents >= 0, "price_cents", "must not be negative");
+///         for (i, tag) in self.tags.iter().enumerate() {
+///             violations.check(
+///                 tag.len() <= 20,
+///                 format!("tags.{i}"),
+///                 "must be at most 20 characters",
+///             );
+///         }
+///     }
+/// }
+///
+/// let product = Product {
+///     name: " ".to_string(),
+///     price_cents: -100,
+///     tags: vec!["sale".to_string()],
+/// };
+///
+/// let violations = match validate(&product) {
+///     Err(FirebaseError::InvalidDocument(violations)) => violations,
+///     _ => panic!("expected the product to be invalid"),
+/// };
+/// assert_eq!(violations.len(), 2);
+/// assert_eq!(violations.iter().next().unwrap().field, "name");
+/// ```
+pub trait Validate {
+    /// Adds a violation for every problem with the document.
+    fn validate(&self, violations: &mut Violations);
+}
+
+/// Runs the document's validation, returning an
+/// [`InvalidDocument`](FirebaseError::InvalidDocument) error with all
+/// violations if it is invalid.
+pub fn validate<T: Validate + ?Sized>(document: &T) -> Result<(), FirebaseError> {
+    let mut violations = Violations::new();
+    document.validate(&mut violations);
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(FirebaseError::InvalidDocument(violations))
+    }
+}
+
+/// A problem with a single field of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The path of the field, e.g. `address.city`, or an empty string if the
+    /// problem is with the document as a whole.
+    pub field: String,
+    pub message: String,
+}
+
+/// The problems found while validating a document. See [`Validate`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Violations {
+    violations: Vec<Violation>,
+}
+
+impl Violations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a violation of the given field.
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.violations.push(Violation {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Add a violation of the given field unless the condition holds.
+    pub fn check(&mut self, condition: bool, field: impl Into<String>, message: impl Into<String>) {
+        if !condition {
+            self.add(field, message);
+        }
+    }
+
+    /// Validate a nested document, prefixing the fields of its violations
+    /// with the given field, e.g. `address.city` for the `city` of a nested
+    /// `address`.
+    pub fn nested<T: Validate + ?Sized>(&mut self, field: &str, document: &T) {
+        let mut nested = Violations::new();
+        document.validate(&mut nested);
+
+        for violation in nested.violations {
+            let field = if violation.field.is_empty() {
+                field.to_string()
+            } else {
+                format!("{}.{}", field, violation.field)
+            };
+            self.add(field, violation.message);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.violations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Violation> {
+        self.violations.iter()
+    }
+}
+
+impl IntoIterator for Violations {
+    type Item = Violation;
+    type IntoIter = std::vec::IntoIter<Violation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.violations.into_iter()
+    }
+}
+
+impl fmt::Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            if violation.field.is_empty() {
+                f.write_str(&violation.message)?;
+            } else {
+                write!(f, "{} {}", violation.field, violation.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Address {
+        city: String,
+    }
+
+    impl Validate for Address {
+        fn validate(&self, violations: &mut Violations) {
+            violations.check(!self.city.is_empty(), "city", "must not be empty");
+        }
+    }
+
+    struct Customer {
+        email: String,
+        address: Address,
+    }
+
+    impl Validate for Customer {
+        fn validate(&self, violations: &mut Violations) {
+            violations.check(
+                self.email.contains('@'),
+                "email",
+                "must be an email address",
+            );
+            violations.nested("address", &self.address);
+        }
+    }
+
+    #[test]
+    fn collects_nested_violations() {
+        let customer = Customer {
+            email: "nope".to_string(),
+            address: Address {
+                city: String::new(),
+            },
+        };
+
+        let err = validate(&customer).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid document: email must be an email address, address.city must not be empty"
+        );
+    }
+
+    #[test]
+    fn valid_documents_pass() {
+        let customer = Customer {
+            email: "leia@example.com".to_string(),
+            address: Address {
+                city: "Alderaan".to_string(),
+            },
+        };
+
+        assert!(validate(&customer).is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+//! Vector values, e.g. text or image embeddings, stored in the representation
+//! Firestore uses for
+//! [vector search](https://firebase.google.com/docs/firestore/vector-search).
+
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+
+const VECTOR_TYPE_KEY: &str = "__type__";
+const VECTOR_TYPE: &str = "__vector__";
+const VECTOR_VALUE_KEY: &str = "value";
+
+/// A vector of doubles. Firestore stores vectors as a map with a `__type__`
+/// field set to `__vector__` and the values in a `value` array, which is what
+/// this type (de)serializes to, so documents written here can be indexed for
+/// vector search and read back by the other SDKs.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::firestore::{collection, vector::Vector};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Article {
+///     title: String,
+///     embedding: Vector,
+/// }
+///
+/// let article = Article {
+///     title: "Bridges of San Francisco".to_string(),
+///     embedding: Vector::new(vec![0.12, -0.5, 0.98]),
+/// };
+///
+/// let doc_ref = collection("articles").doc("bridges");
+/// client.set_document(&doc_ref, &article).await?;
+///
+/// let stored = client.get_document::<Article>(&doc_ref).await?;
+/// assert_eq!(stored, Some(article));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Vector(Vec<f64>);
+
+impl Vector {
+    pub fn new(values: Vec<f64>) -> Self {
+        Self(values)
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn into_inner(self) -> Vec<f64> {
+        self.0
+    }
+}
+
+impl From<Vec<f64>> for Vector {
+    fn from(values: Vec<f64>) -> Self {
+        Self(values)
+    }
+}
+
+impl From<Vector> for Vec<f64> {
+    fn from(vector: Vector) -> Self {
+        vector.0
+    }
+}
+
+impl Serialize for Vector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Vector", 2)?;
+        s.serialize_field(VECTOR_TYPE_KEY, VECTOR_TYPE)?;
+        s.serialize_field(VECTOR_VALUE_KEY, &self.0)?;
+        s.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Vector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawVector {
+            #[serde(rename = "__type__")]
+            type_name: String,
+            value: Vec<f64>,
+        }
+
+        let raw = RawVector::deserialize(deserializer)?;
+        if raw.type_name != VECTOR_TYPE {
+            return Err(de::Error::custom(format!(
+                "expected a vector value, found a map with {} '{}'",
+                VECTOR_TYPE_KEY, raw.type_name
+            )));
+        }
+
+        Ok(Self(raw.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use firestore_grpc::v1::value::ValueType;
+
+    use crate::firestore::serde::serialize_to_value_type;
+
+    use super::*;
+
+    #[test]
+    fn serializes_to_firestore_vector_map() {
+        let value = serialize_to_value_type(&Vector::new(vec![1.0, 2.5]), "root").unwrap();
+
+        let map = match value {
+            ValueType::MapValue(map) => map,
+            other => panic!("expected a map value, got {other:?}"),
+        };
+        assert_eq!(
+            map.fields[VECTOR_TYPE_KEY].value_type,
+            Some(ValueType::StringValue(VECTOR_TYPE.to_string()))
+        );
+        let array = match &map.fields[VECTOR_VALUE_KEY].value_type {
+            Some(ValueType::ArrayValue(array)) => array,
+            other => panic!("expected an array of values, got {other:?}"),
+        };
+        let values: Vec<_> = array.values.iter().map(|v| v.value_type.clone()).collect();
+        assert_eq!(
+            values,
+            vec![
+                Some(ValueType::DoubleValue(1.0)),
+                Some(ValueType::DoubleValue(2.5))
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_maps_that_are_not_vectors() {
+        let json = serde_json::json!({ "__type__": "__point__", "value": [1.0] });
+        assert!(serde_json::from_value::<Vector>(json).is_err());
+
+        let json = serde_json::json!({ "__type__": "__vector__", "value": [1.0, 2] });
+        let vector: Vector = serde_json::from_value(json).unwrap();
+        assert_eq!(vector.values(), &[1.0, 2.0]);
+    }
+}
@@ -0,0 +1,728 @@
+use std::{collections::HashSet, time::SystemTime};
+
+use firestore_grpc::v1::{
+    document_transform::{field_transform::TransformType, FieldTransform},
+    precondition::ConditionType,
+    value::ValueType,
+    write::Operation,
+    Document, DocumentMask, Precondition, Value,
+};
+use once_cell::sync::OnceCell;
+use prost_types::Timestamp;
+use serde::{Serialize, Serializer};
+
+use crate::error::FirebaseError;
+
+use super::{
+    consistency::{system_time, ConsistencyToken},
+    reference::{hashed_type_id, DocumentReference},
+    serde::{field_shape, DocumentSerializer, FieldShape},
+};
+
+pub use firestore_grpc::v1::Write;
+
+/// The maximum number of writes Firestore accepts in a single batch.
+pub(crate) const MAX_BATCH_WRITE_SIZE: usize = 500;
+
+/// Describes how a document should be written when turned into a [`Write`]
+/// with [`FirestoreClient::to_write`](super::client::FirestoreClient::to_write).
+///
+/// Each kind mirrors one of the write methods on the client.
+#[derive(Debug, Clone, Copy)]
+pub enum WriteKind<'a> {
+    /// Overwrites the document, creating it if it doesn't exist. Same
+    /// semantics as `set_document`.
+    Set,
+    /// Creates the document, failing if it already exists. Same semantics as
+    /// `create_document_at_ref`.
+    Create,
+    /// Overwrites the document, failing if it doesn't exist. Same semantics
+    /// as `update_document`.
+    Update,
+    /// Only writes the given fields, creating the document if it doesn't
+    /// exist. Same semantics as `set_document_merge`.
+    SetMerge(&'a [&'a str]),
+    /// Only writes the given fields, failing if the document doesn't exist.
+    /// Same semantics as `update_document_merge`.
+    UpdateMerge(&'a [&'a str]),
+}
+
+/// What a recursive delete did, e.g. with
+/// [`delete_document_recursive`](super::client::FirestoreClient::delete_document_recursive).
+/// Deletes that fail don't stop the remaining documents from being deleted,
+/// so check [`failures`](Self::failures) to tell whether everything is gone.
+/// Calling the delete again retries the failed documents.
+#[derive(Debug, Default)]
+pub struct DeleteSummary {
+    /// The number of documents deleted, not counting documents that didn't
+    /// exist.
+    pub documents_deleted: u64,
+    /// The number of write requests sent, each deleting up to 500 documents.
+    pub batches_committed: u64,
+    /// The documents that couldn't be deleted.
+    pub failures: Vec<DeleteFailure>,
+}
+
+impl DeleteSummary {
+    /// Whether every document was deleted.
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A document that a recursive delete failed to delete.
+#[derive(Debug)]
+pub struct DeleteFailure {
+    /// The path of the document, e.g. `cities/SF`.
+    pub path: String,
+    pub error: FirebaseError,
+}
+
+/// The result of a write that was applied by Firestore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteResult {
+    /// The time at which the write was applied, in seconds of UTC time since
+    /// Unix epoch. Not set for deletes, as the document no longer has an
+    /// update time.
+    pub update_time: Option<i64>,
+    /// The exact time at which the write was applied, with the nanosecond
+    /// precision of Firestore. Not set for deletes.
+    pub update_timestamp: Option<SystemTime>,
+    /// The exact time at which the write was applied.
+    time: Option<ConsistencyToken>,
+}
+
+impl WriteResult {
+    /// A token for the exact time at which the write was applied, for reading
+    /// the database as of the write with
+    /// [`read_at`](super::client::FirestoreClient::read_at). For deletes
+    /// committed with [`commit`](super::client::FirestoreClient::commit) and
+    /// the single document writes built on it, this is the commit time. Not
+    /// set for deletes applied with
+    /// [`batch_write`](super::client::FirestoreClient::batch_write), which
+    /// doesn't return a commit time.
+    pub fn consistency_token(&self) -> Option<ConsistencyToken> {
+        self.time
+    }
+
+    /// Uses the commit time as the consistency token when the write has no
+    /// update time, which is the case for deletes.
+    pub(crate) fn or_commit_time(mut self, commit_time: Option<&Timestamp>) -> Self {
+        if self.time.is_none() {
+            self.time = commit_time.cloned().map(ConsistencyToken::from_timestamp);
+        }
+        self
+    }
+}
+
+impl From<Option<Timestamp>> for WriteResult {
+    fn from(update_time: Option<Timestamp>) -> Self {
+        Self {
+            update_time: update_time.as_ref().map(|t| t.seconds),
+            update_timestamp: update_time.as_ref().map(system_time),
+            time: update_time.map(ConsistencyToken::from_timestamp),
+        }
+    }
+}
+
+impl From<firestore_grpc::v1::WriteResult> for WriteResult {
+    fn from(result: firestore_grpc::v1::WriteResult) -> Self {
+        result.update_time.into()
+    }
+}
+
+static DELETE_FIELD_TYPE_ID: OnceCell<String> = OnceCell::new();
+
+/// A sentinel that deletes a field when used as its value in a merge, like
+/// `FieldValue.delete()` in the official SDKs. The field is left out of the
+/// document, so when its path is in the field mask, Firestore removes it.
+///
+/// It can only be used as the value of a field, not e.g. in an array.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), fireplace::error::FirebaseError> {
+/// # let mut client = fireplace::firestore::test_helpers::initialise().await?;
+/// use fireplace::firestore::{collection, write::DeleteField};
+/// use serde::Serialize;
+///
+/// let doc_ref = collection("people").doc("han");
+/// client
+///     .set_document(&doc_ref, &serde_json::json!({ "name": "Han", "debt": 1000 }))
+///     .await?;
+///
+/// #[derive(Serialize)]
+/// struct PayOff {
+///     debt: DeleteField,
+/// }
+///
+/// let person: serde_json::Value = client
+///     .set_document_merge(&doc_ref, &PayOff { debt: DeleteField }, &["debt"])
+///     .await?;
+///
+/// assert_eq!(person, serde_json::json!({ "name": "Han" }));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DeleteField;
+
+impl DeleteField {
+    pub(crate) fn type_id() -> &'static str {
+        DELETE_FIELD_TYPE_ID.get_or_init(hashed_type_id::<Self>)
+    }
+}
+
+impl Serialize for DeleteField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_struct(Self::type_id())
+    }
+}
+
+pub(crate) fn serialize_write<T: Serialize>(
+    root_resource_path: &str,
+    doc_ref: &DocumentReference,
+    document: &T,
+    kind: WriteKind,
+) -> Result<Write, super::serde::Error> {
+    let name = format!("{}/{}", root_resource_path, doc_ref);
+    let doc = DocumentSerializer::new(root_resource_path)
+        .name(name)
+        .serialize(document)?;
+
+    let (update_mask, current_document) = match kind {
+        WriteKind::Set => (None, None),
+        WriteKind::Create => (None, exists_precondition(false)),
+        WriteKind::Update => (None, exists_precondition(true)),
+        WriteKind::SetMerge(fields) => (Some(field_mask(fields)), None),
+        WriteKind::UpdateMerge(fields) => (Some(field_mask(fields)), exists_precondition(true)),
+    };
+
+    Ok(Write {
+        operation: Some(Operation::Update(doc)),
+        update_mask,
+        update_transforms: vec![],
+        current_document,
+    })
+}
+
+pub(crate) fn delete_write(root_resource_path: &str, doc_ref: &DocumentReference) -> Write {
+    delete_write_by_name(format!("{}/{}", root_resource_path, doc_ref))
+}
+
+/// Like [`delete_write`], but for the full resource name of a document.
+pub(crate) fn delete_write_by_name(name: String) -> Write {
+    Write {
+        operation: Some(Operation::Delete(name)),
+        update_mask: None,
+        update_transforms: vec![],
+        current_document: None,
+    }
+}
+
+/// Server-side transforms of a document's fields, which are applied
+/// atomically without reading the document first. Create them with
+/// [`transforms`] and apply them with
+/// [`FirestoreClient::update_transform`](super::client::FirestoreClient::update_transform).
+#[derive(Debug, Clone, Default)]
+pub struct FieldTransforms {
+    transforms: Vec<FieldTransform>,
+}
+
+/// Creates an empty set of [`FieldTransforms`].
+pub fn transforms() -> FieldTransforms {
+    FieldTransforms::default()
+}
+
+impl FieldTransforms {
+    /// Add the given amount to the field at the given path. If the field
+    /// doesn't exist or isn't a number, it is set to the amount. Pass a
+    /// negative amount to decrement the field.
+    pub fn increment(mut self, field_path: impl Into<String>, by: impl Into<Numeric>) -> Self {
+        self.transforms.push(FieldTransform {
+            field_path: field_path.into(),
+            transform_type: Some(TransformType::Increment(by.into().into_value())),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+}
+
+/// A number that a field can be incremented by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Numeric {
+    Integer(i64),
+    Double(f64),
+}
+
+impl Numeric {
+    fn into_value(self) -> Value {
+        let value_type = match self {
+            Self::Integer(n) => ValueType::IntegerValue(n),
+            Self::Double(n) => ValueType::DoubleValue(n),
+        };
+
+        Value {
+            value_type: Some(value_type),
+        }
+    }
+}
+
+impl From<i64> for Numeric {
+    fn from(n: i64) -> Self {
+        Self::Integer(n)
+    }
+}
+
+impl From<i32> for Numeric {
+    fn from(n: i32) -> Self {
+        Self::Integer(n.into())
+    }
+}
+
+impl From<u32> for Numeric {
+    fn from(n: u32) -> Self {
+        Self::Integer(n.into())
+    }
+}
+
+impl From<f64> for Numeric {
+    fn from(n: f64) -> Self {
+        Self::Double(n)
+    }
+}
+
+/// Creates a write that only applies the given transforms, creating the
+/// document if it doesn't exist. Other fields are left untouched.
+pub(crate) fn transform_write(
+    root_resource_path: &str,
+    doc_ref: &DocumentReference,
+    transforms: FieldTransforms,
+) -> Write {
+    Write {
+        operation: Some(Operation::Update(Document {
+            name: format!("{}/{}", root_resource_path, doc_ref),
+            ..Default::default()
+        })),
+        // An empty mask means that only the transforms are applied
+        update_mask: Some(field_mask(&[])),
+        update_transforms: transforms.transforms,
+        current_document: None,
+    }
+}
+
+/// Creates a write that atomically increments the integer field at the given
+/// path, creating the document and the field if they don't exist. Other fields
+/// are left untouched.
+pub(crate) fn increment_write(
+    root_resource_path: &str,
+    doc_ref: &DocumentReference,
+    field_path: &str,
+    by: i64,
+) -> Write {
+    transform_write(
+        root_resource_path,
+        doc_ref,
+        transforms().increment(field_path, by),
+    )
+}
+
+/// Splits the writes into batches that can each be sent with a single batch
+/// write request. Firestore doesn't allow writing the same document more than
+/// once per batch, so a new batch is started whenever a document repeats.
+/// The order of the writes is preserved.
+pub(crate) fn split_into_batches(writes: Vec<Write>) -> Vec<Vec<Write>> {
+    let mut batches: Vec<Vec<Write>> = vec![];
+    let mut names_in_batch = HashSet::new();
+
+    for write in writes {
+        let name = write_document_name(&write).map(str::to_string);
+
+        let starts_new_batch = match batches.last() {
+            None => true,
+            Some(batch) => {
+                batch.len() >= MAX_BATCH_WRITE_SIZE
+                    || name.as_ref().map_or(false, |n| names_in_batch.contains(n))
+            }
+        };
+
+        if starts_new_batch {
+            batches.push(vec![]);
+            names_in_batch.clear();
+        }
+
+        names_in_batch.extend(name);
+        batches.last_mut().unwrap().push(write);
+    }
+
+    batches
+}
+
+fn write_document_name(write: &Write) -> Option<&str> {
+    match write.operation.as_ref()? {
+        Operation::Update(doc) => Some(&doc.name),
+        Operation::Delete(name) => Some(name),
+        Operation::Transform(transform) => Some(&transform.document),
+    }
+}
+
+fn field_mask(fields: &[&str]) -> DocumentMask {
+    DocumentMask {
+        field_paths: fields.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+fn exists_precondition(exists: bool) -> Option<Precondition> {
+    Some(Precondition {
+        condition_type: Some(ConditionType::Exists(exists)),
+    })
+}
+
+/// Checks that each of the given field paths, as passed to e.g.
+/// [`set_document_merge`](super::client::FirestoreClient::set_document_merge),
+/// refers to a field of the document as it is serialized. Call it in a unit
+/// test next to hand-written field masks, so a `rename_all` on the type and
+/// the mask can't silently drift apart.
+///
+/// Segments that aren't simple identifiers may be quoted with backticks, as
+/// in Firestore's field path syntax.
+///
+/// Fields that are left out of the write still count, i.e.
+/// [`DeleteField`] sentinels and fields skipped with `skip_serializing_if`.
+/// As the fields inside skipped fields and `None` values aren't known, any
+/// path into them is accepted.
+///
+/// # Examples
+///
+/// ```
+/// use fireplace::firestore::write::check_field_paths;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct TestType {
+///     label: String,
+///     nested: NestedItem,
+/// }
+///
+/// #[derive(Serialize)]
+/// #[serde(rename_all = "camelCase")]
+/// struct NestedItem {
+///     field_b: String,
+/// }
+///
+/// let doc = TestType {
+///     label: "Hello".to_string(),
+///     nested: NestedItem { field_b: "B".to_string() },
+/// };
+///
+/// assert!(check_field_paths(&doc, &["label", "nested.fieldB"]).is_ok());
+///
+/// let err = check_field_paths(&doc, &["nested.field_b"]).unwrap_err();
+/// assert!(err.to_string().contains("did you mean 'nested.fieldB'?"));
+/// ```
+pub fn check_field_paths<T: Serialize>(
+    document: &T,
+    field_paths: &[&str],
+) -> Result<(), FirebaseError> {
+    let shape = field_shape(document)?;
+    if !matches!(shape, FieldShape::Map(_)) {
+        return Err(super::serde::Error::InvalidDocument.into());
+    }
+
+    for field_path in field_paths {
+        let segments = split_field_path(field_path);
+        let mut shape = &shape;
+        let mut resolved = Vec::with_capacity(segments.len());
+
+        for segment in &segments {
+            let current = match shape {
+                FieldShape::Map(current) => current,
+                FieldShape::Unknown => break,
+                FieldShape::Value => {
+                    return Err(anyhow::anyhow!(
+                        "Field path '{}' goes into '{}', which is not a map",
+                        field_path,
+                        resolved.join(".")
+                    )
+                    .into())
+                }
+            };
+
+            let value = match current.get(segment) {
+                Some(value) => value,
+                None => {
+                    let suggestion = current
+                        .keys()
+                        .find(|key| normalize_field_name(key) == normalize_field_name(segment))
+                        .map(|key| {
+                            let mut path = resolved.clone();
+                            path.push(key.clone());
+                            format!("; did you mean '{}'?", path.join("."))
+                        })
+                        .unwrap_or_default();
+
+                    return Err(anyhow::anyhow!(
+                        "Field path '{}' doesn't match any field of the document{}",
+                        field_path,
+                        suggestion
+                    )
+                    .into());
+                }
+            };
+
+            resolved.push(segment.clone());
+            shape = value;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits a field path into its segments, removing backtick quotes.
+pub(crate) fn split_field_path(field_path: &str) -> Vec<String> {
+    let mut segments = vec![];
+    let mut segment = String::new();
+    let mut quoted = false;
+    let mut chars = field_path.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => quoted = !quoted,
+            '\\' if quoted => segment.extend(chars.next()),
+            '.' if !quoted => segments.push(std::mem::take(&mut segment)),
+            c => segment.push(c),
+        }
+    }
+    segments.push(segment);
+
+    segments
+}
+
+/// Ignores the differences between naming conventions, e.g. `field_b` and
+/// `fieldB`.
+fn normalize_field_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::firestore::collection;
+
+    use super::*;
+
+    const ROOT: &str = "projects/p/databases/(default)/documents";
+
+    #[test]
+    fn checks_field_paths_against_serialized_document() {
+        let doc = serde_json::json!({
+            "label": "Hello",
+            "nested": { "fieldB": "B", "x-wing": 1 },
+        });
+
+        assert!(check_field_paths(&doc, &["label", "nested", "nested.`x-wing`"]).is_ok());
+        assert!(check_field_paths(&doc, &["missing"]).is_err());
+        assert!(check_field_paths(&doc, &["label.inner"]).is_err());
+        assert_eq!(
+            check_field_paths(&doc, &["nested.field_b"])
+                .unwrap_err()
+                .to_string(),
+            "Field path 'nested.field_b' doesn't match any field of the document; did you mean 'nested.fieldB'?"
+        );
+    }
+
+    #[test]
+    fn checks_field_paths_left_out_of_the_write() {
+        #[derive(Serialize)]
+        struct Profile {
+            debt: DeleteField,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            nickname: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            address: Option<Address>,
+            name: String,
+        }
+
+        #[derive(Serialize)]
+        struct Address {
+            city: String,
+        }
+
+        let profile = Profile {
+            debt: DeleteField,
+            nickname: None,
+            address: None,
+            name: "Han".to_string(),
+        };
+
+        assert!(check_field_paths(&profile, &["debt", "nickname", "address.city", "name"]).is_ok());
+        assert!(check_field_paths(&profile, &["name.first"]).is_err());
+        assert!(check_field_paths(&profile, &["nick_name"]).is_err());
+    }
+
+    #[test]
+    fn create_write_requires_missing_document() {
+        let write = serialize_write(
+            ROOT,
+            &collection("greetings").doc("first"),
+            &serde_json::json!({ "message": "Hi Mom" }),
+            WriteKind::Create,
+        )
+        .unwrap();
+
+        let doc = match write.operation {
+            Some(Operation::Update(doc)) => doc,
+            other => panic!("expected update operation, got {:?}", other),
+        };
+
+        assert_eq!(doc.name, format!("{ROOT}/greetings/first"));
+        assert_eq!(
+            doc.fields.get("message"),
+            Some(&Value {
+                value_type: Some(ValueType::StringValue("Hi Mom".to_string()))
+            })
+        );
+        assert_eq!(write.update_mask, None);
+        assert_eq!(write.current_document, exists_precondition(false));
+    }
+
+    #[test]
+    fn update_merge_write_has_mask_and_precondition() {
+        let write = serialize_write(
+            ROOT,
+            &collection("greetings").doc("first"),
+            &serde_json::json!({ "message": "Hi Mom", "from": "me" }),
+            WriteKind::UpdateMerge(&["message"]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            write.update_mask,
+            Some(DocumentMask {
+                field_paths: vec!["message".to_string()]
+            })
+        );
+        assert_eq!(write.current_document, exists_precondition(true));
+    }
+
+    #[test]
+    fn delete_write_targets_document_name() {
+        let write = delete_write(ROOT, &collection("greetings").doc("first"));
+
+        assert_eq!(
+            write.operation,
+            Some(Operation::Delete(format!("{ROOT}/greetings/first")))
+        );
+    }
+
+    #[test]
+    fn increment_write_only_applies_transform() {
+        let write = increment_write(ROOT, &collection("counters").doc("visits"), "count", 5);
+
+        assert_eq!(write.update_mask, Some(field_mask(&[])));
+        assert_eq!(
+            write.update_transforms,
+            vec![FieldTransform {
+                field_path: "count".to_string(),
+                transform_type: Some(TransformType::Increment(Value {
+                    value_type: Some(ValueType::IntegerValue(5))
+                })),
+            }]
+        );
+    }
+
+    #[test]
+    fn transform_write_applies_all_transforms() {
+        let write = transform_write(
+            ROOT,
+            &collection("orders").doc("pizza"),
+            transforms()
+                .increment("quantity", 2)
+                .increment("total", 19.5),
+        );
+
+        assert_eq!(write.update_mask, Some(field_mask(&[])));
+        assert_eq!(write.current_document, None);
+        assert_eq!(
+            write.update_transforms,
+            vec![
+                FieldTransform {
+                    field_path: "quantity".to_string(),
+                    transform_type: Some(TransformType::Increment(Value {
+                        value_type: Some(ValueType::IntegerValue(2))
+                    })),
+                },
+                FieldTransform {
+                    field_path: "total".to_string(),
+                    transform_type: Some(TransformType::Increment(Value {
+                        value_type: Some(ValueType::DoubleValue(19.5))
+                    })),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn split_into_batches_separates_repeated_documents() {
+        let first = collection("greetings").doc("first");
+        let second = collection("greetings").doc("second");
+
+        let writes = vec![
+            delete_write(ROOT, &first),
+            delete_write(ROOT, &second),
+            delete_write(ROOT, &first),
+        ];
+
+        let batches = split_into_batches(writes.clone());
+
+        assert_eq!(batches, vec![writes[..2].to_vec(), writes[2..].to_vec()]);
+    }
+
+    #[test]
+    fn split_into_batches_respects_max_batch_size() {
+        let writes = (0..MAX_BATCH_WRITE_SIZE + 1)
+            .map(|i| delete_write(ROOT, &collection("greetings").doc(i.to_string())))
+            .collect();
+
+        let batch_sizes: Vec<usize> = split_into_batches(writes).iter().map(Vec::len).collect();
+
+        assert_eq!(batch_sizes, vec![MAX_BATCH_WRITE_SIZE, 1]);
+    }
+
+    #[test]
+    fn deletes_use_commit_time_as_consistency_token() {
+        let commit_time = Timestamp {
+            seconds: 10,
+            nanos: 500,
+        };
+        let update_time = Timestamp {
+            seconds: 9,
+            nanos: 1,
+        };
+
+        let delete = WriteResult::from(None).or_commit_time(Some(&commit_time));
+        assert_eq!(delete.update_time, None);
+        assert_eq!(
+            delete.consistency_token(),
+            Some(ConsistencyToken::from_timestamp(commit_time.clone()))
+        );
+
+        let set = WriteResult::from(Some(update_time.clone())).or_commit_time(Some(&commit_time));
+        assert_eq!(set.update_time, Some(9));
+        assert_eq!(
+            set.update_timestamp,
+            Some(std::time::UNIX_EPOCH + std::time::Duration::new(9, 1))
+        );
+        assert_eq!(
+            set.consistency_token(),
+            Some(ConsistencyToken::from_timestamp(update_time))
+        );
+    }
+}
@@ -8,6 +8,7 @@
 //! See the [`firestore`] module for more information.
 
 pub mod auth;
+pub mod cancel;
 pub mod error;
 pub mod firestore;
 mod service_account;
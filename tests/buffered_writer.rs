@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use fireplace::firestore::{
+    buffered::{BufferedWriteStats, BufferedWriter, BufferedWriterOptions},
+    client::FirestoreClient,
+    collection,
+    reference::{CollectionReference, DocumentReference},
+};
+
+/// Long enough that the buffer is never flushed because of the delay.
+const NEVER: Duration = Duration::from_secs(3600);
+
+fn events() -> CollectionReference {
+    collection("buffered-events")
+        .doc(ulid::Ulid::new())
+        .collection("events")
+}
+
+/// Waits until the document exists, as the writer applies writes in the
+/// background.
+async fn wait_for_document(
+    client: &mut FirestoreClient,
+    doc_ref: &DocumentReference,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    for _ in 0..50 {
+        if let Some(doc) = client.get_document(doc_ref).await? {
+            return Ok(doc);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    Err(format!("Document {} was never written", doc_ref).into())
+}
+
+#[tokio::test]
+async fn flushes_when_buffer_is_full() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    let events = events();
+
+    let writer = BufferedWriter::new(
+        &client,
+        BufferedWriterOptions::new().max_writes(3).max_delay(NEVER),
+    );
+
+    for i in 0..3 {
+        writer
+            .set_document(&events.doc(i.to_string()), &serde_json::json!({ "i": i }))
+            .await?;
+    }
+
+    // Neither flushed nor shut down, so only the full buffer triggers it
+    for i in 0..3 {
+        let doc = wait_for_document(&mut client, &events.doc(i.to_string())).await?;
+        assert_eq!(doc["i"], i);
+    }
+
+    let stats = writer.shutdown().await?;
+    assert_eq!(
+        stats,
+        BufferedWriteStats {
+            written: 3,
+            failed: 0,
+            flushes: 1,
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flushes_remaining_writes_on_shutdown() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    let events = events();
+
+    let writer = BufferedWriter::new(
+        &client,
+        BufferedWriterOptions::new()
+            .max_writes(100)
+            .max_delay(NEVER),
+    );
+    writer
+        .set_document(&events.doc("first"), &serde_json::json!({ "page": "home" }))
+        .await?;
+
+    assert_eq!(
+        client
+            .get_document::<serde_json::Value>(&events.doc("first"))
+            .await?,
+        None
+    );
+
+    let stats = writer.shutdown().await?;
+    assert_eq!(stats.written, 1);
+    assert_eq!(stats.flushes, 1);
+    assert!(client
+        .get_document::<serde_json::Value>(&events.doc("first"))
+        .await?
+        .is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flushes_remaining_writes_on_drop() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    let events = events();
+
+    let writer = BufferedWriter::new(
+        &client,
+        BufferedWriterOptions::new()
+            .max_writes(100)
+            .max_delay(NEVER),
+    );
+    writer
+        .set_document(&events.doc("first"), &serde_json::json!({ "page": "home" }))
+        .await?;
+    drop(writer);
+
+    let doc = wait_for_document(&mut client, &events.doc("first")).await?;
+    assert_eq!(doc["page"], "home");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_write_does_not_fail_the_others() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = fireplace::firestore::test_helpers::initialise().await?;
+    let events = events();
+
+    client
+        .set_document(
+            &events.doc("taken"),
+            &serde_json::json!({ "original": true }),
+        )
+        .await?;
+
+    let writer = BufferedWriter::new(
+        &client,
+        BufferedWriterOptions::new()
+            .max_writes(100)
+            .max_delay(NEVER),
+    );
+    writer
+        .set_document(&events.doc("before"), &serde_json::json!({}))
+        .await?;
+    // Fails, as the document already exists
+    writer
+        .create_document(
+            &events.doc("taken"),
+            &serde_json::json!({ "original": false }),
+        )
+        .await?;
+    writer
+        .set_document(&events.doc("after"), &serde_json::json!({}))
+        .await?;
+
+    let stats = writer.shutdown().await?;
+    assert_eq!(
+        stats,
+        BufferedWriteStats {
+            written: 2,
+            failed: 1,
+            flushes: 1,
+        }
+    );
+
+    let taken: Option<serde_json::Value> = client.get_document(&events.doc("taken")).await?;
+    assert_eq!(taken, Some(serde_json::json!({ "original": true })));
+    for id in ["before", "after"] {
+        assert!(client
+            .get_document::<serde_json::Value>(&events.doc(id))
+            .await?
+            .is_some());
+    }
+
+    Ok(())
+}